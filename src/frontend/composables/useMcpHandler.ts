@@ -94,6 +94,7 @@ export function useMcpHandler() {
           message: request.message,
           predefinedOptions: request.predefined_options || [],
           isMarkdown: request.is_markdown || false,
+          projectPath: request.project_path || null,
         })
         console.log('✅ Telegram同步启动成功')
       }
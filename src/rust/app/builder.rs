@@ -86,7 +86,26 @@ pub fn build_tauri_app() -> Builder<tauri::Wry> {
 
             crate::mcp::tools::ci::commands::execute_ci_tool,
             crate::mcp::tools::xi::commands::execute_xi_tool,
+            crate::mcp::tools::hui::commands::search_conversations,
+            crate::mcp::tools::hui::commands::get_conversation,
+            crate::mcp::tools::hui::commands::reopen_conversation,
             crate::mcp::tools::memory::commands::execute_ji_tool,
+            crate::mcp::tools::memory::commands::export_memories,
+            crate::mcp::tools::memory::commands::import_memories,
+            crate::mcp::tools::memory::commands::list_memory_entries,
+            crate::mcp::tools::memory::commands::update_memory_entry,
+            crate::mcp::tools::memory::commands::delete_memory_entry,
+            crate::mcp::tools::memory::commands::move_memory_entry,
+            crate::mcp::tools::memory::commands::get_memory_history,
+            crate::mcp::tools::memory::commands::list_knowledge_entries,
+            crate::mcp::tools::memory::commands::update_knowledge_entry,
+            crate::mcp::tools::memory::commands::delete_knowledge_entry,
+            crate::mcp::tools::memory::commands::get_knowledge_history,
+            crate::mcp::tools::memory::commands::get_knowledge_diagnostics,
+            crate::mcp::tools::memory::commands::get_knowledge_sync_status,
+            crate::mcp::tools::memory::commands::sync_knowledge_now,
+            crate::mcp::tools::memory::commands::init_knowledge_base,
+            crate::mcp::tools::memory::commands::check_knowledge_base,
 
             // 自定义prompt命令
             get_custom_prompt_config,
@@ -102,6 +121,10 @@ pub fn build_tauri_app() -> Builder<tauri::Wry> {
             update_shortcut_binding,
             reset_shortcuts_to_default,
 
+            // 对话日志命令
+            get_conversation_config,
+            set_conversation_config,
+
             // 窗口注册命令
             register_window_instance,
             unregister_window_instance,
@@ -119,6 +142,11 @@ pub fn build_tauri_app() -> Builder<tauri::Wry> {
             auto_get_chat_id,
             start_telegram_sync,
 
+            // 通知渠道命令
+            get_notification_config,
+            set_notification_config,
+            test_notification_channels,
+
             // 系统命令
             open_external_url,
             open_terminal,
@@ -1,13 +1,23 @@
 use crate::config::load_standalone_telegram_config;
-use crate::telegram::handle_telegram_only_mcp_request;
+use crate::telegram::handle_telegram_mcp_request;
 use crate::log_important;
 use crate::app::builder::run_tauri_app;
+use crate::app::coordinator::{ResponseChannel, ResponseCoordinator};
+use crate::notify::spawn_request_notification;
 use anyhow::Result;
 
 /// 处理命令行参数
 pub fn handle_cli_args() -> Result<()> {
     let args: Vec<String> = std::env::args().collect();
 
+    // 子命令模式
+    if args.len() >= 2 && args[1] == "memory" {
+        return handle_memory_command(&args[2..]);
+    }
+    if args.len() >= 2 && args[1] == "knowledge" {
+        return handle_knowledge_command(&args[2..]);
+    }
+
     match args.len() {
         // 无参数：正常启动GUI
         1 => {
@@ -41,33 +51,182 @@ pub fn handle_cli_args() -> Result<()> {
 }
 
 /// 处理MCP请求
+///
+/// 请求同时发往弹窗和 Telegram（按配置），第一个回复生效，另一个渠道随后撤回请求
 fn handle_mcp_request(request_file: &str) -> Result<()> {
-    // 检查Telegram配置，决定是否启用纯Telegram模式
-    match load_standalone_telegram_config() {
-        Ok(telegram_config) => {
-            if telegram_config.enabled && telegram_config.hide_frontend_popup {
-                // 纯Telegram模式：不启动GUI，直接处理
-                if let Err(e) = tokio::runtime::Runtime::new()
-                    .unwrap()
-                    .block_on(handle_telegram_only_mcp_request(request_file))
-                {
-                    log_important!(error, "处理Telegram请求失败: {}", e);
-                    std::process::exit(1);
-                }
+    let telegram_config = match load_standalone_telegram_config() {
+        Ok(config) => Some(config).filter(|c| c.enabled),
+        Err(e) => {
+            log_important!(warn, "加载Telegram配置失败: {}，使用默认GUI模式", e);
+            None
+        }
+    };
+    // 隐藏前端弹窗时为纯Telegram模式：不启动GUI
+    let show_popup = !telegram_config.as_ref().is_some_and(|c| c.hide_frontend_popup);
+
+    let mut channels = Vec::new();
+    if show_popup {
+        channels.push(ResponseChannel::Popup);
+    }
+    if telegram_config.is_some() {
+        channels.push(ResponseChannel::Telegram);
+    }
+    let coordinator = ResponseCoordinator::new(channels).install();
+
+    // 通过 Webhook、推送或邮件提醒用户有待回复的请求
+    spawn_request_notification(request_file);
+
+    if !show_popup {
+        if let Err(e) = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(handle_telegram_mcp_request(request_file, coordinator))
+        {
+            log_important!(error, "处理Telegram请求失败: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if coordinator.has_channel(ResponseChannel::Telegram) {
+        // Telegram 在独立线程中与弹窗同时等待回复
+        let request_file = request_file.to_string();
+        std::thread::spawn(move || {
+            let result = tokio::runtime::Runtime::new()
+                .map_err(anyhow::Error::from)
+                .and_then(|runtime| runtime.block_on(handle_telegram_mcp_request(&request_file, coordinator.clone())));
+            if let Err(e) = result {
+                log_important!(warn, "Telegram渠道出错，仅使用弹窗: {}", e);
+            }
+            coordinator.settle(ResponseChannel::Telegram);
+        });
+    }
+
+    run_tauri_app();
+    Ok(())
+}
+
+/// 处理 memory 子命令（导入/导出记忆）
+fn handle_memory_command(args: &[String]) -> Result<()> {
+    use crate::mcp::tools::memory::{MemoryManager, RuleFormat};
+    use std::path::Path;
+
+    let SubcommandArgs { positional, project, output, to_stdout, .. } = SubcommandArgs::parse(args)?;
+
+    if positional.len() < 2 {
+        print_help();
+        std::process::exit(1);
+    }
+
+    let format = RuleFormat::parse(&positional[1])?;
+    let project_path = match project {
+        Some(p) => p,
+        None => std::env::current_dir()?.to_string_lossy().to_string(),
+    };
+    let manager = MemoryManager::new(&project_path)?;
+
+    match positional[0].as_str() {
+        "export" => {
+            if to_stdout {
+                println!("{}", manager.export_memories(format)?);
             } else {
-                // 正常模式：启动GUI处理弹窗
-                run_tauri_app();
+                let path = manager.export_to_file(format, output.as_deref().map(Path::new))?;
+                println!("✅ 已导出到 {}", path.display());
             }
         }
-        Err(e) => {
-            log_important!(warn, "加载Telegram配置失败: {}，使用默认GUI模式", e);
-            // 配置加载失败时，使用默认行为（启动GUI）
-            run_tauri_app();
+        "import" => {
+            let input = positional.get(2).map(Path::new);
+            let report = manager.import_from_file(format, input)?;
+            println!("{}", report.summary());
+        }
+        other => {
+            eprintln!("未知的 memory 子命令: {}", other);
+            print_help();
+            std::process::exit(1);
         }
     }
+
     Ok(())
 }
 
+/// 处理 knowledge 子命令（初始化/体检知识库）
+fn handle_knowledge_command(args: &[String]) -> Result<()> {
+    use crate::mcp::tools::memory::{DoctorLevel, KnowledgeInitOptions, MemoryManager};
+
+    let SubcommandArgs { positional, project, repo, remote, .. } = SubcommandArgs::parse(args)?;
+
+    let project_path = match project {
+        Some(p) => p,
+        None => std::env::current_dir()?.to_string_lossy().to_string(),
+    };
+    let manager = MemoryManager::new(&project_path)?;
+
+    match positional.first().map(String::as_str) {
+        Some("init") => {
+            let options = KnowledgeInitOptions { repo_url: repo, remote_url: remote }.with_configured_repo();
+            println!("{}", manager.init_knowledge(&options)?.message());
+        }
+        Some("doctor") => {
+            let report = manager.knowledge_doctor()?;
+            println!("{}", report.render());
+            if report.level() == DoctorLevel::Error {
+                std::process::exit(1);
+            }
+        }
+        other => {
+            eprintln!("未知的 knowledge 子命令: {}", other.unwrap_or(""));
+            print_help();
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// 子命令的通用参数
+struct SubcommandArgs {
+    positional: Vec<String>,
+    project: Option<String>,
+    output: Option<String>,
+    to_stdout: bool,
+    repo: Option<String>,
+    remote: Option<String>,
+}
+
+impl SubcommandArgs {
+    fn parse(args: &[String]) -> Result<Self> {
+        let mut parsed = Self {
+            positional: Vec::new(),
+            project: None,
+            output: None,
+            to_stdout: false,
+            repo: None,
+            remote: None,
+        };
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--project" | "-p" => {
+                    parsed.project = Some(iter.next().cloned().ok_or_else(|| anyhow::anyhow!("--project 缺少参数"))?);
+                }
+                "--output" | "-o" => {
+                    parsed.output = Some(iter.next().cloned().ok_or_else(|| anyhow::anyhow!("--output 缺少参数"))?);
+                }
+                "--repo" => {
+                    parsed.repo = Some(iter.next().cloned().ok_or_else(|| anyhow::anyhow!("--repo 缺少参数"))?);
+                }
+                "--remote" => {
+                    parsed.remote = Some(iter.next().cloned().ok_or_else(|| anyhow::anyhow!("--remote 缺少参数"))?);
+                }
+                "--stdout" => parsed.to_stdout = true,
+                _ => parsed.positional.push(arg.clone()),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
 /// 显示帮助信息
 fn print_help() {
     println!("iterate - 智能代码审查工具");
@@ -75,6 +234,14 @@ fn print_help() {
     println!("用法:");
     println!("  等一下                    启动设置界面");
     println!("  等一下 --mcp-request <文件>  处理 MCP 请求");
+    println!("  等一下 memory export <格式> [--project <路径>] [--output <文件>] [--stdout]");
+    println!("                           导出记忆到规则文件（格式：agents/cursor/windsurf/claude/json）");
+    println!("  等一下 memory import <格式> [文件] [--project <路径>]");
+    println!("                           从规则文件导入记忆");
+    println!("  等一下 knowledge init [--project <路径>] [--repo <共享仓库>] [--remote <远程地址>]");
+    println!("                           初始化 .cunzhi-knowledge/ 模板，可从共享仓库 clone");
+    println!("  等一下 knowledge doctor [--project <路径>]");
+    println!("                           检查知识库结构、编号、重复条目和 git 远程状态");
     println!("  等一下 --help             显示此帮助信息");
     println!("  等一下 --version          显示版本信息");
 }
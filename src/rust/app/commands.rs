@@ -5,7 +5,10 @@ pub use crate::mcp::commands::*;
 
 // Telegram 命令
 pub use crate::telegram::commands::*;
-pub use crate::telegram::handle_telegram_only_mcp_request;
+pub use crate::telegram::handle_telegram_mcp_request;
+
+// 通知渠道命令
+pub use crate::notify::commands::*;
 
 // UI 命令
 pub use crate::ui::{
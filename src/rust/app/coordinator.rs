@@ -0,0 +1,175 @@
+//! 多渠道回复协调
+//!
+//! 一个 zhi 请求同时发往所有已配置的渠道（桌面弹窗、Telegram），第一个回复生效并输出到 stdout，
+//! 其他渠道随后撤回或标记该请求（移除 Telegram 按钮、关闭弹窗窗口），全部处理完后进程再退出
+
+use once_cell::sync::OnceCell;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// 回复渠道
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseChannel {
+    Popup,
+    Telegram,
+}
+
+impl ResponseChannel {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ResponseChannel::Popup => "桌面弹窗",
+            ResponseChannel::Telegram => "Telegram",
+        }
+    }
+}
+
+/// 生效的回复
+#[derive(Debug, Clone, PartialEq)]
+pub struct Answer {
+    pub channel: ResponseChannel,
+    pub response: String,
+}
+
+impl Answer {
+    /// 弹窗关闭时发送的是 CANCELLED
+    pub fn is_cancelled(&self) -> bool {
+        self.response.trim() == "CANCELLED"
+    }
+
+    /// 告知其他渠道该请求已结束的提示
+    pub fn notice(&self) -> String {
+        if self.is_cancelled() {
+            format!("🚫 该请求已在{}中取消", self.channel.label())
+        } else {
+            format!("✅ 该请求已在{}中回复", self.channel.label())
+        }
+    }
+}
+
+type Output = Box<dyn Fn(&str) + Send + Sync>;
+
+/// 一次请求的回复协调器
+pub struct ResponseCoordinator {
+    channels: Vec<ResponseChannel>,
+    answer: watch::Sender<Option<Answer>>,
+    /// 已处理完毕（回复或撤回）的渠道
+    settled: watch::Sender<Vec<ResponseChannel>>,
+    output: Output,
+}
+
+static COORDINATOR: OnceCell<Arc<ResponseCoordinator>> = OnceCell::new();
+
+impl ResponseCoordinator {
+    /// 生效的回复输出到 stdout（MCP 协议要求）
+    pub fn new(channels: Vec<ResponseChannel>) -> Self {
+        Self::with_output(channels, Box::new(|response| {
+            let mut stdout = std::io::stdout();
+            let _ = writeln!(stdout, "{}", response);
+            let _ = stdout.flush();
+        }))
+    }
+
+    pub fn with_output(channels: Vec<ResponseChannel>, output: Output) -> Self {
+        Self {
+            channels,
+            answer: watch::Sender::new(None),
+            settled: watch::Sender::new(Vec::new()),
+            output,
+        }
+    }
+
+    /// 设为本进程的协调器，供弹窗命令和 Telegram 监听使用
+    pub fn install(self) -> Arc<Self> {
+        COORDINATOR.get_or_init(|| Arc::new(self)).clone()
+    }
+
+    pub fn global() -> Option<Arc<Self>> {
+        COORDINATOR.get().cloned()
+    }
+
+    pub fn has_channel(&self, channel: ResponseChannel) -> bool {
+        self.channels.contains(&channel)
+    }
+
+    /// 提交回复：只有第一个回复会输出并生效，返回是否被采纳
+    pub fn submit(&self, channel: ResponseChannel, response: &str) -> bool {
+        self.answer.send_if_modified(|answer| {
+            if answer.is_some() {
+                return false;
+            }
+            (self.output)(response);
+            *answer = Some(Answer { channel, response: response.to_string() });
+            true
+        })
+    }
+
+    pub fn answer(&self) -> Option<Answer> {
+        self.answer.borrow().clone()
+    }
+
+    /// 等待任一渠道回复
+    pub async fn answered(&self) -> Answer {
+        let mut rx = self.answer.subscribe();
+        let answer = rx.wait_for(Option::is_some).await.map(|a| a.clone());
+        // 发送端由 self 持有，等待期间不会关闭
+        answer.ok().flatten().expect("回复通道已关闭")
+    }
+
+    /// 标记渠道已处理完毕（已回复，或已撤回请求）
+    pub fn settle(&self, channel: ResponseChannel) {
+        self.settled.send_if_modified(|settled| {
+            if settled.contains(&channel) {
+                return false;
+            }
+            settled.push(channel);
+            true
+        });
+    }
+
+    /// 等待所有渠道处理完毕，超时返回 false
+    pub async fn wait_settled(&self, timeout: Duration) -> bool {
+        let mut rx = self.settled.subscribe();
+        let all_settled = rx.wait_for(|settled| self.channels.iter().all(|c| settled.contains(c)));
+        let result = tokio::time::timeout(timeout, all_settled).await;
+        matches!(result, Ok(Ok(_)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_first_answer_wins_and_others_settle() {
+        let printed = Arc::new(Mutex::new(Vec::new()));
+        let sink = printed.clone();
+        let coordinator = Arc::new(ResponseCoordinator::with_output(
+            vec![ResponseChannel::Popup, ResponseChannel::Telegram],
+            Box::new(move |response| sink.lock().unwrap().push(response.to_string())),
+        ));
+
+        // Telegram 端等待回复，收到其他渠道的回复后撤回
+        let telegram = {
+            let coordinator = coordinator.clone();
+            tokio::spawn(async move {
+                let answer = coordinator.answered().await;
+                coordinator.settle(ResponseChannel::Telegram);
+                answer
+            })
+        };
+
+        assert!(!coordinator.wait_settled(Duration::from_millis(50)).await);
+        assert!(coordinator.submit(ResponseChannel::Popup, "{\"user_input\":\"好\"}"));
+        assert!(!coordinator.submit(ResponseChannel::Telegram, "{\"user_input\":\"晚了\"}"));
+        coordinator.settle(ResponseChannel::Popup);
+
+        let answer = telegram.await.unwrap();
+        assert_eq!(answer.channel, ResponseChannel::Popup);
+        assert_eq!(answer.notice(), "✅ 该请求已在桌面弹窗中回复");
+        assert!(coordinator.wait_settled(Duration::from_secs(1)).await);
+        assert_eq!(*printed.lock().unwrap(), vec!["{\"user_input\":\"好\"}".to_string()]);
+    }
+}
@@ -1,8 +1,11 @@
+use crate::app::{ResponseChannel, ResponseCoordinator};
 use crate::config::{AppState, load_config_and_apply_window_settings};
+use crate::constants::app::CHANNEL_SETTLE_TIMEOUT_SECS;
 use crate::ui::{initialize_audio_asset_manager, setup_window_event_listeners};
 use crate::ui::exit_handler::setup_exit_handlers;
 use crate::log_important;
-use tauri::{AppHandle, Manager};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
 
 /// 应用设置和初始化
 pub async fn setup_application(app_handle: &AppHandle) -> Result<(), String> {
@@ -26,5 +29,35 @@ pub async fn setup_application(app_handle: &AppHandle) -> Result<(), String> {
         log_important!(warn, "设置退出处理器失败: {}", e);
     }
 
+    // 其他渠道先回复时关闭弹窗
+    setup_answer_watcher(app_handle);
+
     Ok(())
 }
+
+/// 请求在其他渠道（如 Telegram）先得到回复时，通知前端并退出弹窗进程
+fn setup_answer_watcher(app_handle: &AppHandle) {
+    let Some(coordinator) = ResponseCoordinator::global() else {
+        return;
+    };
+    if !coordinator.has_channel(ResponseChannel::Popup) {
+        return;
+    }
+
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        let answer = coordinator.answered().await;
+        if answer.channel == ResponseChannel::Popup {
+            // 弹窗自己的回复由 send_mcp_response 处理
+            return;
+        }
+
+        log_important!(info, "{}，关闭弹窗", answer.notice());
+        let _ = app_handle.emit("mcp-request-answered", answer.notice());
+        coordinator.settle(ResponseChannel::Popup);
+        coordinator
+            .wait_settled(Duration::from_secs(CHANNEL_SETTLE_TIMEOUT_SECS))
+            .await;
+        app_handle.exit(0);
+    });
+}
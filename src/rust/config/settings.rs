@@ -19,6 +19,10 @@ pub struct AppConfig {
     pub custom_prompt_config: CustomPromptConfig, // 自定义prompt配置
     #[serde(default = "default_shortcut_config")]
     pub shortcut_config: ShortcutConfig, // 自定义快捷键配置
+    #[serde(default = "default_conversation_config")]
+    pub conversation_config: ConversationConfig, // 对话日志配置
+    #[serde(default)]
+    pub notification_config: NotificationConfig, // Webhook、推送、邮件等通知渠道
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -114,6 +118,49 @@ pub struct McpConfig {
     pub acemcp_max_lines_per_blob: Option<u32>, // acemcp最大行数/块
     pub acemcp_text_extensions: Option<Vec<String>>, // acemcp文件扩展名
     pub acemcp_exclude_patterns: Option<Vec<String>>, // acemcp排除模式
+    #[serde(default)]
+    pub session_retention: Option<usize>, // sessions.md 保留的会话摘要条数
+    #[serde(default)]
+    pub knowledge_repo_url: Option<String>, // 共享知识库仓库地址（初始化时 clone）
+    #[serde(default)]
+    pub pai_batch_size: Option<usize>, // pai 每批条目数
+    #[serde(default)]
+    pub pai_agent_command: Option<String>, // pai 无界面执行的代理命令（从 stdin 读取提示词）
+    #[serde(default)]
+    pub pai_agent_args: Option<Vec<String>>, // 代理命令参数
+    #[serde(default)]
+    pub pai_parallelism: Option<usize>, // 同时执行的批次数
+    #[serde(default)]
+    pub pai_agent_timeout_secs: Option<u64>, // 单个批次超时（秒）
+}
+
+// 对话日志配置（zhi 交互记录到 .cunzhi-knowledge/conversations/）
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConversationConfig {
+    #[serde(default = "default_conversation_logging_enabled")]
+    pub enabled: bool, // 是否记录对话日志
+    #[serde(default = "default_conversation_redact")]
+    pub redact_api_keys: bool, // 脱敏 API Key（sk-、AKIA、ghp_ 等）
+    #[serde(default = "default_conversation_redact")]
+    pub redact_tokens: bool, // 脱敏 Bearer/JWT/Bot Token/私钥
+    #[serde(default = "default_conversation_redact")]
+    pub redact_emails: bool, // 脱敏邮箱地址
+    #[serde(default)]
+    pub custom_patterns: Vec<String>, // 自定义脱敏正则
+    #[serde(default)]
+    pub excluded_projects: Vec<String>, // 不记录的项目（路径或目录名）
+    #[serde(default)]
+    pub retention_days: Option<u32>, // 日志保留天数，为空或 0 表示永久保留
+    #[serde(default = "default_conversation_remote_sync")]
+    pub remote_sync: bool, // 是否同步到远程知识库（关闭时仅保留本地日志）
+    #[serde(default = "default_conversation_save_images")]
+    pub save_images: bool, // 是否保存附图到 conversations/assets/
+    #[serde(default = "default_conversation_image_max_bytes")]
+    pub image_max_bytes: u64, // 单张附图大小上限（字节），0 表示不限制
+    #[serde(default)]
+    pub image_max_dimension: Option<u32>, // 附图最长边（像素），超过时缩小
+    #[serde(default = "default_conversation_sync_images")]
+    pub sync_images: bool, // 附图是否随对话日志同步到远程
 }
 
 // 自定义prompt结构
@@ -188,6 +235,148 @@ pub struct TelegramConfig {
     pub hide_frontend_popup: bool, // 是否隐藏前端弹窗，仅使用Telegram交互
     #[serde(default = "default_telegram_api_base_url")]
     pub api_base_url: String, // Telegram API基础URL
+    #[serde(default)]
+    pub message_thread_id: Option<i32>, // 默认聊天的论坛话题ID
+    #[serde(default)]
+    pub routes: Vec<TelegramRoute>, // 按项目路径路由到不同聊天或话题
+    #[serde(default)]
+    pub allowed_user_ids: Vec<i64>, // 允许作答的用户ID，为空时不限制
+    #[serde(default)]
+    pub update_mode: TelegramUpdateMode, // 更新接收方式：长轮询或Webhook
+    #[serde(default)]
+    pub webhook_url: String, // Webhook公网地址（由反向代理或隧道转发到本地监听地址）
+    #[serde(default = "default_telegram_webhook_listen_addr")]
+    pub webhook_listen_addr: String, // Webhook本地监听地址
+    #[serde(default = "default_telegram_dispatcher_port")]
+    pub dispatcher_port: u16, // 多个请求共享更新时使用的本地端口
+    #[serde(default)]
+    pub speech_to_text: SpeechToTextConfig, // 语音消息转文字
+}
+
+/// Telegram 更新接收方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TelegramUpdateMode {
+    #[default]
+    Polling,
+    Webhook,
+}
+
+/// 语音转文字配置
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SpeechToTextConfig {
+    #[serde(default)]
+    pub backend: SpeechToTextBackend, // 识别后端
+    #[serde(default)]
+    pub binary_path: String, // whisper.cpp 兼容程序路径（如 whisper-cli）
+    #[serde(default)]
+    pub model_path: String, // 本地模型文件路径（-m）
+    #[serde(default)]
+    pub ffmpeg_path: String, // 先用 ffmpeg 转为 16kHz WAV，为空时直接传入原始音频
+    #[serde(default)]
+    pub extra_args: Vec<String>, // 追加给本地程序的参数
+    #[serde(default)]
+    pub endpoint_url: String, // HTTP 识别接口（multipart 上传 file 字段）
+    #[serde(default)]
+    pub api_key: String, // HTTP 接口的 Bearer Token，可为空
+    #[serde(default)]
+    pub model: String, // HTTP 接口的模型名（如 whisper-1），可为空
+    #[serde(default)]
+    pub language: String, // 语言代码（如 zh），为空时自动检测
+}
+
+/// 语音识别后端
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeechToTextBackend {
+    #[default]
+    Disabled,
+    WhisperCpp,
+    Http,
+}
+
+/// 通知渠道配置：有新的 zhi 请求时提醒用户（只通知，不接收回复）
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NotificationConfig {
+    #[serde(default)]
+    pub enabled: bool, // 是否启用通知
+    #[serde(default)]
+    pub channels: Vec<NotificationChannelConfig>, // 通知渠道列表
+}
+
+/// 单个通知渠道
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NotificationChannelConfig {
+    #[serde(default)]
+    pub kind: NotificationChannelKind, // 渠道类型
+    #[serde(default = "default_notification_channel_enabled")]
+    pub enabled: bool, // 是否启用该渠道
+    #[serde(default)]
+    pub url: String, // Webhook 地址，或 ntfy/Gotify 服务器地址
+    #[serde(default)]
+    pub webhook_format: WebhookFormat, // Webhook 消息格式
+    #[serde(default)]
+    pub topic: String, // ntfy 主题
+    #[serde(default)]
+    pub token: String, // ntfy 访问令牌或 Gotify 应用令牌
+    #[serde(default)]
+    pub priority: Option<u8>, // 推送优先级（ntfy 1-5，Gotify 0-10）
+    #[serde(default)]
+    pub smtp_host: String, // SMTP 服务器
+    #[serde(default)]
+    pub smtp_port: Option<u16>, // SMTP 端口，为空时按加密方式取默认值
+    #[serde(default)]
+    pub smtp_security: SmtpSecurity, // SMTP 加密方式
+    #[serde(default)]
+    pub username: String, // SMTP 用户名，为空时不认证
+    #[serde(default)]
+    pub password: String, // SMTP 密码或授权码
+    #[serde(default)]
+    pub from: String, // 发件人（如 "寸止 <bot@example.com>"）
+    #[serde(default)]
+    pub to: Vec<String>, // 收件人
+}
+
+/// 通知渠道类型
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationChannelKind {
+    #[default]
+    Webhook,
+    Ntfy,
+    Gotify,
+    Email,
+}
+
+/// Webhook 消息格式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    #[default]
+    Json,
+    Slack,
+    Discord,
+    Lark,
+}
+
+/// SMTP 加密方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    #[default]
+    StartTls,
+    Tls,
+    None,
+}
+
+/// Telegram 路由规则：项目路径前缀匹配时发送到指定聊天或话题
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TelegramRoute {
+    pub project_path: String, // 项目路径前缀
+    #[serde(default)]
+    pub chat_id: String, // 目标Chat ID（数字ID或@channel），为空时使用默认聊天
+    #[serde(default)]
+    pub message_thread_id: Option<i32>, // 论坛话题ID
 }
 
 #[derive(Debug)]
@@ -209,6 +398,8 @@ impl Default for AppConfig {
             telegram_config: default_telegram_config(),
             custom_prompt_config: default_custom_prompt_config(),
             shortcut_config: default_shortcut_config(),
+            conversation_config: default_conversation_config(),
+            notification_config: NotificationConfig::default(),
         }
     }
 }
@@ -250,9 +441,57 @@ pub fn default_mcp_config() -> McpConfig {
         acemcp_max_lines_per_blob: None,
         acemcp_text_extensions: None,
         acemcp_exclude_patterns: None,
+        session_retention: None,
+        knowledge_repo_url: None,
+        pai_batch_size: None,
+        pai_agent_command: None,
+        pai_agent_args: None,
+        pai_parallelism: None,
+        pai_agent_timeout_secs: None,
+    }
+}
+
+pub fn default_conversation_config() -> ConversationConfig {
+    ConversationConfig {
+        enabled: default_conversation_logging_enabled(),
+        redact_api_keys: default_conversation_redact(),
+        redact_tokens: default_conversation_redact(),
+        redact_emails: default_conversation_redact(),
+        custom_patterns: Vec::new(),
+        excluded_projects: Vec::new(),
+        retention_days: None,
+        remote_sync: default_conversation_remote_sync(),
+        save_images: default_conversation_save_images(),
+        image_max_bytes: default_conversation_image_max_bytes(),
+        image_max_dimension: None,
+        sync_images: default_conversation_sync_images(),
     }
 }
 
+pub fn default_conversation_logging_enabled() -> bool {
+    true
+}
+
+pub fn default_conversation_redact() -> bool {
+    true
+}
+
+pub fn default_conversation_remote_sync() -> bool {
+    true
+}
+
+pub fn default_conversation_save_images() -> bool {
+    true
+}
+
+pub fn default_conversation_image_max_bytes() -> u64 {
+    mcp::CONVERSATION_IMAGE_MAX_BYTES
+}
+
+pub fn default_conversation_sync_images() -> bool {
+    true
+}
+
 pub fn default_telegram_config() -> TelegramConfig {
     TelegramConfig {
         enabled: default_telegram_enabled(),
@@ -260,9 +499,21 @@ pub fn default_telegram_config() -> TelegramConfig {
         chat_id: default_telegram_chat_id(),
         hide_frontend_popup: default_telegram_hide_frontend_popup(),
         api_base_url: default_telegram_api_base_url(),
+        message_thread_id: None,
+        routes: Vec::new(),
+        allowed_user_ids: Vec::new(),
+        update_mode: TelegramUpdateMode::default(),
+        webhook_url: String::new(),
+        webhook_listen_addr: default_telegram_webhook_listen_addr(),
+        dispatcher_port: default_telegram_dispatcher_port(),
+        speech_to_text: SpeechToTextConfig::default(),
     }
 }
 
+pub fn default_notification_channel_enabled() -> bool {
+    true
+}
+
 pub fn default_custom_prompt_config() -> CustomPromptConfig {
     CustomPromptConfig {
         prompts: default_custom_prompts(),
@@ -350,6 +601,7 @@ pub fn default_mcp_tools() -> HashMap<String, bool> {
     tools.insert(mcp::TOOL_PAI.to_string(), false); // 子代理派发工具默认关闭
     tools.insert(mcp::TOOL_XI.to_string(), false); // 经验查找工具默认关闭
     tools.insert(mcp::TOOL_CI.to_string(), false); // 提示词库搜索工具默认关闭
+    tools.insert(mcp::TOOL_HUI.to_string(), false); // 对话回顾工具默认关闭
     tools
 }
 
@@ -401,6 +653,14 @@ pub fn default_telegram_api_base_url() -> String {
     telegram::API_BASE_URL.to_string()
 }
 
+pub fn default_telegram_webhook_listen_addr() -> String {
+    telegram::DEFAULT_WEBHOOK_LISTEN_ADDR.to_string()
+}
+
+pub fn default_telegram_dispatcher_port() -> u16 {
+    telegram::DEFAULT_DISPATCHER_PORT
+}
+
 impl WindowConfig {
     // 获取当前模式的宽度
     pub fn current_width(&self) -> f64 {
@@ -43,6 +43,10 @@ pub const EXIT_CONFIRMATION_WINDOW_SECS: u64 = 3;
 /// 需要的连续退出尝试次数
 pub const REQUIRED_EXIT_ATTEMPTS: u32 = 2;
 
+// 多渠道回复相关常量
+/// 一个渠道回复后，等待其他渠道撤回请求的最长时间（秒）
+pub const CHANNEL_SETTLE_TIMEOUT_SECS: u64 = 5;
+
 // 应用程序信息结构体
 #[derive(Debug, Clone)]
 pub struct AppInfo {
@@ -18,8 +18,11 @@ pub const TOOL_XI: &str = "xi";
 /// 提示词库搜索工具标识符
 pub const TOOL_CI: &str = "ci";
 
+/// 对话回顾工具标识符
+pub const TOOL_HUI: &str = "hui";
+
 /// 默认启用的工具列表
-pub const DEFAULT_ENABLED_TOOLS: &[&str] = &[TOOL_ZHI, TOOL_JI, TOOL_SOU, TOOL_PAI, TOOL_XI, TOOL_CI];
+pub const DEFAULT_ENABLED_TOOLS: &[&str] = &[TOOL_ZHI, TOOL_JI, TOOL_SOU, TOOL_PAI, TOOL_XI, TOOL_CI, TOOL_HUI];
 
 /// 继续回复默认启用状态
 pub const DEFAULT_CONTINUE_REPLY_ENABLED: bool = true;
@@ -36,6 +39,45 @@ pub const REQUEST_TIMEOUT_MS: u64 = 30000;
 /// MCP 重试次数
 pub const MAX_RETRY_COUNT: u32 = 3;
 
+/// 临时笔记（note）默认有效天数
+pub const MEMORY_NOTE_TTL_DAYS: i64 = 30;
+
+/// 记忆自动整理（归档过期条目）的间隔（小时）
+pub const MEMORY_COMPACTION_INTERVAL_HOURS: i64 = 24;
+
+/// sessions.md 默认保留的会话摘要条数（更早的移入归档）
+pub const SESSION_RETENTION_DEFAULT: usize = 15;
+
+/// xi 默认返回的经验条数
+pub const XI_DEFAULT_RESULT_LIMIT: usize = 5;
+
+/// ci 默认返回的模板条数
+pub const CI_DEFAULT_RESULT_LIMIT: usize = 5;
+
+/// hui 默认返回的对话条数
+pub const HUI_DEFAULT_RESULT_LIMIT: usize = 10;
+
+/// 对话附图默认大小上限（字节）
+pub const CONVERSATION_IMAGE_MAX_BYTES: u64 = 5 * 1024 * 1024;
+
+/// 对话附图解码前允许的最大像素数，防止小文件解压出超大位图
+pub const CONVERSATION_IMAGE_MAX_PIXELS: u64 = 40_000_000;
+
+/// 未被引用的对话附图至少保留的时长（秒），其他进程可能刚保存附图、尚未写入引用它的日志
+pub const CONVERSATION_ASSET_GRACE_SECS: u64 = 60 * 60;
+
+/// pai 默认每批条目数
+pub const PAI_DEFAULT_BATCH_SIZE: usize = 10;
+
+/// pai 无界面执行时默认同时运行的批次数
+pub const PAI_DEFAULT_PARALLELISM: usize = 2;
+
+/// pai 无界面执行时单个批次的默认超时（秒）
+pub const PAI_AGENT_TIMEOUT_SECS: u64 = 30 * 60;
+
+/// 任务文件中保留的子代理输出字符数
+pub const PAI_AGENT_OUTPUT_MAX_CHARS: usize = 4000;
+
 // MCP 工具配置结构体
 #[derive(Debug, Clone)]
 pub struct McpToolConfig {
@@ -75,6 +117,7 @@ impl Default for McpConfig {
                 McpToolConfig::new(TOOL_PAI, false, true), // 子代理派发工具可禁用，默认关闭
                 McpToolConfig::new(TOOL_XI, false, true),  // 经验查找工具可禁用，默认关闭
                 McpToolConfig::new(TOOL_CI, false, true),  // 提示词库搜索工具可禁用，默认关闭
+                McpToolConfig::new(TOOL_HUI, false, true), // 对话回顾工具可禁用，默认关闭
             ],
             continue_reply_enabled: DEFAULT_CONTINUE_REPLY_ENABLED,
             auto_continue_threshold: DEFAULT_AUTO_CONTINUE_THRESHOLD,
@@ -136,5 +179,5 @@ pub fn get_default_mcp_config() -> McpConfig {
 
 /// 检查是否为有效的工具 ID
 pub fn is_valid_tool_id(tool_id: &str) -> bool {
-    matches!(tool_id, TOOL_ZHI | TOOL_JI | TOOL_SOU | TOOL_PAI | TOOL_XI | TOOL_CI)
+    matches!(tool_id, TOOL_ZHI | TOOL_JI | TOOL_SOU | TOOL_PAI | TOOL_XI | TOOL_CI | TOOL_HUI)
 }
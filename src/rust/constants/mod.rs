@@ -6,6 +6,7 @@ pub mod audio;
 pub mod font;
 pub mod mcp;
 pub mod network;
+pub mod notify;
 pub mod telegram;
 pub mod theme;
 pub mod ui;
@@ -0,0 +1,10 @@
+// 通知渠道相关常量
+
+/// 单次通知请求超时时间 (秒)
+pub const REQUEST_TIMEOUT_SECS: u64 = 15;
+
+/// 通知正文最大字符数，超出部分截断
+pub const MAX_MESSAGE_CHARS: usize = 1500;
+
+/// Discord Webhook 消息最大长度
+pub const DISCORD_MAX_CONTENT_CHARS: usize = 2000;
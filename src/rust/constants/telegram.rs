@@ -15,7 +15,7 @@ pub const DEFAULT_HIDE_FRONTEND_POPUP: bool = false;
 /// Telegram API 基础 URL
 pub const API_BASE_URL: &str = "https://api.telegram.org/bot";
 
-/// 消息最大长度
+/// 消息最大长度（按 UTF-16 码元计，与 Telegram 的计数方式一致）
 pub const MAX_MESSAGE_LENGTH: usize = 4096;
 
 /// 请求超时时间 (ms)
@@ -27,6 +27,33 @@ pub const MAX_RETRY_COUNT: u32 = 3;
 /// 轮询间隔 (ms)
 pub const POLLING_INTERVAL_MS: u64 = 1000;
 
+/// getUpdates 长轮询等待时间 (秒)，需小于 Bot 客户端的请求超时
+pub const POLL_TIMEOUT_SECS: u32 = 10;
+
+/// 更新分发主进程的默认本地端口
+pub const DEFAULT_DISPATCHER_PORT: u16 = 9334;
+
+/// Webhook 默认本地监听地址
+pub const DEFAULT_WEBHOOK_LISTEN_ADDR: &str = "127.0.0.1:8787";
+
+/// Webhook 请求体上限
+pub const WEBHOOK_MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// 长消息拆分时每段的 UTF-16 码元数（预留 MarkdownV2 转义后的增长空间）
+pub const SPLIT_CHUNK_UNITS: usize = 3500;
+
+/// 长消息最多拆成几段，超过时改为发送文件
+pub const MAX_SPLIT_PARTS: usize = 3;
+
+/// Bot API 可下载的文件大小上限
+pub const MAX_DOWNLOAD_BYTES: u32 = 20 * 1024 * 1024;
+
+/// 每条 zhi 消息最多转发的 Markdown 图片数
+pub const MAX_FORWARDED_IMAGES: usize = 10;
+
+/// 语音识别超时时间 (秒)
+pub const SPEECH_TO_TEXT_TIMEOUT_SECS: u64 = 120;
+
 // Telegram 配置结构体
 #[derive(Debug, Clone)]
 pub struct TelegramConfig {
@@ -130,7 +157,7 @@ pub fn is_valid_bot_token(token: &str) -> bool {
     !token.is_empty() && token.contains(':')
 }
 
-/// 验证 Chat ID 格式是否有效
+/// 验证 Chat ID 格式是否有效（数字ID或@channel）
 pub fn is_valid_chat_id(chat_id: &str) -> bool {
-    !chat_id.is_empty() && (chat_id.starts_with('-') || chat_id.chars().all(|c| c.is_ascii_digit()))
+    !chat_id.is_empty() && (chat_id.starts_with('-') || chat_id.starts_with('@') || chat_id.chars().all(|c| c.is_ascii_digit()))
 }
@@ -105,6 +105,19 @@ pub async fn get_mcp_tools_config(state: State<'_, AppState>) -> Result<Vec<MCPT
         has_config: false, // 提示词库工具暂无配置选项
     });
     
+    // 对话回顾工具 - 始终存在，无配置选项
+    tools.push(MCPToolConfig {
+        id: mcp::TOOL_HUI.to_string(),
+        name: "对话回顾".to_string(),
+        description: "对话回顾工具。检索 .cunzhi-knowledge/conversations/ 中的历史 zhi 对话，可重新打开某次交互。".to_string(),
+        enabled: config.mcp_config.tools.get(mcp::TOOL_HUI).copied().unwrap_or(false),
+        can_disable: true,
+        icon: "i-carbon-recently-viewed text-lg text-teal-600 dark:text-teal-400".to_string(),
+        icon_bg: "bg-teal-100 dark:bg-teal-900".to_string(),
+        dark_icon_bg: "dark:bg-teal-800".to_string(),
+        has_config: false, // 对话回顾工具暂无配置选项
+    });
+
     // 按启用状态排序，启用的在前
     tools.sort_by(|a, b| b.enabled.cmp(&a.enabled));
     
@@ -10,8 +10,8 @@ use std::sync::Arc;
 use std::time::{Instant, Duration};
 use parking_lot::Mutex;
 
-use super::tools::{InteractionTool, MemoryTool, AcemcpTool, DispatchTool, XiTool, CiTool};
-use super::types::{ZhiRequest, JiyiRequest, PaiRequest, XiRequest, CiRequest};
+use super::tools::{InteractionTool, MemoryTool, AcemcpTool, DispatchTool, XiTool, CiTool, HuiTool};
+use super::types::{ZhiRequest, JiyiRequest, PaiRequest, XiRequest, CiRequest, HuiRequest};
 use crate::config::load_standalone_config;
 use crate::{log_important, log_debug};
 
@@ -143,7 +143,7 @@ impl ServerHandler for ZhiServer {
                 "properties": {
                     "action": {
                         "type": "string",
-                        "description": "操作类型：记忆(添加记忆), 回忆(获取项目信息), 沉淀(写入knowledge), 摘要(添加会话摘要)"
+                        "description": "操作类型：记忆(添加记忆), 回忆(获取项目信息), 沉淀(写入knowledge，可省略 P-/PAT-/R- 编号由系统自动分配), 摘要(添加会话摘要), 置顶/取消置顶(content 为条目 ID 或内容), 整理(归档过期记忆), 查询(按关键词/日期范围搜索历史会话摘要，content 为关键词), 修复/验证/复发(问题状态 open→fixed→verified，复发时自动创建回归条目；content 为说明), 初始化(创建 .cunzhi-knowledge/ 模板并设置 git 远程), 检查(知识库体检：结构、编号、重复条目、git 远程)"
                     },
                     "project_path": {
                         "type": "string",
@@ -156,6 +156,42 @@ impl ServerHandler for ZhiServer {
                     "category": {
                         "type": "string",
                         "description": "记忆分类：rule(规范规则), preference(用户偏好), pattern(最佳实践), context(项目上下文)"
+                    },
+                    "ttl_days": {
+                        "type": "integer",
+                        "description": "有效天数（可选，记忆操作时使用；0 表示永不过期，默认 note 分类 30 天）"
+                    },
+                    "pinned": {
+                        "type": "boolean",
+                        "description": "是否置顶（可选，记忆操作时使用；置顶条目永不过期且总是被回忆）"
+                    },
+                    "date_from": {
+                        "type": "string",
+                        "description": "起始日期 YYYY-MM-DD（可选，查询操作时使用）"
+                    },
+                    "date_to": {
+                        "type": "string",
+                        "description": "结束日期 YYYY-MM-DD（可选，查询操作时使用）"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "返回条数上限（可选，查询操作时使用，默认 10）"
+                    },
+                    "entry_id": {
+                        "type": "string",
+                        "description": "问题编号 P-YYYY-NNN（修复/验证/复发操作时使用，省略时从 content 中提取）"
+                    },
+                    "commit": {
+                        "type": "string",
+                        "description": "修复提交哈希（可选，修复操作时使用）"
+                    },
+                    "repo_url": {
+                        "type": "string",
+                        "description": "共享知识库仓库地址（可选，初始化操作时使用；知识库不存在时从此 clone，省略时使用配置中的 knowledge_repo_url）"
+                    },
+                    "remote_url": {
+                        "type": "string",
+                        "description": "git 远程地址（可选，初始化操作时设置为 origin）"
                     }
                 },
                 "required": ["action", "project_path"]
@@ -164,7 +200,7 @@ impl ServerHandler for ZhiServer {
             if let serde_json::Value::Object(schema_map) = ji_schema {
                 tools.push(Tool {
                     name: Cow::Borrowed("ji"),
-                    description: Some(Cow::Borrowed("全局记忆管理工具。支持 4 种 action：回忆/记忆/沉淀/摘要。必须绑定 git 根目录。用于存储开发规范、用户偏好和最佳实践。")),
+                    description: Some(Cow::Borrowed("全局记忆管理工具。支持 action：回忆/记忆/沉淀/摘要/查询/修复/验证/复发/置顶/取消置顶/整理/初始化/检查。必须绑定 git 根目录。用于存储开发规范、用户偏好和最佳实践。")),
                     input_schema: Arc::new(schema_map),
                     annotations: None,
                 });
@@ -188,6 +224,14 @@ impl ServerHandler for ZhiServer {
                     "project_path": {
                         "type": "string",
                         "description": "项目路径（必需）"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "返回条数上限（可选，默认 5）"
+                    },
+                    "id": {
+                        "type": "string",
+                        "description": "条目编号（可选，如 P-2024-001，传入时返回该条目完整内容）"
                     }
                 },
                 "required": ["query", "project_path"]
@@ -196,7 +240,7 @@ impl ServerHandler for ZhiServer {
             if let serde_json::Value::Object(schema_map) = xi_schema {
                 tools.push(Tool {
                     name: Cow::Borrowed("xi"),
-                    description: Some(Cow::Borrowed("经验查找工具。在 .cunzhi-knowledge/ 中查找相关历史经验（patterns.md、problems.md、regressions.md）。")),
+                    description: Some(Cow::Borrowed("经验查找工具。在 .cunzhi-knowledge/ 中按相关度查找历史经验（patterns.md、problems.md、regressions.md），支持中英文混合与拼写容错，可按编号获取完整条目。")),
                     input_schema: Arc::new(schema_map),
                     annotations: None,
                 });
@@ -210,7 +254,7 @@ impl ServerHandler for ZhiServer {
                 "properties": {
                     "directory": {
                         "type": "string",
-                        "description": "提示词库目录名（如 ci、git、testing）"
+                        "description": "提示词库目录名（如 ci、git、testing），* 表示整个提示词库"
                     },
                     "project_path": {
                         "type": "string",
@@ -218,7 +262,25 @@ impl ServerHandler for ZhiServer {
                     },
                     "query": {
                         "type": "string",
-                        "description": "搜索关键词（可选，用于过滤模板）"
+                        "description": "搜索关键词（可选，按标签、标题、描述排序）"
+                    },
+                    "mode": {
+                        "type": "string",
+                        "enum": ["search", "render"],
+                        "description": "模式：search(搜索模板，默认) 或 render(渲染模板)"
+                    },
+                    "template": {
+                        "type": "string",
+                        "description": "要渲染的模板（render 模式必需，相对目录的路径或模板标题）"
+                    },
+                    "variables": {
+                        "type": "object",
+                        "additionalProperties": { "type": "string" },
+                        "description": "模板变量（render 模式使用，如 {\"branch\": \"main\"}）"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "返回条数上限（可选，默认 5）"
                     }
                 },
                 "required": ["directory", "project_path"]
@@ -227,7 +289,62 @@ impl ServerHandler for ZhiServer {
             if let serde_json::Value::Object(schema_map) = ku_schema {
                 tools.push(Tool {
                     name: Cow::Borrowed("ci"),
-                    description: Some(Cow::Borrowed("提示词库搜索工具。在 .cunzhi-knowledge/prompts/ 中搜索相关模板。触发：用户输入目录名（如 ci、git、testing）。")),
+                    description: Some(Cow::Borrowed("提示词库搜索工具。递归搜索 .cunzhi-knowledge/prompts/ 中的模板（支持 YAML front-matter：title、tags、description、variables），按标签/标题相关度排序；mode=render 时用 variables 替换模板中的 {{变量}}。触发：用户输入目录名（如 ci、git、testing）。")),
+                    input_schema: Arc::new(schema_map),
+                    annotations: None,
+                });
+            }
+        }
+
+        // 对话回顾工具 - 仅在启用时添加
+        if self.is_tool_enabled("hui") {
+            let hui_schema = serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "action": {
+                        "type": "string",
+                        "description": "操作类型：搜索(按条件检索对话记录), 查看(按 id 查看完整记录), 重开(按 id 重新弹出该次交互)，默认为搜索"
+                    },
+                    "project_path": {
+                        "type": "string",
+                        "description": "项目路径（必需，用于定位知识库）"
+                    },
+                    "project": {
+                        "type": "string",
+                        "description": "只看某个项目的对话（可选，项目路径或目录名）"
+                    },
+                    "date_from": {
+                        "type": "string",
+                        "description": "起始日期（可选，YYYY-MM-DD）"
+                    },
+                    "date_to": {
+                        "type": "string",
+                        "description": "结束日期（可选，YYYY-MM-DD）"
+                    },
+                    "text": {
+                        "type": "string",
+                        "description": "关键词（可选，空格分隔，全部命中才算匹配）"
+                    },
+                    "option": {
+                        "type": "string",
+                        "description": "用户选择过的选项（可选）"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "返回条数上限（可选，默认 10）"
+                    },
+                    "id": {
+                        "type": "string",
+                        "description": "对话记录 ID（查看/重开时必需，如 2024-05-01#3）"
+                    }
+                },
+                "required": ["project_path"]
+            });
+
+            if let serde_json::Value::Object(schema_map) = hui_schema {
+                tools.push(Tool {
+                    name: Cow::Borrowed("hui"),
+                    description: Some(Cow::Borrowed("对话回顾工具。按项目、日期范围、关键词、所选选项检索 .cunzhi-knowledge/conversations/ 中的历史 zhi 对话，可按 id 查看完整记录或重新打开该次交互。")),
                     input_schema: Arc::new(schema_map),
                     annotations: None,
                 });
@@ -239,14 +356,43 @@ impl ServerHandler for ZhiServer {
             let pai_schema = serde_json::json!({
                 "type": "object",
                 "properties": {
+                    "action": {
+                        "type": "string",
+                        "description": "操作类型：派发(默认，切分批次并生成子代理提示词), 完成(汇报批次完成，需 job_id 与 batch), 进度(查询剩余批次，省略 job_id 时列出未完成任务), 执行(用配置的命令行代理在后台逐批执行；可传 job_id 续做或 batch 指定批次), 验收(检查 target_file 中新增条目的数量、重复和格式，结果通过寸止窗口汇报)"
+                    },
+                    "project_path": {
+                        "type": "string",
+                        "description": "项目路径（可选，任务文件保存在项目 .cunzhi-memory/dispatch/ 下）"
+                    },
                     "task_type": {
                         "type": "string",
-                        "description": "任务类型（如：补录回归检查、批量重命名、代码审查）"
+                        "description": "任务类型（如：补录回归检查、批量重命名、代码审查；派发时必需）"
                     },
                     "items": {
                         "type": "array",
                         "items": { "type": "string" },
-                        "description": "任务范围列表（显式列表，不用模糊表述）"
+                        "description": "任务范围列表（显式列表，不用模糊表述；派发时必需）"
+                    },
+                    "batch_size": {
+                        "type": "integer",
+                        "description": "每批条目数（可选，默认使用配置 pai_batch_size 或 10）"
+                    },
+                    "job_id": {
+                        "type": "string",
+                        "description": "任务 ID（完成/进度/执行/验收操作时使用）"
+                    },
+                    "batch": {
+                        "type": "integer",
+                        "description": "批次号，从 1 开始（完成操作时必需，执行/验收操作时可选）"
+                    },
+                    "failed_items": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "处理失败的条目（可选，完成操作时使用，其余条目视为完成）"
+                    },
+                    "note": {
+                        "type": "string",
+                        "description": "批次说明（可选，完成操作时使用）"
                     },
                     "source_file": {
                         "type": "string",
@@ -254,7 +400,7 @@ impl ServerHandler for ZhiServer {
                     },
                     "target_file": {
                         "type": "string",
-                        "description": "目标文件路径（可选）"
+                        "description": "目标文件路径（可选，相对路径按项目路径解析；验收时可覆盖派发时的目标文件）"
                     },
                     "output_format": {
                         "type": "string",
@@ -263,15 +409,23 @@ impl ServerHandler for ZhiServer {
                     "extra_steps": {
                         "type": "string",
                         "description": "额外步骤说明（可选）"
+                    },
+                    "acceptance_criteria": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "验收标准列表（可选，默认：条目数量正确、格式符合规范、无重复条目）"
+                    },
+                    "entry_pattern": {
+                        "type": "string",
+                        "description": "每个新增条目必须匹配的正则（可选，验收操作时使用）"
                     }
-                },
-                "required": ["task_type", "items"]
+                }
             });
 
             if let serde_json::Value::Object(schema_map) = pai_schema {
                 tools.push(Tool {
                     name: Cow::Borrowed("pai"),
-                    description: Some(Cow::Borrowed("子代理派发工具。生成子代理提示词供用户复制到新聊天窗口执行。遵循 batch-task.md 工作流，禁止模糊范围。")),
+                    description: Some(Cow::Borrowed("子代理派发工具。按批次切分任务并生成子代理提示词（可用 .cunzhi-knowledge/prompts/pai/<任务类型>.md 模板定制）供用户复制到新聊天窗口执行，任务进度记录在任务文件中，可用 完成/进度 操作跨窗口续做，或用 执行 操作交给命令行代理无界面运行，用 验收 操作检查结果。遵循 batch-task.md 工作流，禁止模糊范围。")),
                     input_schema: Arc::new(schema_map),
                     annotations: None,
                 });
@@ -435,6 +589,26 @@ impl ServerHandler for ZhiServer {
                 // 调用提示词库搜索工具
                 CiTool::search_prompts(ci_request).await
             }
+            "hui" => {
+                // 检查对话回顾工具是否启用
+                if !self.is_tool_enabled("hui") {
+                    return Err(McpError::internal_error(
+                        "对话回顾工具已被禁用".to_string(),
+                        None
+                    ));
+                }
+
+                // 解析请求参数
+                let arguments_value = request.arguments
+                    .map(serde_json::Value::Object)
+                    .unwrap_or(serde_json::Value::Object(serde_json::Map::new()));
+
+                let hui_request: HuiRequest = serde_json::from_value(arguments_value)
+                    .map_err(|e| McpError::invalid_params(format!("参数解析失败: {}", e), None))?;
+
+                // 调用对话回顾工具
+                HuiTool::hui(hui_request).await
+            }
             _ => {
                 Err(McpError::invalid_request(
                     format!("未知的工具: {}", request.name),
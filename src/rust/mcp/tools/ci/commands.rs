@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use super::CiTool;
 use crate::mcp::types::CiRequest;
 
@@ -8,6 +10,14 @@ pub struct ExecuteCiArgs {
     pub project_path: String,
     #[serde(default)]
     pub query: Option<String>,
+    #[serde(default)]
+    pub mode: Option<String>,
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
 }
 
 fn call_tool_result_to_text(result: &rmcp::model::CallToolResult) -> Result<String, String> {
@@ -36,6 +46,10 @@ pub async fn execute_ci_tool(args: ExecuteCiArgs) -> Result<String, String> {
         directory: args.directory,
         project_path: args.project_path,
         query: args.query,
+        mode: args.mode,
+        template: args.template,
+        variables: args.variables,
+        limit: args.limit,
     };
 
     let result = CiTool::search_prompts(req)
@@ -0,0 +1,572 @@
+//! 提示词模板库
+//!
+//! 模板是 prompts/ 下任意层级的 .md/.txt 文件，可带 front-matter（YAML 的常用子集）：
+//!
+//! ```text
+//! ---
+//! title: 提交前检查
+//! tags: [git, review]
+//! description: 提交前让代理自查改动
+//! variables:
+//!   - name: branch
+//!     description: 目标分支
+//!     default: main
+//!   - scope
+//! ---
+//! 请检查 {{scope}} 的改动并合并到 {{branch}}
+//! ```
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::utils::{token_match, tokenize};
+
+/// 各字段权重：标签、标题优先
+const WEIGHT_TAG: f64 = 4.0;
+const WEIGHT_TITLE: f64 = 3.0;
+const WEIGHT_PATH: f64 = 2.0;
+const WEIGHT_DESCRIPTION: f64 = 1.5;
+const WEIGHT_BODY: f64 = 0.5;
+
+/// 模板变量：可只写变量名，也可写完整定义
+#[derive(Debug, Clone, PartialEq)]
+enum RawVariable {
+    Name(String),
+    Full {
+        name: String,
+        description: Option<String>,
+        default: Option<String>,
+        required: Option<bool>,
+    },
+}
+
+#[derive(Debug, Default)]
+struct FrontMatter {
+    title: Option<String>,
+    tags: Vec<String>,
+    description: Option<String>,
+    variables: Vec<RawVariable>,
+}
+
+impl FrontMatter {
+    /// 解析 front-matter：`键: 值`、`[a, b]` 行内列表、`- 项` 块列表，
+    /// 以及 variables 中以 `- name: x` 开头、缩进续写其余字段的变量定义；未知的键连同缩进内容一起忽略。
+    /// 行尾 `# 注释` 会被去掉，多行文本（`|`、`>`）、锚点等其他 YAML 语法报错
+    fn parse(text: &str) -> Result<Self> {
+        let lines: Vec<(usize, usize, &str)> = text
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty() && !l.trim_start().starts_with('#'))
+            .map(|(i, l)| (i + 1, l.len() - l.trim_start().len(), l.trim()))
+            .collect();
+
+        let mut front = Self::default();
+        let mut i = 0;
+        while i < lines.len() {
+            let (line_no, indent, line) = lines[i];
+            let (key, value) = split_key_value(line)
+                .filter(|_| indent == 0)
+                .ok_or_else(|| anyhow!("第 {} 行格式错误: {}", line_no, line))?;
+            // 该键下方缩进的续行
+            let end = lines[i + 1..].iter().position(|(_, indent, _)| *indent == 0).map_or(lines.len(), |p| i + 1 + p);
+            let block = &lines[i + 1..end];
+            i = end;
+
+            match key {
+                "title" | "description" if !block.is_empty() => {
+                    return Err(anyhow!("第 {} 行 {} 不支持多行内容", block[0].0, key));
+                }
+                "title" => front.title = parse_scalar(value, line_no)?,
+                "description" => front.description = parse_scalar(value, line_no)?,
+                "tags" => front.tags = parse_list(value, block, line_no)?,
+                "variables" if value.starts_with('[') || block.is_empty() => {
+                    front.variables = parse_list(value, block, line_no)?.into_iter().map(RawVariable::Name).collect();
+                }
+                "variables" => front.variables = parse_variables(block)?,
+                _ => {}
+            }
+        }
+        Ok(front)
+    }
+}
+
+/// 拆分 `键: 值`，键只能是字母、数字、下划线和连字符
+fn split_key_value(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim();
+    let valid = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    (valid && (value.is_empty() || value.starts_with(' '))).then(|| (key, strip_comment(value.trim())))
+}
+
+/// 去掉行尾的 `# 注释`（引号内的 `#` 保留；`#` 前须有空白，与 YAML 一致）
+fn strip_comment(value: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in value.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if (c == '"' || c == '\'') && matches!(prev, ' ' | '[' | ',') => quote = Some(c),
+            None if c == '#' && prev.is_whitespace() => return value[..i].trim_end(),
+            None => {}
+        }
+        prev = c;
+    }
+    value
+}
+
+/// 标量：去掉成对引号，空值视为未填写；不支持多行文本、锚点、标签等语法
+fn parse_scalar(value: &str, line_no: usize) -> Result<Option<String>> {
+    let value = strip_comment(value.trim());
+    if let Some(indicator) = value.chars().next().filter(|c| "|>&*!{".contains(*c)) {
+        let what = match indicator {
+            '|' | '>' => "多行文本",
+            '&' | '*' => "锚点和别名",
+            '!' => "类型标签",
+            _ => "行内映射",
+        };
+        return Err(anyhow!("第 {} 行不支持 YAML {}（{}）: {}", line_no, what, indicator, value));
+    }
+    for quote in ['"', '\''] {
+        if let Some(rest) = value.strip_prefix(quote) {
+            return rest
+                .strip_suffix(quote)
+                .map(|v| Some(v.to_string()))
+                .ok_or_else(|| anyhow!("第 {} 行引号未闭合: {}", line_no, value));
+        }
+    }
+    Ok((!value.is_empty() && value != "~" && value != "null").then(|| value.to_string()))
+}
+
+/// 列表：行内 `[a, b]`，或下方缩进的 `- 项`
+fn parse_list(value: &str, block: &[(usize, usize, &str)], line_no: usize) -> Result<Vec<String>> {
+    let mut items = Vec::new();
+    if let Some(rest) = value.strip_prefix('[') {
+        let inner = rest.strip_suffix(']').ok_or_else(|| anyhow!("第 {} 行列表未闭合: {}", line_no, value))?;
+        for item in inner.split(',') {
+            items.extend(parse_scalar(item, line_no)?);
+        }
+        return Ok(items);
+    }
+    if !value.is_empty() {
+        return Ok(parse_scalar(value, line_no)?.into_iter().collect());
+    }
+    for (line_no, _, line) in block {
+        let item = line.strip_prefix('-').ok_or_else(|| anyhow!("第 {} 行应为列表项: {}", line_no, line))?;
+        items.extend(parse_scalar(item, *line_no)?);
+    }
+    Ok(items)
+}
+
+/// variables 块：`- 变量名`，或 `- name: x` 后跟缩进的 description/default/required
+fn parse_variables(block: &[(usize, usize, &str)]) -> Result<Vec<RawVariable>> {
+    let mut variables = Vec::new();
+    // 正在读取的完整定义：（列表项缩进, 变量）
+    let mut current: Option<(usize, RawVariable)> = None;
+
+    for &(line_no, indent, line) in block {
+        if let Some(item) = line.strip_prefix('-') {
+            variables.extend(current.take().map(|(_, v)| v));
+            let item = item.trim();
+            match split_key_value(item) {
+                Some((key, value)) => {
+                    let mut variable = RawVariable::Full { name: String::new(), description: None, default: None, required: None };
+                    set_variable_field(&mut variable, key, value, line_no)?;
+                    current = Some((indent, variable));
+                }
+                None => {
+                    let name = parse_scalar(item, line_no)?.ok_or_else(|| anyhow!("第 {} 行缺少变量名", line_no))?;
+                    variables.push(RawVariable::Name(name));
+                }
+            }
+            continue;
+        }
+
+        let (key, value) = current
+            .as_ref()
+            .filter(|(item_indent, _)| indent > *item_indent)
+            .and_then(|_| split_key_value(line))
+            .ok_or_else(|| anyhow!("第 {} 行格式错误: {}", line_no, line))?;
+        if let Some((_, variable)) = current.as_mut() {
+            set_variable_field(variable, key, value, line_no)?;
+        }
+    }
+    variables.extend(current.map(|(_, v)| v));
+
+    if variables.iter().any(|v| matches!(v, RawVariable::Full { name, .. } if name.is_empty())) {
+        return Err(anyhow!("变量定义缺少 name"));
+    }
+    Ok(variables)
+}
+
+fn set_variable_field(variable: &mut RawVariable, key: &str, value: &str, line_no: usize) -> Result<()> {
+    let RawVariable::Full { name, description, default, required } = variable else {
+        return Ok(());
+    };
+    match key {
+        "name" => *name = parse_scalar(value, line_no)?.unwrap_or_default(),
+        "description" => *description = parse_scalar(value, line_no)?,
+        "default" => *default = parse_scalar(value, line_no)?,
+        "required" => {
+            *required = match value {
+                "true" | "yes" => Some(true),
+                "false" | "no" => Some(false),
+                _ => return Err(anyhow!("第 {} 行 required 应为 true 或 false: {}", line_no, value)),
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// 模板变量定义
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptVariable {
+    pub name: String,
+    pub description: Option<String>,
+    pub default: Option<String>,
+    /// 未声明 required 时，没有默认值即为必填
+    pub required: bool,
+}
+
+/// 一个提示词模板
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    /// 相对搜索根目录的路径（使用 `/` 分隔）
+    pub path: String,
+    pub title: String,
+    pub tags: Vec<String>,
+    pub description: Option<String>,
+    pub variables: Vec<PromptVariable>,
+    pub body: String,
+}
+
+fn placeholder_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_-]*)\s*\}\}").unwrap())
+}
+
+impl PromptTemplate {
+    /// 解析模板文件内容，front-matter 格式错误时返回错误
+    pub fn parse(path: &str, content: &str) -> Result<Self> {
+        let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+        let (front, body) = split_front_matter(content);
+        let front = match front {
+            Some(text) => FrontMatter::parse(text).map_err(|e| anyhow!("{}: front-matter 解析失败: {}", path, e))?,
+            None => FrontMatter::default(),
+        };
+
+        let title = front.title
+            .filter(|t| !t.trim().is_empty())
+            .or_else(|| body.lines().find_map(|l| l.strip_prefix("# ")).map(|t| t.trim().to_string()))
+            .unwrap_or_else(|| {
+                Path::new(path).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default()
+            });
+
+        let variables = front.variables.into_iter().map(|v| match v {
+            RawVariable::Name(name) => PromptVariable { name, description: None, default: None, required: true },
+            RawVariable::Full { name, description, default, required } => {
+                let required = required.unwrap_or(default.is_none());
+                PromptVariable { name, description, default, required }
+            }
+        }).collect();
+
+        Ok(Self {
+            path: path.to_string(),
+            title,
+            tags: front.tags,
+            description: front.description.filter(|d| !d.trim().is_empty()),
+            variables,
+            body: body.trim_start_matches(['\r', '\n']).to_string(),
+        })
+    }
+
+    /// 正文中出现但未在 front-matter 声明的变量
+    pub fn undeclared_placeholders(&self) -> Vec<String> {
+        let mut names: Vec<String> = placeholder_regex()
+            .captures_iter(&self.body)
+            .map(|c| c[1].to_string())
+            .filter(|name| !self.variables.iter().any(|v| &v.name == name))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// 替换 `{{变量}}`，缺少必填变量时报错
+    pub fn render(&self, values: &HashMap<String, String>) -> Result<String> {
+        let missing: Vec<&str> = self.variables.iter()
+            .filter(|v| v.required && v.default.is_none() && !values.contains_key(&v.name))
+            .map(|v| v.name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            return Err(anyhow!("缺少必填变量: {}", missing.join(", ")));
+        }
+
+        let rendered = placeholder_regex().replace_all(&self.body, |caps: &regex::Captures| {
+            let name = &caps[1];
+            if let Some(value) = values.get(name) {
+                return value.clone();
+            }
+            match self.variables.iter().find(|v| v.name == name) {
+                Some(var) => var.default.clone().unwrap_or_default(),
+                // 未声明也未传入的占位符原样保留
+                None => caps[0].to_string(),
+            }
+        });
+        Ok(rendered.into_owned())
+    }
+
+    /// 相关度得分，0 表示不相关
+    pub fn score(&self, query: &str) -> f64 {
+        let query_tokens = tokenize(query);
+        if query_tokens.is_empty() {
+            return 0.0;
+        }
+
+        let tag_tokens: Vec<String> = self.tags.iter().flat_map(|t| tokenize(t)).collect();
+        let title_tokens = tokenize(&self.title);
+        let path_tokens = tokenize(&self.path);
+        let description_tokens = tokenize(self.description.as_deref().unwrap_or(""));
+        let body_tokens = tokenize(&self.body);
+
+        let mut score = 0.0;
+        let mut matched = 0usize;
+        for token in &query_tokens {
+            let best = [
+                token_match(token, &tag_tokens) * WEIGHT_TAG,
+                token_match(token, &title_tokens) * WEIGHT_TITLE,
+                token_match(token, &path_tokens) * WEIGHT_PATH,
+                token_match(token, &description_tokens) * WEIGHT_DESCRIPTION,
+                token_match(token, &body_tokens) * WEIGHT_BODY,
+            ]
+            .into_iter()
+            .fold(0.0, f64::max);
+            if best > 0.0 {
+                matched += 1;
+                score += best;
+            }
+        }
+
+        score * matched as f64 / query_tokens.len() as f64
+    }
+}
+
+/// 拆分 front-matter 与正文
+fn split_front_matter(content: &str) -> (Option<&str>, &str) {
+    let Some(rest) = content.strip_prefix("---\n").or_else(|| content.strip_prefix("---\r\n")) else {
+        return (None, content);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    // 没有结束标记时按普通正文处理
+    (None, content)
+}
+
+/// 模板库：递归读取目录下的模板
+#[derive(Debug, Default)]
+pub struct PromptLibrary {
+    pub templates: Vec<PromptTemplate>,
+    /// 解析失败的文件说明
+    pub errors: Vec<String>,
+}
+
+impl PromptLibrary {
+    pub fn load(root: &Path) -> Result<Self> {
+        let mut library = Self::default();
+        library.walk(root, root)?;
+        library.templates.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(library)
+    }
+
+    /// 递归读取模板；子目录或文件读取失败时记入 errors 并继续，不跟随指向目录的符号链接（避免循环）
+    fn walk(&mut self, root: &Path, dir: &Path) -> Result<()> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if dir == root => return Err(e.into()),
+            Err(e) => {
+                self.errors.push(format!("{}: 读取目录失败: {}", relative_path(root, dir), e));
+                return Ok(());
+            }
+        };
+
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.errors.push(format!("{}: 读取目录失败: {}", relative_path(root, dir), e));
+                    continue;
+                }
+            };
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                continue;
+            }
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(e) => {
+                    self.errors.push(format!("{}: 读取失败: {}", relative_path(root, &path), e));
+                    continue;
+                }
+            };
+            if file_type.is_dir() {
+                self.walk(root, &path)?;
+                continue;
+            }
+            if file_type.is_symlink() && path.is_dir() {
+                continue;
+            }
+            let is_template = path.extension()
+                .and_then(|e| e.to_str())
+                .map(|e| e == "md" || e == "txt")
+                .unwrap_or(false);
+            if !is_template {
+                continue;
+            }
+
+            let relative = relative_path(root, &path);
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    self.errors.push(format!("{}: 读取失败: {}", relative, e));
+                    continue;
+                }
+            };
+            match PromptTemplate::parse(&relative, &content) {
+                Ok(template) => self.templates.push(template),
+                Err(e) => self.errors.push(e.to_string()),
+            }
+        }
+        Ok(())
+    }
+
+    /// 按相关度排序；查询为空时按路径列出
+    pub fn search(&self, query: &str, limit: usize) -> Vec<(&PromptTemplate, f64)> {
+        let mut hits: Vec<(&PromptTemplate, f64)> = if query.trim().is_empty() {
+            self.templates.iter().map(|t| (t, 0.0)).collect()
+        } else {
+            self.templates.iter()
+                .map(|t| (t, t.score(query)))
+                .filter(|(_, score)| *score > 0.0)
+                .collect()
+        };
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.path.cmp(&b.0.path)));
+        hits.truncate(limit);
+        hits
+    }
+
+    /// 按路径（可省略扩展名）或标题精确定位模板；找不到时在错误中列出相近的候选，不做模糊选择
+    pub fn find(&self, name: &str) -> Result<&PromptTemplate> {
+        let name = name.trim().trim_start_matches("./");
+        let by_path = self.templates.iter().find(|t| {
+            t.path == name || t.path.rsplit_once('.').map(|(stem, _)| stem == name).unwrap_or(false)
+        });
+        if let Some(template) = by_path {
+            return Ok(template);
+        }
+        if let Some(template) = self.templates.iter().find(|t| t.title.eq_ignore_ascii_case(name)) {
+            return Ok(template);
+        }
+
+        let candidates: Vec<&str> = self.search(name, 3).into_iter().map(|(t, _)| t.path.as_str()).collect();
+        if candidates.is_empty() {
+            Err(anyhow!("未找到模板: {}", name))
+        } else {
+            Err(anyhow!("未找到模板: {}，请使用完整路径或标题，候选：{}", name, candidates.join("、")))
+        }
+    }
+}
+
+/// 相对搜索根目录的路径（使用 `/` 分隔）
+fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_library_ranks_and_renders_templates() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("git/review")).unwrap();
+        fs::write(dir.path().join("git/review/pre-commit.md"), "---\ntitle: 提交前检查\ntags: [git, review]\ndescription: 提交前自查\nvariables:\n  - scope\n  - name: branch\n    default: main\n---\n检查 {{scope}} 并合并到 {{ branch }}，{{other}} 保留\n").unwrap();
+        fs::write(dir.path().join("git/notes.txt"), "# 提交信息规范\n\n使用中文描述 review 结论").unwrap();
+        fs::write(dir.path().join("git/broken.md"), "---\ntags: [unclosed\n---\n正文").unwrap();
+
+        let library = PromptLibrary::load(dir.path()).unwrap();
+        assert_eq!(library.templates.len(), 2);
+        assert_eq!(library.errors.len(), 1);
+
+        // 标签命中排在正文命中之前
+        let hits = library.search("review", 5);
+        assert_eq!(hits[0].0.path, "git/review/pre-commit.md");
+        assert_eq!(hits[1].0.title, "提交信息规范");
+
+        let template = library.find("git/review/pre-commit").unwrap();
+        assert!(template.render(&HashMap::new()).is_err());
+        let values = HashMap::from([("scope".to_string(), "src/".to_string())]);
+        assert_eq!(template.render(&values).unwrap(), "检查 src/ 并合并到 main，{{other}} 保留\n");
+        assert_eq!(template.undeclared_placeholders(), vec!["other"]);
+        assert_eq!(library.find("提交前检查").unwrap().path, template.path);
+
+        // 渲染只接受完整路径或标题，模糊查询只给出候选
+        let err = library.find("pre-commit").unwrap_err().to_string();
+        assert!(err.contains("候选：git/review/pre-commit.md"), "{}", err);
+
+        // 指向上级目录的符号链接不会造成循环
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path(), dir.path().join("git/loop")).unwrap();
+            assert_eq!(PromptLibrary::load(dir.path()).unwrap().templates.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_front_matter_subset() {
+        let template = PromptTemplate::parse("a.md", "---\n# 注释\ntitle: \"部署: 生产\"\ntags:\n  - deploy\n  - 'ops'\nvariables:\n  - name: env\n    description: 环境\n    required: true\n    default: prod\n  - version\nextra:\n  nested: ignored\n---\n部署 {{version}} 到 {{env}}").unwrap();
+        assert_eq!(template.title, "部署: 生产");
+        assert_eq!(template.tags, vec!["deploy", "ops"]);
+        assert_eq!(template.variables, vec![
+            PromptVariable { name: "env".to_string(), description: Some("环境".to_string()), default: Some("prod".to_string()), required: true },
+            PromptVariable { name: "version".to_string(), description: None, default: None, required: true },
+        ]);
+
+        // 行尾注释被去掉，引号内的 # 保留
+        let template = PromptTemplate::parse("c.md", "---\ntitle: 部署 # 生产环境\ndescription: \"#1 优先\" # 说明\ntags: [a, 'b # c'] # 标签\n---\n正文").unwrap();
+        assert_eq!(template.title, "部署");
+        assert_eq!(template.description.as_deref(), Some("#1 优先"));
+        assert_eq!(template.tags, vec!["a", "b # c"]);
+
+        for broken in [
+            "title 缺少冒号",
+            "tags: [a, b",
+            "title: \"未闭合",
+            "variables:\n  - description: 没有名字",
+            "variables:\n  - name: a\n  required: maybe",
+            "description: |\n  第一行\n  第二行",
+            "description: >-\n  折叠",
+            "title: 第一行\n  第二行",
+            "variables:\n  - name: a\n    description: |\n      多行",
+            "tags: &common [a]",
+        ] {
+            assert!(PromptTemplate::parse("b.md", &format!("---\n{}\n---\n正文", broken)).is_err(), "{}", broken);
+        }
+    }
+}
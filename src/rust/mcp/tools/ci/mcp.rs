@@ -1,23 +1,25 @@
 use anyhow::Result;
 use rmcp::{Error as McpError, model::*};
 use std::fs;
-use std::path::Path;
+use std::path::{Component, Path};
 
+use super::library::{PromptLibrary, PromptTemplate};
+use crate::constants::mcp::CI_DEFAULT_RESULT_LIMIT;
 use crate::mcp::types::CiRequest;
 
 /// 提示词库搜索工具
 ///
-/// 在 .cunzhi-knowledge/prompts/ 中搜索相关模板
+/// 在 .cunzhi-knowledge/prompts/ 中搜索相关模板，并可按变量渲染
 #[derive(Clone)]
 pub struct CiTool;
 
 impl CiTool {
-    /// 搜索提示词库
+    /// 搜索或渲染提示词模板
     pub async fn search_prompts(
         request: CiRequest,
     ) -> Result<CallToolResult, McpError> {
         let project_path = Path::new(&request.project_path);
-        
+
         // 验证项目路径
         if !project_path.exists() {
             return Err(McpError::invalid_params(
@@ -42,8 +44,20 @@ impl CiTool {
             )]));
         }
 
-        let dir_name = request.directory.to_lowercase();
-        let target_dir = prompts_dir.join(&dir_name);
+        // 目录为空或 * 时搜索整个提示词库
+        let dir_name = request.directory.trim().trim_matches('/').to_lowercase();
+        let (target_dir, display_dir) = if dir_name.is_empty() || dir_name == "*" {
+            (prompts_dir.clone(), "prompts/".to_string())
+        } else {
+            // 只允许 prompts/ 下的相对子目录，拒绝 `..`、绝对路径等
+            if !Path::new(&dir_name).components().all(|c| matches!(c, Component::Normal(_))) {
+                return Err(McpError::invalid_params(
+                    format!("目录必须是 prompts/ 下的子目录: {}", request.directory),
+                    None
+                ));
+            }
+            (prompts_dir.join(&dir_name), format!("prompts/{}/", dir_name))
+        };
 
         // 检查目录是否存在
         if !target_dir.exists() || !target_dir.is_dir() {
@@ -54,18 +68,107 @@ impl CiTool {
             )]));
         }
 
-        // 搜索目录中的模板
+        // 子目录是指向外部的符号链接时同样拒绝
+        let inside = match (target_dir.canonicalize(), prompts_dir.canonicalize()) {
+            (Ok(target), Ok(root)) => target.starts_with(root),
+            _ => false,
+        };
+        if !inside {
+            return Err(McpError::invalid_params(
+                format!("目录必须是 prompts/ 下的子目录: {}", request.directory),
+                None
+            ));
+        }
+
+        let library = PromptLibrary::load(&target_dir)
+            .map_err(|e| McpError::internal_error(format!("读取提示词库失败: {}", e), None))?;
+
+        match request.mode.as_deref().map(str::trim).unwrap_or("search") {
+            "" | "search" | "搜索" => Ok(Self::search(&library, &request, &display_dir)),
+            "render" | "渲染" => Self::render(&library, &request, &display_dir),
+            other => Err(McpError::invalid_params(
+                format!("未知的模式: {}，支持：search、render", other),
+                None
+            )),
+        }
+    }
+
+    fn search(library: &PromptLibrary, request: &CiRequest, display_dir: &str) -> CallToolResult {
         let query = request.query.as_deref().unwrap_or("");
-        let results = Self::search_in_directory(&target_dir, query)?;
+        let limit = request.limit.filter(|l| *l > 0).unwrap_or(CI_DEFAULT_RESULT_LIMIT);
+        let hits = library.search(query, limit);
+        let errors_hint = Self::errors_hint(library);
+
+        if hits.is_empty() {
+            return CallToolResult::success(vec![Content::text(
+                format!("📭 在 `{}` 中未找到匹配的模板{}", display_dir, errors_hint)
+            )]);
+        }
+
+        let results: Vec<String> = hits.iter().map(|(template, _)| Self::summary(template)).collect();
+        CallToolResult::success(vec![Content::text(format!(
+            "# 📚 提示词库搜索结果\n\n目录：`{}`（共 {} 条，传入 mode=render 与 template 渲染模板）\n\n{}{}",
+            display_dir,
+            hits.len(),
+            results.join("\n\n---\n\n"),
+            errors_hint
+        ))])
+    }
+
+    fn render(library: &PromptLibrary, request: &CiRequest, display_dir: &str) -> Result<CallToolResult, McpError> {
+        let name = request.template.as_deref()
+            .or(request.query.as_deref())
+            .filter(|n| !n.trim().is_empty())
+            .ok_or_else(|| McpError::invalid_params("render 模式需要 template 参数".to_string(), None))?;
+
+        let template = library.find(name)
+            .map_err(|e| McpError::invalid_params(e.to_string(), None))?;
+        let rendered = template.render(&request.variables)
+            .map_err(|e| McpError::invalid_params(format!("{}\n\n{}", e, Self::variables_doc(template)), None))?;
+
+        Ok(CallToolResult::success(vec![Content::text(
+            format!("<!-- {}{} -->\n{}", display_dir, template.path, rendered)
+        )]))
+    }
 
-        if results.is_empty() {
-            Ok(CallToolResult::success(vec![Content::text(
-                format!("📭 在 `prompts/{}/` 中未找到匹配的模板", dir_name)
-            )]))
+    /// 模板摘要：标题、标签、描述、变量和正文前 20 行
+    fn summary(template: &PromptTemplate) -> String {
+        let mut out = format!("## {}\n\n`{}`", template.title, template.path);
+        if !template.tags.is_empty() {
+            out.push_str(&format!("  🏷️ {}", template.tags.join(", ")));
+        }
+        if let Some(description) = &template.description {
+            out.push_str(&format!("\n\n{}", description));
+        }
+        if !template.variables.is_empty() {
+            out.push_str(&format!("\n\n{}", Self::variables_doc(template)));
+        }
+        out.push_str(&format!("\n\n{}", Self::get_file_summary(&template.body)));
+        out
+    }
+
+    fn variables_doc(template: &PromptTemplate) -> String {
+        let lines: Vec<String> = template.variables.iter().map(|v| {
+            let mut line = format!("- `{}`", v.name);
+            if v.required {
+                line.push_str("（必填）");
+            }
+            if let Some(default) = &v.default {
+                line.push_str(&format!(" 默认 `{}`", default));
+            }
+            if let Some(description) = &v.description {
+                line.push_str(&format!("：{}", description));
+            }
+            line
+        }).collect();
+        format!("**变量**：\n{}", lines.join("\n"))
+    }
+
+    fn errors_hint(library: &PromptLibrary) -> String {
+        if library.errors.is_empty() {
+            String::new()
         } else {
-            Ok(CallToolResult::success(vec![Content::text(
-                format!("# 📚 提示词库搜索结果\n\n目录：`prompts/{}/`\n\n{}", dir_name, results)
-            )]))
+            format!("\n\n⚠️ 以下模板无法解析：\n{}", library.errors.iter().map(|e| format!("- {}", e)).collect::<Vec<_>>().join("\n"))
         }
     }
 
@@ -87,49 +190,7 @@ impl CiTool {
         dirs.join("\n")
     }
 
-    /// 在目录中搜索模板
-    fn search_in_directory(dir: &Path, query: &str) -> Result<String, McpError> {
-        let mut results = Vec::new();
-        let query_lower = query.to_lowercase();
-
-        if let Ok(entries) = fs::read_dir(dir) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.is_file() {
-                    if let Some(ext) = path.extension() {
-                        if ext == "md" || ext == "txt" {
-                            if let Some(filename) = path.file_name().and_then(|n| n.to_str()) {
-                                // 如果没有查询或文件名/内容匹配
-                                if query.is_empty() {
-                                    // 列出所有文件
-                                    if let Ok(content) = fs::read_to_string(&path) {
-                                        let summary = Self::get_file_summary(&content);
-                                        results.push(format!("## {}\n\n{}", filename, summary));
-                                    }
-                                } else {
-                                    // 搜索匹配的文件
-                                    let filename_lower = filename.to_lowercase();
-                                    let content = fs::read_to_string(&path).unwrap_or_default();
-                                    let content_lower = content.to_lowercase();
-
-                                    if filename_lower.contains(&query_lower) || content_lower.contains(&query_lower) {
-                                        let summary = Self::get_file_summary(&content);
-                                        results.push(format!("## {}\n\n{}", filename, summary));
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        // 限制返回数量
-        results.truncate(5);
-        Ok(results.join("\n\n---\n\n"))
-    }
-
-    /// 获取文件摘要（前 20 行）
+    /// 获取正文摘要（前 20 行）
     fn get_file_summary(content: &str) -> String {
         let lines: Vec<&str> = content.lines().take(20).collect();
         let truncated = if content.lines().count() > 20 { "\n\n..." } else { "" };
@@ -2,5 +2,7 @@
 
 pub mod mcp;
 pub mod commands;
+pub mod library;
 
 pub use mcp::CiTool;
+pub use library::{PromptLibrary, PromptTemplate, PromptVariable};
@@ -0,0 +1,402 @@
+//! 派发任务记录
+//!
+//! 每次 pai 派发生成一个任务文件（`.cunzhi-memory/dispatch/<任务ID>.json`），
+//! 记录批次划分和每个条目的完成状态，换一个聊天窗口也能接着做剩下的批次
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::utils::{update_locked, write_locked};
+
+/// 任务文件目录（位于项目 .cunzhi-memory/ 下）
+pub const DISPATCH_DIR: &str = "dispatch";
+
+/// 条目状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemStatus {
+    Pending,
+    Done,
+    Failed,
+}
+
+/// 批次状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+impl BatchStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            BatchStatus::Pending => "⏳ 待执行",
+            BatchStatus::Running => "🔄 执行中",
+            BatchStatus::Done => "✅ 已完成",
+            BatchStatus::Failed => "❌ 有失败",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DispatchItem {
+    pub name: String,
+    pub status: ItemStatus,
+}
+
+/// 一个批次
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DispatchBatch {
+    /// 批次号，从 1 开始
+    pub number: usize,
+    pub items: Vec<DispatchItem>,
+    pub status: BatchStatus,
+    #[serde(default)]
+    pub started_at: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub completed_at: Option<DateTime<Local>>,
+    /// 无界面执行时负责该批次的进程 ID
+    #[serde(default)]
+    pub runner_pid: Option<u32>,
+    /// 无界面执行的截止时间，超过后仍为执行中视为中断
+    #[serde(default)]
+    pub deadline: Option<DateTime<Local>>,
+    /// 子代理汇报的说明
+    #[serde(default)]
+    pub note: Option<String>,
+    /// 无界面执行时子进程的退出码
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// 无界面执行时子进程的输出（仅保留末尾）
+    #[serde(default)]
+    pub output: Option<String>,
+    /// 启动失败、超时或 stderr 内容
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// 一次派发任务
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DispatchJob {
+    pub id: String,
+    pub task_type: String,
+    pub created_at: DateTime<Local>,
+    pub updated_at: DateTime<Local>,
+    #[serde(default)]
+    pub source_file: Option<String>,
+    #[serde(default)]
+    pub target_file: Option<String>,
+    #[serde(default)]
+    pub output_format: Option<String>,
+    #[serde(default)]
+    pub extra_steps: Option<String>,
+    /// 验收标准，为空时使用默认标准
+    #[serde(default)]
+    pub acceptance_criteria: Vec<String>,
+    /// 派发时目标文件中已有的条目标题，验收时据此找出新增条目
+    #[serde(default)]
+    pub target_baseline: Vec<String>,
+    pub batch_size: usize,
+    pub batches: Vec<DispatchBatch>,
+}
+
+impl DispatchJob {
+    /// 按批次大小切分条目
+    pub fn new(task_type: &str, items: &[String], batch_size: usize) -> Self {
+        let now = Local::now();
+        let batch_size = batch_size.max(1);
+        let batches = items
+            .chunks(batch_size)
+            .enumerate()
+            .map(|(i, chunk)| DispatchBatch {
+                number: i + 1,
+                items: chunk.iter().map(|name| DispatchItem { name: name.clone(), status: ItemStatus::Pending }).collect(),
+                status: BatchStatus::Pending,
+                started_at: None,
+                completed_at: None,
+                runner_pid: None,
+                deadline: None,
+                note: None,
+                exit_code: None,
+                output: None,
+                error: None,
+            })
+            .collect();
+        let suffix = uuid::Uuid::new_v4().simple().to_string();
+
+        Self {
+            id: format!("{}-{}", now.format("%Y%m%d-%H%M%S"), &suffix[..6]),
+            task_type: task_type.to_string(),
+            created_at: now,
+            updated_at: now,
+            source_file: None,
+            target_file: None,
+            output_format: None,
+            extra_steps: None,
+            acceptance_criteria: Vec::new(),
+            target_baseline: Vec::new(),
+            batch_size,
+            batches,
+        }
+    }
+
+    pub fn item_count(&self) -> usize {
+        self.batches.iter().map(|b| b.items.len()).sum()
+    }
+
+    pub fn batch(&self, number: usize) -> Result<&DispatchBatch> {
+        self.batches.iter().find(|b| b.number == number)
+            .ok_or_else(|| anyhow!("批次 {} 不存在（共 {} 批）", number, self.batches.len()))
+    }
+
+    pub fn batch_mut(&mut self, number: usize) -> Result<&mut DispatchBatch> {
+        let total = self.batches.len();
+        self.batches.iter_mut().find(|b| b.number == number)
+            .ok_or_else(|| anyhow!("批次 {} 不存在（共 {} 批）", number, total))
+    }
+
+    /// 汇报批次完成：未列入 failed 的条目标记为完成，可重复汇报以覆盖上次结果
+    pub fn complete_batch(&mut self, number: usize, failed: &[String], note: Option<String>) -> Result<()> {
+        let batch = self.batch_mut(number)?;
+        let failed: Vec<&str> = failed.iter().map(|f| f.trim()).filter(|f| !f.is_empty()).collect();
+        let unknown: Vec<&str> = failed.iter()
+            .filter(|f| !batch.items.iter().any(|item| item.name.trim() == **f))
+            .copied()
+            .collect();
+        if !unknown.is_empty() {
+            return Err(anyhow!("以下条目不属于批次 {}: {}", number, unknown.join("、")));
+        }
+
+        for item in &mut batch.items {
+            item.status = if failed.contains(&item.name.trim()) { ItemStatus::Failed } else { ItemStatus::Done };
+        }
+        batch.status = if failed.is_empty() { BatchStatus::Done } else { BatchStatus::Failed };
+        batch.completed_at = Some(Local::now());
+        batch.note = note.filter(|n| !n.trim().is_empty());
+        self.updated_at = Local::now();
+        Ok(())
+    }
+
+    /// 执行进程已退出或超过截止时间仍为执行中的批次视为失败（进程崩溃或被强制结束时不会写回结果）
+    pub fn expire_stale_runs(&mut self) -> bool {
+        let now = Local::now();
+        let mut changed = false;
+        for batch in self.batches.iter_mut().filter(|b| b.status == BatchStatus::Running) {
+            let expired = batch.deadline.map(|d| now > d).unwrap_or(false);
+            let exited = batch.runner_pid
+                .map(|pid| !crate::ui::window_registry::is_process_running(pid))
+                .unwrap_or(false);
+            if !(expired || exited) {
+                continue;
+            }
+            for item in &mut batch.items {
+                if item.status != ItemStatus::Done {
+                    item.status = ItemStatus::Failed;
+                }
+            }
+            batch.status = BatchStatus::Failed;
+            batch.error = Some(if exited { "执行进程已退出，未写回结果".to_string() } else { "超过截止时间仍未写回结果".to_string() });
+            batch.completed_at = Some(now);
+            changed = true;
+        }
+        if changed {
+            self.updated_at = now;
+        }
+        changed
+    }
+
+    /// 尚未完成的批次（待执行、执行中或有失败条目）
+    pub fn remaining_batches(&self) -> Vec<&DispatchBatch> {
+        self.batches.iter().filter(|b| b.status != BatchStatus::Done).collect()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.remaining_batches().is_empty()
+    }
+
+    /// 进度报告
+    pub fn render_progress(&self) -> String {
+        let done = self.batches.iter()
+            .flat_map(|b| &b.items)
+            .filter(|i| i.status == ItemStatus::Done)
+            .count();
+        let mut out = format!(
+            "## 📦 派发任务 `{}`\n\n**任务类型**: {}\n**进度**: {}/{} 条，{}/{} 批完成\n",
+            self.id,
+            self.task_type,
+            done,
+            self.item_count(),
+            self.batches.len() - self.remaining_batches().len(),
+            self.batches.len(),
+        );
+        for batch in &self.batches {
+            out.push_str(&format!("\n- 批次 {} {}（{} 条）", batch.number, batch.status.label(), batch.items.len()));
+            let failed: Vec<&str> = batch.items.iter()
+                .filter(|i| i.status == ItemStatus::Failed)
+                .map(|i| i.name.as_str())
+                .collect();
+            if !failed.is_empty() {
+                out.push_str(&format!("\n  失败：{}", failed.join("、")));
+            }
+            if let Some(note) = &batch.note {
+                out.push_str(&format!("\n  说明：{}", note));
+            }
+            if let Some(code) = batch.exit_code {
+                out.push_str(&format!("\n  退出码：{}", code));
+            }
+            if let Some(error) = &batch.error {
+                out.push_str(&format!("\n  错误：{}", error.lines().last().unwrap_or("")));
+            }
+        }
+
+        let remaining = self.remaining_batches();
+        if remaining.is_empty() {
+            out.push_str("\n\n🎉 全部批次已完成");
+        } else {
+            let numbers: Vec<String> = remaining.iter().map(|b| b.number.to_string()).collect();
+            out.push_str(&format!("\n\n**剩余批次**: {}", numbers.join(", ")));
+        }
+        out
+    }
+}
+
+/// 任务文件存储
+#[derive(Debug, Clone)]
+pub struct JobStore {
+    dir: PathBuf,
+}
+
+impl JobStore {
+    /// 项目下的任务目录；未提供项目路径时使用全局配置目录
+    pub fn for_project(project_path: Option<&str>) -> Result<Self> {
+        let dir = match project_path.map(str::trim).filter(|p| !p.is_empty()) {
+            Some(path) => {
+                let normalized = crate::mcp::utils::decode_and_normalize_path(path)
+                    .map_err(|e| anyhow!("路径格式错误: {}", e))?;
+                Path::new(&normalized).join(".cunzhi-memory").join(DISPATCH_DIR)
+            }
+            None => dirs::config_dir()
+                .ok_or_else(|| anyhow!("无法获取配置目录"))?
+                .join("cunzhi")
+                .join(DISPATCH_DIR),
+        };
+        Ok(Self { dir })
+    }
+
+    pub fn new(dir: &Path) -> Self {
+        Self { dir: dir.to_path_buf() }
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf> {
+        if id.is_empty() || id.contains(['/', '\\']) || id.contains("..") {
+            return Err(anyhow!("无效的任务 ID: {}", id));
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+
+    /// 整体写入任务文件（仅用于新建任务），已有任务的修改使用 update
+    pub fn save(&self, job: &DispatchJob) -> Result<()> {
+        write_locked(&self.path(&job.id)?, serde_json::to_string_pretty(job)?)
+    }
+
+    pub fn load(&self, id: &str) -> Result<DispatchJob> {
+        let path = self.path(id.trim())?;
+        let content = fs::read_to_string(&path).map_err(|_| anyhow!("未找到派发任务: {}", id))?;
+        let mut job = parse_job(&path, &content)?;
+        job.expire_stale_runs();
+        Ok(job)
+    }
+
+    /// 在文件锁内读取、修改并写回任务，避免与其他进程（如子代理汇报完成）的写入互相覆盖
+    pub fn update<T, F>(&self, id: &str, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut DispatchJob) -> Result<T>,
+    {
+        let path = self.path(id.trim())?;
+        if !path.exists() {
+            return Err(anyhow!("未找到派发任务: {}", id));
+        }
+        update_locked(&path, "", |content| {
+            let mut job = parse_job(&path, content)?;
+            job.expire_stale_runs();
+            let result = f(&mut job)?;
+            *content = serde_json::to_string_pretty(&job)?;
+            Ok(result)
+        })
+    }
+
+    /// 所有任务，最近创建的在前
+    pub fn list(&self) -> Result<Vec<DispatchJob>> {
+        let mut jobs = Vec::new();
+        if !self.dir.exists() {
+            return Ok(jobs);
+        }
+        for entry in fs::read_dir(&self.dir)?.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match fs::read_to_string(&path).ok().and_then(|c| serde_json::from_str::<DispatchJob>(&c).ok()) {
+                Some(mut job) => {
+                    job.expire_stale_runs();
+                    jobs.push(job);
+                }
+                None => crate::log_important!(warn, "跳过无法解析的任务文件: {}", path.display()),
+            }
+        }
+        jobs.sort_by_key(|job| std::cmp::Reverse(job.created_at));
+        Ok(jobs)
+    }
+}
+
+fn parse_job(path: &Path, content: &str) -> Result<DispatchJob> {
+    serde_json::from_str(content).map_err(|e| anyhow!("任务文件解析失败 {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_job_batches_and_progress_persist() {
+        let dir = tempdir().unwrap();
+        let store = JobStore::new(dir.path());
+        let items: Vec<String> = (1..=5).map(|i| format!("P-2024-00{}", i)).collect();
+        let job = DispatchJob::new("补录回归检查", &items, 2);
+        assert_eq!(job.batches.iter().map(|b| b.items.len()).collect::<Vec<_>>(), vec![2, 2, 1]);
+        store.save(&job).unwrap();
+
+        store.update(&job.id, |job| {
+            job.complete_batch(1, &[], None)?;
+            job.complete_batch(2, &["P-2024-004".to_string()], Some("源文件缺少条目".to_string()))
+        }).unwrap();
+        assert!(store.update(&job.id, |job| job.complete_batch(3, &["P-2024-001".to_string()], None)).is_err());
+
+        let loaded = store.load(&job.id).unwrap();
+        let remaining: Vec<usize> = loaded.remaining_batches().iter().map(|b| b.number).collect();
+        assert_eq!(remaining, vec![2, 3]);
+        assert_eq!(loaded.batch(2).unwrap().items[1].status, ItemStatus::Failed);
+        assert!(loaded.render_progress().contains("3/5 条"));
+        assert_eq!(store.list().unwrap().len(), 1);
+        assert!(store.load("../config").is_err());
+
+        // 执行进程中断后遗留的执行中批次在加载时判定为失败
+        store.update(&job.id, |job| {
+            let batch = job.batch_mut(3)?;
+            batch.status = BatchStatus::Running;
+            batch.runner_pid = Some(std::process::id());
+            batch.deadline = Some(Local::now() - chrono::Duration::seconds(1));
+            Ok(())
+        }).unwrap();
+        let stale = store.load(&job.id).unwrap();
+        let batch = stale.batch(3).unwrap();
+        assert_eq!((batch.status, batch.items[0].status), (BatchStatus::Failed, ItemStatus::Failed));
+    }
+}
@@ -1,103 +1,284 @@
 use rmcp::{Error as McpError, model::*};
 
+use std::sync::Arc;
+
+use super::job::{BatchStatus, DispatchJob, JobStore};
+use super::prompt::{build_prompt, SubagentPrompt};
+use super::runner::{run_batches, AgentCommand};
+use super::verify::{baseline_headings, resolve_target, verify};
+use crate::constants::mcp::PAI_DEFAULT_BATCH_SIZE;
 use crate::mcp::types::PaiRequest;
 use crate::mcp::{PopupRequest, handlers::create_tauri_popup, utils::generate_request_id};
-use crate::log_debug;
+use crate::{log_debug, log_important};
 
 /// 子代理派发工具
 ///
-/// 根据任务参数生成子代理提示词，供用户复制到新聊天窗口
+/// 根据任务参数切分批次、记录任务文件，并生成子代理提示词供用户复制到新聊天窗口
 #[derive(Clone)]
 pub struct DispatchTool;
 
 impl DispatchTool {
-    /// 生成子代理提示词
-    fn generate_subagent_prompt(request: &PaiRequest) -> String {
-        let items_list = request.items
-            .iter()
-            .enumerate()
-            .map(|(i, item)| format!("{}. {}", i + 1, item))
-            .collect::<Vec<_>>()
-            .join("\n");
-
-        let mut prompt = format!(
-            r#"## 子代理任务
-
-**任务类型**: {}
-**范围**（共 {} 个）：
-{}
-"#,
-            request.task_type,
-            request.items.len(),
-            items_list
-        );
+    pub async fn pai(request: PaiRequest) -> Result<CallToolResult, McpError> {
+        let store = JobStore::for_project(request.project_path.as_deref())
+            .map_err(|e| McpError::invalid_params(e.to_string(), None))?;
 
-        // 添加源文件和目标文件
-        if let Some(ref source) = request.source_file {
-            prompt.push_str(&format!("**源文件**: {}\n", source));
-        }
-        if let Some(ref target) = request.target_file {
-            prompt.push_str(&format!("**目标文件**: {}\n", target));
+        let action = request.action.as_deref().map(str::trim).unwrap_or("派发").to_string();
+        match action.as_str() {
+            "" | "派发" | "dispatch" => Self::dispatch(request, &store).await,
+            "完成" | "complete" => Self::complete(request, &store),
+            "进度" | "progress" => Self::progress(request, &store),
+            "执行" | "run" => Self::run(request, store),
+            "验收" | "verify" => Self::verify(request, &store),
+            _ => Err(McpError::invalid_params(
+                format!("未知的操作类型: {}，支持：派发、完成、进度、执行、验收", action),
+                None,
+            )),
         }
+    }
 
-        // 添加步骤
-        prompt.push_str("\n### 步骤\n");
-        prompt.push_str("1. 读取源文件中以上列表对应的条目\n");
-        prompt.push_str("2. 按格式要求生成目标内容");
+    /// 汇报批次完成
+    fn complete(request: PaiRequest, store: &JobStore) -> Result<CallToolResult, McpError> {
+        let id = Self::job_id(&request)?;
+        let batch = request.batch
+            .ok_or_else(|| McpError::invalid_params("缺少批次号 batch".to_string(), None))?;
+        let job = store.update(id, |job| {
+            job.complete_batch(batch, &request.failed_items, request.note.clone())?;
+            Ok(job.clone())
+        }).map_err(|e| McpError::invalid_params(e.to_string(), None))?;
 
-        // 添加输出格式模板
-        if let Some(ref format) = request.output_format {
-            prompt.push_str(&format!("，格式：\n\n{}\n", format));
-        } else {
-            prompt.push_str("\n");
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "已记录批次 {} 的结果\n\n{}",
+            batch,
+            job.render_progress()
+        ))]))
+    }
+
+    /// 查询进度：指定任务时显示详情，否则列出未完成的任务
+    fn progress(request: PaiRequest, store: &JobStore) -> Result<CallToolResult, McpError> {
+        if request.job_id.as_deref().map(|id| !id.trim().is_empty()).unwrap_or(false) {
+            let job = Self::require_job(&request, store)?;
+            return Ok(CallToolResult::success(vec![Content::text(job.render_progress())]));
         }
 
-        prompt.push_str("3. 追加到目标文件末尾\n");
-        prompt.push_str("4. 完成后报告：已处理 X 条\n");
+        let jobs = store.list()
+            .map_err(|e| McpError::internal_error(format!("读取任务文件失败: {}", e), None))?;
+        let unfinished: Vec<String> = jobs.iter()
+            .filter(|job| !job.is_finished())
+            .map(|job| {
+                let remaining: Vec<String> = job.remaining_batches().iter().map(|b| b.number.to_string()).collect();
+                format!(
+                    "- `{}` {}（{} 条，剩余批次 {}）",
+                    job.id,
+                    job.task_type,
+                    job.item_count(),
+                    remaining.join(", ")
+                )
+            })
+            .collect();
 
-        // 添加额外步骤
-        if let Some(ref extra) = request.extra_steps {
-            prompt.push_str(&format!("\n### 额外说明\n{}\n", extra));
+        if unfinished.is_empty() {
+            return Ok(CallToolResult::success(vec![Content::text("📭 没有未完成的派发任务")]));
         }
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "## 📦 未完成的派发任务\n\n{}\n\n传入 job_id 查看详情",
+            unfinished.join("\n")
+        ))]))
+    }
 
-        // 添加验收标准和汇报要求
-        prompt.push_str(r#"
-### 验收标准
-- 条目数量正确
-- 格式符合规范
-- 无重复条目
-
-### 完成后汇报（必须）
-任务完成后，**必须调用 `zhi` 工具**向用户汇报结果，包含：
-- 处理条目数量
-- 完成的操作列表
-- Git commit 信息（如有）
+    fn job_id(request: &PaiRequest) -> Result<&str, McpError> {
+        request.job_id.as_deref().map(str::trim).filter(|id| !id.is_empty())
+            .ok_or_else(|| McpError::invalid_params("缺少任务 ID job_id".to_string(), None))
+    }
 
-*你是子代理现在帮我做*：
-"#);
+    fn require_job(request: &PaiRequest, store: &JobStore) -> Result<DispatchJob, McpError> {
+        store.load(Self::job_id(request)?).map_err(|e| McpError::invalid_params(e.to_string(), None))
+    }
 
-        prompt
+    /// 批次大小：请求参数 > 配置 > 默认值
+    fn batch_size(request: &PaiRequest) -> usize {
+        request.batch_size
+            .or_else(|| crate::config::load_standalone_config().ok().and_then(|c| c.mcp_config.pai_batch_size))
+            .filter(|size| *size > 0)
+            .unwrap_or(PAI_DEFAULT_BATCH_SIZE)
     }
 
-    pub async fn pai(request: PaiRequest) -> Result<CallToolResult, McpError> {
-        log_debug!("生成子代理提示词，任务类型: {}, 条目数: {}", 
-            request.task_type, request.items.len());
+    fn prompt(job: &DispatchJob, project_path: Option<&str>) -> Result<SubagentPrompt, McpError> {
+        build_prompt(job, project_path)
+            .map_err(|e| McpError::invalid_params(format!("生成子代理提示词失败: {}", e), None))
+    }
 
+    /// 切分批次并保存任务文件
+    fn create_job(request: &PaiRequest, store: &JobStore) -> Result<DispatchJob, McpError> {
         if request.items.is_empty() {
             return Err(McpError::invalid_params(
                 "任务范围列表不能为空",
                 None
             ));
         }
+        if request.task_type.trim().is_empty() {
+            return Err(McpError::invalid_params("缺少任务类型 task_type", None));
+        }
 
-        let prompt = Self::generate_subagent_prompt(&request);
+        let mut job = DispatchJob::new(&request.task_type, &request.items, Self::batch_size(request));
+        job.source_file = request.source_file.clone();
+        job.target_file = request.target_file.clone();
+        job.output_format = request.output_format.clone();
+        job.extra_steps = request.extra_steps.clone();
+        job.acceptance_criteria = request.acceptance_criteria.iter()
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .collect();
+        // 记录目标文件现有条目，验收时据此找出新增条目
+        if let Some(target) = &job.target_file {
+            let path = resolve_target(request.project_path.as_deref(), target);
+            if let Ok(content) = std::fs::read_to_string(path) {
+                job.target_baseline = baseline_headings(&content);
+            }
+        }
+        store.save(&job)
+            .map_err(|e| McpError::internal_error(format!("保存任务文件失败: {}", e), None))?;
+        Ok(job)
+    }
+
+    /// 验收：检查目标文件中的新增条目，结果写回任务并通过寸止窗口汇报
+    fn verify(request: PaiRequest, store: &JobStore) -> Result<CallToolResult, McpError> {
+        let job = Self::require_job(&request, store)?;
+        let target = request.target_file.clone()
+            .or_else(|| job.target_file.clone())
+            .filter(|t| !t.trim().is_empty())
+            .ok_or_else(|| McpError::invalid_params("缺少目标文件 target_file".to_string(), None))?;
+        if let Some(number) = request.batch {
+            job.batch(number).map_err(|e| McpError::invalid_params(e.to_string(), None))?;
+        }
+        let pattern = request.entry_pattern.as_deref()
+            .filter(|p| !p.trim().is_empty())
+            .map(regex::Regex::new)
+            .transpose()
+            .map_err(|e| McpError::invalid_params(format!("entry_pattern 不是有效的正则: {}", e), None))?;
+
+        let path = resolve_target(request.project_path.as_deref(), &target);
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| McpError::invalid_params(format!("读取目标文件失败 {}: {}", path.display(), e), None))?;
+
+        // 在文件锁内重新读取任务再验收，避免覆盖子代理同时写入的批次结果
+        let report = store.update(&job.id, |job| {
+            let report = verify(job, &target, &content, pattern.as_ref(), request.batch);
+            report.apply(job);
+            Ok(report)
+        }).map_err(|e| McpError::internal_error(format!("保存任务文件失败: {}", e), None))?;
+
+        let message = report.render();
+        let popup_request = PopupRequest {
+            id: generate_request_id(),
+            message: message.clone(),
+            predefined_options: Some(if report.passed() {
+                vec!["验收通过".to_string()]
+            } else {
+                vec!["重新执行失败批次".to_string(), "忽略".to_string()]
+            }),
+            is_markdown: true,
+            project_path: request.project_path.clone(),
+            link_url: None,
+            link_title: None,
+            browser_ai_response: None,
+        };
+
+        let result = match create_tauri_popup(&popup_request) {
+            Ok(response) => format!("{}\n\n用户响应: {}", message, response),
+            Err(e) => {
+                log_debug!("寸止窗口显示失败，降级返回文本: {}", e);
+                message
+            }
+        };
+        Ok(CallToolResult::success(vec![Content::text(result)]))
+    }
+
+    /// 无界面执行：每个批次启动一个命令行代理，后台运行，结果写回任务文件
+    fn run(request: PaiRequest, store: JobStore) -> Result<CallToolResult, McpError> {
+        let mut agent = AgentCommand::from_config().ok_or_else(|| McpError::invalid_params(
+            "未配置子代理命令（mcp_config.pai_agent_command），无法无界面执行；可改用 派发 操作手动执行".to_string(),
+            None,
+        ))?;
+        if let Some(path) = request.project_path.as_deref().filter(|p| !p.trim().is_empty()) {
+            let dir = crate::mcp::utils::decode_and_normalize_path(path)
+                .map_err(|e| McpError::invalid_params(format!("路径格式错误: {}", e), None))?;
+            agent.working_dir = Some(dir.into());
+        }
+
+        let has_job_id = request.job_id.as_deref().map(|id| !id.trim().is_empty()).unwrap_or(false);
+        let job = if has_job_id {
+            Self::require_job(&request, &store)?
+        } else {
+            Self::create_job(&request, &store)?
+        };
+
+        // 指定批次时只执行该批次，否则执行所有未完成且未在执行中的批次
+        let numbers: Vec<usize> = match request.batch {
+            Some(number) => {
+                let batch = job.batch(number).map_err(|e| McpError::invalid_params(e.to_string(), None))?;
+                if batch.status == BatchStatus::Running {
+                    return Err(McpError::invalid_params(
+                        format!("批次 {} 正在执行中，请等待完成后再重新执行", number),
+                        None,
+                    ));
+                }
+                vec![number]
+            }
+            None => job.remaining_batches().iter()
+                .filter(|b| b.status != BatchStatus::Running)
+                .map(|b| b.number)
+                .collect(),
+        };
+        if numbers.is_empty() {
+            return Ok(CallToolResult::success(vec![Content::text(job.render_progress())]));
+        }
+
+        let prompt = Self::prompt(&job, request.project_path.as_deref())?;
+        let prompts: Vec<(usize, String)> = numbers.iter()
+            .map(|number| (*number, prompt.for_batch(*number)))
+            .collect();
+
+        let job_id = job.id.clone();
+        let parallelism = agent.parallelism;
+        let store = Arc::new(store);
+        tokio::spawn(async move {
+            match run_batches(store, &job_id, prompts, &agent).await {
+                Ok(runs) => {
+                    let failed = runs.iter().filter(|r| !r.success()).count();
+                    log_important!(info, "派发任务 {} 执行结束：{} 个批次，{} 个失败", job_id, runs.len(), failed);
+                }
+                Err(e) => log_important!(error, "派发任务 {} 执行失败: {}", job_id, e),
+            }
+        });
+
+        let list: Vec<String> = numbers.iter().map(|n| n.to_string()).collect();
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "🚀 已在后台启动子代理\n\n**任务 ID**: `{}`\n**批次**: {}（同时执行 {} 个）\n\n使用 `pai(action=进度, job_id=\"{}\")` 查看执行结果",
+            job.id,
+            list.join(", "),
+            parallelism,
+            job.id
+        ))]))
+    }
+
+    /// 切分批次、保存任务文件并显示提示词
+    async fn dispatch(request: PaiRequest, store: &JobStore) -> Result<CallToolResult, McpError> {
+        log_debug!("生成子代理提示词，任务类型: {}, 条目数: {}", 
+            request.task_type, request.items.len());
+
+        let job = Self::create_job(&request, store)?;
+
+        let subagent_prompt = Self::prompt(&job, request.project_path.as_deref())?;
+        let prompt = &subagent_prompt.text;
 
         // 通过寸止窗口显示提示词，方便用户复制
         let popup_message = format!(
             r#"## 📋 子代理提示词
 
+**任务 ID**: `{}`
 **任务类型**: {}
-**条目数量**: {} 个
+**条目数量**: {} 个（{} 批，每批 {} 个）
+**提示词模板**: {}
 
 ---
 
@@ -108,9 +289,13 @@ impl DispatchTool {
 ```
 
 ---
-💡 复制后在新窗口末尾输入批次号开始执行"#,
-            request.task_type,
-            request.items.len(),
+💡 复制后在新窗口末尾输入批次号开始执行，可随时用 `pai(action=进度)` 查看剩余批次"#,
+            job.id,
+            job.task_type,
+            job.item_count(),
+            job.batches.len(),
+            job.batch_size,
+            subagent_prompt.source_label(),
             prompt
         );
 
@@ -122,7 +307,7 @@ impl DispatchTool {
                 "取消".to_string(),
             ]),
             is_markdown: true,
-            project_path: None,
+            project_path: request.project_path.clone(),
             link_url: None,
             link_title: None,
             browser_ai_response: None,
@@ -131,7 +316,9 @@ impl DispatchTool {
         match create_tauri_popup(&popup_request) {
             Ok(response) => {
                 let result = format!(
-                    "子代理提示词已显示在寸止窗口\n\n用户响应: {}\n\n提示词长度: {} 字符",
+                    "子代理提示词已显示在寸止窗口\n\n任务 ID: {}（{} 批）\n\n用户响应: {}\n\n提示词长度: {} 字符",
+                    job.id,
+                    job.batches.len(),
                     response,
                     prompt.len()
                 );
@@ -143,11 +330,15 @@ impl DispatchTool {
                 let result = format!(
                     r#"📋 **子代理提示词**（寸止窗口不可用，直接显示）
 
+**任务 ID**: `{}`（{} 批）
+
 ```markdown
 {}
 ```
 
 **提示词长度**: {} 字符"#,
+                    job.id,
+                    job.batches.len(),
                     prompt,
                     prompt.len()
                 );
@@ -1,8 +1,17 @@
 //! 子代理派发工具模块
 //!
-//! 生成子代理提示词，供用户复制到新聊天窗口执行批量任务
+//! 生成子代理提示词，供用户复制到新聊天窗口执行批量任务；
+//! 任务按批次切分并记录在任务文件中，可跨聊天窗口续做，也可交给命令行代理无界面执行
 
 pub mod mcp;
+pub mod job;
+pub mod runner;
+pub mod prompt;
+pub mod verify;
 
 // 重新导出主要类型和功能
 pub use mcp::DispatchTool;
+pub use job::{DispatchJob, DispatchBatch, DispatchItem, BatchStatus, ItemStatus, JobStore};
+pub use runner::{AgentCommand, BatchRun, run_batches};
+pub use prompt::{build_prompt, SubagentPrompt};
+pub use verify::{verify, VerifyReport, ItemCheck};
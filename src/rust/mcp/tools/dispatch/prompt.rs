@@ -0,0 +1,233 @@
+//! 子代理提示词
+//!
+//! 优先使用 `.cunzhi-knowledge/prompts/pai/<任务类型>.md`（其次 `default.md`）模板，
+//! 模板语法与 ci 提示词库相同，可用占位符：
+//!
+//! - `{{job_id}}` `{{task_type}}` `{{item_count}}` `{{batch_count}}`
+//! - `{{items}}`：按批次分组的条目列表
+//! - `{{source_file}}` `{{target_file}}` `{{output_format}}` `{{extra_steps}}`
+//! - `{{acceptance_criteria}}`：验收标准列表
+//!
+//! 没有模板时使用内置提示词。无论使用哪种，末尾都会追加进度汇报说明和批次号提示
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use super::job::DispatchJob;
+use crate::mcp::tools::ci::PromptTemplate;
+
+/// 模板目录（相对知识库目录）
+pub const PAI_TEMPLATE_DIR: &str = "prompts/pai";
+
+/// 未指定验收标准时的默认值
+pub const DEFAULT_ACCEPTANCE_CRITERIA: &[&str] = &["条目数量正确", "格式符合规范", "无重复条目"];
+
+/// 生成的提示词及其来源
+#[derive(Debug, Clone)]
+pub struct SubagentPrompt {
+    pub text: String,
+    /// 使用的模板（相对知识库目录），内置提示词为 None
+    pub template: Option<String>,
+}
+
+impl SubagentPrompt {
+    pub fn source_label(&self) -> String {
+        match &self.template {
+            Some(path) => format!("`{}`", path),
+            None => "内置模板".to_string(),
+        }
+    }
+
+    /// 指定批次号的提示词（无界面执行时使用）
+    pub fn for_batch(&self, number: usize) -> String {
+        format!("{}{}\n", self.text, number)
+    }
+}
+
+/// 生成子代理提示词
+pub fn build_prompt(job: &DispatchJob, project_path: Option<&str>) -> Result<SubagentPrompt> {
+    let knowledge_dir = project_path
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .and_then(|p| crate::mcp::utils::decode_and_normalize_path(p).ok())
+        .map(|p| Path::new(&p).join(".cunzhi-knowledge"));
+
+    let (body, template) = match knowledge_dir.as_deref().and_then(|dir| find_template(dir, &job.task_type)) {
+        Some((relative, content)) => (render_template(job, &relative, &content)?, Some(relative)),
+        None => (builtin_prompt(job), None),
+    };
+
+    let mut text = body.trim_end().to_string();
+    text.push('\n');
+    text.push_str(&report_footer(job, project_path));
+    Ok(SubagentPrompt { text, template })
+}
+
+/// 按任务类型查找模板，找不到时尝试 default.md
+fn find_template(knowledge_dir: &Path, task_type: &str) -> Option<(String, String)> {
+    let dir = knowledge_dir.join(PAI_TEMPLATE_DIR);
+    let name: String = task_type.trim()
+        .chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '-' } else { c })
+        .collect();
+    [name, "default".to_string()]
+        .into_iter()
+        .filter(|n| !n.is_empty() && n != "." && n != "..")
+        .find_map(|n| {
+            let filename = format!("{}.md", n);
+            let content = fs::read_to_string(dir.join(&filename)).ok()?;
+            Some((format!("{}/{}", PAI_TEMPLATE_DIR, filename), content))
+        })
+}
+
+fn render_template(job: &DispatchJob, path: &str, content: &str) -> Result<String> {
+    let template = PromptTemplate::parse(path, content)?;
+    let values: HashMap<String, String> = [
+        ("job_id", job.id.clone()),
+        ("task_type", job.task_type.clone()),
+        ("item_count", job.item_count().to_string()),
+        ("batch_count", job.batches.len().to_string()),
+        ("items", items_list(job)),
+        ("source_file", job.source_file.clone().unwrap_or_default()),
+        ("target_file", job.target_file.clone().unwrap_or_default()),
+        ("output_format", job.output_format.clone().unwrap_or_default()),
+        ("extra_steps", job.extra_steps.clone().unwrap_or_default()),
+        ("acceptance_criteria", criteria_list(job)),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect();
+
+    template.render(&values).map_err(|e| anyhow!("{} 渲染失败: {}", path, e))
+}
+
+/// 按批次分组的条目列表，序号全局连续
+fn items_list(job: &DispatchJob) -> String {
+    let mut index = 0;
+    job.batches
+        .iter()
+        .map(|batch| {
+            let lines = batch.items.iter().map(|item| {
+                index += 1;
+                format!("{}. {}", index, item.name)
+            }).collect::<Vec<_>>().join("\n");
+            format!("**批次 {}**：\n{}", batch.number, lines)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn criteria_list(job: &DispatchJob) -> String {
+    let criteria: Vec<&str> = if job.acceptance_criteria.is_empty() {
+        DEFAULT_ACCEPTANCE_CRITERIA.to_vec()
+    } else {
+        job.acceptance_criteria.iter().map(String::as_str).collect()
+    };
+    criteria.iter().map(|c| format!("- {}", c)).collect::<Vec<_>>().join("\n")
+}
+
+/// 内置提示词
+fn builtin_prompt(job: &DispatchJob) -> String {
+    let mut prompt = format!(
+        r#"## 子代理任务
+
+**任务 ID**: {}
+**任务类型**: {}
+**范围**（共 {} 个，分 {} 批）：
+{}
+"#,
+        job.id,
+        job.task_type,
+        job.item_count(),
+        job.batches.len(),
+        items_list(job)
+    );
+
+    // 添加源文件和目标文件
+    if let Some(ref source) = job.source_file {
+        prompt.push_str(&format!("**源文件**: {}\n", source));
+    }
+    if let Some(ref target) = job.target_file {
+        prompt.push_str(&format!("**目标文件**: {}\n", target));
+    }
+
+    // 添加步骤
+    prompt.push_str("\n### 步骤\n");
+    prompt.push_str("1. 读取源文件中指定批次对应的条目\n");
+    prompt.push_str("2. 按格式要求生成目标内容");
+
+    // 添加输出格式模板
+    if let Some(ref format) = job.output_format {
+        prompt.push_str(&format!("，格式：\n\n{}\n", format));
+    } else {
+        prompt.push('\n');
+    }
+
+    prompt.push_str("3. 追加到目标文件末尾\n");
+    prompt.push_str("4. 完成后报告：已处理 X 条\n");
+
+    // 添加额外步骤
+    if let Some(ref extra) = job.extra_steps {
+        prompt.push_str(&format!("\n### 额外说明\n{}\n", extra));
+    }
+
+    // 添加验收标准和汇报要求
+    prompt.push_str(&format!("\n### 验收标准\n{}\n", criteria_list(job)));
+    prompt.push_str(r#"
+### 完成后汇报（必须）
+任务完成后，**必须调用 `zhi` 工具**向用户汇报结果，包含：
+- 处理条目数量
+- 完成的操作列表
+- Git commit 信息（如有）
+"#);
+
+    prompt
+}
+
+/// 进度汇报说明和批次号提示
+fn report_footer(job: &DispatchJob, project_path: Option<&str>) -> String {
+    let project_arg = project_path
+        .map(|p| format!(", project_path=\"{}\"", p))
+        .unwrap_or_default();
+    format!(
+        "\n汇报后调用 `pai(action=\"完成\", job_id=\"{}\", batch=批次号{})` 记录进度，失败的条目放入 failed_items\n\n*你是子代理现在帮我做批次*：\n",
+        job.id, project_arg
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_task_type_template_overrides_builtin_prompt() {
+        let project = tempdir().unwrap();
+        let project_path = project.path().to_string_lossy().to_string();
+        let items = vec!["P-2024-001".to_string(), "P-2024-002".to_string()];
+        let mut job = DispatchJob::new("补录回归检查", &items, 1);
+        job.target_file = Some("regressions.md".to_string());
+
+        let builtin = build_prompt(&job, Some(&project_path)).unwrap();
+        assert!(builtin.template.is_none());
+        assert!(builtin.text.contains("- 无重复条目"));
+        assert!(builtin.for_batch(2).ends_with("帮我做批次*：\n2\n"));
+
+        let dir = project.path().join(".cunzhi-knowledge").join(PAI_TEMPLATE_DIR);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("补录回归检查.md"), "---\ntitle: 回归补录\n---\n为 {{item_count}} 个问题补录回归到 {{target_file}}：\n{{items}}\n\n{{acceptance_criteria}}\n").unwrap();
+        job.acceptance_criteria = vec!["每条都有验证方式".to_string()];
+
+        let custom = build_prompt(&job, Some(&project_path)).unwrap();
+        assert_eq!(custom.template.as_deref(), Some("prompts/pai/补录回归检查.md"));
+        assert!(custom.text.starts_with("为 2 个问题补录回归到 regressions.md：\n**批次 1**：\n1. P-2024-001"));
+        assert!(custom.text.contains("- 每条都有验证方式\n"));
+        assert!(custom.text.contains("pai(action=\"完成\""));
+
+        // 模板声明了无默认值的变量时报错，而不是静默回退
+        fs::write(dir.join("补录回归检查.md"), "---\nvariables: [owner]\n---\n{{owner}}").unwrap();
+        assert!(build_prompt(&job, Some(&project_path)).is_err());
+    }
+}
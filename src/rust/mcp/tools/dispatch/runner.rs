@@ -0,0 +1,271 @@
+//! 无界面子代理执行
+//!
+//! 为每个批次启动一个配置好的命令行代理（提示词从 stdin 传入），
+//! 捕获输出与退出码写回任务文件，按并发上限同时执行多个批次
+
+use anyhow::{anyhow, Result};
+use chrono::Local;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use super::job::{BatchStatus, ItemStatus, JobStore};
+use crate::constants::mcp::{PAI_AGENT_OUTPUT_MAX_CHARS, PAI_AGENT_TIMEOUT_SECS, PAI_DEFAULT_PARALLELISM};
+
+/// 截止时间在超时之外额外留出的余量
+const RUN_DEADLINE_GRACE: Duration = Duration::from_secs(60);
+
+/// 子代理命令行
+#[derive(Debug, Clone)]
+pub struct AgentCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    /// 子进程工作目录（通常为项目根目录）
+    pub working_dir: Option<PathBuf>,
+    pub timeout: Duration,
+    /// 同时执行的批次数
+    pub parallelism: usize,
+}
+
+impl AgentCommand {
+    /// 从配置读取：pai_agent_command 为空时返回 None
+    pub fn from_config() -> Option<Self> {
+        let config = crate::config::load_standalone_config().ok()?.mcp_config;
+        let program = config.pai_agent_command.filter(|c| !c.trim().is_empty())?;
+        Some(Self {
+            program,
+            args: config.pai_agent_args.unwrap_or_default(),
+            working_dir: None,
+            timeout: Duration::from_secs(config.pai_agent_timeout_secs.filter(|s| *s > 0).unwrap_or(PAI_AGENT_TIMEOUT_SECS)),
+            parallelism: config.pai_parallelism.filter(|p| *p > 0).unwrap_or(PAI_DEFAULT_PARALLELISM),
+        })
+    }
+}
+
+/// 单个批次的执行结果
+#[derive(Debug, Clone)]
+pub struct BatchRun {
+    pub number: usize,
+    pub exit_code: Option<i32>,
+    pub output: String,
+    pub error: Option<String>,
+}
+
+impl BatchRun {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0) && self.error.is_none()
+    }
+}
+
+/// 执行指定批次，prompts 为（批次号, 提示词）；每个批次结束后立即写回任务文件
+pub async fn run_batches(
+    store: Arc<JobStore>,
+    job_id: &str,
+    prompts: Vec<(usize, String)>,
+    agent: &AgentCommand,
+) -> Result<Vec<BatchRun>> {
+    // 先标记为执行中，便于其他窗口查询进度；记录进程 ID 和截止时间，
+    // 进程崩溃后再次加载任务时据此把遗留的执行中批次判定为失败
+    let parallelism = agent.parallelism.max(1);
+    let waves = prompts.len().div_ceil(parallelism) as u32;
+    let deadline = Local::now() + agent.timeout * waves + RUN_DEADLINE_GRACE;
+    store.update(job_id, |job| {
+        for (number, _) in &prompts {
+            let batch = job.batch_mut(*number)?;
+            // 另一个进程可能已在执行该批次（检查和标记在同一把锁内）
+            if batch.status == BatchStatus::Running {
+                return Err(anyhow!("批次 {} 正在执行中", number));
+            }
+            batch.status = BatchStatus::Running;
+            batch.started_at = Some(Local::now());
+            batch.runner_pid = Some(std::process::id());
+            batch.deadline = Some(deadline);
+            batch.exit_code = None;
+            batch.output = None;
+            batch.error = None;
+        }
+        job.updated_at = Local::now();
+        Ok(())
+    })?;
+
+    let semaphore = Arc::new(Semaphore::new(parallelism));
+    let mut tasks = JoinSet::new();
+
+    for (number, prompt) in prompts {
+        let semaphore = semaphore.clone();
+        let store = store.clone();
+        let agent = agent.clone();
+        let job_id = job_id.to_string();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.map_err(|e| anyhow!("并发控制失败: {}", e))?;
+            let run = run_agent(&agent, number, &prompt).await;
+            record_run(&store, &job_id, &run)?;
+            Ok::<_, anyhow::Error>(run)
+        });
+    }
+
+    let mut runs = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        runs.push(joined.map_err(|e| anyhow!("批次任务异常退出: {}", e))??);
+    }
+    runs.sort_by_key(|run| run.number);
+    Ok(runs)
+}
+
+async fn run_agent(agent: &AgentCommand, number: usize, prompt: &str) -> BatchRun {
+    let mut run = BatchRun { number, exit_code: None, output: String::new(), error: None };
+
+    let mut command = Command::new(&agent.program);
+    command
+        .args(&agent.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(dir) = &agent.working_dir {
+        command.current_dir(dir);
+    }
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            run.error = Some(format!("启动子代理失败（{}）: {}", agent.program, e));
+            return run;
+        }
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // 写入失败通常是代理提前退出，以退出码为准
+        let _ = stdin.write_all(prompt.as_bytes()).await;
+        drop(stdin);
+    }
+
+    match tokio::time::timeout(agent.timeout, child.wait_with_output()).await {
+        Ok(Ok(output)) => {
+            run.exit_code = output.status.code();
+            run.output = tail(&String::from_utf8_lossy(&output.stdout));
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if !output.status.success() {
+                run.error = Some(if stderr.trim().is_empty() {
+                    format!("退出码 {:?}", output.status.code())
+                } else {
+                    tail(stderr.trim())
+                });
+            }
+        }
+        Ok(Err(e)) => run.error = Some(format!("等待子代理失败: {}", e)),
+        Err(_) => run.error = Some(format!("执行超时（{} 秒）", agent.timeout.as_secs())),
+    }
+    run
+}
+
+/// 写回结果：代理自己已通过 pai(action=完成) 汇报时保留其条目状态
+fn record_run(store: &JobStore, job_id: &str, run: &BatchRun) -> Result<()> {
+    store.update(job_id, |job| {
+        let batch = job.batch_mut(run.number)?;
+        let reported = batch.status != BatchStatus::Running;
+
+        if run.success() {
+            if !reported {
+                for item in &mut batch.items {
+                    item.status = ItemStatus::Done;
+                }
+                batch.status = BatchStatus::Done;
+            }
+        } else {
+            for item in &mut batch.items {
+                if item.status != ItemStatus::Done {
+                    item.status = ItemStatus::Failed;
+                }
+            }
+            batch.status = BatchStatus::Failed;
+        }
+        batch.exit_code = run.exit_code;
+        batch.output = Some(run.output.clone()).filter(|o| !o.is_empty());
+        batch.error = run.error.clone();
+        batch.completed_at = Some(Local::now());
+        job.updated_at = Local::now();
+        Ok(())
+    })
+}
+
+/// 只保留输出末尾，避免任务文件过大
+fn tail(text: &str) -> String {
+    let count = text.chars().count();
+    if count <= PAI_AGENT_OUTPUT_MAX_CHARS {
+        return text.to_string();
+    }
+    let kept: String = text.chars().skip(count - PAI_AGENT_OUTPUT_MAX_CHARS).collect();
+    format!("…（已截断前 {} 字符）\n{}", count - PAI_AGENT_OUTPUT_MAX_CHARS, kept)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use super::super::job::DispatchJob;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_stub_agent_runs_batches_in_parallel() {
+        let dir = tempdir().unwrap();
+        let script = dir.path().join("agent.sh");
+        // 从 stdin 读取提示词；包含 FAIL 的批次以非零退出。
+        // 成功批次先登记，再等到三个批次都已登记（最多约 5 秒）后记下看到的数量：
+        // 只有三个批次同时在执行才会都记下 3，串行执行时会记下更小的数
+        std::fs::create_dir(dir.path().join("running")).unwrap();
+        std::fs::write(&script, concat!(
+            "#!/bin/sh\nprompt=$(cat)\necho \"got: $prompt\"\n",
+            "case \"$prompt\" in *FAIL*) echo boom >&2; exit 3;; esac\n",
+            "base=$(dirname \"$0\")\ntouch \"$base/running/$prompt\"\ni=0\n",
+            "while [ $(ls \"$base/running\" | wc -l) -lt 3 ] && [ $i -lt 100 ]; do sleep 0.05; i=$((i+1)); done\n",
+            "ls \"$base/running\" | wc -l >> \"$base/concurrency.log\"\n",
+        )).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let store = Arc::new(JobStore::new(&dir.path().join("jobs")));
+        let items: Vec<String> = ["a", "b", "c", "FAIL"].iter().map(|s| s.to_string()).collect();
+        let job = DispatchJob::new("测试", &items, 1);
+        store.save(&job).unwrap();
+
+        let agent = AgentCommand {
+            program: script.to_string_lossy().to_string(),
+            args: Vec::new(),
+            working_dir: None,
+            timeout: Duration::from_secs(10),
+            parallelism: 3,
+        };
+        let prompts = job.batches.iter().map(|b| (b.number, b.items[0].name.clone())).collect();
+        let runs = run_batches(store.clone(), &job.id, prompts, &agent).await.unwrap();
+        // 三个成功批次并行执行
+        let seen: Vec<usize> = std::fs::read_to_string(dir.path().join("concurrency.log")).unwrap()
+            .lines()
+            .map(|l| l.trim().parse().unwrap())
+            .collect();
+        assert_eq!(seen, vec![3, 3, 3]);
+
+        assert_eq!(runs.iter().filter(|r| r.success()).count(), 3);
+        let job = store.load(&job.id).unwrap();
+        assert_eq!(job.batch(1).unwrap().output.as_deref(), Some("got: a\n"));
+        let failed = job.batch(4).unwrap();
+        assert_eq!((failed.status, failed.exit_code, failed.error.as_deref()), (BatchStatus::Failed, Some(3), Some("boom")));
+        assert_eq!(job.remaining_batches().len(), 1);
+
+        // 正在执行的批次不会被再次启动
+        store.update(&job.id, |job| {
+            let batch = job.batch_mut(4)?;
+            batch.status = BatchStatus::Running;
+            batch.runner_pid = Some(std::process::id());
+            Ok(())
+        }).unwrap();
+        assert!(run_batches(store.clone(), &job.id, vec![(4, String::new())], &agent).await.is_err());
+
+        let missing = AgentCommand { program: "/nonexistent/agent".to_string(), ..agent };
+        let runs = run_batches(store.clone(), &job.id, vec![(1, String::new())], &missing).await.unwrap();
+        assert!(runs[0].error.as_deref().unwrap().contains("启动子代理失败"));
+    }
+}
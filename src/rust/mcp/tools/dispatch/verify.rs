@@ -0,0 +1,343 @@
+//! 派发结果验收
+//!
+//! 对照派发时记录的目标文件条目（基线），找出新增条目并逐个检查：
+//! 每个已执行的任务条目应恰好对应一个新条目，新条目不能与已有条目重复，
+//! 可选地要求每个新条目匹配指定正则
+
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::job::{BatchStatus, DispatchBatch, DispatchJob, ItemStatus};
+
+/// 目标文件中的一个条目（以标题分隔）
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetEntry {
+    pub heading: String,
+    pub text: String,
+}
+
+/// 按标题切分条目：取文件中最高级别的 `##`~`######` 标题作为条目标题
+pub fn parse_entries(content: &str) -> Vec<TargetEntry> {
+    let level_of = |line: &str| {
+        let level = line.chars().take_while(|c| *c == '#').count();
+        ((2..=6).contains(&level) && line[level..].starts_with(' ')).then_some(level)
+    };
+    let Some(entry_level) = content.lines().filter_map(level_of).min() else {
+        return Vec::new();
+    };
+
+    let mut entries: Vec<TargetEntry> = Vec::new();
+    for line in content.lines() {
+        if level_of(line) == Some(entry_level) {
+            entries.push(TargetEntry { heading: line[entry_level..].trim().to_string(), text: String::new() });
+        }
+        if let Some(entry) = entries.last_mut() {
+            entry.text.push_str(line);
+            entry.text.push('\n');
+        }
+    }
+    entries
+}
+
+/// 派发时记录的目标文件条目标题
+pub fn baseline_headings(content: &str) -> Vec<String> {
+    parse_entries(content).into_iter().map(|e| e.heading).collect()
+}
+
+/// 相对路径按项目根目录解析
+pub fn resolve_target(project_path: Option<&str>, target_file: &str) -> PathBuf {
+    let path = Path::new(target_file);
+    match project_path.map(str::trim).filter(|p| !p.is_empty()) {
+        Some(root) if path.is_relative() => Path::new(root).join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase).collect()
+}
+
+/// 条目文本是否提到任务条目：忽略大小写、连续空白视为一个空格，
+/// 且编号两端不能紧跟字母数字或连字符（P-2024-001 不匹配 P-2024-0010）
+fn mentions(text: &str, name: &str) -> bool {
+    let collapse = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    let (text, name) = (collapse(text), collapse(name));
+    let (Some(first), Some(last)) = (name.chars().next(), name.chars().last()) else {
+        return false;
+    };
+    let is_id_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    text.match_indices(&name).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + name.len()..].chars().next();
+        let joined_before = is_id_char(first) && before.is_some_and(is_id_char);
+        let joined_after = is_id_char(last) && after.is_some_and(is_id_char);
+        !joined_before && !joined_after
+    })
+}
+
+/// 单个任务条目的验收结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemCheck {
+    pub name: String,
+    pub batch: usize,
+    pub passed: bool,
+    /// 未通过的原因
+    pub reasons: Vec<String>,
+}
+
+/// 验收报告
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyReport {
+    pub job_id: String,
+    pub target_file: String,
+    /// 参与验收的条目数（已执行批次中的条目）
+    pub expected: usize,
+    /// 目标文件中对应参与验收条目的新增条目数
+    pub new_entries: usize,
+    pub items: Vec<ItemCheck>,
+    /// 与已有条目或其他新条目重复的新条目标题
+    pub duplicates: Vec<String>,
+    /// 不对应任何任务条目的新条目标题
+    pub unmatched: Vec<String>,
+    /// 尚未执行而跳过的条目数
+    pub skipped: usize,
+}
+
+impl VerifyReport {
+    pub fn count_ok(&self) -> bool {
+        self.expected == self.new_entries
+    }
+
+    pub fn passed(&self) -> bool {
+        self.count_ok() && self.duplicates.is_empty() && self.unmatched.is_empty() && self.items.iter().all(|i| i.passed)
+    }
+
+    pub fn render(&self) -> String {
+        let mark = |ok: bool| if ok { "✅" } else { "❌" };
+        let passed = self.items.iter().filter(|i| i.passed).count();
+        let mut out = format!(
+            "## {} 派发结果验收 `{}`\n\n**目标文件**: {}\n\n- {} 条目数量：新增 {} 条，应为 {} 条\n- {} 重复检查：{}\n- {} 逐条检查：{}/{} 通过",
+            mark(self.passed()),
+            self.job_id,
+            self.target_file,
+            mark(self.count_ok()),
+            self.new_entries,
+            self.expected,
+            mark(self.duplicates.is_empty()),
+            if self.duplicates.is_empty() { "无重复".to_string() } else { self.duplicates.join("、") },
+            mark(passed == self.items.len()),
+            passed,
+            self.items.len(),
+        );
+        if self.skipped > 0 {
+            out.push_str(&format!("\n- ⏭️ {} 条所在批次尚未执行，已跳过", self.skipped));
+        }
+
+        let failed: Vec<&ItemCheck> = self.items.iter().filter(|i| !i.passed).collect();
+        if !failed.is_empty() {
+            out.push_str("\n\n### 未通过\n");
+            for item in failed {
+                out.push_str(&format!("\n- 批次 {} `{}`：{}", item.batch, item.name, item.reasons.join("；")));
+            }
+        }
+        if !self.unmatched.is_empty() {
+            out.push_str(&format!("\n\n### 无对应任务的新条目\n\n{}", self.unmatched.iter().map(|h| format!("- {}", h)).collect::<Vec<_>>().join("\n")));
+        }
+        out
+    }
+
+    /// 把验收结果写回任务：未通过的条目标记为失败
+    pub fn apply(&self, job: &mut DispatchJob) {
+        let results: HashMap<(usize, &str), bool> = self.items.iter()
+            .map(|i| ((i.batch, i.name.as_str()), i.passed))
+            .collect();
+        for batch in &mut job.batches {
+            let mut touched = false;
+            for item in &mut batch.items {
+                if let Some(passed) = results.get(&(batch.number, item.name.as_str())) {
+                    item.status = if *passed { ItemStatus::Done } else { ItemStatus::Failed };
+                    touched = true;
+                }
+            }
+            if touched {
+                batch.status = if batch.items.iter().all(|i| i.status == ItemStatus::Done) {
+                    BatchStatus::Done
+                } else {
+                    BatchStatus::Failed
+                };
+            }
+        }
+        job.updated_at = chrono::Local::now();
+    }
+}
+
+/// 验收目标文件；指定批次时只检查该批次，否则检查所有已执行的批次
+pub fn verify(job: &DispatchJob, target_file: &str, content: &str, pattern: Option<&Regex>, batch: Option<usize>) -> VerifyReport {
+    // 基线中的标题按出现次数抵消，剩下的即新增条目
+    let mut baseline: HashMap<String, usize> = HashMap::new();
+    for heading in &job.target_baseline {
+        *baseline.entry(normalize(heading)).or_default() += 1;
+    }
+    let mut new_entries = Vec::new();
+    for entry in parse_entries(content) {
+        match baseline.get_mut(&normalize(&entry.heading)) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => new_entries.push(entry),
+        }
+    }
+
+    let selected = |b: &DispatchBatch| match batch {
+        Some(number) => b.number == number,
+        None => !matches!(b.status, BatchStatus::Pending | BatchStatus::Running),
+    };
+
+    // 只对应未参与验收批次（其他批次、执行中的批次）的新条目不计入本次验收
+    let new_entries: Vec<TargetEntry> = new_entries
+        .into_iter()
+        .filter(|entry| {
+            let owners: Vec<&DispatchBatch> = job.batches.iter()
+                .filter(|b| b.items.iter().any(|item| mentions(&entry.text, &item.name)))
+                .collect();
+            owners.is_empty() || owners.iter().any(|b| selected(b))
+        })
+        .collect();
+
+    // 新条目标题与已有条目或其他新条目相同即为重复
+    let mut seen: HashMap<String, usize> = job.target_baseline.iter().map(|h| (normalize(h), 1)).collect();
+    let mut duplicates = Vec::new();
+    for entry in &new_entries {
+        let count = seen.entry(normalize(&entry.heading)).or_default();
+        *count += 1;
+        if *count == 2 {
+            duplicates.push(entry.heading.clone());
+        }
+    }
+
+    let mut items = Vec::new();
+    let mut matched = vec![false; new_entries.len()];
+    let mut skipped = 0;
+    for b in &job.batches {
+        if !selected(b) {
+            if batch.is_none() {
+                skipped += b.items.len();
+            }
+            continue;
+        }
+
+        for item in &b.items {
+            let hits: Vec<usize> = new_entries.iter().enumerate()
+                .filter(|(_, e)| mentions(&e.text, &item.name))
+                .map(|(i, _)| i)
+                .collect();
+            for i in &hits {
+                matched[*i] = true;
+            }
+
+            let mut reasons = Vec::new();
+            match hits.len() {
+                0 => reasons.push("目标文件中没有对应的新条目".to_string()),
+                1 => {}
+                n => reasons.push(format!("对应 {} 个新条目（重复）", n)),
+            }
+            if let Some(re) = pattern {
+                let mismatched = hits.iter().filter(|i| !re.is_match(&new_entries[**i].text)).count();
+                if mismatched > 0 {
+                    reasons.push(format!("条目格式不匹配 `{}`", re.as_str()));
+                }
+            }
+            items.push(ItemCheck { name: item.name.clone(), batch: b.number, passed: reasons.is_empty(), reasons });
+        }
+    }
+
+    let unmatched = new_entries.iter().zip(&matched)
+        .filter(|(_, m)| !**m)
+        .map(|(e, _)| e.heading.clone())
+        .collect();
+
+    VerifyReport {
+        job_id: job.id.clone(),
+        target_file: target_file.to_string(),
+        expected: items.len(),
+        new_entries: matched.iter().filter(|m| **m).count(),
+        items,
+        duplicates,
+        unmatched,
+        skipped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_counts_duplicates_and_pattern() {
+        let before = "# 回归检查\n\n## R-2024-001 旧条目\n\n- 关联：P-2024-000\n";
+        let items: Vec<String> = ["P-2024-001", "P-2024-002", "P-2024-003", "P-2024-004"].iter().map(|s| s.to_string()).collect();
+        let mut job = DispatchJob::new("补录回归检查", &items, 3);
+        job.target_baseline = baseline_headings(before);
+        job.complete_batch(1, &[], None).unwrap();
+
+        let after = format!(
+            "{}\n## R-2024-002 弹窗\n\n- 关联：P-2024-001\n- 验证：手动\n\n## R-2024-003 快捷键\n\n- 关联：P-2024-002\n\n## R-2024-001 旧条目\n\n- 关联：P-2024-002\n",
+            before
+        );
+        let pattern = Regex::new(r"验证：").unwrap();
+        let report = verify(&job, "regressions.md", &after, Some(&pattern), None);
+
+        assert_eq!((report.expected, report.new_entries, report.skipped), (3, 3, 1));
+        assert_eq!(report.duplicates, vec!["R-2024-001 旧条目"]);
+        let status: Vec<(&str, bool)> = report.items.iter().map(|i| (i.name.as_str(), i.passed)).collect();
+        assert_eq!(status, vec![("P-2024-001", true), ("P-2024-002", false), ("P-2024-003", false)]);
+        assert!(!report.passed());
+
+        report.apply(&mut job);
+        assert_eq!(job.batches[0].status, BatchStatus::Failed);
+        assert_eq!(job.batches[1].status, BatchStatus::Pending);
+    }
+
+    #[test]
+    fn test_verify_one_batch_at_a_time() {
+        let items: Vec<String> = ["P-2024-001", "P-2024-002", "P-2024-003", "P-2024-004"].iter().map(|s| s.to_string()).collect();
+        let mut job = DispatchJob::new("补录回归检查", &items, 2);
+        job.target_baseline = baseline_headings("# 回归检查\n");
+        job.complete_batch(1, &[], None).unwrap();
+        job.complete_batch(2, &[], None).unwrap();
+
+        let after = (1..=4)
+            .map(|n| format!("## R-2024-00{} 条目\n\n- 关联：P-2024-00{}\n", n, n))
+            .collect::<Vec<_>>()
+            .join("\n");
+        for number in [1, 2] {
+            let report = verify(&job, "regressions.md", &after, None, Some(number));
+            assert_eq!((report.expected, report.new_entries), (2, 2), "批次 {}", number);
+            assert!(report.unmatched.is_empty());
+            assert!(report.passed(), "{}", report.render());
+        }
+
+        // 不对应任何任务条目的新条目在每个批次的验收中都列出
+        let stray = format!("{}\n## R-2024-009 杂项\n", after);
+        let report = verify(&job, "regressions.md", &stray, None, Some(1));
+        assert_eq!((report.expected, report.new_entries), (2, 2));
+        assert_eq!(report.unmatched, vec!["R-2024-009 杂项"]);
+        assert!(!report.passed());
+
+        // 执行中批次已追加的条目不计入
+        job.batches[1].status = BatchStatus::Running;
+        let report = verify(&job, "regressions.md", &after, None, None);
+        assert_eq!((report.expected, report.new_entries, report.skipped), (2, 2, 2));
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn test_item_match_respects_id_boundaries() {
+        let text = "## R-2024-010 快捷键\n\n- 关联：P-2024-0010、p-2024-002\n- 模块：设置 面板\n";
+        assert!(!mentions(text, "P-2024-001"));
+        assert!(!mentions(text, "2024-001"));
+        assert!(mentions(text, "P-2024-002"));
+        assert!(mentions(text, "设置  面板"));
+        assert!(!mentions(text, "  "));
+    }
+}
@@ -0,0 +1,87 @@
+use super::mcp::{load_index, reopen_request};
+use crate::mcp::tools::interaction::{ConversationQuery, ConversationRecord, InteractionTool};
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SearchConversationsArgs {
+    #[serde(alias = "projectPath", alias = "project_path")]
+    pub project_path: String,
+    #[serde(flatten)]
+    pub query: ConversationQuery,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ConversationIdArgs {
+    #[serde(alias = "projectPath", alias = "project_path")]
+    pub project_path: String,
+    pub id: String,
+}
+
+fn call_tool_result_to_text(result: &rmcp::model::CallToolResult) -> Result<String, String> {
+    let val = serde_json::to_value(result).map_err(|e| format!("结果序列化失败: {}", e))?;
+    let mut out = String::new();
+    if let Some(arr) = val.get("content").and_then(|v| v.as_array()) {
+        for item in arr {
+            if item.get("type").and_then(|t| t.as_str()) == Some("text") {
+                if let Some(txt) = item.get("text").and_then(|t| t.as_str()) {
+                    out.push_str(txt);
+                }
+            }
+        }
+    }
+
+    if out.is_empty() {
+        Ok(val.to_string())
+    } else {
+        Ok(out)
+    }
+}
+
+/// 在阻塞线程池中读取对话记录和等待弹窗，避免占用异步运行时的工作线程
+async fn run_blocking<T, F>(task: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(task)
+        .await
+        .map_err(|e| format!("后台任务执行失败: {}", e))?
+}
+
+/// 检索对话记录（最近的在前）
+#[tauri::command]
+pub async fn search_conversations(args: SearchConversationsArgs) -> Result<Vec<ConversationRecord>, String> {
+    run_blocking(move || {
+        let index = load_index(&args.project_path).map_err(|e| e.to_string())?;
+        let records = index.search(&args.query).map_err(|e| e.to_string())?;
+        Ok(records.into_iter().cloned().collect())
+    })
+    .await
+}
+
+/// 获取单条对话记录
+#[tauri::command]
+pub async fn get_conversation(args: ConversationIdArgs) -> Result<ConversationRecord, String> {
+    run_blocking(move || {
+        let index = load_index(&args.project_path).map_err(|e| e.to_string())?;
+        index.get(&args.id)
+            .cloned()
+            .ok_or_else(|| format!("未找到对话记录: {}", args.id))
+    })
+    .await
+}
+
+/// 重新打开一次历史交互，返回用户新的回复
+#[tauri::command]
+pub async fn reopen_conversation(args: ConversationIdArgs) -> Result<String, String> {
+    run_blocking(move || {
+        let index = load_index(&args.project_path).map_err(|e| e.to_string())?;
+        let record = index.get(&args.id).ok_or_else(|| format!("未找到对话记录: {}", args.id))?;
+
+        // 弹窗会一直阻塞到用户回复，放在阻塞线程中等待
+        let result = tauri::async_runtime::block_on(InteractionTool::zhi(reopen_request(record, &args.project_path)))
+            .map_err(|e| e.to_string())?;
+
+        call_tool_result_to_text(&result)
+    })
+    .await
+}
@@ -0,0 +1,143 @@
+use anyhow::Result;
+use rmcp::{Error as McpError, model::*};
+
+use crate::constants::mcp::HUI_DEFAULT_RESULT_LIMIT;
+use crate::mcp::types::{HuiRequest, ZhiRequest};
+use crate::mcp::tools::interaction::logger::find_knowledge_dir;
+use crate::mcp::tools::interaction::{ConversationIndex, ConversationQuery, ConversationRecord, InteractionTool};
+
+/// 对话回顾工具
+///
+/// 检索历史 zhi 对话记录，并可重新打开某次交互
+#[derive(Clone)]
+pub struct HuiTool;
+
+impl HuiTool {
+    pub async fn hui(request: HuiRequest) -> Result<CallToolResult, McpError> {
+        let action = request.action.as_deref().map(str::trim).unwrap_or("搜索").to_string();
+        match action.as_str() {
+            "" | "搜索" | "search" => Self::search(request),
+            "查看" | "view" => {
+                let index = load_index(&request.project_path)?;
+                let record = Self::require_record(&index, request.id.as_deref())?;
+                Ok(CallToolResult::success(vec![Content::text(format_record(record))]))
+            }
+            "重开" | "reopen" => {
+                let index = load_index(&request.project_path)?;
+                let record = Self::require_record(&index, request.id.as_deref())?;
+                InteractionTool::zhi(reopen_request(record, &request.project_path)).await
+            }
+            _ => Err(McpError::invalid_params(
+                format!("未知的操作类型: {}，支持：搜索、查看、重开", action),
+                None,
+            )),
+        }
+    }
+
+    fn search(request: HuiRequest) -> Result<CallToolResult, McpError> {
+        let query = ConversationQuery {
+            project: request.project,
+            date_from: request.date_from,
+            date_to: request.date_to,
+            text: request.text,
+            option: request.option,
+            limit: Some(request.limit.filter(|l| *l > 0).unwrap_or(HUI_DEFAULT_RESULT_LIMIT)),
+        };
+        let index = load_index(&request.project_path)?;
+        let records = index.search(&query)
+            .map_err(|e| McpError::invalid_params(e.to_string(), None))?;
+
+        if records.is_empty() {
+            return Ok(CallToolResult::success(vec![Content::text("📭 没有符合条件的对话记录")]));
+        }
+
+        let lines: Vec<String> = records.iter().map(|r| {
+            let options = if r.selected_options.is_empty() {
+                String::new()
+            } else {
+                format!(" **选择**: {}", r.selected_options.join(", "))
+            };
+            format!(
+                "- `{}` {}{}{}\n  🤖 {}\n  👤 {}",
+                r.id,
+                r.time,
+                r.project.as_deref().map(|p| format!(" @ {}", p)).unwrap_or_default(),
+                options,
+                preview(&r.ai_message, 80),
+                preview(&r.user_response, 80),
+            )
+        }).collect();
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "# 🕘 对话记录（{} 条，最近的在前）\n\n{}\n\n传入 id 并使用「查看」或「重开」获取完整交互",
+            records.len(),
+            lines.join("\n")
+        ))]))
+    }
+
+    fn require_record<'a>(index: &'a ConversationIndex, id: Option<&str>) -> Result<&'a ConversationRecord, McpError> {
+        let id = id.map(str::trim).filter(|id| !id.is_empty())
+            .ok_or_else(|| McpError::invalid_params("缺少对话记录 id".to_string(), None))?;
+        index.get(id)
+            .ok_or_else(|| McpError::invalid_params(format!("未找到对话记录: {}", id), None))
+    }
+}
+
+/// 加载项目对应知识库中的对话记录
+pub fn load_index(project_path: &str) -> Result<ConversationIndex, McpError> {
+    let knowledge_dir = find_knowledge_dir(Some(project_path))
+        .map_err(|e| McpError::invalid_params(e.to_string(), None))?;
+    ConversationIndex::load(&knowledge_dir)
+        .map_err(|e| McpError::internal_error(format!("读取对话记录失败: {}", e), None))
+}
+
+/// 完整展示一次交互
+pub fn format_record(record: &ConversationRecord) -> String {
+    let mut out = format!(
+        "# 🕘 {} {}{}\n\n### 🤖 AI\n{}{}\n\n### 👤 用户\n",
+        record.date.format("%Y-%m-%d"),
+        record.time,
+        record.project.as_deref().map(|p| format!(" @ {}", p)).unwrap_or_default(),
+        record.ai_message,
+        if record.ai_truncated { "\n\n*(日志中已截断)*" } else { "" },
+    );
+    if !record.selected_options.is_empty() {
+        out.push_str(&format!("**选择**: {}\n\n", record.selected_options.join(", ")));
+    }
+    out.push_str(&record.user_response);
+    if record.image_count > 0 {
+        out.push_str(&format!("\n\n📷 *附图 {} 张*", record.image_count));
+        for path in &record.attachments {
+            out.push_str(&format!("\n- conversations/{}", path));
+        }
+    }
+    out
+}
+
+/// 以原 AI 消息重新发起 zhi 交互，上次的选择作为预定义选项
+pub fn reopen_request(record: &ConversationRecord, project_path: &str) -> ZhiRequest {
+    let mut message = format!(
+        "> 🕘 重新打开 {} {} 的对话\n\n{}",
+        record.date.format("%Y-%m-%d"),
+        record.time,
+        record.ai_message
+    );
+    if !record.user_response.is_empty() {
+        message.push_str(&format!("\n\n---\n\n**上次回复**：{}", record.user_response));
+    }
+    ZhiRequest {
+        message,
+        predefined_options: record.selected_options.clone(),
+        is_markdown: true,
+        project_path: Some(project_path.to_string()),
+    }
+}
+
+fn preview(text: &str, max_chars: usize) -> String {
+    let flat = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if flat.chars().count() <= max_chars {
+        flat
+    } else {
+        format!("{}...", flat.chars().take(max_chars).collect::<String>())
+    }
+}
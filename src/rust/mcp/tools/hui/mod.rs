@@ -0,0 +1,8 @@
+//! 对话回顾工具模块
+//!
+//! 检索 .cunzhi-knowledge/conversations/ 中的 zhi 对话记录，并可重新打开某次交互
+
+pub mod mcp;
+pub mod commands;
+
+pub use mcp::HuiTool;
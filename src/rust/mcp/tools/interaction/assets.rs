@@ -0,0 +1,242 @@
+//! 对话附图存储
+//!
+//! zhi 弹窗中粘贴的图片保存到 conversations/assets/，文件名取内容哈希以去重，
+//! 可按最长边缩小，超过大小上限的图片不保存
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::config::ConversationConfig;
+use crate::constants::mcp::CONVERSATION_IMAGE_MAX_PIXELS;
+use crate::mcp::types::ImageAttachment;
+
+/// 附图目录名（位于 conversations/ 下）
+pub const ASSETS_DIR: &str = "assets";
+
+/// 一张附图的保存结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum SavedAttachment {
+    /// 相对 conversations/ 的路径，如 `assets/3fa2….png`
+    Saved(String),
+    /// 未保存的原因
+    Skipped(String),
+}
+
+/// 保存附图，已存在相同内容时直接复用
+pub fn save_attachment(conversations_dir: &Path, image: &ImageAttachment, config: &ConversationConfig) -> SavedAttachment {
+    match save_attachment_inner(conversations_dir, image, config) {
+        Ok(path) => SavedAttachment::Saved(path),
+        Err(e) => SavedAttachment::Skipped(e.to_string()),
+    }
+}
+
+fn save_attachment_inner(conversations_dir: &Path, image: &ImageAttachment, config: &ConversationConfig) -> Result<String> {
+    let original = decode_data(&image.data)?;
+    let hash = hex::encode(ring::digest::digest(&ring::digest::SHA256, &original).as_ref());
+    let stem = &hash[..16];
+
+    let assets_dir = conversations_dir.join(ASSETS_DIR);
+    fs::create_dir_all(&assets_dir)?;
+    if let Some(existing) = find_existing(&assets_dir, stem) {
+        // 刷新修改时间，避免在写入日志前被当作孤立附图清理
+        if let Ok(file) = fs::File::options().write(true).open(assets_dir.join(&existing)) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        return Ok(format!("{}/{}", ASSETS_DIR, existing));
+    }
+
+    let mut ext = extension(image);
+    let mut bytes = original;
+    if let Some(max_dimension) = config.image_max_dimension.filter(|d| *d > 0) {
+        if let Some((scaled, scaled_ext)) = downscale(&bytes, ext, max_dimension)? {
+            bytes = scaled;
+            ext = scaled_ext;
+        }
+    }
+
+    if config.image_max_bytes > 0 && bytes.len() as u64 > config.image_max_bytes {
+        return Err(anyhow!(
+            "超过大小上限（{} KB > {} KB）",
+            bytes.len() / 1024,
+            config.image_max_bytes / 1024
+        ));
+    }
+
+    let filename = format!("{}.{}", stem, ext);
+    fs::write(assets_dir.join(&filename), &bytes)?;
+    Ok(format!("{}/{}", ASSETS_DIR, filename))
+}
+
+/// 解码 base64（兼容 data URL 前缀）
+fn decode_data(data: &str) -> Result<Vec<u8>> {
+    let payload = match data.strip_prefix("data:") {
+        Some(rest) => rest.split_once(',').map(|(_, payload)| payload).unwrap_or(rest),
+        None => data,
+    };
+    let compact: String = payload.chars().filter(|c| !c.is_whitespace()).collect();
+    base64::engine::general_purpose::STANDARD
+        .decode(compact)
+        .map_err(|e| anyhow!("图片数据解码失败: {}", e))
+}
+
+fn extension(image: &ImageAttachment) -> &'static str {
+    match image.media_type.to_lowercase().as_str() {
+        "image/png" => "png",
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        _ => {
+            let from_name = image.filename.as_deref()
+                .and_then(|name| Path::new(name).extension())
+                .map(|e| e.to_string_lossy().to_lowercase());
+            match from_name.as_deref() {
+                Some("png") => "png",
+                Some("jpg") | Some("jpeg") => "jpg",
+                Some("gif") => "gif",
+                Some("webp") => "webp",
+                _ => "bin",
+            }
+        }
+    }
+}
+
+/// 按最长边缩小，图片本身已足够小或格式不支持（如 GIF 动图）时返回 None
+fn downscale(bytes: &[u8], ext: &'static str, max_dimension: u32) -> Result<Option<(Vec<u8>, &'static str)>> {
+    let format = match ext {
+        "png" => image::ImageFormat::Png,
+        "jpg" => image::ImageFormat::Jpeg,
+        "webp" => image::ImageFormat::WebP,
+        _ => return Ok(None),
+    };
+
+    // 先只读文件头里的尺寸，像素数过大时不解码
+    let mut reader = image::ImageReader::new(std::io::Cursor::new(bytes));
+    reader.set_format(format);
+    let (width, height) = reader.into_dimensions()
+        .map_err(|e| anyhow!("图片解析失败: {}", e))?;
+    if u64::from(width) * u64::from(height) > CONVERSATION_IMAGE_MAX_PIXELS {
+        return Err(anyhow!("图片尺寸过大（{}x{}）", width, height));
+    }
+
+    let decoded = image::load_from_memory_with_format(bytes, format)
+        .map_err(|e| anyhow!("图片解析失败: {}", e))?;
+    if decoded.width().max(decoded.height()) <= max_dimension {
+        return Ok(None);
+    }
+
+    let resized = decoded.resize(max_dimension, max_dimension, image::imageops::FilterType::Triangle);
+    // WebP 编码只支持无损，缩小后统一转为 PNG
+    let (out_format, out_ext) = match format {
+        image::ImageFormat::Jpeg => (image::ImageFormat::Jpeg, "jpg"),
+        _ => (image::ImageFormat::Png, "png"),
+    };
+    let mut out = std::io::Cursor::new(Vec::new());
+    resized.write_to(&mut out, out_format)
+        .map_err(|e| anyhow!("图片编码失败: {}", e))?;
+    Ok(Some((out.into_inner(), out_ext)))
+}
+
+fn find_existing(assets_dir: &Path, stem: &str) -> Option<String> {
+    fs::read_dir(assets_dir).ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .find(|name| Path::new(name).file_stem().map(|s| s == stem).unwrap_or(false))
+}
+
+/// 删除不再被任何日志引用的附图，返回删除的文件数
+///
+/// 修改时间在 `grace` 以内的附图不删除：其他进程可能刚保存、还未写入引用它的日志
+pub fn prune_orphan_assets(conversations_dir: &Path, grace: Duration) -> std::io::Result<usize> {
+    let assets_dir = conversations_dir.join(ASSETS_DIR);
+    if !assets_dir.exists() {
+        return Ok(0);
+    }
+
+    let marker = format!("]({}/", ASSETS_DIR);
+    let mut referenced = HashSet::new();
+    for entry in fs::read_dir(conversations_dir)?.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("md") {
+            continue;
+        }
+        let content = fs::read_to_string(&path)?;
+        for segment in content.split(&marker).skip(1) {
+            if let Some(name) = segment.split(')').next() {
+                referenced.insert(name.to_string());
+            }
+        }
+    }
+
+    let mut removed = 0;
+    for entry in fs::read_dir(&assets_dir)?.filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let recent = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .map(|modified| modified.elapsed().map(|age| age < grace).unwrap_or(true))
+            .unwrap_or(true);
+        if !referenced.contains(&name) && !recent {
+            fs::remove_file(entry.path())?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::default_conversation_config;
+    use tempfile::tempdir;
+
+    fn png(width: u32, height: u32) -> String {
+        let img = image::RgbImage::from_pixel(width, height, image::Rgb([200, 30, 30]));
+        let mut out = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut out, image::ImageFormat::Png).unwrap();
+        base64::engine::general_purpose::STANDARD.encode(out.into_inner())
+    }
+
+    #[test]
+    fn test_attachments_are_deduplicated_downscaled_and_capped() {
+        let dir = tempdir().unwrap();
+        let mut config = default_conversation_config();
+        config.image_max_dimension = Some(64);
+
+        let image = ImageAttachment { data: png(256, 128), media_type: "image/png".to_string(), filename: None };
+        let first = save_attachment(dir.path(), &image, &config);
+        let SavedAttachment::Saved(path) = first.clone() else { panic!("应保存成功: {:?}", first) };
+        assert_eq!(save_attachment(dir.path(), &image, &config), first);
+
+        let saved = image::open(dir.path().join(&path)).unwrap();
+        assert_eq!((saved.width(), saved.height()), (64, 32));
+
+        config.image_max_dimension = None;
+        config.image_max_bytes = 16;
+        let large = ImageAttachment { data: format!("data:image/png;base64,{}", png(32, 32)), media_type: "image/png".to_string(), filename: None };
+        assert!(matches!(save_attachment(dir.path(), &large, &config), SavedAttachment::Skipped(_)));
+
+        // 尺寸超限的图片在解码前就被拒绝（只改写 JPEG 帧头中的宽高为 10000x10000）
+        config.image_max_dimension = Some(64);
+        config.image_max_bytes = 0;
+        let mut jpeg = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(8, 8).write_to(&mut jpeg, image::ImageFormat::Jpeg).unwrap();
+        let mut jpeg = jpeg.into_inner();
+        let sof = jpeg.windows(2).position(|w| w == [0xFF, 0xC0]).unwrap();
+        jpeg[sof + 5..sof + 9].copy_from_slice(&[0x27, 0x10, 0x27, 0x10]);
+        let huge = ImageAttachment {
+            data: base64::engine::general_purpose::STANDARD.encode(jpeg),
+            media_type: "image/jpeg".to_string(),
+            filename: None,
+        };
+        let skipped = save_attachment(dir.path(), &huge, &config);
+        assert!(matches!(&skipped, SavedAttachment::Skipped(reason) if reason.contains("尺寸过大")), "{:?}", skipped);
+
+        // 没有日志引用的附图过了宽限期才被清理
+        assert_eq!(prune_orphan_assets(dir.path(), Duration::from_secs(3600)).unwrap(), 0);
+        assert_eq!(prune_orphan_assets(dir.path(), Duration::ZERO).unwrap(), 1);
+    }
+}
@@ -0,0 +1,275 @@
+//! 对话历史：读回 conversations/YYYY-MM-DD.md
+//!
+//! 日志格式由 logger.rs 写入，每条以 `## HH:MM:SS @ 项目` 开头，
+//! 依次为 `### 🤖 AI` 与 `### 👤 用户` 两段，以 `---` 结束
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// 对话日志目录名（位于 .cunzhi-knowledge/ 下）
+pub const CONVERSATIONS_DIR: &str = "conversations";
+
+/// logger 截断 AI 消息时追加的标记
+const TRUNCATED_MARKER: &str = "...\n\n*(已截断)*";
+
+/// 一次 zhi 交互记录
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationRecord {
+    /// 形如 `2024-05-01#3`（日期 + 当天第几条，从 1 开始）
+    pub id: String,
+    pub date: NaiveDate,
+    pub time: String,
+    /// 项目目录名（日志只记录目录名）
+    pub project: Option<String>,
+    pub ai_message: String,
+    /// AI 消息在写日志时是否被截断
+    pub ai_truncated: bool,
+    pub selected_options: Vec<String>,
+    pub user_response: String,
+    pub image_count: usize,
+    /// 已保存附图的路径（相对 conversations/）
+    pub attachments: Vec<String>,
+}
+
+/// 对话检索条件
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ConversationQuery {
+    /// 项目路径或目录名
+    #[serde(default, alias = "projectName")]
+    pub project: Option<String>,
+    #[serde(default, alias = "dateFrom")]
+    pub date_from: Option<String>,
+    #[serde(default, alias = "dateTo")]
+    pub date_to: Option<String>,
+    /// 关键词（空格分隔，全部命中才算匹配，不区分大小写）
+    #[serde(default)]
+    pub text: Option<String>,
+    /// 用户选择过的选项（包含即可）
+    #[serde(default)]
+    pub option: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+fn heading_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^## (\d{2}:\d{2}:\d{2})\s*(?:@\s*(.+?))?\s*$").unwrap())
+}
+
+fn image_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^📷 \*附图 (\d+) 张\*$").unwrap())
+}
+
+/// 附图链接 `![附图 1](assets/xxx.png)` 或未保存说明 `*附图 1 未保存：原因*`
+fn attachment_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(?:!\[附图 \d+\]\(([^)]+)\)|\*附图 \d+ 未保存：.*\*)$").unwrap())
+}
+
+/// 解析单个日志文件
+pub fn parse_conversation_file(date: NaiveDate, content: &str) -> Vec<ConversationRecord> {
+    let lines: Vec<&str> = content.lines().collect();
+    let starts: Vec<usize> = lines.iter()
+        .enumerate()
+        .filter(|(_, line)| heading_regex().is_match(line))
+        .map(|(i, _)| i)
+        .collect();
+
+    starts.iter().enumerate().filter_map(|(n, &start)| {
+        let end = starts.get(n + 1).copied().unwrap_or(lines.len());
+        let caps = heading_regex().captures(lines[start])?;
+        let mut record = parse_block(&lines[start + 1..end])?;
+        record.id = format!("{}#{}", date.format("%Y-%m-%d"), n + 1);
+        record.date = date;
+        record.time = caps[1].to_string();
+        record.project = caps.get(2).map(|m| m.as_str().to_string());
+        Some(record)
+    }).collect()
+}
+
+/// 解析标题之后的 AI / 用户两段
+fn parse_block(lines: &[&str]) -> Option<ConversationRecord> {
+    let ai_start = lines.iter().position(|l| l.trim() == "### 🤖 AI")?;
+    let user_start = ai_start + lines[ai_start..].iter().position(|l| l.trim() == "### 👤 用户")?;
+
+    let ai_message = lines[ai_start + 1..user_start].join("\n").trim().to_string();
+    let (ai_message, ai_truncated) = match ai_message.strip_suffix(TRUNCATED_MARKER) {
+        Some(message) => (message.to_string(), true),
+        None => (ai_message, false),
+    };
+
+    // 去掉结尾的分隔线
+    let mut user_lines = &lines[user_start + 1..];
+    while let Some((last, rest)) = user_lines.split_last() {
+        if last.trim().is_empty() || last.trim() == "---" {
+            user_lines = rest;
+        } else {
+            break;
+        }
+    }
+
+    let mut selected_options = Vec::new();
+    let mut image_count = 0;
+    let mut attachments = Vec::new();
+    let mut text = Vec::new();
+    for line in user_lines {
+        if let Some(options) = line.strip_prefix("**选择**: ") {
+            if selected_options.is_empty() && text.iter().all(|l: &&str| l.trim().is_empty()) {
+                selected_options = options.split(", ").map(str::to_string).collect();
+                continue;
+            }
+        }
+        if let Some(caps) = image_regex().captures(line.trim()) {
+            image_count = caps[1].parse().unwrap_or(0);
+            continue;
+        }
+        if let Some(caps) = attachment_regex().captures(line.trim()) {
+            attachments.extend(caps.get(1).map(|m| m.as_str().to_string()));
+            continue;
+        }
+        text.push(*line);
+    }
+
+    Some(ConversationRecord {
+        id: String::new(),
+        date: NaiveDate::MIN,
+        time: String::new(),
+        project: None,
+        ai_message,
+        ai_truncated,
+        selected_options,
+        user_response: text.join("\n").trim().to_string(),
+        image_count,
+        attachments,
+    })
+}
+
+/// 对话日志索引
+#[derive(Debug, Default)]
+pub struct ConversationIndex {
+    /// 按时间正序
+    pub records: Vec<ConversationRecord>,
+}
+
+impl ConversationIndex {
+    /// 读取知识库下全部对话日志
+    pub fn load(knowledge_dir: &Path) -> Result<Self> {
+        Self::load_range(knowledge_dir, None, None)
+    }
+
+    /// 只读取日期范围内的日志文件
+    pub fn load_range(knowledge_dir: &Path, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Result<Self> {
+        let dir = knowledge_dir.join(CONVERSATIONS_DIR);
+        if !dir.exists() {
+            return Ok(Self::default());
+        }
+
+        let mut files: Vec<(NaiveDate, PathBuf)> = std::fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                let stem = path.file_stem()?.to_str()?.to_string();
+                if path.extension()? != "md" {
+                    return None;
+                }
+                let date = NaiveDate::parse_from_str(&stem, "%Y-%m-%d").ok()?;
+                Some((date, path))
+            })
+            .filter(|(date, _)| from.map(|f| *date >= f).unwrap_or(true) && to.map(|t| *date <= t).unwrap_or(true))
+            .collect();
+        files.sort();
+
+        let mut records = Vec::new();
+        for (date, path) in files {
+            let content = std::fs::read_to_string(&path)?;
+            records.extend(parse_conversation_file(date, &content));
+        }
+        Ok(Self { records })
+    }
+
+    /// 按条件检索，最近的在前
+    pub fn search(&self, query: &ConversationQuery) -> Result<Vec<&ConversationRecord>> {
+        let from = query.date_from.as_deref().filter(|d| !d.trim().is_empty()).map(parse_date).transpose()?;
+        let to = query.date_to.as_deref().filter(|d| !d.trim().is_empty()).map(parse_date).transpose()?;
+        let project = query.project.as_deref()
+            .map(|p| Path::new(p.trim()).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default())
+            .filter(|p| !p.is_empty());
+        let keywords: Vec<String> = query.text.as_deref()
+            .map(|t| t.split_whitespace().map(str::to_lowercase).collect())
+            .unwrap_or_default();
+        let option = query.option.as_deref().map(|o| o.trim().to_lowercase()).filter(|o| !o.is_empty());
+
+        let mut matched: Vec<&ConversationRecord> = self.records.iter()
+            .filter(|r| from.map(|f| r.date >= f).unwrap_or(true) && to.map(|t| r.date <= t).unwrap_or(true))
+            .filter(|r| project.as_ref().map(|p| r.project.as_deref() == Some(p.as_str())).unwrap_or(true))
+            .filter(|r| option.as_ref().map(|o| r.selected_options.iter().any(|s| s.to_lowercase().contains(o))).unwrap_or(true))
+            .filter(|r| {
+                let haystack = format!("{}\n{}\n{}", r.ai_message, r.user_response, r.selected_options.join(" ")).to_lowercase();
+                keywords.iter().all(|k| haystack.contains(k))
+            })
+            .collect();
+
+        matched.reverse();
+        if let Some(limit) = query.limit.filter(|l| *l > 0) {
+            matched.truncate(limit);
+        }
+        Ok(matched)
+    }
+
+    /// 按 ID 取记录
+    pub fn get(&self, id: &str) -> Option<&ConversationRecord> {
+        self.records.iter().find(|r| r.id == id.trim())
+    }
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("日期格式错误: {}，应为 YYYY-MM-DD", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_search_conversations() {
+        let content = "## 10:00:01  @ iterate\n\n### 🤖 AI\n修复完成，是否提交？\n\n---\n\n表格里的分隔线\n\n### 👤 用户\n**选择**: 提交, 推送\n\n顺便更新 README\n\n📷 *附图 2 张*\n\n![附图 1](assets/3fa2.png)\n\n*附图 2 未保存：超过大小上限*\n\n---\n\n\
+                       ## 11:30:00 \n\n### 🤖 AI\n长消息...\n\n*(已截断)*\n\n### 👤 用户\n好的\n\n---\n\n";
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let records = parse_conversation_file(date, content);
+        assert_eq!(records.len(), 2);
+
+        let first = &records[0];
+        assert_eq!(first.id, "2024-05-01#1");
+        assert_eq!(first.project.as_deref(), Some("iterate"));
+        assert!(first.ai_message.contains("表格里的分隔线"));
+        assert_eq!(first.selected_options, vec!["提交", "推送"]);
+        assert_eq!(first.user_response, "顺便更新 README");
+        assert_eq!(first.image_count, 2);
+        assert_eq!(first.attachments, vec!["assets/3fa2.png"]);
+
+        assert!(records[1].project.is_none());
+        assert!(records[1].ai_truncated);
+        assert_eq!(records[1].ai_message, "长消息");
+
+        let index = ConversationIndex { records };
+        let by_project = index.search(&ConversationQuery {
+            project: Some("/Users/me/iterate".to_string()),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(by_project.len(), 1);
+
+        let by_option = index.search(&ConversationQuery { option: Some("推送".to_string()), ..Default::default() }).unwrap();
+        assert_eq!(by_option[0].id, "2024-05-01#1");
+
+        let all = index.search(&ConversationQuery::default()).unwrap();
+        assert_eq!(all[0].id, "2024-05-01#2");
+        assert!(index.search(&ConversationQuery { date_from: Some("2024-05-02".to_string()), ..Default::default() }).unwrap().is_empty());
+        assert_eq!(index.search(&ConversationQuery { text: Some("readme 提交".to_string()), ..Default::default() }).unwrap().len(), 1);
+    }
+}
@@ -1,15 +1,23 @@
 //! 对话日志记录模块
 //!
 //! 自动记录 zhi 工具的 AI 提问和用户回答
-//! 支持 5 分钟防抖自动同步到 GitHub
+//! 写入前按配置脱敏，支持按项目关闭、按天数保留
+//! 支持 5 分钟防抖自动同步到远程知识库（可关闭，仅保留本地日志）
 
-use chrono::Local;
+use chrono::{Duration, Local, NaiveDate};
 use std::fs::{self, OpenOptions};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Mutex;
-use std::process::Command;
+
+use super::assets::{prune_orphan_assets, save_attachment, SavedAttachment, ASSETS_DIR};
+use super::history::CONVERSATIONS_DIR;
+use super::redact::{is_project_excluded, Redactor};
+use crate::config::{default_conversation_config, load_standalone_config, ConversationConfig};
+use crate::constants::mcp::CONVERSATION_ASSET_GRACE_SECS;
+use crate::mcp::tools::memory::KnowledgeSync;
+use crate::mcp::types::ImageAttachment;
 
 /// 全局状态：是否有待同步的对话
 static PENDING_SYNC: AtomicBool = AtomicBool::new(false);
@@ -28,7 +36,7 @@ pub struct ConversationEntry {
     pub ai_message: String,
     pub user_response: String,
     pub project_path: Option<String>,
-    pub image_count: usize,
+    pub images: Vec<ImageAttachment>,
     pub selected_options: Vec<String>,
 }
 
@@ -40,10 +48,22 @@ pub fn append_conversation_log(entry: &ConversationEntry) {
     }
 }
 
+/// 读取对话日志配置，失败时使用默认配置
+fn load_conversation_config() -> ConversationConfig {
+    load_standalone_config()
+        .map(|config| config.conversation_config)
+        .unwrap_or_else(|_| default_conversation_config())
+}
+
 fn append_conversation_log_inner(entry: &ConversationEntry) -> std::io::Result<()> {
+    let config = load_conversation_config();
+    if !config.enabled || is_project_excluded(&config, entry.project_path.as_deref()) {
+        return Ok(());
+    }
+
     // 查找 .cunzhi-knowledge 目录
     let knowledge_dir = find_knowledge_dir(entry.project_path.as_deref())?;
-    let conversations_dir = knowledge_dir.join("conversations");
+    let conversations_dir = knowledge_dir.join(CONVERSATIONS_DIR);
     
     // 确保目录存在
     fs::create_dir_all(&conversations_dir)?;
@@ -54,7 +74,12 @@ fn append_conversation_log_inner(entry: &ConversationEntry) -> std::io::Result<(
     
     // 生成日志条目
     let timestamp = Local::now().format("%H:%M:%S").to_string();
-    let log_content = format_log_entry(entry, &timestamp);
+    let attachments: Vec<SavedAttachment> = if config.save_images {
+        entry.images.iter().map(|image| save_attachment(&conversations_dir, image, &config)).collect()
+    } else {
+        entry.images.iter().map(|_| SavedAttachment::Skipped("未开启附图保存".to_string())).collect()
+    };
+    let log_content = format_log_entry(&redact_entry(entry, &Redactor::from_config(&config)), &timestamp, &attachments);
     
     // 追加到文件
     let mut file = OpenOptions::new()
@@ -64,14 +89,61 @@ fn append_conversation_log_inner(entry: &ConversationEntry) -> std::io::Result<(
     
     file.write_all(log_content.as_bytes())?;
     
-    // 标记有待同步，并启动防抖同步
-    mark_pending_sync(&knowledge_dir);
+    // 清理超出保留期的日志，以及不再被任何日志引用的附图（超过宽限期的才删除）
+    if let Some(days) = config.retention_days.filter(|d| *d > 0) {
+        let removed = prune_expired_logs(&conversations_dir, days, Local::now().date_naive())?;
+        if removed > 0 {
+            eprintln!("[cunzhi] 已清理 {} 个过期对话日志", removed);
+        }
+
+        let grace = std::time::Duration::from_secs(CONVERSATION_ASSET_GRACE_SECS);
+        let orphans = prune_orphan_assets(&conversations_dir, grace)?;
+        if orphans > 0 {
+            eprintln!("[cunzhi] 已清理 {} 张未被引用的附图", orphans);
+        }
+    }
+    
+    // 标记有待同步，并启动防抖同步（关闭远程同步时只保留本地日志）
+    if config.remote_sync {
+        mark_pending_sync(&knowledge_dir);
+    }
     
     Ok(())
 }
 
+/// 对 AI 消息、用户回复和选项脱敏（附图已单独保存，不再携带）
+fn redact_entry(entry: &ConversationEntry, redactor: &Redactor) -> ConversationEntry {
+    ConversationEntry {
+        ai_message: redactor.redact(&entry.ai_message),
+        user_response: redactor.redact(&entry.user_response),
+        project_path: entry.project_path.clone(),
+        images: Vec::new(),
+        selected_options: entry.selected_options.iter().map(|o| redactor.redact(o)).collect(),
+    }
+}
+
+/// 删除早于保留期的 YYYY-MM-DD.md，返回删除的文件数
+fn prune_expired_logs(conversations_dir: &Path, retention_days: u32, today: NaiveDate) -> std::io::Result<usize> {
+    let cutoff = today - Duration::days(i64::from(retention_days));
+    let mut removed = 0;
+    for entry in fs::read_dir(conversations_dir)?.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("md") {
+            continue;
+        }
+        let date = path.file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok());
+        if matches!(date, Some(date) if date < cutoff) {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
 /// 查找 .cunzhi-knowledge 目录
-fn find_knowledge_dir(project_path: Option<&str>) -> std::io::Result<PathBuf> {
+pub fn find_knowledge_dir(project_path: Option<&str>) -> std::io::Result<PathBuf> {
     // 优先从项目路径查找
     if let Some(path) = project_path {
         let project_knowledge = PathBuf::from(path).join(".cunzhi-knowledge");
@@ -102,7 +174,7 @@ fn find_knowledge_dir(project_path: Option<&str>) -> std::io::Result<PathBuf> {
 }
 
 /// 格式化日志条目
-fn format_log_entry(entry: &ConversationEntry, timestamp: &str) -> String {
+fn format_log_entry(entry: &ConversationEntry, timestamp: &str, attachments: &[SavedAttachment]) -> String {
     let mut content = String::new();
     
     // 标题行：时间戳 + 项目（如果有）
@@ -135,8 +207,14 @@ fn format_log_entry(entry: &ConversationEntry, timestamp: &str) -> String {
     }
     
     // 图片标记
-    if entry.image_count > 0 {
-        content.push_str(&format!("\n📷 *附图 {} 张*\n", entry.image_count));
+    if !attachments.is_empty() {
+        content.push_str(&format!("\n📷 *附图 {} 张*\n", attachments.len()));
+        for (i, attachment) in attachments.iter().enumerate() {
+            match attachment {
+                SavedAttachment::Saved(path) => content.push_str(&format!("\n![附图 {}]({})\n", i + 1, path)),
+                SavedAttachment::Skipped(reason) => content.push_str(&format!("\n*附图 {} 未保存：{}*\n", i + 1, reason)),
+            }
+        }
     }
     
     content.push_str("\n---\n\n");
@@ -195,62 +273,33 @@ fn mark_pending_sync(knowledge_dir: &PathBuf) {
     });
 }
 
-/// 同步对话记录到 GitHub
+/// 同步对话记录到远程知识库（先 pull --rebase，离线时排队）
 fn sync_conversations(knowledge_dir: &PathBuf) {
     // 重置待同步标记
     PENDING_SYNC.store(false, Ordering::SeqCst);
     
-    let conversations_dir = knowledge_dir.join("conversations");
+    let conversations_dir = knowledge_dir.join(CONVERSATIONS_DIR);
     if !conversations_dir.exists() {
         return;
     }
     
-    // git add conversations/
-    let add_result = Command::new("git")
-        .args(["add", "conversations/"])
-        .current_dir(knowledge_dir)
-        .output();
-    
-    if let Err(e) = add_result {
-        eprintln!("[cunzhi] git add 失败: {}", e);
-        return;
-    }
-    
-    // git commit
     let today = Local::now().format("%Y-%m-%d").to_string();
     let commit_msg = format!("sync: 对话记录 {}", today);
-    let commit_result = Command::new("git")
-        .args(["commit", "-m", &commit_msg])
-        .current_dir(knowledge_dir)
-        .output();
-    
-    if let Ok(output) = commit_result {
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            // 如果是 "nothing to commit" 则忽略
-            if !stderr.contains("nothing to commit") {
-                eprintln!("[cunzhi] git commit 失败: {}", stderr);
-            }
-            return;
-        }
+    // 配置为不同步附图时，用 pathspec 排除 assets/（附图仍保留在本地）
+    let exclude_assets = format!(":(exclude){}/{}/", CONVERSATIONS_DIR, ASSETS_DIR);
+    let mut paths = vec!["conversations/"];
+    if !load_conversation_config().sync_images {
+        paths.push(&exclude_assets);
     }
-    
-    // git push
-    let push_result = Command::new("git")
-        .args(["push"])
-        .current_dir(knowledge_dir)
-        .output();
-    
-    if let Err(e) = push_result {
-        eprintln!("[cunzhi] git push 失败: {}", e);
-    } else {
-        eprintln!("[cunzhi] 对话记录已同步到 GitHub");
+    match KnowledgeSync::new(knowledge_dir).try_commit_and_sync(&paths, &commit_msg) {
+        Ok(outcome) => eprintln!("[cunzhi] {}", outcome.message()),
+        Err(e) => eprintln!("[cunzhi] 对话记录同步失败: {}", e),
     }
 }
 
 /// 强制立即同步（用于应用退出时）
 pub fn force_sync_conversations() {
-    if !PENDING_SYNC.load(Ordering::SeqCst) {
+    if !PENDING_SYNC.load(Ordering::SeqCst) || !load_conversation_config().remote_sync {
         return;
     }
     
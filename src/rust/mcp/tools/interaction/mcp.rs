@@ -1,7 +1,7 @@
 use anyhow::Result;
 use rmcp::{Error as McpError, model::*};
 
-use crate::mcp::{ZhiRequest, PopupRequest, McpResponse};
+use crate::mcp::{ZhiRequest, PopupRequest, McpResponse, ImageAttachment};
 use crate::mcp::handlers::{create_tauri_popup, parse_mcp_response};
 use crate::mcp::utils::{generate_request_id, popup_error};
 use super::logger::{append_conversation_log, ConversationEntry};
@@ -58,13 +58,13 @@ fn log_conversation(ai_message: &str, response: &str, project_path: Option<Strin
     }
     
     // 解析响应获取用户输入详情
-    let (user_text, selected_options, image_count) = parse_response_for_log(response);
+    let (user_text, selected_options, images) = parse_response_for_log(response);
     
     let entry = ConversationEntry {
         ai_message: ai_message.to_string(),
         user_response: user_text,
         project_path,
-        image_count,
+        images,
         selected_options,
     };
     
@@ -72,15 +72,14 @@ fn log_conversation(ai_message: &str, response: &str, project_path: Option<Strin
 }
 
 /// 解析响应用于日志记录
-fn parse_response_for_log(response: &str) -> (String, Vec<String>, usize) {
+fn parse_response_for_log(response: &str) -> (String, Vec<String>, Vec<ImageAttachment>) {
     // 尝试解析结构化格式
     if let Ok(structured) = serde_json::from_str::<McpResponse>(response) {
         let user_text = structured.user_input.unwrap_or_default();
         let selected_options = structured.selected_options;
-        let image_count = structured.images.len();
-        return (user_text, selected_options, image_count);
+        return (user_text, selected_options, structured.images);
     }
     
     // 回退：直接作为文本
-    (response.to_string(), vec![], 0)
+    (response.to_string(), vec![], vec![])
 }
@@ -4,7 +4,11 @@
 
 pub mod mcp;
 pub mod logger;
+pub mod history;
+pub mod redact;
+pub mod assets;
 
 // 重新导出主要类型和功能
 pub use mcp::InteractionTool;
 pub use logger::{append_conversation_log, ConversationEntry};
+pub use history::{ConversationIndex, ConversationQuery, ConversationRecord};
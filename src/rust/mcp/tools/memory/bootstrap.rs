@@ -0,0 +1,410 @@
+//! 知识库初始化与体检
+//!
+//! 初始化：创建 .cunzhi-knowledge/ 与 patterns/problems/regressions/prompts 模板，
+//! 可从共享仓库 clone，并设置 git 远程。
+//! 体检：检查目录结构、条目编号、重复条目与 git 远程状态
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::knowledge_base::{DiagnosticSeverity, KnowledgeBase, KnowledgeKind};
+use super::manager::MemoryManager;
+use super::sync::KnowledgeSync;
+use crate::utils::create_if_missing;
+
+/// 知识库目录名
+pub const KNOWLEDGE_DIR_NAME: &str = ".cunzhi-knowledge";
+
+/// 提示词库示例模板（相对知识库目录）
+const PROMPT_EXAMPLE_PATH: &str = "prompts/general/example.md";
+
+const PROMPT_EXAMPLE: &str = r#"---
+title: 示例模板
+tags: [example]
+description: 提示词模板示例，可用 ci(mode=render) 渲染
+variables:
+  - name: task
+    description: 要完成的任务
+  - name: language
+    description: 回复语言
+    default: 中文
+---
+请用{{language}}完成以下任务，并在结束前调用 zhi 确认：
+
+{{task}}
+"#;
+
+/// 体检时单项最多列出的明细条数
+const DOCTOR_DETAIL_LIMIT: usize = 10;
+
+/// 初始化参数
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct KnowledgeInitOptions {
+    /// 共享知识库仓库地址：知识库目录不存在时从这里 clone
+    #[serde(default, alias = "repoUrl")]
+    pub repo_url: Option<String>,
+    /// 设置为 origin 的远程地址
+    #[serde(default, alias = "remoteUrl")]
+    pub remote_url: Option<String>,
+}
+
+impl KnowledgeInitOptions {
+    /// 未指定共享仓库时使用配置中的 knowledge_repo_url
+    pub fn with_configured_repo(mut self) -> Self {
+        if self.repo_url.as_deref().map(str::trim).unwrap_or("").is_empty() {
+            self.repo_url = crate::config::load_standalone_config()
+                .ok()
+                .and_then(|c| c.mcp_config.knowledge_repo_url)
+                .filter(|url| !url.trim().is_empty());
+        }
+        self
+    }
+}
+
+/// 初始化结果
+#[derive(Debug, Clone, Serialize)]
+pub struct KnowledgeInitReport {
+    pub knowledge_dir: String,
+    /// 是否从共享仓库 clone
+    pub cloned: bool,
+    /// 新建的模板文件（相对知识库目录）
+    pub created: Vec<String>,
+    /// 设置后的 origin 地址
+    pub remote: Option<String>,
+    /// 提交/同步说明
+    pub sync_message: Option<String>,
+}
+
+impl KnowledgeInitReport {
+    pub fn message(&self) -> String {
+        let mut lines = vec![format!("✅ 知识库已就绪：{}", self.knowledge_dir)];
+        if self.cloned {
+            lines.push("📥 已从共享仓库 clone".to_string());
+        }
+        if self.created.is_empty() {
+            lines.push("📄 模板文件均已存在，未做修改".to_string());
+        } else {
+            lines.push(format!("📄 新建：{}", self.created.join("、")));
+        }
+        match &self.remote {
+            Some(remote) => lines.push(format!("🔗 远程仓库：{}", remote)),
+            None => lines.push("💡 未配置远程仓库，知识只保存在本地".to_string()),
+        }
+        if let Some(message) = &self.sync_message {
+            lines.push(message.clone());
+        }
+        lines.join("\n")
+    }
+}
+
+/// 体检级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DoctorLevel {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// 一项体检结果
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorCheck {
+    pub name: String,
+    pub level: DoctorLevel,
+    pub summary: String,
+    pub details: Vec<String>,
+}
+
+impl DoctorCheck {
+    fn new(name: &str, level: DoctorLevel, summary: impl Into<String>, details: Vec<String>) -> Self {
+        Self { name: name.to_string(), level, summary: summary.into(), details }
+    }
+}
+
+/// 体检报告
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorReport {
+    pub knowledge_dir: String,
+    pub checks: Vec<DoctorCheck>,
+}
+
+impl DoctorReport {
+    /// 最严重的级别
+    pub fn level(&self) -> DoctorLevel {
+        self.checks.iter().map(|c| c.level).max().unwrap_or(DoctorLevel::Ok)
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = format!("# 🩺 知识库体检：{}\n", self.knowledge_dir);
+        for check in &self.checks {
+            let icon = match check.level {
+                DoctorLevel::Ok => "✅",
+                DoctorLevel::Warning => "⚠️",
+                DoctorLevel::Error => "❌",
+            };
+            out.push_str(&format!("\n{} **{}**：{}\n", icon, check.name, check.summary));
+            for detail in check.details.iter().take(DOCTOR_DETAIL_LIMIT) {
+                out.push_str(&format!("  - {}\n", detail));
+            }
+            if check.details.len() > DOCTOR_DETAIL_LIMIT {
+                out.push_str(&format!("  - …另有 {} 条\n", check.details.len() - DOCTOR_DETAIL_LIMIT));
+            }
+        }
+        out
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git").args(args).current_dir(dir).output()?;
+    if !output.status.success() {
+        return Err(anyhow!("git {} 失败: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn knowledge_template(kind: KnowledgeKind) -> String {
+    let example = match kind {
+        KnowledgeKind::Problem => "问题标题\n\n    - 状态：open\n    - 标签：…\n    - 相关文件：…",
+        KnowledgeKind::Pattern => "实践标题\n\n    - 标签：…",
+        KnowledgeKind::Regression => "回归检查标题\n\n    - 关联：P-…\n    - 验证：…",
+    };
+    // 示例行缩进，避免被解析为条目标题
+    format!(
+        "# {}\n\n<!--\n条目格式（编号可省略，沉淀时自动分配）：\n\n    ## {}-YYYY-NNN {}\n-->\n",
+        kind.title(),
+        kind.prefix(),
+        example
+    )
+}
+
+/// 初始化知识库目录，已存在的文件保持不变
+pub fn init_knowledge_dir(knowledge_dir: &Path, options: &KnowledgeInitOptions) -> Result<KnowledgeInitReport> {
+    let repo_url = options.repo_url.as_deref().map(str::trim).filter(|u| !u.is_empty());
+    let mut cloned = false;
+
+    if !knowledge_dir.exists() {
+        if let Some(url) = repo_url {
+            let parent = knowledge_dir.parent().ok_or_else(|| anyhow!("无法获取知识库上级目录"))?;
+            fs::create_dir_all(parent)?;
+            let target = knowledge_dir.to_string_lossy().to_string();
+            git(parent, &["clone", url, &target]).map_err(|e| anyhow!("从共享仓库 clone 失败: {}", e))?;
+            cloned = true;
+        }
+    }
+    fs::create_dir_all(knowledge_dir)?;
+    if !knowledge_dir.join(".git").exists() {
+        git(knowledge_dir, &["init"])?;
+    }
+
+    let mut created = Vec::new();
+    // 只在文件不存在时写入模板，不覆盖其他进程同时写入的内容
+    for kind in KnowledgeKind::ALL {
+        if create_if_missing(&knowledge_dir.join(kind.filename()), &knowledge_template(kind))? {
+            created.push(kind.filename().to_string());
+        }
+    }
+    let prompts_dir = knowledge_dir.join("prompts");
+    if !prompts_dir.exists() {
+        let example = knowledge_dir.join(PROMPT_EXAMPLE_PATH);
+        fs::create_dir_all(example.parent().unwrap_or(&prompts_dir))?;
+        if create_if_missing(&example, PROMPT_EXAMPLE)? {
+            created.push(PROMPT_EXAMPLE_PATH.to_string());
+        }
+    }
+
+    // 设置 origin：不存在时添加，地址不同时更新
+    if let Some(url) = options.remote_url.as_deref().map(str::trim).filter(|u| !u.is_empty()) {
+        let action = match git(knowledge_dir, &["remote", "get-url", "origin"]) {
+            Ok(current) if current == url => None,
+            Ok(_) => Some("set-url"),
+            Err(_) => Some("add"),
+        };
+        if let Some(action) = action {
+            git(knowledge_dir, &["remote", action, "origin", url])?;
+        }
+    }
+    let remote = git(knowledge_dir, &["remote", "get-url", "origin"]).ok();
+
+    let sync_message = (!created.is_empty()).then(|| {
+        let paths: Vec<&str> = created.iter().map(String::as_str).collect();
+        KnowledgeSync::new(knowledge_dir).commit_and_sync(&paths, "初始化知识库模板")
+    });
+
+    Ok(KnowledgeInitReport {
+        knowledge_dir: knowledge_dir.to_string_lossy().to_string(),
+        cloned,
+        created,
+        remote,
+        sync_message,
+    })
+}
+
+/// 检查知识库健康状况
+pub fn doctor_knowledge_dir(knowledge_dir: &Path) -> Result<DoctorReport> {
+    let mut checks = Vec::new();
+    let report = |checks| DoctorReport { knowledge_dir: knowledge_dir.to_string_lossy().to_string(), checks };
+
+    if !knowledge_dir.exists() {
+        checks.push(DoctorCheck::new(
+            "目录结构", DoctorLevel::Error, "知识库目录不存在，请执行 ji(action=初始化) 或 `等一下 knowledge init`", Vec::new(),
+        ));
+        return Ok(report(checks));
+    }
+
+    // 目录结构
+    let mut missing: Vec<String> = KnowledgeKind::ALL.iter()
+        .map(|k| k.filename())
+        .filter(|f| !knowledge_dir.join(f).exists())
+        .map(str::to_string)
+        .collect();
+    if !knowledge_dir.join("prompts").is_dir() {
+        missing.push("prompts/".to_string());
+    }
+    checks.push(if missing.is_empty() {
+        DoctorCheck::new("目录结构", DoctorLevel::Ok, "模板文件齐全", Vec::new())
+    } else {
+        DoctorCheck::new("目录结构", DoctorLevel::Warning, format!("缺少 {} 项，可执行初始化补齐", missing.len()), missing)
+    });
+
+    // 编号与格式（含跨文件重复编号、失效关联）
+    let base = KnowledgeBase::load(knowledge_dir)?;
+    let errors = base.diagnostics.iter().filter(|d| d.severity == DiagnosticSeverity::Error).count();
+    let details: Vec<String> = base.diagnostics.iter().map(|d| d.to_string()).collect();
+    checks.push(match (errors, details.len()) {
+        (0, 0) => DoctorCheck::new("编号与格式", DoctorLevel::Ok, format!("共 {} 个条目，格式正确", base.entries.len()), Vec::new()),
+        (0, n) => DoctorCheck::new("编号与格式", DoctorLevel::Warning, format!("{} 个警告", n), details),
+        (e, n) => DoctorCheck::new("编号与格式", DoctorLevel::Error, format!("{} 个错误，{} 个警告", e, n - e), details),
+    });
+
+    // 重复条目：同类条目标题相同
+    let mut titles: HashMap<(KnowledgeKind, String), Vec<&str>> = HashMap::new();
+    for entry in &base.entries {
+        let key: String = entry.title.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase).collect();
+        if !key.is_empty() {
+            titles.entry((entry.kind, key)).or_default().push(&entry.id);
+        }
+    }
+    let mut duplicates: Vec<String> = titles.values()
+        .filter(|ids| ids.len() > 1)
+        .map(|ids| format!("标题相同：{}", ids.join("、")))
+        .collect();
+    duplicates.sort();
+    checks.push(if duplicates.is_empty() {
+        DoctorCheck::new("重复条目", DoctorLevel::Ok, "未发现重复条目", Vec::new())
+    } else {
+        DoctorCheck::new("重复条目", DoctorLevel::Warning, format!("{} 组疑似重复", duplicates.len()), duplicates)
+    });
+
+    checks.push(git_check(knowledge_dir));
+    Ok(report(checks))
+}
+
+fn git_check(knowledge_dir: &Path) -> DoctorCheck {
+    if !knowledge_dir.join(".git").exists() {
+        return DoctorCheck::new("Git 远程", DoctorLevel::Error, "知识库不是 git 仓库，无法同步", Vec::new());
+    }
+    let status = match KnowledgeSync::new(knowledge_dir).status() {
+        Ok(status) => status,
+        Err(e) => return DoctorCheck::new("Git 远程", DoctorLevel::Error, format!("读取 git 状态失败: {}", e), Vec::new()),
+    };
+    if !status.has_remote {
+        return DoctorCheck::new("Git 远程", DoctorLevel::Warning, "未配置远程仓库，知识只保存在本地", Vec::new());
+    }
+
+    let mut details = Vec::new();
+    if status.pending_commits > 0 {
+        details.push(format!("{} 个提交尚未推送", status.pending_commits));
+    }
+    if status.uncommitted_files > 0 {
+        details.push(format!("{} 个文件未提交", status.uncommitted_files));
+    }
+    if status.offline {
+        details.push("上次同步时网络不可用".to_string());
+    }
+    if let Some(error) = &status.last_error {
+        details.push(format!("上次同步失败: {}", error));
+    }
+    let remote = git(knowledge_dir, &["remote", "get-url", "origin"]).unwrap_or_else(|_| "已配置".to_string());
+    if details.is_empty() {
+        DoctorCheck::new("Git 远程", DoctorLevel::Ok, format!("{}，已同步", remote), Vec::new())
+    } else {
+        DoctorCheck::new("Git 远程", DoctorLevel::Warning, remote, details)
+    }
+}
+
+impl MemoryManager {
+    /// 项目的知识库目录（不要求已存在）
+    pub fn knowledge_dir_path(&self) -> PathBuf {
+        self.project_root().join(KNOWLEDGE_DIR_NAME)
+    }
+
+    /// 初始化项目知识库
+    pub fn init_knowledge(&self, options: &KnowledgeInitOptions) -> Result<KnowledgeInitReport> {
+        init_knowledge_dir(&self.knowledge_dir_path(), options)
+    }
+
+    /// 知识库体检
+    pub fn knowledge_doctor(&self) -> Result<DoctorReport> {
+        doctor_knowledge_dir(&self.knowledge_dir_path())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn run(dir: &Path, args: &[&str]) {
+        assert!(Command::new("git").args(args).current_dir(dir).output().unwrap().status.success());
+    }
+
+    #[test]
+    fn test_init_is_idempotent_and_doctor_reports_problems() {
+        let root = tempdir().unwrap();
+        let remote = root.path().join("shared.git");
+        fs::create_dir_all(&remote).unwrap();
+        run(&remote, &["init", "--bare", "-q"]);
+
+        let dir = root.path().join(KNOWLEDGE_DIR_NAME);
+        let options = KnowledgeInitOptions { repo_url: None, remote_url: Some(remote.to_string_lossy().to_string()) };
+        let report = init_knowledge_dir(&dir, &options).unwrap();
+        assert_eq!(report.created.len(), 4);
+        assert!(report.remote.is_some());
+        run(&dir, &["config", "user.email", "t@example.com"]);
+        run(&dir, &["config", "user.name", "t"]);
+        run(&dir, &["add", "-A"]);
+        run(&dir, &["commit", "-qm", "init"]);
+        run(&dir, &["push", "-q", "-u", "origin", "HEAD"]);
+
+        // 加锁写入和同步之后工作区仍然干净（锁文件不落在知识库目录里）
+        crate::utils::update_locked(&dir.join("problems.md"), "", |_| Ok(())).unwrap();
+        KnowledgeSync::new(&dir).sync().unwrap();
+        let doctor = doctor_knowledge_dir(&dir).unwrap();
+        assert_eq!(doctor.level(), DoctorLevel::Ok, "{}", doctor.render());
+
+        // 再次初始化不覆盖已有文件
+        assert!(init_knowledge_dir(&dir, &options).unwrap().created.is_empty());
+
+        // 从共享仓库 clone
+        let cloned = init_knowledge_dir(&root.path().join("other/.cunzhi-knowledge"), &KnowledgeInitOptions {
+            repo_url: Some(remote.to_string_lossy().to_string()),
+            remote_url: None,
+        }).unwrap();
+        assert!(cloned.cloned && cloned.created.is_empty());
+
+        fs::write(dir.join("problems.md"), "# 问题记录\n\n## P-2024-001 弹窗不显示\n\n## P-2024-001 弹窗 不显示\n\n## P-24-2 格式错误\n").unwrap();
+        fs::remove_dir_all(dir.join("prompts")).unwrap();
+        let doctor = doctor_knowledge_dir(&dir).unwrap();
+        let levels: Vec<(&str, DoctorLevel)> = doctor.checks.iter().map(|c| (c.name.as_str(), c.level)).collect();
+        assert_eq!(levels, vec![
+            ("目录结构", DoctorLevel::Warning),
+            ("编号与格式", DoctorLevel::Error),
+            ("重复条目", DoctorLevel::Warning),
+            ("Git 远程", DoctorLevel::Warning),
+        ]);
+    }
+}
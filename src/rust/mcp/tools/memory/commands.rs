@@ -1,4 +1,7 @@
-use super::MemoryTool;
+use super::{
+    DoctorReport, ImportReport, KnowledgeDiagnostic, KnowledgeInitOptions, KnowledgeInitReport, KnowledgeEntry, KnowledgeHistoryRecord, MemoryEntry, MemoryFilter,
+    KnowledgeSync, MemoryHistoryRecord, MemoryManager, MemoryTool, MemoryUpdate, RuleFormat, SyncStatus,
+};
 use crate::mcp::types::JiyiRequest;
 
 fn default_category() -> String {
@@ -14,6 +17,24 @@ pub struct ExecuteJiArgs {
     pub content: String,
     #[serde(default = "default_category")]
     pub category: String,
+    #[serde(default, alias = "ttlDays")]
+    pub ttl_days: Option<i64>,
+    #[serde(default)]
+    pub pinned: Option<bool>,
+    #[serde(default, alias = "dateFrom")]
+    pub date_from: Option<String>,
+    #[serde(default, alias = "dateTo")]
+    pub date_to: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default, alias = "entryId")]
+    pub entry_id: Option<String>,
+    #[serde(default)]
+    pub commit: Option<String>,
+    #[serde(default, alias = "repoUrl")]
+    pub repo_url: Option<String>,
+    #[serde(default, alias = "remoteUrl")]
+    pub remote_url: Option<String>,
 }
 
 fn call_tool_result_to_text(result: &rmcp::model::CallToolResult) -> Result<String, String> {
@@ -36,6 +57,17 @@ fn call_tool_result_to_text(result: &rmcp::model::CallToolResult) -> Result<Stri
     }
 }
 
+/// 在阻塞线程池中执行文件读写和 git 操作，避免占用异步运行时的工作线程
+async fn run_blocking<T, F>(task: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(task)
+        .await
+        .map_err(|e| format!("后台任务执行失败: {}", e))?
+}
+
 #[tauri::command]
 pub async fn execute_ji_tool(args: ExecuteJiArgs) -> Result<String, String> {
     let req = JiyiRequest {
@@ -43,11 +75,329 @@ pub async fn execute_ji_tool(args: ExecuteJiArgs) -> Result<String, String> {
         project_path: args.project_path,
         content: args.content,
         category: args.category,
+        ttl_days: args.ttl_days,
+        pinned: args.pinned,
+        date_from: args.date_from,
+        date_to: args.date_to,
+        limit: args.limit,
+        entry_id: args.entry_id,
+        commit: args.commit,
+        repo_url: args.repo_url,
+        remote_url: args.remote_url,
     };
 
-    let result = MemoryTool::jiyi(req)
-        .await
+    // 记忆工具内部是同步的文件和 git 操作（沉淀时还会推送远程）
+    run_blocking(move || {
+        let result = tauri::async_runtime::block_on(MemoryTool::jiyi(req))
+            .map_err(|e| e.to_string())?;
+        call_tool_result_to_text(&result)
+    })
+    .await
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ExportMemoriesArgs {
+    #[serde(alias = "projectPath", alias = "project_path")]
+    pub project_path: String,
+    pub format: String,
+    /// 为 true 时写入文件（默认写入项目根目录下该格式的默认文件）
+    #[serde(default, alias = "writeFile")]
+    pub write_file: bool,
+    #[serde(default, alias = "outputPath")]
+    pub output_path: Option<String>,
+}
+
+/// 导出记忆：返回渲染后的内容，或写入文件后返回文件路径
+#[tauri::command]
+pub async fn export_memories(args: ExportMemoriesArgs) -> Result<String, String> {
+    let format = RuleFormat::parse(&args.format).map_err(|e| e.to_string())?;
+    run_blocking(move || {
+        let manager = open_manager(&args.project_path)?;
+        if args.write_file {
+            let output = args.output_path.as_deref().map(std::path::Path::new);
+            let path = manager.export_to_file(format, output)
+                .map_err(|e| format!("导出记忆失败: {}", e))?;
+            Ok(path.to_string_lossy().to_string())
+        } else {
+            manager.export_memories(format)
+                .map_err(|e| format!("导出记忆失败: {}", e))
+        }
+    })
+    .await
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ImportMemoriesArgs {
+    #[serde(alias = "projectPath", alias = "project_path")]
+    pub project_path: String,
+    pub format: String,
+    /// 直接传入的内容；为空时读取 input_path 或项目根目录下该格式的默认文件
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default, alias = "inputPath")]
+    pub input_path: Option<String>,
+}
+
+/// 导入记忆
+#[tauri::command]
+pub async fn import_memories(args: ImportMemoriesArgs) -> Result<ImportReport, String> {
+    let format = RuleFormat::parse(&args.format).map_err(|e| e.to_string())?;
+    run_blocking(move || {
+        let manager = open_manager(&args.project_path)?;
+        let report = match args.content {
+            Some(content) => manager.import_memories(format, &content),
+            None => {
+                let input = args.input_path.as_deref().map(std::path::Path::new);
+                manager.import_from_file(format, input)
+            }
+        };
+        report.map_err(|e| format!("导入记忆失败: {}", e))
+    })
+    .await
+}
+
+fn open_manager(project_path: &str) -> Result<MemoryManager, String> {
+    MemoryManager::new(project_path).map_err(|e| format!("创建记忆管理器失败: {}", e))
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ListMemoryEntriesArgs {
+    #[serde(alias = "projectPath", alias = "project_path")]
+    pub project_path: String,
+    #[serde(default, flatten)]
+    pub filter: MemoryFilter,
+}
+
+/// 按条件列出记忆条目
+#[tauri::command]
+pub async fn list_memory_entries(args: ListMemoryEntriesArgs) -> Result<Vec<MemoryEntry>, String> {
+    run_blocking(move || {
+        open_manager(&args.project_path)?
+            .list_memories(&args.filter)
+            .map_err(|e| format!("读取记忆失败: {}", e))
+    })
+    .await
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct UpdateMemoryEntryArgs {
+    #[serde(alias = "projectPath", alias = "project_path")]
+    pub project_path: String,
+    pub id: String,
+    #[serde(default, flatten)]
+    pub update: MemoryUpdate,
+}
+
+/// 修改记忆条目（内容、置顶、有效期）
+#[tauri::command]
+pub async fn update_memory_entry(args: UpdateMemoryEntryArgs) -> Result<MemoryEntry, String> {
+    run_blocking(move || {
+        open_manager(&args.project_path)?
+            .update_memory(&args.id, &args.update)
+            .map_err(|e| format!("修改记忆失败: {}", e))
+    })
+    .await
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct MemoryEntryArgs {
+    #[serde(alias = "projectPath", alias = "project_path")]
+    pub project_path: String,
+    pub id: String,
+}
+
+/// 删除记忆条目
+#[tauri::command]
+pub async fn delete_memory_entry(args: MemoryEntryArgs) -> Result<MemoryEntry, String> {
+    run_blocking(move || {
+        open_manager(&args.project_path)?
+            .delete_memory(&args.id)
+            .map_err(|e| format!("删除记忆失败: {}", e))
+    })
+    .await
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct MoveMemoryEntryArgs {
+    #[serde(alias = "projectPath", alias = "project_path")]
+    pub project_path: String,
+    pub id: String,
+    #[serde(alias = "targetCategory")]
+    pub target_category: String,
+}
+
+/// 将记忆条目移动到另一个分类
+#[tauri::command]
+pub async fn move_memory_entry(args: MoveMemoryEntryArgs) -> Result<MemoryEntry, String> {
+    run_blocking(move || {
+        open_manager(&args.project_path)?
+            .move_memory(&args.id, &args.target_category)
+            .map_err(|e| format!("移动记忆失败: {}", e))
+    })
+    .await
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct MemoryHistoryArgs {
+    #[serde(alias = "projectPath", alias = "project_path")]
+    pub project_path: String,
+    /// 为空时返回全部历史
+    #[serde(default)]
+    pub id: Option<String>,
+}
+
+/// 查看记忆修改历史
+#[tauri::command]
+pub async fn get_memory_history(args: MemoryHistoryArgs) -> Result<Vec<MemoryHistoryRecord>, String> {
+    run_blocking(move || {
+        open_manager(&args.project_path)?
+            .memory_history(args.id.as_deref())
+            .map_err(|e| format!("读取记忆历史失败: {}", e))
+    })
+    .await
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ListKnowledgeEntriesArgs {
+    #[serde(alias = "projectPath", alias = "project_path")]
+    pub project_path: String,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub keyword: Option<String>,
+}
+
+/// 列出知识库条目
+#[tauri::command]
+pub async fn list_knowledge_entries(args: ListKnowledgeEntriesArgs) -> Result<Vec<KnowledgeEntry>, String> {
+    run_blocking(move || {
+        open_manager(&args.project_path)?
+            .list_knowledge(args.category.as_deref(), args.keyword.as_deref())
+            .map_err(|e| format!("读取知识库失败: {}", e))
+    })
+    .await
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct UpdateKnowledgeEntryArgs {
+    #[serde(alias = "projectPath", alias = "project_path")]
+    pub project_path: String,
+    pub id: String,
+    pub content: String,
+}
+
+/// 修改知识库条目并同步
+#[tauri::command]
+pub async fn update_knowledge_entry(args: UpdateKnowledgeEntryArgs) -> Result<String, String> {
+    run_blocking(move || {
+        open_manager(&args.project_path)?
+            .update_knowledge(&args.id, &args.content)
+            .map_err(|e| format!("修改知识库条目失败: {}", e))
+    })
+    .await
+}
+
+/// 删除知识库条目并同步
+#[tauri::command]
+pub async fn delete_knowledge_entry(args: MemoryEntryArgs) -> Result<String, String> {
+    run_blocking(move || {
+        open_manager(&args.project_path)?
+            .delete_knowledge(&args.id)
+            .map_err(|e| format!("删除知识库条目失败: {}", e))
+    })
+    .await
+}
+
+/// 查看知识库条目的 git 历史
+#[tauri::command]
+pub async fn get_knowledge_history(args: MemoryEntryArgs) -> Result<Vec<KnowledgeHistoryRecord>, String> {
+    run_blocking(move || {
+        open_manager(&args.project_path)?
+            .knowledge_history(&args.id)
+            .map_err(|e| format!("读取知识库历史失败: {}", e))
+    })
+    .await
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct KnowledgeDiagnosticsArgs {
+    #[serde(alias = "projectPath", alias = "project_path")]
+    pub project_path: String,
+}
+
+/// 检查知识库格式，返回诊断信息
+#[tauri::command]
+pub async fn get_knowledge_diagnostics(args: KnowledgeDiagnosticsArgs) -> Result<Vec<KnowledgeDiagnostic>, String> {
+    run_blocking(move || {
+        open_manager(&args.project_path)?
+            .knowledge_diagnostics()
+            .map_err(|e| format!("检查知识库失败: {}", e))
+    })
+    .await
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct KnowledgeSyncArgs {
+    /// 为空时使用全局知识库（~/cunzhi/.cunzhi-knowledge 或 ~/.cunzhi-knowledge）
+    #[serde(default, alias = "projectPath", alias = "project_path")]
+    pub project_path: Option<String>,
+}
+
+fn open_sync(args: &KnowledgeSyncArgs) -> Result<KnowledgeSync, String> {
+    let dir = crate::mcp::tools::interaction::logger::find_knowledge_dir(args.project_path.as_deref())
         .map_err(|e| e.to_string())?;
+    Ok(KnowledgeSync::new(&dir))
+}
 
-    call_tool_result_to_text(&result)
+/// 查看知识库同步状态（排队提交数、离线状态、最近错误）
+#[tauri::command]
+pub async fn get_knowledge_sync_status(args: KnowledgeSyncArgs) -> Result<SyncStatus, String> {
+    run_blocking(move || {
+        open_sync(&args)?
+            .status()
+            .map_err(|e| format!("读取同步状态失败: {}", e))
+    })
+    .await
+}
+
+/// 立即同步知识库（拉取远程更新并推送排队的提交）
+#[tauri::command]
+pub async fn sync_knowledge_now(args: KnowledgeSyncArgs) -> Result<String, String> {
+    run_blocking(move || {
+        open_sync(&args)?
+            .sync()
+            .map(|outcome| outcome.message())
+            .map_err(|e| format!("同步知识库失败: {}", e))
+    })
+    .await
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct InitKnowledgeBaseArgs {
+    #[serde(alias = "projectPath", alias = "project_path")]
+    pub project_path: String,
+    #[serde(default, flatten)]
+    pub options: KnowledgeInitOptions,
+}
+
+/// 初始化项目知识库（模板、共享仓库 clone、git 远程）
+#[tauri::command]
+pub async fn init_knowledge_base(args: InitKnowledgeBaseArgs) -> Result<KnowledgeInitReport, String> {
+    run_blocking(move || {
+        open_manager(&args.project_path)?
+            .init_knowledge(&args.options.with_configured_repo())
+            .map_err(|e| format!("初始化知识库失败: {}", e))
+    })
+    .await
+}
+
+/// 知识库体检（结构、编号、重复条目、git 远程）
+#[tauri::command]
+pub async fn check_knowledge_base(args: KnowledgeDiagnosticsArgs) -> Result<DoctorReport, String> {
+    run_blocking(move || {
+        open_manager(&args.project_path)?
+            .knowledge_doctor()
+            .map_err(|e| format!("知识库体检失败: {}", e))
+    })
+    .await
 }
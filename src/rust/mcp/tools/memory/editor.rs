@@ -0,0 +1,288 @@
+//! 记忆编辑：按条件列出、修改、删除、跨分类移动记忆条目，并记录修改历史
+//!
+//! 修改历史以 JSON Lines 追加到 .cunzhi-memory/history.jsonl，供设置窗口查看
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::lifecycle::ENTRY_CATEGORIES;
+use super::manager::MemoryManager;
+use super::types::{MemoryCategory, MemoryEntry};
+use crate::utils::update_locked;
+
+/// 修改历史文件名
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+/// 列出记忆时的过滤条件
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MemoryFilter {
+    /// 分类标识（rule/preference/note/context），为空表示全部
+    #[serde(default)]
+    pub category: Option<String>,
+    /// 内容关键词（不区分大小写）
+    #[serde(default)]
+    pub keyword: Option<String>,
+    /// 只列出置顶/非置顶条目
+    #[serde(default)]
+    pub pinned: Option<bool>,
+    /// 是否包含已过期但尚未归档的条目
+    #[serde(default, alias = "includeExpired")]
+    pub include_expired: bool,
+}
+
+/// 修改记忆时的字段，None 表示保持不变
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MemoryUpdate {
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub pinned: Option<bool>,
+    /// 从现在起的有效天数，0 表示永不过期
+    #[serde(default, alias = "ttlDays")]
+    pub ttl_days: Option<i64>,
+}
+
+/// 一条修改历史
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryHistoryRecord {
+    pub timestamp: DateTime<Utc>,
+    /// edit / delete / move
+    pub action: String,
+    pub entry_id: String,
+    pub category: MemoryCategory,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_category: Option<MemoryCategory>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+/// 解析可编辑的分类（会话摘要不是条目格式，不在此编辑）
+fn editable_category(key: &str) -> Result<MemoryCategory> {
+    match MemoryCategory::from_key(key) {
+        Some(category) if ENTRY_CATEGORIES.contains(&category) => Ok(category),
+        Some(_) => Err(anyhow!("会话摘要不支持在记忆编辑器中修改")),
+        None => Err(anyhow!("未知的记忆分类: {}", key)),
+    }
+}
+
+/// 条目是一行，编辑内容中的换行折叠为空格
+fn normalize_content(content: &str) -> Result<String> {
+    let normalized = content.split_whitespace().collect::<Vec<_>>().join(" ");
+    if normalized.is_empty() {
+        return Err(anyhow!("记忆内容不能为空"));
+    }
+    Ok(normalized)
+}
+
+impl MemoryManager {
+    /// 按条件列出记忆，按更新时间倒序
+    pub fn list_memories(&self, filter: &MemoryFilter) -> Result<Vec<MemoryEntry>> {
+        let categories = match filter.category.as_deref().filter(|c| !c.trim().is_empty()) {
+            Some(key) => vec![editable_category(key)?],
+            None => ENTRY_CATEGORIES.to_vec(),
+        };
+        let keyword = filter.keyword.as_deref().map(|k| k.trim().to_lowercase()).filter(|k| !k.is_empty());
+        let now = Utc::now();
+
+        let mut entries = Vec::new();
+        for category in categories {
+            entries.extend(self.get_memories_by_category(category)?.into_iter().filter(|entry| {
+                (filter.include_expired || !entry.is_expired(now))
+                    && filter.pinned.map(|p| entry.pinned == p).unwrap_or(true)
+                    && keyword.as_ref().map(|k| entry.content.to_lowercase().contains(k)).unwrap_or(true)
+            }));
+        }
+
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.updated_at));
+        Ok(entries)
+    }
+
+    /// 修改记忆条目，返回修改后的条目
+    pub fn update_memory(&self, id: &str, update: &MemoryUpdate) -> Result<MemoryEntry> {
+        let content = update.content.as_deref().map(normalize_content).transpose()?;
+        let now = Utc::now();
+
+        for category in ENTRY_CATEGORIES {
+            let mut updated = None;
+            self.rewrite_category(category, |entry| {
+                if entry.id == id {
+                    let before = entry.content.clone();
+                    if let Some(content) = &content {
+                        entry.content = content.clone();
+                    }
+                    if let Some(pinned) = update.pinned {
+                        entry.pinned = pinned;
+                    }
+                    if let Some(days) = update.ttl_days {
+                        entry.expires_at = (days > 0).then(|| now + Duration::days(days));
+                    }
+                    if entry.pinned {
+                        entry.expires_at = None;
+                    }
+                    entry.updated_at = now;
+                    updated = Some((before, entry.clone()));
+                }
+                true
+            })?;
+
+            if let Some((before, entry)) = updated {
+                self.record_history(MemoryHistoryRecord {
+                    timestamp: now,
+                    action: "edit".to_string(),
+                    entry_id: entry.id.clone(),
+                    category,
+                    target_category: None,
+                    before: Some(before),
+                    after: Some(entry.content.clone()),
+                })?;
+                return Ok(entry);
+            }
+        }
+
+        Err(anyhow!("未找到记忆条目: {}", id))
+    }
+
+    /// 删除记忆条目，返回被删除的条目
+    pub fn delete_memory(&self, id: &str) -> Result<MemoryEntry> {
+        let entry = self.take_memory(id)?;
+        self.update_metadata()?;
+        self.record_history(MemoryHistoryRecord {
+            timestamp: Utc::now(),
+            action: "delete".to_string(),
+            entry_id: entry.id.clone(),
+            category: entry.category,
+            target_category: None,
+            before: Some(entry.content.clone()),
+            after: None,
+        })?;
+        Ok(entry)
+    }
+
+    /// 将记忆条目移动到另一个分类（保留 ID、创建时间、置顶和有效期）
+    ///
+    /// 先写入目标分类再从原分类移除，中途失败最多留下一条重复条目，不会丢失记忆
+    pub fn move_memory(&self, id: &str, target: &str) -> Result<MemoryEntry> {
+        let target = editable_category(target)?;
+        let mut entry = self.find_memory(id)?;
+        let source = entry.category;
+        if source == target {
+            return Ok(entry);
+        }
+
+        entry.category = target;
+        entry.updated_at = Utc::now();
+        self.append_to_category_file(&entry)?;
+
+        let removed = self.rewrite_category(source, |e| e.id != id)?;
+        if removed.is_empty() {
+            // 读取之后条目已被其他进程删除或移动，撤回刚写入的副本
+            self.rewrite_category(target, |e| e.id != id)?;
+            return Err(anyhow!("记忆条目已被其他操作修改，请刷新后重试: {}", id));
+        }
+
+        self.record_history(MemoryHistoryRecord {
+            timestamp: entry.updated_at,
+            action: "move".to_string(),
+            entry_id: entry.id.clone(),
+            category: source,
+            target_category: Some(target),
+            before: Some(entry.content.clone()),
+            after: Some(entry.content.clone()),
+        })?;
+        Ok(entry)
+    }
+
+    /// 读取修改历史（最新在前），可按条目 ID 过滤
+    pub fn memory_history(&self, id: Option<&str>) -> Result<Vec<MemoryHistoryRecord>> {
+        let path = self.memory_dir().join(HISTORY_FILE);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        let mut records: Vec<MemoryHistoryRecord> = content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .filter(|r: &MemoryHistoryRecord| id.map(|id| r.entry_id == id).unwrap_or(true))
+            .collect();
+        records.reverse();
+        Ok(records)
+    }
+
+    /// 按 ID 查找条目
+    fn find_memory(&self, id: &str) -> Result<MemoryEntry> {
+        for category in ENTRY_CATEGORIES {
+            if let Some(entry) = self.get_memories_by_category(category)?.into_iter().find(|e| e.id == id) {
+                return Ok(entry);
+            }
+        }
+        Err(anyhow!("未找到记忆条目: {}", id))
+    }
+
+    /// 从所在分类文件中移除条目并返回
+    fn take_memory(&self, id: &str) -> Result<MemoryEntry> {
+        for category in ENTRY_CATEGORIES {
+            let mut removed = self.rewrite_category(category, |entry| entry.id != id)?;
+            if let Some(entry) = removed.pop() {
+                return Ok(entry);
+            }
+        }
+        Err(anyhow!("未找到记忆条目: {}", id))
+    }
+
+    fn record_history(&self, record: MemoryHistoryRecord) -> Result<()> {
+        let line = serde_json::to_string(&record)?;
+        update_locked(&self.memory_dir().join(HISTORY_FILE), "", |content| {
+            content.push_str(&line);
+            content.push('\n');
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_edit_move_delete_are_recorded() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(".git")).unwrap();
+        let manager = MemoryManager::new(dir.path().to_str().unwrap()).unwrap();
+
+        let id = manager.add_memory("使用 pnpm 安装依赖", MemoryCategory::Note).unwrap();
+        manager.add_memory("回复使用中文", MemoryCategory::Preference).unwrap();
+
+        let edited = manager.update_memory(&id, &MemoryUpdate {
+            content: Some("统一使用\npnpm".to_string()),
+            pinned: Some(true),
+            ttl_days: None,
+        }).unwrap();
+        assert_eq!(edited.content, "统一使用 pnpm");
+        assert!(edited.pinned && edited.expires_at.is_none());
+
+        let moved = manager.move_memory(&id, "rule").unwrap();
+        assert_eq!(moved.category, MemoryCategory::Rule);
+        assert_eq!(moved.id, id);
+
+        let rules = manager.list_memories(&MemoryFilter {
+            category: Some("rule".to_string()),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(rules.len(), 1);
+        assert!(manager.list_memories(&MemoryFilter {
+            category: Some("note".to_string()),
+            ..Default::default()
+        }).unwrap().is_empty());
+
+        manager.delete_memory(&id).unwrap();
+        assert!(manager.delete_memory(&id).is_err());
+
+        let actions: Vec<String> = manager.memory_history(Some(&id)).unwrap().into_iter().map(|r| r.action).collect();
+        assert_eq!(actions, vec!["delete", "move", "edit"]);
+    }
+}
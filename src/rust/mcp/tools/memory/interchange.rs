@@ -0,0 +1,524 @@
+//! 记忆导入导出
+//!
+//! 在 .cunzhi-memory 分类与各 IDE/代理实际读取的规则文件之间互相转换：
+//! AGENTS.md、.cursorrules、.windsurfrules、CLAUDE.md，以及完整的 JSON 包
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::manager::{new_entry_id, MemoryManager};
+use super::sessions::SessionSummary;
+use super::types::{MemoryCategory, MemoryEntry};
+use crate::constants::mcp::SESSION_RETENTION_DEFAULT;
+use crate::utils::{update_locked, write_locked};
+
+/// 导出到规则文件时的托管区块标记，区块外的内容保持不变
+pub const MANAGED_BLOCK_BEGIN: &str = "<!-- cunzhi-memory:begin -->";
+pub const MANAGED_BLOCK_END: &str = "<!-- cunzhi-memory:end -->";
+
+/// JSON 包格式版本（2 起包含条目 ID、置顶、过期时间和时间戳）
+const BUNDLE_VERSION: &str = "2";
+
+/// 规则文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleFormat {
+    AgentsMd,
+    CursorRules,
+    WindsurfRules,
+    ClaudeMd,
+    JsonBundle,
+}
+
+impl RuleFormat {
+    /// 解析格式名称
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "agents" | "agents.md" | "agents_md" => Ok(RuleFormat::AgentsMd),
+            "cursor" | "cursorrules" | ".cursorrules" => Ok(RuleFormat::CursorRules),
+            "windsurf" | "windsurfrules" | ".windsurfrules" => Ok(RuleFormat::WindsurfRules),
+            "claude" | "claude.md" | "claude_md" => Ok(RuleFormat::ClaudeMd),
+            "json" | "bundle" => Ok(RuleFormat::JsonBundle),
+            other => Err(anyhow::anyhow!(
+                "不支持的格式: {}，可选：agents/cursor/windsurf/claude/json",
+                other
+            )),
+        }
+    }
+
+    /// 该格式在项目根目录下的默认文件名
+    pub fn default_filename(&self) -> &'static str {
+        match self {
+            RuleFormat::AgentsMd => "AGENTS.md",
+            RuleFormat::CursorRules => ".cursorrules",
+            RuleFormat::WindsurfRules => ".windsurfrules",
+            RuleFormat::ClaudeMd => "CLAUDE.md",
+            RuleFormat::JsonBundle => "cunzhi-memory.json",
+        }
+    }
+
+    /// 规则文件只导出长期有效的分类，会话摘要只进入 JSON 包
+    fn exported_categories(&self) -> &'static [MemoryCategory] {
+        match self {
+            RuleFormat::JsonBundle => &MemoryCategory::ALL,
+            _ => &[
+                MemoryCategory::Rule,
+                MemoryCategory::Preference,
+                MemoryCategory::Context,
+                MemoryCategory::Note,
+            ],
+        }
+    }
+}
+
+/// JSON 包中的单条记忆
+///
+/// 除分类和内容外的字段均可缺省，旧版本的包仍可导入；缺少 created_at 时视为没有元数据，
+/// 按分类默认有效期处理
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BundleEntry {
+    pub category: String,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl BundleEntry {
+    fn from_entry(entry: MemoryEntry) -> Self {
+        Self {
+            category: entry.category.key().to_string(),
+            content: entry.content,
+            id: Some(entry.id),
+            pinned: entry.pinned,
+            expires_at: entry.expires_at,
+            created_at: Some(entry.created_at),
+            updated_at: Some(entry.updated_at),
+        }
+    }
+}
+
+/// JSON 记忆包
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryBundle {
+    pub version: String,
+    pub project_path: String,
+    pub exported_at: DateTime<Utc>,
+    pub memories: Vec<BundleEntry>,
+}
+
+/// 导入结果
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub skipped_duplicates: usize,
+    pub per_category: Vec<(String, usize)>,
+}
+
+impl ImportReport {
+    fn record(&mut self, category: MemoryCategory) {
+        self.imported += 1;
+        let key = category.key();
+        match self.per_category.iter_mut().find(|(k, _)| k.as_str() == key) {
+            Some((_, count)) => *count += 1,
+            None => self.per_category.push((key.to_string(), 1)),
+        }
+    }
+
+    /// 生成简短的文本描述
+    pub fn summary(&self) -> String {
+        let detail = self.per_category
+            .iter()
+            .map(|(k, n)| format!("{}: {}", k, n))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "✅ 已导入 {} 条记忆（跳过重复 {} 条）{}",
+            self.imported,
+            self.skipped_duplicates,
+            if detail.is_empty() { String::new() } else { format!("\n📂 {}", detail) }
+        )
+    }
+}
+
+impl MemoryManager {
+    /// 将记忆渲染为指定格式的文本
+    pub fn export_memories(&self, format: RuleFormat) -> Result<String> {
+        let now = Utc::now();
+        let mut grouped: Vec<(MemoryCategory, Vec<MemoryEntry>)> = Vec::new();
+        for category in format.exported_categories() {
+            let entries: Vec<MemoryEntry> = self
+                .get_memories_by_category(*category)?
+                .into_iter()
+                .filter(|e| !e.is_expired(now))
+                .collect();
+            if !entries.is_empty() {
+                grouped.push((*category, entries));
+            }
+        }
+
+        if format == RuleFormat::JsonBundle {
+            let bundle = MemoryBundle {
+                version: BUNDLE_VERSION.to_string(),
+                project_path: self.project_root().to_string_lossy().to_string(),
+                exported_at: Utc::now(),
+                memories: grouped
+                    .into_iter()
+                    .flat_map(|(_, entries)| entries.into_iter().map(BundleEntry::from_entry))
+                    .collect(),
+            };
+            return Ok(serde_json::to_string_pretty(&bundle)?);
+        }
+
+        Ok(render_managed_block(&grouped))
+    }
+
+    /// 导出到文件，返回写入路径
+    ///
+    /// 未指定路径时写入项目根目录下该格式的默认文件；规则文件只替换托管区块
+    pub fn export_to_file(&self, format: RuleFormat, output: Option<&Path>) -> Result<PathBuf> {
+        let path = match output {
+            Some(p) => p.to_path_buf(),
+            None => self.project_root().join(format.default_filename()),
+        };
+        let rendered = self.export_memories(format)?;
+
+        if format == RuleFormat::JsonBundle {
+            write_locked(&path, rendered)?;
+        } else {
+            update_locked(&path, "", |content| {
+                *content = merge_managed_block(content, &rendered)
+                    .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+                Ok(())
+            })?;
+        }
+
+        Ok(path)
+    }
+
+    /// 从文本导入记忆，已存在的相同内容会被跳过
+    pub fn import_memories(&self, format: RuleFormat, input: &str) -> Result<ImportReport> {
+        let candidates = if format == RuleFormat::JsonBundle {
+            parse_bundle(input)?
+        } else {
+            parse_rule_text(input)
+        };
+
+        let existing = self.get_all_memories()?;
+        let mut known: HashSet<(MemoryCategory, String)> = existing
+            .iter()
+            .map(|e| (e.category, normalize_content(&e.content)))
+            .collect();
+        let mut known_ids: HashSet<String> = existing.into_iter().map(|e| e.id).collect();
+
+        let mut report = ImportReport::default();
+        for (category, candidate) in candidates {
+            let content = candidate.content.as_str();
+            let normalized = normalize_content(content);
+            if normalized.is_empty() {
+                continue;
+            }
+            if !known.insert((category, normalized.clone())) {
+                report.skipped_duplicates += 1;
+                continue;
+            }
+
+            // 会话摘要写回 sessions.md 的区块格式，保留原时间戳
+            if category == MemoryCategory::Session {
+                self.insert_session(&SessionSummary::parse_input(content), SESSION_RETENTION_DEFAULT)?;
+                report.record(category);
+                continue;
+            }
+
+            // 包中带有的 ID、置顶、过期时间和时间戳原样保留；ID 与已有条目冲突时重新分配
+            let now = Utc::now();
+            let id = candidate.id.clone()
+                .filter(|id| !id.trim().is_empty() && !known_ids.contains(id))
+                .unwrap_or_else(new_entry_id);
+            known_ids.insert(id.clone());
+            let expires_at = match candidate.created_at {
+                Some(_) => candidate.expires_at,
+                None => category.default_ttl_days().map(|days| now + chrono::Duration::days(days)),
+            };
+            let created_at = candidate.created_at.unwrap_or(now);
+            let entry = MemoryEntry {
+                id,
+                content: normalized,
+                category,
+                created_at,
+                updated_at: candidate.updated_at.unwrap_or(created_at),
+                pinned: candidate.pinned,
+                expires_at,
+            };
+            self.append_to_category_file(&entry)?;
+            report.record(category);
+        }
+
+        if report.imported > 0 {
+            self.update_metadata()?;
+        }
+
+        Ok(report)
+    }
+
+    /// 从文件导入记忆；未指定路径时读取项目根目录下该格式的默认文件
+    pub fn import_from_file(&self, format: RuleFormat, input: Option<&Path>) -> Result<ImportReport> {
+        let path = match input {
+            Some(p) => p.to_path_buf(),
+            None => self.project_root().join(format.default_filename()),
+        };
+        let content = fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("读取 {} 失败: {}", path.display(), e))?;
+        self.import_memories(format, &content)
+    }
+}
+
+/// 渲染托管区块
+fn render_managed_block(grouped: &[(MemoryCategory, Vec<MemoryEntry>)]) -> String {
+    let mut out = String::new();
+    out.push_str(MANAGED_BLOCK_BEGIN);
+    out.push_str("\n<!-- 由 cunzhi 从 .cunzhi-memory 同步生成，请勿手动修改本区块 -->\n\n");
+
+    for (category, entries) in grouped {
+        out.push_str(&format!("## {}\n\n", category_heading(*category)));
+        for entry in entries {
+            out.push_str(&format!("- {}\n", entry.content));
+        }
+        out.push('\n');
+    }
+
+    out.push_str(MANAGED_BLOCK_END);
+    out.push('\n');
+    out
+}
+
+/// 用新的托管区块替换现有区块，没有区块时追加到文件末尾
+///
+/// 标记不成对、重复或顺序颠倒时报错，避免把用户内容当作区块覆盖
+fn merge_managed_block(existing: &str, block: &str) -> Result<String> {
+    let begins: Vec<usize> = existing.match_indices(MANAGED_BLOCK_BEGIN).map(|(i, _)| i).collect();
+    let ends: Vec<usize> = existing.match_indices(MANAGED_BLOCK_END).map(|(i, _)| i).collect();
+
+    match (begins.as_slice(), ends.as_slice()) {
+        ([start], [end]) if start < end => {
+            let mut after = &existing[end + MANAGED_BLOCK_END.len()..];
+            after = after.strip_prefix('\n').unwrap_or(after);
+            return Ok(format!("{}{}{}", &existing[..*start], block, after));
+        }
+        ([], []) => {}
+        _ => {
+            return Err(anyhow::anyhow!(
+                "托管区块标记不完整或顺序错误（{} 个开始标记，{} 个结束标记），请手动修复后再导出",
+                begins.len(),
+                ends.len()
+            ));
+        }
+    }
+
+    Ok(if existing.trim().is_empty() {
+        block.to_string()
+    } else {
+        let sep = if existing.ends_with("\n\n") {
+            ""
+        } else if existing.ends_with('\n') {
+            "\n"
+        } else {
+            "\n\n"
+        };
+        format!("{}{}{}", existing, sep, block)
+    })
+}
+
+/// 导出时使用的分类标题
+fn category_heading(category: MemoryCategory) -> &'static str {
+    match category {
+        MemoryCategory::Rule => "开发规范和规则 (Rules)",
+        MemoryCategory::Preference => "用户偏好设置 (Preferences)",
+        MemoryCategory::Note => "临时笔记 (Notes)",
+        MemoryCategory::Context => "项目上下文信息 (Context)",
+        MemoryCategory::Session => "会话摘要 (Sessions)",
+    }
+}
+
+/// 根据标题文字推断分类
+fn category_from_heading(heading: &str) -> Option<MemoryCategory> {
+    let h = heading.to_lowercase();
+    if h.contains("规范") || h.contains("规则") || h.contains("rule") || h.contains("guideline") || h.contains("convention") {
+        Some(MemoryCategory::Rule)
+    } else if h.contains("偏好") || h.contains("preference") || h.contains("style") {
+        Some(MemoryCategory::Preference)
+    } else if h.contains("笔记") || h.contains("note") {
+        Some(MemoryCategory::Note)
+    } else if h.contains("上下文") || h.contains("背景") || h.contains("context") || h.contains("overview") {
+        Some(MemoryCategory::Context)
+    } else if h.contains("会话") || h.contains("session") {
+        Some(MemoryCategory::Session)
+    } else {
+        None
+    }
+}
+
+/// 解析 Markdown/纯文本规则文件
+///
+/// 只导入列表项，分类由最近的标题推断（无法识别时视为规则）；
+/// 普通段落多为说明文字，不作为记忆导入
+fn parse_rule_text(input: &str) -> Vec<(MemoryCategory, BundleEntry)> {
+    let mut items = Vec::new();
+    let mut current_category = MemoryCategory::Rule;
+    let mut in_code_block = false;
+
+    for raw in input.lines() {
+        let line = raw.trim();
+
+        if line.starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block || line.starts_with("<!--") {
+            continue;
+        }
+
+        if line.starts_with('#') {
+            let heading = line.trim_start_matches('#').trim();
+            current_category = category_from_heading(heading).unwrap_or(MemoryCategory::Rule);
+            continue;
+        }
+
+        if let Some(item) = strip_list_marker(line) {
+            items.push((current_category, BundleEntry {
+                category: current_category.key().to_string(),
+                content: item.to_string(),
+                ..Default::default()
+            }));
+        }
+    }
+
+    items
+}
+
+/// 去掉 `- `、`* `、`1. ` 等列表标记
+fn strip_list_marker(line: &str) -> Option<&str> {
+    if let Some(rest) = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")) {
+        return Some(rest.trim());
+    }
+
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        if let Some(rest) = line[digits..].strip_prefix(". ") {
+            return Some(rest.trim());
+        }
+    }
+
+    None
+}
+
+/// 解析 JSON 记忆包
+fn parse_bundle(input: &str) -> Result<Vec<(MemoryCategory, BundleEntry)>> {
+    let bundle: MemoryBundle = serde_json::from_str(input)
+        .map_err(|e| anyhow::anyhow!("JSON 记忆包格式错误: {}", e))?;
+
+    bundle
+        .memories
+        .into_iter()
+        .map(|entry| {
+            let category = MemoryCategory::from_key(&entry.category)
+                .ok_or_else(|| anyhow::anyhow!("JSON 记忆包中存在未知分类: {}", entry.category))?;
+            Ok((category, entry))
+        })
+        .collect()
+}
+
+/// 记忆文件一行一条，导入时压缩空白
+fn normalize_content(content: &str) -> String {
+    content.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::types::MemoryOptions;
+    use tempfile::tempdir;
+
+    fn manager(dir: &Path) -> MemoryManager {
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        MemoryManager::new(dir.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_and_managed_block_merge() {
+        let source_dir = tempdir().unwrap();
+        let source = manager(source_dir.path());
+        source.add_memory("提交前运行 cargo clippy", MemoryCategory::Rule).unwrap();
+        source.add_memory("回复使用中文", MemoryCategory::Preference).unwrap();
+
+        // 托管区块外的用户内容保持不变，重复导出只替换区块
+        let agents = source_dir.path().join("AGENTS.md");
+        fs::write(&agents, "# 项目说明\n\n这是一段说明文字。\n").unwrap();
+        source.export_to_file(RuleFormat::AgentsMd, None).unwrap();
+        source.add_memory("使用 pnpm 安装依赖", MemoryCategory::Rule).unwrap();
+        source.export_to_file(RuleFormat::AgentsMd, None).unwrap();
+        let exported = fs::read_to_string(&agents).unwrap();
+        assert!(exported.starts_with("# 项目说明\n\n这是一段说明文字。\n\n"));
+        assert_eq!(exported.matches(MANAGED_BLOCK_BEGIN).count(), 1);
+
+        // 导入到另一个项目：只导入列表项，说明段落不会变成规则；再次导入全部跳过
+        let target_dir = tempdir().unwrap();
+        let target = manager(target_dir.path());
+        let report = target.import_from_file(RuleFormat::AgentsMd, Some(&agents)).unwrap();
+        assert_eq!((report.imported, report.skipped_duplicates), (3, 0));
+        let rules: Vec<String> = target.get_memories_by_category(MemoryCategory::Rule).unwrap().into_iter().map(|e| e.content).collect();
+        assert_eq!(rules.len(), 2);
+        assert!(!rules.iter().any(|r| r.contains("说明文字")));
+        assert_eq!(target.import_from_file(RuleFormat::AgentsMd, Some(&agents)).unwrap().skipped_duplicates, 3);
+
+        // JSON 包保留置顶、过期时间、ID 和时间戳
+        let pinned_id = source
+            .add_memory_with_options("发布前通知测试组", MemoryCategory::Note, MemoryOptions { ttl_days: Some(3), pinned: true })
+            .unwrap();
+        let bundle = source.export_memories(RuleFormat::JsonBundle).unwrap();
+        let json_dir = tempdir().unwrap();
+        let imported = manager(json_dir.path());
+        assert_eq!(imported.import_memories(RuleFormat::JsonBundle, &bundle).unwrap().imported, 4);
+        let original = source.get_all_memories().unwrap();
+        let copied = imported.get_all_memories().unwrap();
+        for entry in &original {
+            let copy = copied.iter().find(|c| c.id == entry.id).unwrap();
+            assert_eq!(
+                (&copy.content, copy.pinned, copy.expires_at, copy.created_at, copy.updated_at),
+                (&entry.content, entry.pinned, entry.expires_at, entry.created_at, entry.updated_at)
+            );
+        }
+        assert!(copied.iter().any(|c| c.id == pinned_id && c.pinned));
+
+        // 旧版本的包只有分类和内容，仍可导入
+        let legacy = r#"{"version":"1","project_path":"/p","exported_at":"2024-01-01T00:00:00Z","memories":[{"category":"rule","content":"旧包规则"}]}"#;
+        assert_eq!(manager(tempdir().unwrap().path()).import_memories(RuleFormat::JsonBundle, legacy).unwrap().imported, 1);
+
+        // 标记不完整或顺序颠倒时报错，不改动文件
+        let block = "<!-- cunzhi-memory:begin -->\n- 新\n<!-- cunzhi-memory:end -->\n";
+        for broken in [
+            "开头\n<!-- cunzhi-memory:begin -->\n用户内容\n",
+            "<!-- cunzhi-memory:end -->\n用户内容\n<!-- cunzhi-memory:begin -->\n",
+            "<!-- cunzhi-memory:begin -->\n<!-- cunzhi-memory:end -->\n<!-- cunzhi-memory:begin -->\n",
+        ] {
+            assert!(merge_managed_block(broken, block).is_err(), "{}", broken);
+            fs::write(&agents, broken).unwrap();
+            assert!(source.export_to_file(RuleFormat::AgentsMd, None).is_err());
+            assert_eq!(fs::read_to_string(&agents).unwrap(), broken);
+        }
+        assert_eq!(
+            merge_managed_block("前\n<!-- cunzhi-memory:begin -->\n- 旧\n<!-- cunzhi-memory:end -->\n后\n", block).unwrap(),
+            format!("前\n{}后\n", block)
+        );
+    }
+}
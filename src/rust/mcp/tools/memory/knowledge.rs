@@ -0,0 +1,396 @@
+//! 知识库条目编辑：按编号列出、修改、删除 .cunzhi-knowledge 中的条目
+//!
+//! 条目的解析与写入见 knowledge_base；历史通过知识库的 git log 查看
+
+use anyhow::{anyhow, Result};
+use chrono::Datelike;
+use serde::Serialize;
+use std::path::Path;
+use std::process::Command;
+
+use super::knowledge_base::{
+    next_free_id, parse_knowledge, KnowledgeBase, KnowledgeDiagnostic, KnowledgeEntry, KnowledgeKind, KnowledgeStatus,
+};
+use super::manager::MemoryManager;
+use super::sync::KnowledgeSync;
+use crate::utils::update_locked;
+
+/// 条目的一次 git 提交记录
+#[derive(Debug, Clone, Serialize)]
+pub struct KnowledgeHistoryRecord {
+    pub commit: String,
+    pub author: String,
+    pub date: String,
+    pub subject: String,
+}
+
+/// 问题状态流转（open → fixed → verified，已修复的问题复发时回到 open）
+#[derive(Debug, Clone)]
+pub enum ProblemTransition {
+    /// open → fixed，附带修复提交
+    Fix { commit: Option<String>, notes: Option<String> },
+    /// fixed → verified，附带验证说明
+    Verify { notes: Option<String> },
+    /// fixed/verified → open，并自动创建关联的回归条目
+    Reopen { notes: String },
+}
+
+/// 状态流转结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ProblemTransitionResult {
+    pub id: String,
+    pub status: KnowledgeStatus,
+    /// 复发时自动创建的回归条目编号
+    pub regression_id: Option<String>,
+    pub message: String,
+}
+
+/// 校验提交哈希（7-40 位十六进制）
+fn validate_commit(commit: &str) -> Result<String> {
+    let commit = commit.trim();
+    if (7..=40).contains(&commit.len()) && commit.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(commit.to_lowercase())
+    } else {
+        Err(anyhow!("无效的提交哈希: {}（应为 7-40 位十六进制）", commit))
+    }
+}
+
+impl MemoryManager {
+    /// 加载结构化知识库
+    pub fn load_knowledge_base(&self) -> Result<KnowledgeBase> {
+        KnowledgeBase::load(&self.get_knowledge_dir()?)
+    }
+
+    /// 列出知识库条目，可按分类与关键词过滤
+    pub fn list_knowledge(&self, category: Option<&str>, keyword: Option<&str>) -> Result<Vec<KnowledgeEntry>> {
+        let kind = match category.filter(|c| !c.trim().is_empty()) {
+            Some(category) => Some(KnowledgeKind::from_category(category).ok_or_else(|| {
+                anyhow!("不支持的知识库分类: {}，仅支持 patterns/problems/regressions", category)
+            })?),
+            None => None,
+        };
+        let keyword = keyword.map(|k| k.trim().to_lowercase()).filter(|k| !k.is_empty());
+
+        Ok(self.load_knowledge_base()?
+            .entries
+            .into_iter()
+            .filter(|e| kind.map(|k| e.kind == k).unwrap_or(true))
+            .filter(|e| {
+                keyword.as_ref().map(|k| {
+                    e.id.to_lowercase().contains(k)
+                        || e.title.to_lowercase().contains(k)
+                        || e.body.to_lowercase().contains(k)
+                        || e.tags.iter().any(|t| t.to_lowercase().contains(k))
+                }).unwrap_or(true)
+            })
+            .collect())
+    }
+
+    /// 知识库格式诊断
+    pub fn knowledge_diagnostics(&self) -> Result<Vec<KnowledgeDiagnostic>> {
+        Ok(self.load_knowledge_base()?.diagnostics)
+    }
+
+    /// 用新的 Markdown 替换条目（新内容必须保留原编号）
+    pub fn update_knowledge(&self, id: &str, new_content: &str) -> Result<String> {
+        let kind = KnowledgeKind::from_id(id).ok_or_else(|| anyhow!("无效的知识库编号: {}", id))?;
+        let entry = KnowledgeEntry::from_input(new_content, kind)?;
+        if entry.id != id {
+            return Err(anyhow!("修改后的条目必须保留编号 {}", id));
+        }
+        self.rewrite_knowledge_entry(kind, id, Some(&entry.render()), &format!("编辑: {}", id))
+    }
+
+    /// 删除条目
+    pub fn delete_knowledge(&self, id: &str) -> Result<String> {
+        let kind = KnowledgeKind::from_id(id).ok_or_else(|| anyhow!("无效的知识库编号: {}", id))?;
+        self.rewrite_knowledge_entry(kind, id, None, &format!("删除: {}", id))
+    }
+
+    /// 查看条目的 git 修改历史（最新在前）
+    pub fn knowledge_history(&self, id: &str) -> Result<Vec<KnowledgeHistoryRecord>> {
+        let knowledge_dir = self.get_knowledge_dir()?;
+        let output = Command::new("git")
+            .args(["log", "--format=%H%x1f%an%x1f%aI%x1f%s", &format!("-G{}", regex::escape(id)), "--"])
+            .args(KnowledgeKind::ALL.iter().map(|k| k.filename()))
+            .current_dir(&knowledge_dir)
+            .output()?;
+
+        if !output.status.success() {
+            return Err(anyhow!("git log 失败: {}", String::from_utf8_lossy(&output.stderr)));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split('\u{1f}');
+                Some(KnowledgeHistoryRecord {
+                    commit: parts.next()?.to_string(),
+                    author: parts.next()?.to_string(),
+                    date: parts.next()?.to_string(),
+                    subject: parts.next()?.to_string(),
+                })
+            })
+            .collect())
+    }
+
+    /// 变更问题状态
+    ///
+    /// 状态检查和改写在 problems.md 的文件锁内完成；复发时先写入回归条目再更新问题，问题更新失败时撤回回归条目
+    pub fn transition_problem(&self, id: &str, transition: ProblemTransition) -> Result<ProblemTransitionResult> {
+        let id = id.trim();
+        if KnowledgeKind::from_id(id) != Some(KnowledgeKind::Problem) {
+            return Err(anyhow!("{} 不是问题编号（应为 P-YYYY-NNN）", id));
+        }
+
+        let knowledge_dir = self.get_knowledge_dir()?;
+        let today = chrono::Local::now().format("%Y-%m-%d");
+        let commit_msg = match &transition {
+            ProblemTransition::Fix { .. } => format!("修复: {}", id),
+            ProblemTransition::Verify { .. } => format!("验证: {}", id),
+            ProblemTransition::Reopen { .. } => format!("复发: {}", id),
+        };
+
+        // 复发时先在 regressions.md 的锁内写入回归条目，再把关联记录到问题上；问题更新失败则撤回
+        let regression_id = match &transition {
+            ProblemTransition::Reopen { notes } => {
+                let problem = KnowledgeBase::load(&knowledge_dir)?
+                    .get(id)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("{} 中未找到 {}", KnowledgeKind::Problem.filename(), id))?;
+                if problem.status.unwrap_or(KnowledgeStatus::Open) == KnowledgeStatus::Open {
+                    return Err(anyhow!("{} 仍处于 open 状态，无需标记复发", id));
+                }
+                Some(self.create_regression(&knowledge_dir, &problem, notes)?)
+            }
+            _ => None,
+        };
+
+        let updated = self.modify_knowledge_entry(KnowledgeKind::Problem, id, |entry| {
+            let status = entry.status.unwrap_or(KnowledgeStatus::Open);
+
+            let next = match transition {
+                ProblemTransition::Fix { commit, notes } => {
+                    if status != KnowledgeStatus::Open {
+                        return Err(anyhow!("{} 当前状态为 {}，只有 open 的问题可以标记为 fixed", id, status.as_str()));
+                    }
+                    if let Some(commit) = commit.filter(|c| !c.trim().is_empty()) {
+                        entry.commit = Some(validate_commit(&commit)?);
+                    }
+                    if let Some(notes) = notes.filter(|n| !n.trim().is_empty()) {
+                        append_log(&mut entry.body, &format!("{} 修复：{}", today, notes.trim()));
+                    }
+                    KnowledgeStatus::Fixed
+                }
+                ProblemTransition::Verify { notes } => {
+                    // 禁止跳过 fixed 直接到 verified
+                    if status != KnowledgeStatus::Fixed {
+                        return Err(anyhow!("{} 当前状态为 {}，只有 fixed 的问题可以标记为 verified", id, status.as_str()));
+                    }
+                    if let Some(notes) = notes.filter(|n| !n.trim().is_empty()) {
+                        entry.verification = Some(notes.split_whitespace().collect::<Vec<_>>().join(" "));
+                    }
+                    KnowledgeStatus::Verified
+                }
+                ProblemTransition::Reopen { notes } => {
+                    if status == KnowledgeStatus::Open {
+                        return Err(anyhow!("{} 仍处于 open 状态，无需标记复发", id));
+                    }
+                    let regression_id = regression_id.clone().unwrap_or_default();
+                    append_log(&mut entry.body, &format!("{} 复发（{}）：{}", today, regression_id, notes.trim()));
+                    if !entry.links.contains(&regression_id) {
+                        entry.links.push(regression_id);
+                    }
+                    entry.verification = None;
+                    KnowledgeStatus::Open
+                }
+            };
+
+            entry.status = Some(next);
+            Ok((status, next))
+        });
+        let (status, next) = match updated {
+            Ok(result) => result,
+            Err(e) => {
+                // 问题未能更新时撤回刚写入的回归条目
+                if let Some(regression_id) = &regression_id {
+                    if let Err(rollback) = self.replace_knowledge_entry(KnowledgeKind::Regression, regression_id, None) {
+                        crate::log_important!(warn, "撤回回归条目 {} 失败: {}", regression_id, rollback);
+                    }
+                }
+                return Err(e);
+            }
+        };
+
+        let mut files = vec![KnowledgeKind::Problem.filename()];
+        if regression_id.is_some() {
+            files.push(KnowledgeKind::Regression.filename());
+        }
+        let sync = self.sync_knowledge_files(&knowledge_dir, &files, &commit_msg);
+
+        let mut message = format!("🔁 {}: {} → {}", id, status.as_str(), next.as_str());
+        if let Some(regression_id) = &regression_id {
+            message.push_str(&format!("\n🧪 已创建回归条目 {}", regression_id));
+        }
+        message.push('\n');
+        message.push_str(&sync);
+
+        Ok(ProblemTransitionResult { id: id.to_string(), status: next, regression_id, message })
+    }
+
+    /// 在 regressions.md 的文件锁内分配编号并写入问题的回归条目，返回回归编号
+    ///
+    /// P-ID 与 R-ID 一一对应：优先使用同号的 R 编号，已被占用时自动分配
+    fn create_regression(&self, knowledge_dir: &Path, problem: &KnowledgeEntry, notes: &str) -> Result<String> {
+        let kind = KnowledgeKind::Regression;
+        let today = chrono::Local::now().format("%Y-%m-%d");
+        update_locked(&knowledge_dir.join(kind.filename()), &format!("# {}\n", kind.title()), |content| {
+            let paired = problem.id.replacen("P-", "R-", 1);
+            let taken = parse_knowledge(content, kind, kind.filename()).entries.iter().any(|e| e.id == paired);
+            let regression_id = if taken { next_free_id(content, kind, chrono::Local::now().year()) } else { paired };
+
+            let regression = KnowledgeEntry::from_input(
+                &format!(
+                    "## {} {} 复发回归\n\n- 关联：{}\n\n{} 复发：{}\n上次修复提交：{}",
+                    regression_id,
+                    problem.title,
+                    problem.id,
+                    today,
+                    notes.trim(),
+                    problem.commit.as_deref().unwrap_or("未记录"),
+                ),
+                kind,
+            )?;
+            if !content.ends_with("\n\n") {
+                content.push('\n');
+            }
+            content.push_str(&regression.render());
+            Ok(regression_id)
+        })
+    }
+
+    /// 在文件锁内读取条目、修改并写回（不提交），返回闭包的结果
+    fn modify_knowledge_entry<T, F>(&self, kind: KnowledgeKind, id: &str, modify: F) -> Result<T>
+    where
+        F: FnOnce(&mut KnowledgeEntry) -> Result<T>,
+    {
+        let path = self.get_knowledge_dir()?.join(kind.filename());
+        if !path.exists() {
+            return Err(anyhow!("{} 中未找到 {}", kind.filename(), id));
+        }
+
+        update_locked(&path, "", |content| {
+            let parsed = parse_knowledge(content, kind, kind.filename());
+            let mut entry = parsed
+                .entries
+                .into_iter()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow!("{} 中未找到 {}", kind.filename(), id))?;
+            let result = modify(&mut entry)?;
+            *content = replace_span(content, entry.span, Some(&entry.render()));
+            Ok(result)
+        })
+    }
+
+    /// 在所在文件中替换或删除条目，并同步到 git
+    pub(super) fn rewrite_knowledge_entry(
+        &self,
+        kind: KnowledgeKind,
+        id: &str,
+        replacement: Option<&str>,
+        commit_msg: &str,
+    ) -> Result<String> {
+        let knowledge_dir = self.get_knowledge_dir()?;
+        let path = knowledge_dir.join(kind.filename());
+        if !path.exists() {
+            return Err(anyhow!("知识库中未找到条目: {}", id));
+        }
+
+        if !self.replace_knowledge_entry(kind, id, replacement)? {
+            return Err(anyhow!("知识库中未找到条目: {}", id));
+        }
+        Ok(self.sync_knowledge_files(&knowledge_dir, &[kind.filename()], commit_msg))
+    }
+
+    /// 在文件锁内替换（或删除）条目（不提交），返回是否找到条目
+    fn replace_knowledge_entry(&self, kind: KnowledgeKind, id: &str, replacement: Option<&str>) -> Result<bool> {
+        let path = self.get_knowledge_dir()?.join(kind.filename());
+        update_locked(&path, "", |content| {
+            let parsed = parse_knowledge(content, kind, kind.filename());
+            let Some(span) = parsed.entries.iter().find(|e| e.id == id).map(|e| e.span) else {
+                return Ok(false);
+            };
+            *content = replace_span(content, span, replacement);
+            Ok(true)
+        })
+    }
+
+    /// 提交并同步知识库文件，返回给用户的状态说明
+    pub(super) fn sync_knowledge_files(&self, knowledge_dir: &Path, files: &[&str], commit_msg: &str) -> String {
+        let sync_msg = KnowledgeSync::new(knowledge_dir).commit_and_sync(files, commit_msg);
+        let files: Vec<String> = files.iter().map(|f| format!(".cunzhi-knowledge/{}", f)).collect();
+        format!("✅ 已更新 {}\n{}", files.join("、"), sync_msg)
+    }
+}
+
+/// 用新内容替换（或删除）`span` 覆盖的行
+fn replace_span(content: &str, (start, end): (usize, usize), replacement: Option<&str>) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut rewritten: Vec<String> = lines[..start].iter().map(|l| l.to_string()).collect();
+    if let Some(replacement) = replacement {
+        rewritten.push(replacement.trim_end().to_string());
+        rewritten.push(String::new());
+    }
+    rewritten.extend(lines[end..].iter().map(|l| l.to_string()));
+
+    let mut joined = rewritten.join("\n").trim_end().to_string();
+    joined.push('\n');
+    joined
+}
+
+/// 在正文末尾追加一行状态记录
+fn append_log(body: &mut String, line: &str) {
+    if !body.is_empty() {
+        body.push_str("\n\n");
+    }
+    body.push_str("> ");
+    body.push_str(line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_problem_lifecycle_and_regression_on_reopen() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(".git")).unwrap();
+        std::fs::create_dir_all(dir.path().join(".cunzhi-knowledge")).unwrap();
+        let manager = MemoryManager::new(dir.path().to_str().unwrap()).unwrap();
+
+        let id = manager.settle_to_knowledge("P-2024-022 弹窗不显示\n状态：open", "problems").unwrap().id;
+        let verify = ProblemTransition::Verify { notes: Some("手工回归通过".to_string()) };
+
+        // 禁止跳过 fixed 直接到 verified
+        assert!(manager.transition_problem(&id, verify.clone()).is_err());
+        assert!(manager.transition_problem(&id, ProblemTransition::Fix { commit: Some("xyz".to_string()), notes: None }).is_err());
+
+        manager.transition_problem(&id, ProblemTransition::Fix { commit: Some("ABC1234".to_string()), notes: None }).unwrap();
+        manager.transition_problem(&id, verify).unwrap();
+
+        let result = manager.transition_problem(&id, ProblemTransition::Reopen { notes: "升级后再次出现".to_string() }).unwrap();
+        assert_eq!(result.regression_id.as_deref(), Some("R-2024-022"));
+
+        let base = manager.load_knowledge_base().unwrap();
+        let problem = base.get(&id).unwrap();
+        assert_eq!(problem.status, Some(KnowledgeStatus::Open));
+        assert_eq!(problem.commit.as_deref(), Some("abc1234"));
+        assert!(problem.links.contains(&"R-2024-022".to_string()));
+        assert!(base.get("R-2024-022").unwrap().links.contains(&id));
+
+        // 状态检查失败时不会写入回归条目
+        assert!(manager.transition_problem(&id, ProblemTransition::Reopen { notes: "再次出现".to_string() }).is_err());
+        assert_eq!(manager.load_knowledge_base().unwrap().by_kind(KnowledgeKind::Regression).count(), 1);
+    }
+}
@@ -0,0 +1,623 @@
+//! 全局知识库（.cunzhi-knowledge）的结构化模型
+//!
+//! 每个条目以包含编号的 `##`/`###` 标题开始，标题后可跟元信息行：
+//!
+//! ```markdown
+//! ## P-2024-001 弹窗不显示
+//!
+//! - 状态：open
+//! - 标签：popup, macos
+//! - 相关文件：src/rust/mcp/handlers/popup.rs
+//! - 关联：R-2024-001, PAT-2024-003
+//!
+//! 正文……
+//! ```
+//!
+//! 解析器对格式问题生成诊断信息而不是直接失败，写入器输出上面的规范格式
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// 知识条目类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KnowledgeKind {
+    Problem,
+    Pattern,
+    Regression,
+}
+
+impl KnowledgeKind {
+    pub const ALL: [KnowledgeKind; 3] = [KnowledgeKind::Pattern, KnowledgeKind::Problem, KnowledgeKind::Regression];
+
+    /// 编号前缀
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            KnowledgeKind::Problem => "P",
+            KnowledgeKind::Pattern => "PAT",
+            KnowledgeKind::Regression => "R",
+        }
+    }
+
+    /// 分类标识（与 ji 的 category 参数一致）
+    pub fn category(&self) -> &'static str {
+        match self {
+            KnowledgeKind::Problem => "problems",
+            KnowledgeKind::Pattern => "patterns",
+            KnowledgeKind::Regression => "regressions",
+        }
+    }
+
+    pub fn filename(&self) -> &'static str {
+        match self {
+            KnowledgeKind::Problem => "problems.md",
+            KnowledgeKind::Pattern => "patterns.md",
+            KnowledgeKind::Regression => "regressions.md",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            KnowledgeKind::Problem => "问题记录",
+            KnowledgeKind::Pattern => "最佳实践",
+            KnowledgeKind::Regression => "回归检查",
+        }
+    }
+
+    pub fn from_category(category: &str) -> Option<Self> {
+        match category.trim() {
+            "problems" | "problem" => Some(KnowledgeKind::Problem),
+            "patterns" | "pattern" => Some(KnowledgeKind::Pattern),
+            "regressions" | "regression" => Some(KnowledgeKind::Regression),
+            _ => None,
+        }
+    }
+
+    /// 根据编号前缀判断类型
+    pub fn from_id(id: &str) -> Option<Self> {
+        let prefix = id.split('-').next()?;
+        Self::ALL.into_iter().find(|k| k.prefix() == prefix)
+    }
+}
+
+/// 问题状态（open → fixed → verified）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KnowledgeStatus {
+    Open,
+    Fixed,
+    Verified,
+}
+
+impl KnowledgeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KnowledgeStatus::Open => "open",
+            KnowledgeStatus::Fixed => "fixed",
+            KnowledgeStatus::Verified => "verified",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        // 只看第一个词，允许 "fixed（2024-05-01）" 之类的补充说明
+        let word = value
+            .split(|c: char| c.is_whitespace() || "（(，,".contains(c))
+            .next()
+            .unwrap_or("")
+            .trim_matches('`')
+            .to_lowercase();
+        match word.as_str() {
+            "open" | "待修复" => Some(KnowledgeStatus::Open),
+            "fixed" | "已修复" => Some(KnowledgeStatus::Fixed),
+            "verified" | "已验证" => Some(KnowledgeStatus::Verified),
+            _ => None,
+        }
+    }
+}
+
+/// 知识条目
+#[derive(Debug, Clone, Serialize)]
+pub struct KnowledgeEntry {
+    pub id: String,
+    pub kind: KnowledgeKind,
+    pub title: String,
+    pub status: Option<KnowledgeStatus>,
+    pub tags: Vec<String>,
+    pub files: Vec<String>,
+    /// 关联的其他条目编号（显式关联 + 正文中提到的编号）
+    pub links: Vec<String>,
+    /// 修复提交（问题条目）
+    pub commit: Option<String>,
+    /// 验证说明（问题条目）
+    pub verification: Option<String>,
+    /// 去掉标题和元信息行后的正文
+    pub body: String,
+    /// 条目在文件中的行范围（起始行, 结束行（不含））
+    #[serde(skip)]
+    pub span: (usize, usize),
+}
+
+impl KnowledgeEntry {
+    /// 渲染为规范的 Markdown
+    pub fn render(&self) -> String {
+        let heading = [self.id.as_str(), self.title.as_str()]
+            .iter()
+            .filter(|s| !s.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut out = format!("## {}\n\n", heading);
+
+        let mut meta = Vec::new();
+        if let Some(status) = self.status {
+            meta.push(format!("- 状态：{}", status.as_str()));
+        }
+        if !self.tags.is_empty() {
+            meta.push(format!("- 标签：{}", self.tags.join(", ")));
+        }
+        if !self.files.is_empty() {
+            meta.push(format!("- 相关文件：{}", self.files.join(", ")));
+        }
+        // 只写出正文中没有出现的关联，避免重复
+        let explicit: Vec<&str> = self.links.iter()
+            .map(|l| l.as_str())
+            .filter(|l| !self.body.contains(l))
+            .collect();
+        if !explicit.is_empty() {
+            meta.push(format!("- 关联：{}", explicit.join(", ")));
+        }
+        if let Some(commit) = &self.commit {
+            meta.push(format!("- 修复提交：{}", commit));
+        }
+        if let Some(verification) = &self.verification {
+            meta.push(format!("- 验证：{}", verification));
+        }
+        if !meta.is_empty() {
+            out.push_str(&meta.join("\n"));
+            out.push_str("\n\n");
+        }
+
+        if !self.body.is_empty() {
+            out.push_str(&self.body);
+            out.push('\n');
+        }
+        out
+    }
+
+    /// 解析 ji 沉淀的输入为单个条目
+    ///
+    /// 输入可以是完整的条目（含 `##` 标题），也可以是首行包含编号的纯文本；
+    /// 没有编号时以首个非空行为标题，返回 `id` 为空的条目，由写入时分配编号
+    pub fn from_input(content: &str, kind: KnowledgeKind) -> Result<Self> {
+        let content = content.trim();
+        if content.is_empty() {
+            return Err(anyhow!("缺少沉淀内容"));
+        }
+
+        // 只识别本类型的编号，其他类型的编号（如回归记录中引用的 P- 编号）留在正文中
+        let own_id = kind_id_regex(kind);
+        let has_heading = content.lines().any(|l| heading_level(l).is_some() && own_id.is_match(l));
+        let placeholder = format!("{}-0000-000", kind.prefix());
+        let mut lines: Vec<String> = content.lines().map(|l| l.to_string()).collect();
+
+        let allocate = if has_heading {
+            false
+        } else {
+            // 首个包含编号的行作为标题；都没有编号时用首个非空行，并临时使用占位编号解析
+            let (index, allocate) = match lines.iter().position(|l| own_id.is_match(l)) {
+                Some(index) => (index, false),
+                None => (lines.iter().position(|l| !l.trim().is_empty()).unwrap_or(0), true),
+            };
+            let heading = lines.remove(index);
+            let heading = heading.trim().trim_start_matches(['#', '-', '*', ' ']);
+            if allocate {
+                lines.insert(0, format!("## {} {}", placeholder, heading));
+            } else {
+                lines.insert(0, format!("## {}", heading));
+            }
+            allocate
+        };
+
+        let parsed = parse_knowledge(&lines.join("\n"), kind, kind.filename());
+        if let Some(error) = parsed.diagnostics.iter().find(|d| d.severity == DiagnosticSeverity::Error) {
+            return Err(anyhow!("{}", error.message));
+        }
+        let mut entry = parsed.entries.into_iter().next().ok_or_else(|| anyhow!("未能解析出知识条目"))?;
+        if allocate {
+            entry.id.clear();
+        }
+        // 标题中引用的其他类型编号记为关联
+        for other in id_regex().find_iter(&entry.title).map(|m| m.as_str().to_string()).collect::<Vec<_>>() {
+            if KnowledgeKind::from_id(&other) != Some(kind) && !entry.links.contains(&other) {
+                entry.links.push(other);
+            }
+        }
+        Ok(entry)
+    }
+}
+
+/// 分配指定类型在某年的下一个空闲编号
+///
+/// 扫描文件中出现的所有同前缀同年份编号（包括索引表、关联中的引用），取最大序号加一
+pub fn next_free_id(content: &str, kind: KnowledgeKind, year: i32) -> String {
+    let prefix = format!("{}-{}-", kind.prefix(), year);
+    let max = id_regex()
+        .find_iter(content)
+        .filter_map(|m| m.as_str().strip_prefix(&prefix).and_then(|n| n.parse::<u32>().ok()))
+        .max()
+        .unwrap_or(0);
+    format!("{}{:03}", prefix, max + 1)
+}
+
+/// 诊断级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+}
+
+/// 知识库格式诊断
+#[derive(Debug, Clone, Serialize)]
+pub struct KnowledgeDiagnostic {
+    pub file: String,
+    /// 从 1 开始的行号
+    pub line: usize,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+}
+
+impl std::fmt::Display for KnowledgeDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let level = match self.severity {
+            DiagnosticSeverity::Error => "错误",
+            DiagnosticSeverity::Warning => "警告",
+        };
+        write!(f, "{}:{} [{}] {}", self.file, self.line, level, self.message)
+    }
+}
+
+/// 单个文件的解析结果
+#[derive(Debug, Default)]
+pub struct ParsedKnowledge {
+    pub entries: Vec<KnowledgeEntry>,
+    /// 没有编号的 `## ` 小节（手写的旧内容等），不参与编号检查，只用于检索
+    pub sections: Vec<KnowledgeEntry>,
+    pub diagnostics: Vec<KnowledgeDiagnostic>,
+}
+
+pub(super) fn id_regex() -> Regex {
+    Regex::new(r"\b(?:PAT|P|R)-\d{4}-\d{3}\b").unwrap()
+}
+
+/// 指定类型的知识编号正则
+pub(super) fn kind_id_regex(kind: KnowledgeKind) -> Regex {
+    Regex::new(&format!(r"\b{}-\d{{4}}-\d{{3}}\b", kind.prefix())).unwrap()
+}
+
+/// 标题级别（仅识别 `## ` 及更深的标题）
+pub(super) fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    (level >= 2 && line[level..].starts_with(' ')).then_some(level)
+}
+
+/// 元信息行：`- 状态：open`、`**标签**: a, b` 等（只在标题下方的字段块中识别）
+fn meta_regex() -> Regex {
+    Regex::new(r"^\s*(?:[-*]\s+)?(?:\*\*)?(状态|status|标签|tags|相关文件|文件|files|关联|links|修复提交|commit|验证|verification)(?:\*\*)?\s*[：:]\s*(?:\*\*)?\s*(.*)$").unwrap()
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split([',', '，', '、'])
+        .map(|v| v.trim().trim_matches('`').to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+/// 解析一个知识库文件
+pub fn parse_knowledge(content: &str, kind: KnowledgeKind, file: &str) -> ParsedKnowledge {
+    let id_regex = id_regex();
+    let malformed_id = Regex::new(r"^(?:PAT|P|R)-\S+").unwrap();
+    let meta_regex = meta_regex();
+    let lines: Vec<&str> = content.lines().collect();
+    let mut entries = Vec::new();
+    let mut diagnostics = Vec::new();
+    let mut diagnose = |line: usize, severity, message: String| {
+        diagnostics.push(KnowledgeDiagnostic { file: file.to_string(), line: line + 1, severity, message });
+    };
+
+    // 先切分条目范围：（起始行, 结束行, 标题级别）；未编号的二级标题另记为小节
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    let mut open: Option<(usize, usize)> = None;
+    let mut section_spans: Vec<(usize, usize)> = Vec::new();
+    let mut open_section: Option<usize> = None;
+    for (i, line) in lines.iter().enumerate() {
+        let Some(level) = heading_level(line) else {
+            // 不在标题中的行首编号，多半是漏写了标题
+            if open.is_none() && id_regex.find(line.trim()).map(|m| m.start() == 0).unwrap_or(false) {
+                diagnose(i, DiagnosticSeverity::Warning, format!("编号行缺少 `##` 标题，未被识别为条目: {}", line.trim()));
+            }
+            continue;
+        };
+        let heading = line[level..].trim();
+        let is_entry = id_regex.is_match(heading);
+
+        if let Some(start) = open_section {
+            if is_entry || level == 2 {
+                section_spans.push((start, i));
+                open_section = None;
+            }
+        }
+
+        if let Some((start, open_level)) = open {
+            if is_entry || level <= open_level {
+                spans.push((start, i));
+                open = None;
+            }
+        }
+        if is_entry {
+            open = Some((i, level));
+        } else if malformed_id.is_match(heading) {
+            diagnose(i, DiagnosticSeverity::Error, format!("编号格式错误，应为 {}-YYYY-NNN: {}", kind.prefix(), heading));
+        } else if level == 2 {
+            open_section = Some(i);
+        }
+    }
+    if let Some((start, _)) = open {
+        spans.push((start, lines.len()));
+    }
+    if let Some(start) = open_section {
+        section_spans.push((start, lines.len()));
+    }
+
+    let sections = section_spans
+        .into_iter()
+        .map(|(start, end)| KnowledgeEntry {
+            id: String::new(),
+            kind,
+            title: lines[start][2..].trim().to_string(),
+            status: None,
+            commit: None,
+            verification: None,
+            tags: Vec::new(),
+            files: Vec::new(),
+            links: Vec::new(),
+            body: lines[start + 1..end].join("\n").trim().to_string(),
+            span: (start, end),
+        })
+        .collect();
+
+    for (start, end) in spans {
+        let heading_line = lines[start];
+        let level = heading_level(heading_line).unwrap_or(2);
+        let heading = heading_line[level..].trim();
+        let id_match = id_regex.find(heading).unwrap();
+        let id = id_match.as_str().to_string();
+        let title = heading[id_match.end()..]
+            .trim_start_matches(|c: char| c.is_whitespace() || ":：-—|".contains(c))
+            .trim()
+            .to_string();
+
+        match KnowledgeKind::from_id(&id) {
+            Some(k) if k == kind => {}
+            _ => diagnose(start, DiagnosticSeverity::Error, format!("编号 {} 不属于 {}（应以 {}- 开头）", id, file, kind.prefix())),
+        }
+
+        let mut entry = KnowledgeEntry {
+            id: id.clone(),
+            kind,
+            title,
+            status: None,
+            commit: None,
+            verification: None,
+            tags: Vec::new(),
+            files: Vec::new(),
+            links: Vec::new(),
+            body: String::new(),
+            span: (start, end),
+        };
+
+        // 字段块：标题后连续的元信息行，遇到空行或正文即结束，正文里的「验证：」「文件：」等不再当作字段
+        let mut body_lines = Vec::new();
+        let mut in_fields = true;
+        let mut has_fields = false;
+        for (offset, line) in lines[start + 1..end].iter().enumerate() {
+            if in_fields && line.trim().is_empty() {
+                in_fields = !has_fields;
+                continue;
+            }
+            let caps = if in_fields { meta_regex.captures(line) } else { None };
+            let Some(caps) = caps else {
+                in_fields = false;
+                body_lines.push(*line);
+                continue;
+            };
+            has_fields = true;
+            let value = caps[2].trim();
+            match &caps[1].to_lowercase()[..] {
+                "状态" | "status" => match KnowledgeStatus::parse(value) {
+                    Some(status) => entry.status = Some(status),
+                    None => diagnose(start + 1 + offset, DiagnosticSeverity::Error,
+                        format!("{} 的状态「{}」无效，应为 open/fixed/verified", id, value)),
+                },
+                "标签" | "tags" => entry.tags.extend(split_list(value)),
+                "相关文件" | "文件" | "files" => entry.files.extend(split_list(value)),
+                "修复提交" | "commit" => entry.commit = Some(value.trim_matches('`').to_string()),
+                "验证" | "verification" => entry.verification = Some(value.to_string()),
+                _ => entry.links.extend(split_list(value)),
+            }
+        }
+
+        entry.body = body_lines.join("\n").trim().to_string();
+        for m in id_regex.find_iter(&entry.body) {
+            entry.links.push(m.as_str().to_string());
+        }
+        let mut seen = HashSet::new();
+        entry.links.retain(|l| l != &id && seen.insert(l.clone()));
+
+        if kind == KnowledgeKind::Problem && entry.status.is_none() {
+            diagnose(start, DiagnosticSeverity::Warning, format!("{} 缺少状态（open/fixed/verified）", id));
+        }
+        entries.push(entry);
+    }
+
+    ParsedKnowledge { entries, sections, diagnostics }
+}
+
+/// 整个知识库
+#[derive(Debug, Default)]
+pub struct KnowledgeBase {
+    pub entries: Vec<KnowledgeEntry>,
+    /// 未编号的小节，只用于检索
+    pub sections: Vec<KnowledgeEntry>,
+    pub diagnostics: Vec<KnowledgeDiagnostic>,
+}
+
+impl KnowledgeBase {
+    /// 读取并解析知识库目录下的 problems/patterns/regressions
+    pub fn load(knowledge_dir: &Path) -> Result<Self> {
+        let mut base = KnowledgeBase::default();
+        for kind in KnowledgeKind::ALL {
+            let path = knowledge_dir.join(kind.filename());
+            if !path.exists() {
+                continue;
+            }
+            let content = std::fs::read_to_string(&path)?;
+            let parsed = parse_knowledge(&content, kind, kind.filename());
+            base.entries.extend(parsed.entries);
+            base.sections.extend(parsed.sections);
+            base.diagnostics.extend(parsed.diagnostics);
+        }
+        base.check_references();
+        Ok(base)
+    }
+
+    /// 跨文件检查：重复编号与指向不存在条目的关联
+    fn check_references(&mut self) {
+        let mut seen: HashMap<&str, &KnowledgeEntry> = HashMap::new();
+        let mut diagnostics = Vec::new();
+
+        for entry in &self.entries {
+            if let Some(first) = seen.insert(&entry.id, entry) {
+                diagnostics.push(KnowledgeDiagnostic {
+                    file: entry.kind.filename().to_string(),
+                    line: entry.span.0 + 1,
+                    severity: DiagnosticSeverity::Error,
+                    message: format!("编号 {} 重复（首次出现在 {}:{}）", entry.id, first.kind.filename(), first.span.0 + 1),
+                });
+            }
+        }
+        for entry in &self.entries {
+            for link in &entry.links {
+                if !seen.contains_key(link.as_str()) {
+                    diagnostics.push(KnowledgeDiagnostic {
+                        file: entry.kind.filename().to_string(),
+                        line: entry.span.0 + 1,
+                        severity: DiagnosticSeverity::Warning,
+                        message: format!("{} 关联的 {} 不存在", entry.id, link),
+                    });
+                }
+            }
+        }
+
+        self.diagnostics.extend(diagnostics);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&KnowledgeEntry> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    pub fn by_kind(&self, kind: KnowledgeKind) -> impl Iterator<Item = &KnowledgeEntry> {
+        self.entries.iter().filter(move |e| e.kind == kind)
+    }
+
+    /// 指定状态的问题数量
+    pub fn count_status(&self, status: KnowledgeStatus) -> usize {
+        self.by_kind(KnowledgeKind::Problem).filter(|e| e.status == Some(status)).count()
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.diagnostics.iter().filter(|d| d.severity == DiagnosticSeverity::Error).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_entries_metadata_and_diagnostics() {
+        let content = "# 问题记录\n\n\
+            ## P-2024-001 弹窗不显示\n\n\
+            - **状态**：fixed\n\
+            - 标签：popup, macos\n\
+            - 相关文件：`src/rust/mcp/handlers/popup.rs`\n\n\
+            根因见 PAT-2024-002，回归 R-2024-001。\n\
+            验证: 重启后弹窗正常\n\n\
+            ## P-2024-002 未知状态\n\
+            状态：done\n\n\
+            ## PAT-2024-003 放错文件\n\n\
+            ## P-24-1 编号错误\n\
+            P-2024-009 漏写标题\n";
+
+        let parsed = parse_knowledge(content, KnowledgeKind::Problem, "problems.md");
+        assert_eq!(parsed.entries.len(), 3);
+        // `## P-24-1` 编号格式错误，不算作未编号小节
+        assert!(parsed.sections.is_empty());
+
+        let first = &parsed.entries[0];
+        assert_eq!(first.title, "弹窗不显示");
+        assert_eq!(first.status, Some(KnowledgeStatus::Fixed));
+        assert_eq!(first.tags, vec!["popup", "macos"]);
+        assert_eq!(first.files, vec!["src/rust/mcp/handlers/popup.rs"]);
+        assert_eq!(first.links, vec!["PAT-2024-002", "R-2024-001"]);
+        assert_eq!(first.body, "根因见 PAT-2024-002，回归 R-2024-001。\n验证: 重启后弹窗正常");
+        assert_eq!(first.verification, None);
+
+        let messages: Vec<String> = parsed.diagnostics.iter().map(|d| d.message.clone()).collect();
+        assert!(messages.iter().any(|m| m.contains("状态「done」无效")));
+        assert!(messages.iter().any(|m| m.contains("PAT-2024-003 不属于 problems.md")));
+        assert!(messages.iter().any(|m| m.contains("编号格式错误")));
+        assert!(messages.iter().any(|m| m.contains("缺少 `##` 标题")));
+
+        // 写入后再解析应得到相同的条目
+        let rendered = first.render();
+        let reparsed = parse_knowledge(&rendered, KnowledgeKind::Problem, "problems.md");
+        assert!(reparsed.diagnostics.is_empty());
+        assert_eq!(reparsed.entries[0].tags, first.tags);
+        assert_eq!(reparsed.entries[0].links, first.links);
+        assert_eq!(reparsed.entries[0].body, first.body);
+    }
+
+    #[test]
+    fn test_from_input_promotes_first_id_line_to_heading() {
+        let entry = KnowledgeEntry::from_input("P-2024-007 重复推送\n状态：open", KnowledgeKind::Problem).unwrap();
+        assert_eq!(entry.id, "P-2024-007");
+        assert_eq!(entry.title, "重复推送");
+        assert_eq!(entry.status, Some(KnowledgeStatus::Open));
+
+        // 其他类型的编号不会被当作本条目的编号，留在标题中并记为关联
+        let regression = KnowledgeEntry::from_input("P-2024-003 弹窗复发\n状态：open", KnowledgeKind::Regression).unwrap();
+        assert!(regression.id.is_empty());
+        assert_eq!(regression.title, "P-2024-003 弹窗复发");
+        assert_eq!(regression.links, vec!["P-2024-003".to_string()]);
+        let regression = KnowledgeEntry::from_input("R-2024-002 P-2024-003 弹窗复发", KnowledgeKind::Regression).unwrap();
+        assert_eq!(regression.id, "R-2024-002");
+
+        // 没有编号时以首行作为标题，等待分配
+        let draft = KnowledgeEntry::from_input("弹窗闪退\n状态：open", KnowledgeKind::Problem).unwrap();
+        assert!(draft.id.is_empty());
+        assert_eq!(draft.title, "弹窗闪退");
+    }
+
+    #[test]
+    fn test_next_free_id_scans_same_prefix_and_year() {
+        let content = "| PAT-2024-004 | 索引 |\n## PAT-2024-002 a\n## PAT-2023-009 b\n关联 P-2024-010\n";
+        assert_eq!(next_free_id(content, KnowledgeKind::Pattern, 2024), "PAT-2024-005");
+        assert_eq!(next_free_id(content, KnowledgeKind::Pattern, 2025), "PAT-2025-001");
+        assert_eq!(next_free_id(content, KnowledgeKind::Regression, 2024), "R-2024-001");
+    }
+}
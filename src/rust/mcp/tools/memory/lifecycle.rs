@@ -0,0 +1,212 @@
+//! 记忆生命周期：过期、置顶与归档
+//!
+//! 过期条目在整理（compaction）时移入 .cunzhi-memory/archive.md，
+//! 整理完成后更新 metadata.json 的 last_organized
+
+use anyhow::Result;
+use chrono::{Duration, SecondsFormat, Utc};
+use serde::Serialize;
+
+use super::manager::{format_entry_line, parse_entry_line, MemoryManager};
+use super::types::{MemoryCategory, MemoryEntry};
+use crate::constants::mcp::MEMORY_COMPACTION_INTERVAL_HOURS;
+use crate::utils::update_locked;
+
+/// 归档文件名
+pub const ARCHIVE_FILE: &str = "archive.md";
+
+/// 以条目行存储、参与过期整理的分类（会话摘要有独立的格式与保留策略）
+pub(super) const ENTRY_CATEGORIES: [MemoryCategory; 4] = [
+    MemoryCategory::Rule,
+    MemoryCategory::Preference,
+    MemoryCategory::Note,
+    MemoryCategory::Context,
+];
+
+/// 整理结果
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct CompactionReport {
+    pub archived: usize,
+    pub duplicates_removed: usize,
+    pub remaining: usize,
+}
+
+impl CompactionReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "🧹 记忆整理完成：归档 {} 条过期记忆，移除 {} 条重复记忆（已移入归档），剩余 {} 条",
+            self.archived, self.duplicates_removed, self.remaining
+        )
+    }
+}
+
+impl MemoryManager {
+    /// 在文件锁保护下逐条改写某分类文件
+    ///
+    /// 闭包返回 false 的条目会从文件中移除并返回；非条目行（标题、说明等）原样保留。
+    /// 没有元信息的旧条目会在改写时补全元信息，并按分类默认有效期计算过期时间
+    pub(super) fn rewrite_category<F>(&self, category: MemoryCategory, mut keep: F) -> Result<Vec<MemoryEntry>>
+    where
+        F: FnMut(&mut MemoryEntry) -> bool,
+    {
+        let file_path = self.category_file(category);
+        if !file_path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let header = format!("# {}\n\n", self.get_category_title(&category));
+        update_locked(&file_path, &header, |content| {
+            let mut removed = Vec::new();
+            let mut lines = Vec::new();
+
+            for line in content.lines() {
+                match parse_entry_line(line, category) {
+                    Some((mut entry, annotated)) => {
+                        if !annotated {
+                            if let Some(days) = category.default_ttl_days() {
+                                entry.expires_at = Some(entry.created_at + Duration::days(days));
+                            }
+                        }
+                        if keep(&mut entry) {
+                            lines.push(format_entry_line(&entry));
+                        } else {
+                            removed.push(entry);
+                        }
+                    }
+                    None => lines.push(line.to_string()),
+                }
+            }
+
+            let mut rewritten = lines.join("\n");
+            rewritten.push('\n');
+            *content = rewritten;
+            Ok(removed)
+        })
+    }
+
+    /// 将条目追加到归档文件
+    fn archive_entries(&self, entries: &[MemoryEntry]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let archive_path = self.memory_dir().join(ARCHIVE_FILE);
+        let archived_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+
+        update_locked(&archive_path, "# 已归档记忆\n\n", |content| {
+            for entry in entries {
+                // 归档行格式：- [分类] 内容 <!-- ... archived:时间 -->
+                let line = format_entry_line(entry);
+                let line = line.replacen("- ", &format!("- [{}] ", entry.category.key()), 1);
+                let line = match line.strip_suffix(" -->") {
+                    Some(stripped) => format!("{} archived:{} -->", stripped, archived_at),
+                    None => line,
+                };
+                content.push_str(&line);
+                content.push('\n');
+            }
+            Ok(())
+        })
+    }
+
+    /// 读取归档条目
+    pub fn get_archived_memories(&self) -> Result<Vec<MemoryEntry>> {
+        let archive_path = self.memory_dir().join(ARCHIVE_FILE);
+        if !archive_path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = std::fs::read_to_string(&archive_path)?;
+        let mut entries = Vec::new();
+        for line in content.lines() {
+            // 先取出 [分类] 前缀，再按普通条目解析
+            let Some(rest) = line.trim().strip_prefix("- [") else { continue };
+            let Some((key, body)) = rest.split_once("] ") else { continue };
+            let Some(category) = MemoryCategory::from_key(key) else { continue };
+            if let Some((entry, _)) = parse_entry_line(&format!("- {}", body), category) {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// 整理记忆：归档过期条目、去除重复条目，并更新 last_organized
+    pub fn compact(&self) -> Result<CompactionReport> {
+        let now = Utc::now();
+        let mut report = CompactionReport::default();
+
+        for category in ENTRY_CATEGORIES {
+            // 有置顶副本的内容只保留置顶的那一条
+            let pinned_contents: std::collections::HashSet<String> = self
+                .get_memories_by_category(category)?
+                .into_iter()
+                .filter(|e| e.pinned)
+                .map(|e| e.content)
+                .collect();
+            let mut seen = std::collections::HashSet::new();
+            let mut duplicates = 0usize;
+
+            let removed = self.rewrite_category(category, |entry| {
+                if entry.is_expired(now) {
+                    return false;
+                }
+                let shadowed = !entry.pinned && pinned_contents.contains(&entry.content);
+                if shadowed || !seen.insert(entry.content.clone()) {
+                    duplicates += 1;
+                    return false;
+                }
+                true
+            })?;
+
+            // 过期条目和重复条目都进入归档，整理不会丢失内容
+            report.archived += removed.iter().filter(|e| e.is_expired(now)).count();
+            report.duplicates_removed += duplicates;
+            self.archive_entries(&removed)?;
+        }
+
+        report.remaining = ENTRY_CATEGORIES
+            .iter()
+            .map(|c| self.get_memories_by_category(*c).map(|v| v.len()))
+            .sum::<Result<usize>>()?;
+
+        self.write_metadata(Some(now))?;
+        Ok(report)
+    }
+
+    /// 距上次整理超过间隔时执行整理
+    pub fn compact_if_due(&self) -> Result<Option<CompactionReport>> {
+        let due = match self.read_metadata()? {
+            Some(metadata) => Utc::now() - metadata.last_organized > Duration::hours(MEMORY_COMPACTION_INTERVAL_HOURS),
+            None => false,
+        };
+
+        if due {
+            self.compact().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// 设置置顶状态；`key` 可以是条目 ID 或完整内容，返回是否找到条目
+    pub fn set_pinned(&self, key: &str, pinned: bool) -> Result<bool> {
+        let key = key.trim();
+        let mut found = false;
+
+        for category in ENTRY_CATEGORIES {
+            self.rewrite_category(category, |entry| {
+                if entry.id == key || entry.content == key {
+                    found = true;
+                    entry.pinned = pinned;
+                    entry.updated_at = Utc::now();
+                    // 置顶条目不再过期
+                    if pinned {
+                        entry.expires_at = None;
+                    }
+                }
+                true
+            })?;
+        }
+
+        Ok(found)
+    }
+}
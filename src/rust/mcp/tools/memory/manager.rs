@@ -1,9 +1,12 @@
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{DateTime, Datelike, Duration, Local, SecondsFormat, Utc};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use super::types::{MemoryEntry, MemoryCategory, MemoryMetadata};
+use super::types::{MemoryEntry, MemoryCategory, MemoryMetadata, MemoryOptions};
+use super::knowledge_base::{next_free_id, parse_knowledge, KnowledgeBase, KnowledgeEntry, KnowledgeKind, KnowledgeStatus};
+use super::sync::KnowledgeSync;
+use crate::utils::{update_locked, create_if_missing};
 
 /// 记忆管理器
 pub struct MemoryManager {
@@ -157,11 +160,10 @@ impl MemoryManager {
                 MemoryCategory::Session => "sessions.md",
             };
 
+            // 使用 create_new 语义，避免并发初始化时覆盖其他进程刚写入的内容
             let file_path = self.memory_dir.join(filename);
-            if !file_path.exists() {
-                let header_content = self.get_category_header(category);
-                fs::write(&file_path, header_content)?;
-            }
+            let header_content = self.get_category_header(category);
+            create_if_missing(&file_path, &header_content)?;
         }
 
         // 创建或更新元数据
@@ -172,15 +174,28 @@ impl MemoryManager {
 
     /// 添加记忆条目
     pub fn add_memory(&self, content: &str, category: MemoryCategory) -> Result<String> {
-        let id = uuid::Uuid::new_v4().to_string();
+        self.add_memory_with_options(content, category, MemoryOptions::default())
+    }
+
+    /// 添加记忆条目，可指定有效期和置顶
+    pub fn add_memory_with_options(&self, content: &str, category: MemoryCategory, options: MemoryOptions) -> Result<String> {
+        let id = new_entry_id();
         let now = Utc::now();
 
+        let ttl_days = options.ttl_days.or_else(|| category.default_ttl_days());
+        let expires_at = match ttl_days {
+            Some(days) if days > 0 && !options.pinned => Some(now + Duration::days(days)),
+            _ => None,
+        };
+
         let entry = MemoryEntry {
             id: id.clone(),
             content: content.to_string(),
             category,
             created_at: now,
             updated_at: now,
+            pinned: options.pinned,
+            expires_at,
         };
 
         // 将记忆添加到对应的文件中
@@ -189,6 +204,11 @@ impl MemoryManager {
         // 更新元数据
         self.update_metadata()?;
 
+        // 只在写入路径上按间隔自动整理，读取记忆不会改写文件（失败不影响正常使用）
+        if let Err(e) = self.compact_if_due() {
+            crate::log_important!(warn, "记忆自动整理失败: {}", e);
+        }
+
         Ok(id)
     }
 
@@ -196,21 +216,8 @@ impl MemoryManager {
     pub fn get_all_memories(&self) -> Result<Vec<MemoryEntry>> {
         let mut memories = Vec::new();
 
-        let categories = [
-            (MemoryCategory::Rule, "rules.md"),
-            (MemoryCategory::Preference, "preferences.md"),
-            (MemoryCategory::Note, "notes.md"),
-            (MemoryCategory::Context, "context.md"),
-            (MemoryCategory::Session, "sessions.md"),
-        ];
-
-        for (category, filename) in categories.iter() {
-            let file_path = self.memory_dir.join(filename);
-            if file_path.exists() {
-                let content = fs::read_to_string(&file_path)?;
-                let entries = self.parse_memory_file(&content, *category)?;
-                memories.extend(entries);
-            }
+        for category in MemoryCategory::ALL {
+            memories.extend(self.get_memories_by_category(category)?);
         }
 
         // 按更新时间排序
@@ -219,17 +226,13 @@ impl MemoryManager {
         Ok(memories)
     }
 
-    /// 获取指定分类的记忆
+    /// 获取指定分类的记忆；会话摘要按区块解析后转换为条目
     pub fn get_memories_by_category(&self, category: MemoryCategory) -> Result<Vec<MemoryEntry>> {
-        let filename = match category {
-            MemoryCategory::Rule => "rules.md",
-            MemoryCategory::Preference => "preferences.md",
-            MemoryCategory::Note => "notes.md",
-            MemoryCategory::Context => "context.md",
-            MemoryCategory::Session => "sessions.md",
-        };
+        if category == MemoryCategory::Session {
+            return Ok(self.get_sessions()?.iter().map(|s| s.to_entry()).collect());
+        }
 
-        let file_path = self.memory_dir.join(filename);
+        let file_path = self.category_file(category);
         if !file_path.exists() {
             return Ok(Vec::new());
         }
@@ -238,58 +241,47 @@ impl MemoryManager {
         self.parse_memory_file(&content, category)
     }
 
-    /// 将记忆条目添加到对应分类文件
-    fn append_to_category_file(&self, entry: &MemoryEntry) -> Result<()> {
-        let filename = match entry.category {
+    /// 分类对应的记忆文件路径
+    pub(super) fn category_file(&self, category: MemoryCategory) -> PathBuf {
+        let filename = match category {
             MemoryCategory::Rule => "rules.md",
             MemoryCategory::Preference => "preferences.md",
             MemoryCategory::Note => "notes.md",
             MemoryCategory::Context => "context.md",
             MemoryCategory::Session => "sessions.md",
         };
+        self.memory_dir.join(filename)
+    }
 
-        let file_path = self.memory_dir.join(filename);
-        let mut content = if file_path.exists() {
-            fs::read_to_string(&file_path)?
-        } else {
-            format!("# {}\n\n", self.get_category_title(&entry.category))
-        };
-
-        // 简化格式：一行一个记忆
-        content.push_str(&format!("- {}\n", entry.content));
-
-        fs::write(&file_path, content)?;
-        Ok(())
+    /// 记忆目录路径
+    pub(super) fn memory_dir(&self) -> &Path {
+        &self.memory_dir
     }
 
-    /// 解析记忆文件内容 - 简化版本
-    fn parse_memory_file(&self, content: &str, category: MemoryCategory) -> Result<Vec<MemoryEntry>> {
-        let mut memories = Vec::new();
+    /// 将记忆条目添加到对应分类文件
+    pub(super) fn append_to_category_file(&self, entry: &MemoryEntry) -> Result<()> {
+        let file_path = self.category_file(entry.category);
+        let header = format!("# {}\n\n", self.get_category_title(&entry.category));
+
+        // 一行一个记忆，元信息写在行尾注释中（加锁读改写，避免多个代理同时写入时丢条目）
+        update_locked(&file_path, &header, |content| {
+            content.push_str(&format_entry_line(entry));
+            content.push('\n');
+            Ok(())
+        })
+    }
 
+    /// 解析记忆文件内容
+    pub(super) fn parse_memory_file(&self, content: &str, category: MemoryCategory) -> Result<Vec<MemoryEntry>> {
         // 按列表项解析，每个 "- " 开头的行是一个记忆条目
-        for line in content.lines() {
-            let line = line.trim();
-            if line.starts_with("- ") && line.len() > 2 {
-                let content = line[2..].trim(); // 去掉 "- " 前缀
-                if !content.is_empty() {
-                    let entry = MemoryEntry {
-                        id: uuid::Uuid::new_v4().to_string(),
-                        content: content.to_string(),
-                        category,
-                        created_at: Utc::now(),
-                        updated_at: Utc::now(),
-                    };
-
-                    memories.push(entry);
-                }
-            }
-        }
-
-        Ok(memories)
+        Ok(content
+            .lines()
+            .filter_map(|line| parse_entry_line(line, category).map(|(entry, _)| entry))
+            .collect())
     }
 
     /// 获取分类标题
-    fn get_category_title(&self, category: &MemoryCategory) -> &str {
+    pub(super) fn get_category_title(&self, category: &MemoryCategory) -> &str {
         match category {
             MemoryCategory::Rule => "开发规范和规则",
             MemoryCategory::Preference => "用户偏好设置",
@@ -304,20 +296,46 @@ impl MemoryManager {
         format!("# {}\n\n", self.get_category_title(category))
     }
 
-    /// 更新元数据
-    fn update_metadata(&self) -> Result<()> {
-        let metadata = MemoryMetadata {
-            project_path: self.project_path.clone(),
-            last_organized: Utc::now(),
-            total_entries: self.get_all_memories()?.len(),
-            version: "1.0.0".to_string(),
-        };
+    /// 更新元数据（保留上次整理时间）
+    pub(super) fn update_metadata(&self) -> Result<()> {
+        self.write_metadata(None)
+    }
 
+    /// 写入元数据；`organized_at` 为 Some 时表示刚完成一次整理
+    pub(super) fn write_metadata(&self, organized_at: Option<DateTime<Utc>>) -> Result<()> {
+        let total_entries = self.get_all_memories()?.len();
         let metadata_path = self.memory_dir.join("metadata.json");
-        let metadata_json = serde_json::to_string_pretty(&metadata)?;
-        fs::write(metadata_path, metadata_json)?;
 
-        Ok(())
+        update_locked(&metadata_path, "", |content| {
+            let previous = serde_json::from_str::<MemoryMetadata>(content).ok();
+            let last_organized = organized_at
+                .or_else(|| previous.map(|m| m.last_organized))
+                .unwrap_or_else(Utc::now);
+
+            let metadata = MemoryMetadata {
+                project_path: self.project_path.clone(),
+                last_organized,
+                total_entries,
+                version: "1.0.0".to_string(),
+            };
+            *content = serde_json::to_string_pretty(&metadata)?;
+            Ok(())
+        })
+    }
+
+    /// 读取元数据
+    pub fn read_metadata(&self) -> Result<Option<MemoryMetadata>> {
+        let metadata_path = self.memory_dir.join("metadata.json");
+        if !metadata_path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&metadata_path)?;
+        Ok(serde_json::from_str(&content).ok())
+    }
+
+    /// 项目根目录（git 根目录）
+    pub fn project_root(&self) -> &Path {
+        Path::new(&self.project_path)
     }
 
     /// 获取知识库目录路径
@@ -328,149 +346,90 @@ impl MemoryManager {
         let knowledge_dir = project_root.join(".cunzhi-knowledge");
         
         if !knowledge_dir.exists() {
-            return Err(anyhow::anyhow!("项目未接入全局知识库，请先初始化 .cunzhi-knowledge/"));
+            return Err(anyhow::anyhow!("项目未接入全局知识库，请先执行 ji(action=初始化) 初始化 .cunzhi-knowledge/"));
         }
         
         Ok(knowledge_dir)
     }
 
     /// 写入全局知识库（沉淀）并自动 git push
-    pub fn settle_to_knowledge(&self, content: &str, category: &str) -> Result<String> {
+    ///
+    /// 内容会被解析为结构化条目并按规范格式写入：未提供编号时按分类和年份分配下一个空闲编号，
+    /// 提供的编号在文件中已存在时拒绝写入
+    pub fn settle_to_knowledge(&self, content: &str, category: &str) -> Result<SettleResult> {
         let knowledge_dir = self.get_knowledge_dir()?;
         
-        let filename = match category {
-            "patterns" => "patterns.md",
-            "problems" => "problems.md",
-            "regressions" => "regressions.md",
-            _ => return Err(anyhow::anyhow!("不支持的知识库分类: {}，仅支持 patterns/problems/regressions", category)),
-        };
-        
+        let kind = KnowledgeKind::from_category(category).ok_or_else(|| {
+            anyhow::anyhow!("不支持的知识库分类: {}，仅支持 patterns/problems/regressions", category)
+        })?;
+        let mut entry = KnowledgeEntry::from_input(content, kind)?;
+        let filename = kind.filename();
         let file_path = knowledge_dir.join(filename);
         
-        // 读取现有内容
-        let mut file_content = if file_path.exists() {
-            fs::read_to_string(&file_path)?
-        } else {
-            String::new()
-        };
-        
-        // 追加新内容
-        file_content.push_str("\n");
-        file_content.push_str(content);
-        file_content.push_str("\n");
-        
-        // 写入文件
-        fs::write(&file_path, file_content)?;
-        
-        // 自动 git add/commit/push
-        let git_result = self.git_push_knowledge(&knowledge_dir, filename, content);
-        
-        match git_result {
-            Ok(msg) => Ok(format!("✅ 已沉淀到 .cunzhi-knowledge/{}\n{}", filename, msg)),
-            Err(e) => Ok(format!("✅ 已沉淀到 .cunzhi-knowledge/{}\n⚠️ Git 同步失败: {}\n请手动执行 git push", filename, e)),
-        }
-    }
-    
-    /// 自动 git push 知识库更改
-    fn git_push_knowledge(&self, knowledge_dir: &Path, filename: &str, content: &str) -> Result<String> {
-        use std::process::Command;
-        
-        // 提取简短描述作为 commit message
-        let short_desc = content.lines()
-            .find(|l| !l.trim().is_empty())
-            .unwrap_or("沉淀内容")
-            .chars()
-            .take(50)
-            .collect::<String>();
-        
-        // git add
-        let add_output = Command::new("git")
-            .args(["add", filename])
-            .current_dir(knowledge_dir)
-            .output()?;
-        
-        if !add_output.status.success() {
-            return Err(anyhow::anyhow!("git add 失败: {}", String::from_utf8_lossy(&add_output.stderr)));
-        }
-        
-        // git commit
-        let commit_msg = format!("沉淀: {}", short_desc);
-        let commit_output = Command::new("git")
-            .args(["commit", "-m", &commit_msg])
-            .current_dir(knowledge_dir)
-            .output()?;
-        
-        if !commit_output.status.success() {
-            let stderr = String::from_utf8_lossy(&commit_output.stderr);
-            // 如果是 "nothing to commit" 则忽略
-            if !stderr.contains("nothing to commit") {
-                return Err(anyhow::anyhow!("git commit 失败: {}", stderr));
+        // 加锁分配/检查编号并追加新条目，原子写回（分配与写入在同一把锁内，避免并发撞号）
+        let entry = update_locked(&file_path, &format!("# {}\n", kind.title()), |file_content| {
+            if entry.id.is_empty() {
+                entry.id = next_free_id(file_content, kind, Local::now().year());
+            } else {
+                let existing = parse_knowledge(file_content, kind, filename);
+                if existing.entries.iter().any(|e| e.id == entry.id) {
+                    return Err(anyhow::anyhow!("{} 中已存在编号 {}，可省略编号由系统自动分配", filename, entry.id));
+                }
             }
-        }
-        
-        // git push
-        let push_output = Command::new("git")
-            .args(["push"])
-            .current_dir(knowledge_dir)
-            .output()?;
+            
+            if !file_content.ends_with("\n\n") {
+                file_content.push('\n');
+            }
+            file_content.push_str(&entry.render());
+            Ok(entry)
+        })?;
         
-        if !push_output.status.success() {
-            return Err(anyhow::anyhow!("git push 失败: {}", String::from_utf8_lossy(&push_output.stderr)));
-        }
+        // 自动提交并同步（先 pull --rebase 再 push，离线时排队）
+        let sync_msg = KnowledgeSync::new(&knowledge_dir)
+            .commit_and_sync(&[filename], &format!("沉淀: {} {}", entry.id, entry.title));
+        let message = format!("✅ 已沉淀 {} 到 .cunzhi-knowledge/{}\n{}", entry.id, filename, sync_msg);
         
-        Ok("🚀 已自动推送到 GitHub".to_string())
+        Ok(SettleResult { id: entry.id, file: filename.to_string(), message })
     }
-
+    
     /// 读取全局知识库内容
     pub fn read_knowledge(&self) -> Result<String> {
-        // 从 memory_dir 的父目录查找 .cunzhi-knowledge
-        let project_root = self.memory_dir.parent()
-            .ok_or_else(|| anyhow::anyhow!("无法获取项目根目录"))?;
-        
-        let knowledge_dir = project_root.join(".cunzhi-knowledge");
+        let knowledge_dir = self.project_root().join(".cunzhi-knowledge");
         
         if !knowledge_dir.exists() {
             return Ok("📭 项目未接入全局知识库".to_string());
         }
         
+        let base = KnowledgeBase::load(&knowledge_dir)?;
         let mut knowledge_parts = Vec::new();
         
-        // 读取 patterns.md 摘要
-        let patterns_path = knowledge_dir.join("patterns.md");
-        if patterns_path.exists() {
-            if let Ok(content) = fs::read_to_string(&patterns_path) {
-                // 提取 Expertise Sections 索引表
-                if let Some(start) = content.find("## Expertise Sections") {
-                    if let Some(end) = content.find("## 详细记录") {
-                        let summary = &content[start..end];
-                        let lines: Vec<&str> = summary.lines()
-                            .filter(|l| l.starts_with("| PAT-"))
-                            .take(5)  // 只取前5条
-                            .collect();
-                        if !lines.is_empty() {
-                            knowledge_parts.push(format!("**最佳实践**: {}", lines.join("; ")));
-                        }
-                    }
-                }
-            }
+        // 最佳实践：只取前 5 条标题
+        let patterns: Vec<String> = base.by_kind(KnowledgeKind::Pattern)
+            .take(5)
+            .map(|e| format!("{} {}", e.id, e.title))
+            .collect();
+        if !patterns.is_empty() {
+            knowledge_parts.push(format!("**最佳实践**: {}", patterns.join("; ")));
         }
         
-        // 读取 problems.md 摘要（只读最近的问题）
-        let problems_path = knowledge_dir.join("problems.md");
-        if problems_path.exists() {
-            if let Ok(content) = fs::read_to_string(&problems_path) {
-                // 统计问题数量
-                let open_count = content.matches("状态：open").count();
-                let fixed_count = content.matches("状态：fixed").count();
-                let verified_count = content.matches("状态：verified").count();
-                
-                if open_count + fixed_count + verified_count > 0 {
-                    knowledge_parts.push(format!(
-                        "**问题记录**: {} open, {} fixed, {} verified",
-                        open_count, fixed_count, verified_count
-                    ));
-                }
-            }
+        // 问题记录：按状态统计
+        let open_count = base.count_status(KnowledgeStatus::Open);
+        let fixed_count = base.count_status(KnowledgeStatus::Fixed);
+        let verified_count = base.count_status(KnowledgeStatus::Verified);
+        if open_count + fixed_count + verified_count > 0 {
+            knowledge_parts.push(format!(
+                "**问题记录**: {} open, {} fixed, {} verified",
+                open_count, fixed_count, verified_count
+            ));
+        }
+        
+        if !base.diagnostics.is_empty() {
+            knowledge_parts.push(format!(
+                "⚠️ 知识库有 {} 处格式问题（{} 处错误），如: {}",
+                base.diagnostics.len(),
+                base.error_count(),
+                base.diagnostics[0]
+            ));
         }
         
         if knowledge_parts.is_empty() {
@@ -480,80 +439,6 @@ impl MemoryManager {
         }
     }
 
-    /// 添加会话摘要（L3 近期对话摘要层）
-    /// 
-    /// 格式: ## YYYY-MM-DD HH:MM
-    ///       主题：xxx | 关键词：xxx | 意图：xxx
-    /// 
-    /// 自动保留最近 15 条，超出自动清理
-    pub fn add_session_summary(&self, content: &str) -> Result<String> {
-        let file_path = self.memory_dir.join("sessions.md");
-        let now = Utc::now();
-        let timestamp = now.format("%Y-%m-%d %H:%M").to_string();
-        
-        // 构建新的摘要条目
-        let new_entry = format!("## {}\n{}\n\n", timestamp, content);
-        
-        // 读取现有内容
-        let mut existing_content = if file_path.exists() {
-            fs::read_to_string(&file_path)?
-        } else {
-            "# 会话摘要\n\n".to_string()
-        };
-        
-        // 解析现有条目数量
-        let entry_count = existing_content.matches("## 20").count();
-        
-        // 如果超过 14 条，删除最旧的一条（保留 header + 14 条 + 新增 1 条 = 15 条）
-        if entry_count >= 15 {
-            // 找到最后一个 ## 的位置，删除它及之后的内容
-            if let Some(last_entry_pos) = existing_content.rfind("\n## 20") {
-                existing_content.truncate(last_entry_pos + 1);
-            }
-        }
-        
-        // 在 header 后插入新条目（最新的在前）
-        let header_end = existing_content.find("\n\n").unwrap_or(0) + 2;
-        let (header, rest) = existing_content.split_at(header_end);
-        let new_content = format!("{}{}{}", header, new_entry, rest);
-        
-        fs::write(&file_path, new_content)?;
-        
-        Ok(format!("✅ 会话摘要已添加\n📅 时间: {}\n📝 内容: {}", timestamp, content))
-    }
-
-    /// 获取最近的会话摘要（用于上下文注入）
-    pub fn get_recent_sessions(&self, limit: usize) -> Result<String> {
-        let file_path = self.memory_dir.join("sessions.md");
-        
-        if !file_path.exists() {
-            return Ok("📭 暂无会话摘要".to_string());
-        }
-        
-        let content = fs::read_to_string(&file_path)?;
-        let mut sessions = Vec::new();
-        
-        // 按 ## 分割解析
-        for part in content.split("\n## ").skip(1) {
-            if let Some(first_line_end) = part.find('\n') {
-                let timestamp = &part[..first_line_end];
-                let summary = part[first_line_end..].trim();
-                if !summary.is_empty() {
-                    sessions.push(format!("- **{}**: {}", timestamp, summary.lines().next().unwrap_or("")));
-                }
-            }
-            if sessions.len() >= limit {
-                break;
-            }
-        }
-        
-        if sessions.is_empty() {
-            Ok("📭 暂无会话摘要".to_string())
-        } else {
-            Ok(format!("📋 最近会话:\n{}", sessions.join("\n")))
-        }
-    }
-
     /// 获取项目信息供MCP调用方分析 - 压缩简化版本
     pub fn get_project_info(&self) -> Result<String> {
         // 汇总所有记忆规则并压缩
@@ -563,18 +448,32 @@ impl MemoryManager {
         }
 
         let mut compressed_info = Vec::new();
+        let now = Utc::now();
 
-        // 按分类压缩汇总
+        // 置顶条目总是优先返回
+        let pinned: Vec<String> = all_memories
+            .iter()
+            .filter(|m| m.pinned)
+            .map(|m| m.content.split_whitespace().collect::<Vec<&str>>().join(" "))
+            .collect();
+        if !pinned.is_empty() {
+            compressed_info.push(format!("**📌 置顶**: {}", pinned.join("; ")));
+        }
+
+        // 按分类压缩汇总（跳过已过期、尚未归档的条目；会话摘要由 get_recent_sessions 单独提供）
         let categories = [
             (MemoryCategory::Rule, "规范"),
             (MemoryCategory::Preference, "偏好"),
             (MemoryCategory::Note, "笔记"),
             (MemoryCategory::Context, "背景"),
-            (MemoryCategory::Session, "摘要"),
         ];
 
         for (category, title) in categories.iter() {
-            let memories = self.get_memories_by_category(*category)?;
+            let memories: Vec<MemoryEntry> = self
+                .get_memories_by_category(*category)?
+                .into_iter()
+                .filter(|m| !m.pinned && !m.is_expired(now))
+                .collect();
             if !memories.is_empty() {
                 let mut items = Vec::new();
                 for memory in memories {
@@ -601,3 +500,222 @@ impl MemoryManager {
         }
     }
 }
+
+/// 沉淀结果
+#[derive(Debug, Clone)]
+pub struct SettleResult {
+    /// 写入的条目编号（调用方提供或自动分配）
+    pub id: String,
+    pub file: String,
+    /// 给调用方的状态说明
+    pub message: String,
+}
+
+/// 行尾元信息注释的前缀
+const ENTRY_META_PREFIX: &str = "<!-- ";
+const ENTRY_META_SUFFIX: &str = " -->";
+
+/// 生成新的短条目 ID
+pub(super) fn new_entry_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()[..8].to_string()
+}
+
+/// 为没有元信息的旧条目生成稳定 ID（分类 + 内容的哈希）
+fn legacy_entry_id(category: MemoryCategory, content: &str) -> String {
+    let digest = ring::digest::digest(
+        &ring::digest::SHA256,
+        format!("{}:{}", category.key(), content).as_bytes(),
+    );
+    hex::encode(&digest.as_ref()[..4])
+}
+
+/// 序列化记忆条目为一行：`- 内容 <!-- id:xxx created:... expires:... pinned -->`
+pub(super) fn format_entry_line(entry: &MemoryEntry) -> String {
+    let mut meta = vec![
+        format!("id:{}", entry.id),
+        format!("created:{}", entry.created_at.to_rfc3339_opts(SecondsFormat::Secs, true)),
+    ];
+    if entry.updated_at != entry.created_at {
+        meta.push(format!("updated:{}", entry.updated_at.to_rfc3339_opts(SecondsFormat::Secs, true)));
+    }
+    if let Some(expires_at) = entry.expires_at {
+        meta.push(format!("expires:{}", expires_at.to_rfc3339_opts(SecondsFormat::Secs, true)));
+    }
+    if entry.pinned {
+        meta.push("pinned".to_string());
+    }
+
+    format!("- {} {}{}{}", entry.content, ENTRY_META_PREFIX, meta.join(" "), ENTRY_META_SUFFIX)
+}
+
+/// 解析一行记忆条目，返回（条目, 是否带有元信息）
+///
+/// 旧格式（无行尾注释）的条目使用基于内容的稳定 ID，创建时间视为当前时间
+pub(super) fn parse_entry_line(line: &str, category: MemoryCategory) -> Option<(MemoryEntry, bool)> {
+    let line = line.trim();
+    let body = line.strip_prefix("- ")?.trim();
+
+    let (content, meta) = match body.rfind(ENTRY_META_PREFIX) {
+        Some(pos) if body.ends_with(ENTRY_META_SUFFIX) => {
+            let meta = &body[pos + ENTRY_META_PREFIX.len()..body.len() - ENTRY_META_SUFFIX.len()];
+            (body[..pos].trim(), Some(meta))
+        }
+        _ => (body, None),
+    };
+
+    if content.is_empty() {
+        return None;
+    }
+
+    let now = Utc::now();
+    let mut entry = MemoryEntry {
+        id: legacy_entry_id(category, content),
+        content: content.to_string(),
+        category,
+        created_at: now,
+        updated_at: now,
+        pinned: false,
+        expires_at: None,
+    };
+
+    let Some(meta) = meta else {
+        return Some((entry, false));
+    };
+
+    let parse_time = |v: &str| DateTime::parse_from_rfc3339(v).ok().map(|t| t.with_timezone(&Utc));
+    let mut updated_at = None;
+    for token in meta.split_whitespace() {
+        match token.split_once(':') {
+            Some(("id", v)) => entry.id = v.to_string(),
+            Some(("created", v)) => {
+                if let Some(t) = parse_time(v) {
+                    entry.created_at = t;
+                }
+            }
+            Some(("updated", v)) => updated_at = parse_time(v),
+            Some(("expires", v)) => entry.expires_at = parse_time(v),
+            None if token == "pinned" => entry.pinned = true,
+            _ => {}
+        }
+    }
+    entry.updated_at = updated_at.unwrap_or(entry.created_at);
+
+    Some((entry, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+    use tempfile::tempdir;
+
+    /// 创建带 .git 与 .cunzhi-knowledge 的临时项目
+    fn setup_project() -> tempfile::TempDir {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        fs::create_dir_all(dir.path().join(".cunzhi-knowledge")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_concurrent_writers_do_not_lose_entries() {
+        let project = setup_project();
+        let project_path = Arc::new(project.path().to_string_lossy().to_string());
+
+        const WRITERS: usize = 8;
+        const PER_WRITER: usize = 10;
+
+        let handles: Vec<_> = (0..WRITERS)
+            .map(|w| {
+                let project_path = Arc::clone(&project_path);
+                thread::spawn(move || {
+                    // 每个线程独立创建管理器，模拟多个代理进程
+                    let manager = MemoryManager::new(&project_path).unwrap();
+                    for i in 0..PER_WRITER {
+                        manager.add_memory(&format!("writer-{}-entry-{}", w, i), MemoryCategory::Rule).unwrap();
+                        manager.settle_to_knowledge(&format!("P-2024-{:03} writer-{}-{}", w * PER_WRITER + i, w, i), "problems").unwrap();
+                        manager.settle_to_knowledge(&format!("writer-{}-{} 自动编号", w, i), "regressions").unwrap();
+                    }
+                    manager.add_session_summary(&format!("主题：writer-{}", w)).unwrap();
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let manager = MemoryManager::new(&project_path).unwrap();
+        let rules = manager.get_memories_by_category(MemoryCategory::Rule).unwrap();
+        assert_eq!(rules.len(), WRITERS * PER_WRITER);
+
+        let problems = fs::read_to_string(project.path().join(".cunzhi-knowledge/problems.md")).unwrap();
+        for w in 0..WRITERS {
+            for i in 0..PER_WRITER {
+                assert!(problems.contains(&format!("writer-{}-{}\n", w, i)));
+            }
+        }
+
+        // 并发自动分配的编号不应重复
+        let regressions = fs::read_to_string(project.path().join(".cunzhi-knowledge/regressions.md")).unwrap();
+        let year = Local::now().year();
+        for n in 1..=WRITERS * PER_WRITER {
+            assert_eq!(regressions.matches(&format!("## R-{}-{:03} ", year, n)).count(), 1);
+        }
+
+        let sessions = fs::read_to_string(project.path().join(".cunzhi-memory/sessions.md")).unwrap();
+        assert_eq!(sessions.matches("## 20").count(), WRITERS);
+        assert!(sessions.starts_with("# 会话摘要\n\n"));
+
+        // 原子写入不应残留临时文件
+        let leftovers = fs::read_dir(project.path().join(".cunzhi-memory"))
+            .unwrap()
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().contains(".tmp-"))
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn test_compaction_archives_expired_and_keeps_pinned() {
+        let project = setup_project();
+        let manager = MemoryManager::new(&project.path().to_string_lossy()).unwrap();
+
+        let pinned_id = manager.add_memory_with_options(
+            "始终使用 pnpm",
+            MemoryCategory::Note,
+            MemoryOptions { ttl_days: None, pinned: true },
+        ).unwrap();
+        manager.add_memory("仍然有效的笔记", MemoryCategory::Note).unwrap();
+        // 未置顶的重复条目写在置顶条目之前，整理时应保留置顶的那一条
+        let notes_path = project.path().join(".cunzhi-memory/notes.md");
+        let notes = fs::read_to_string(&notes_path).unwrap()
+            .replacen("- 始终使用 pnpm", "- 始终使用 pnpm <!-- id:0badc0de created:2024-01-01T00:00:00Z -->\n- 始终使用 pnpm", 1);
+        fs::write(&notes_path, notes).unwrap();
+
+        // 手工写入一条已过期的笔记
+        let mut notes = fs::read_to_string(&notes_path).unwrap();
+        notes.push_str("- 已经过期的笔记 <!-- id:deadbeef created:2020-01-01T00:00:00Z expires:2020-01-02T00:00:00Z -->\n");
+        fs::write(&notes_path, notes).unwrap();
+
+        let report = manager.compact().unwrap();
+        assert_eq!(report.archived, 1);
+        assert_eq!(report.duplicates_removed, 1);
+
+        let notes = manager.get_memories_by_category(MemoryCategory::Note).unwrap();
+        assert_eq!(notes.len(), 2);
+        assert!(notes.iter().any(|n| n.id == pinned_id && n.pinned && n.expires_at.is_none()));
+
+        // 被去除的重复条目同样进入归档
+        let archived = manager.get_archived_memories().unwrap();
+        let mut archived_ids: Vec<&str> = archived.iter().map(|e| e.id.as_str()).collect();
+        archived_ids.sort();
+        assert_eq!(archived_ids, vec!["0badc0de", "deadbeef"]);
+        assert!(archived.iter().all(|e| e.category == MemoryCategory::Note));
+
+        let info = manager.get_project_info().unwrap();
+        assert!(info.contains("📌 置顶"));
+        assert!(!info.contains("已经过期的笔记"));
+    }
+}
@@ -1,7 +1,9 @@
 use anyhow::Result;
 use rmcp::{Error as McpError, model::*};
 
-use super::{MemoryManager, MemoryCategory};
+use super::{MemoryManager, MemoryCategory, MemoryOptions, SessionQuery, KnowledgeEntry, KnowledgeKind, ProblemTransition, KnowledgeInitOptions};
+use super::sessions::parse_query_date;
+use crate::constants::mcp::SESSION_RETENTION_DEFAULT;
 use crate::mcp::{JiyiRequest, utils::{validate_project_path, project_path_error}};
 
 /// 全局记忆管理工具
@@ -40,10 +42,43 @@ impl MemoryTool {
                     _ => MemoryCategory::Context,
                 };
 
-                let id = manager.add_memory(&request.content, category)
+                let options = MemoryOptions {
+                    ttl_days: request.ttl_days,
+                    pinned: request.pinned.unwrap_or(false),
+                };
+                let pinned_hint = if options.pinned { "\n📌 已置顶" } else { "" };
+
+                let id = manager.add_memory_with_options(&request.content, category, options)
                     .map_err(|e| McpError::internal_error(format!("添加记忆失败: {}", e), None))?;
 
-                format!("✅ 记忆已添加，ID: {}\n📝 内容: {}\n📂 分类: {:?}", id, request.content, category)
+                format!("✅ 记忆已添加，ID: {}\n📝 内容: {}\n📂 分类: {:?}{}", id, request.content, category, pinned_hint)
+            }
+            "置顶" | "取消置顶" => {
+                if request.content.trim().is_empty() {
+                    return Err(McpError::invalid_params("缺少要置顶的记忆 ID 或内容".to_string(), None));
+                }
+
+                let pinned = request.action == "置顶";
+                let found = manager.set_pinned(&request.content, pinned)
+                    .map_err(|e| McpError::internal_error(format!("更新置顶状态失败: {}", e), None))?;
+
+                if !found {
+                    return Err(McpError::invalid_params(
+                        format!("未找到记忆: {}", request.content),
+                        None
+                    ));
+                }
+
+                if pinned {
+                    format!("📌 已置顶: {}", request.content)
+                } else {
+                    format!("✅ 已取消置顶: {}", request.content)
+                }
+            }
+            "整理" => {
+                manager.compact()
+                    .map_err(|e| McpError::internal_error(format!("整理记忆失败: {}", e), None))?
+                    .summary()
             }
             "回忆" => {
                 let memory_info = manager.get_project_info()
@@ -63,64 +98,35 @@ impl MemoryTool {
                 }
                 
                 // 验证 category 是否为 knowledge 专用类型
-                let category = match request.category.as_str() {
-                    "patterns" | "problems" | "regressions" => request.category.as_str(),
-                    _ => return Err(McpError::invalid_params(
-                            format!("沉淀仅支持 patterns/problems/regressions 分类，收到: {}", request.category),
-                        None
-                    )),
-                };
+                let kind = KnowledgeKind::from_category(&request.category).ok_or_else(|| McpError::invalid_params(
+                    format!("沉淀仅支持 patterns/problems/regressions 分类，收到: {}", request.category),
+                    None
+                ))?;
                 
-                // 验证格式
-                match category {
-                    "problems" => {
-                        let pattern = regex::Regex::new(r"P-\d{4}-\d{3}").unwrap();
-                        if !pattern.is_match(&request.content) {
-                            return Err(McpError::invalid_params(
-                                "沉淀 problems 必须包含 P-YYYY-NNN 格式的编号（如 P-2024-001）".to_string(),
-                                None
-                            ));
-                        }
-                    }
-                    "patterns" => {
-                        let pattern = regex::Regex::new(r"PAT-\d{4}-\d{3}").unwrap();
-                        if !pattern.is_match(&request.content) {
-                            return Err(McpError::invalid_params(
-                                "沉淀 patterns 必须包含 PAT-YYYY-NNN 格式的编号（如 PAT-2024-001）".to_string(),
-                                None
-                            ));
-                        }
-                    }
-                    "regressions" => {
-                        let pattern = regex::Regex::new(r"R-\d{4}-\d{3}").unwrap();
-                        if !pattern.is_match(&request.content) {
-                            return Err(McpError::invalid_params(
-                                "沉淀 regressions 必须包含 R-YYYY-NNN 格式的编号（如 R-2024-001）".to_string(),
-                                None
-                            ));
-                        }
-                    }
-                    _ => {}
-                }
+                // 解析为结构化条目（校验编号、状态等格式）
+                let entry = KnowledgeEntry::from_input(&request.content, kind)
+                    .map_err(|e| McpError::invalid_params(e.to_string(), None))?;
                 
                 // problems 和 regressions: 直接写入 + 自动 push
                 // patterns: 返回预览，需要用户确认
-                if category == "problems" || category == "regressions" {
-                    manager.settle_to_knowledge(&request.content, category)
+                if kind != KnowledgeKind::Pattern {
+                    manager.settle_to_knowledge(&request.content, kind.category())
                         .map_err(|e| McpError::internal_error(format!("沉淀失败: {}", e), None))?
+                        .message
                 } else {
                     // patterns: 返回预览，不执行写入
                     format!(
                         r#"📋 **沉淀预览**
 
-> 目标文件: `.cunzhi-knowledge/patterns.md`
+> 目标文件: `.cunzhi-knowledge/patterns.md`{}
 
 ```
 {}
 ```
 
 ⚠️ **请调用 `zhi` 工具让用户确认**，确认后再调用 `ji(action=确认沉淀)` 执行写入。"#,
-                        &request.content
+                        if entry.id.is_empty() { "\n> 编号: 写入时自动分配" } else { "" },
+                        entry.render().trim_end()
                     )
                 }
             }
@@ -139,25 +145,93 @@ impl MemoryTool {
                 }
                 
                 // 验证 patterns 格式
-                let pattern = regex::Regex::new(r"PAT-\d{4}-\d{3}").unwrap();
-                if !pattern.is_match(&request.content) {
-                    return Err(McpError::invalid_params(
-                        "沉淀 patterns 必须包含 PAT-YYYY-NNN 格式的编号（如 PAT-2024-001）".to_string(),
-                        None
-                    ));
-                }
+                KnowledgeEntry::from_input(&request.content, KnowledgeKind::Pattern)
+                    .map_err(|e| McpError::invalid_params(e.to_string(), None))?;
                 
                 manager.settle_to_knowledge(&request.content, "patterns")
                     .map_err(|e| McpError::internal_error(format!("沉淀失败: {}", e), None))?
+                    .message
+            }
+            "修复" | "验证" | "复发" => {
+                // 问题编号优先取 entry_id，否则从 content 中提取
+                let id_pattern = regex::Regex::new(r"\bP-\d{4}-\d{3}\b").unwrap();
+                let id = match request.entry_id.as_deref().filter(|id| !id.trim().is_empty()) {
+                    Some(id) => id.trim().to_string(),
+                    None => id_pattern.find(&request.content)
+                        .map(|m| m.as_str().to_string())
+                        .ok_or_else(|| McpError::invalid_params("缺少问题编号（entry_id 或 content 中的 P-YYYY-NNN）".to_string(), None))?,
+                };
+                // 说明中去掉编号本身
+                let notes = request.content.replace(&id, "").trim().to_string();
+                let notes = (!notes.is_empty()).then_some(notes);
+
+                let transition = match request.action.as_str() {
+                    "修复" => ProblemTransition::Fix { commit: request.commit.clone(), notes },
+                    "验证" => ProblemTransition::Verify { notes },
+                    _ => ProblemTransition::Reopen {
+                        notes: notes.ok_or_else(|| McpError::invalid_params("复发需要在 content 中说明复现情况".to_string(), None))?,
+                    },
+                };
+
+                manager.transition_problem(&id, transition)
+                    .map_err(|e| McpError::invalid_params(format!("更新问题状态失败: {}", e), None))?
+                    .message
             }
             "摘要" => {
                 if request.content.trim().is_empty() {
                     return Err(McpError::invalid_params("缺少摘要内容".to_string(), None));
                 }
                 
-                manager.add_session_summary(&request.content)
+                // 保留条数可在配置中调整，超出部分移入 sessions-archive.md
+                let retention = crate::config::load_standalone_config()
+                    .ok()
+                    .and_then(|c| c.mcp_config.session_retention)
+                    .unwrap_or(SESSION_RETENTION_DEFAULT);
+
+                manager.add_session_summary_with_retention(&request.content, retention)
                     .map_err(|e| McpError::internal_error(format!("添加摘要失败: {}", e), None))?
             }
+            "查询" => {
+                let parse_date = |value: &Option<String>| -> Result<_, McpError> {
+                    value
+                        .as_deref()
+                        .filter(|v| !v.trim().is_empty())
+                        .map(parse_query_date)
+                        .transpose()
+                        .map_err(|e| McpError::invalid_params(e.to_string(), None))
+                };
+
+                let query = SessionQuery {
+                    keyword: Some(request.content.clone()),
+                    date_from: parse_date(&request.date_from)?,
+                    date_to: parse_date(&request.date_to)?,
+                    limit: request.limit.unwrap_or(10),
+                };
+
+                let sessions = manager.query_sessions(&query)
+                    .map_err(|e| McpError::internal_error(format!("查询会话失败: {}", e), None))?;
+
+                if sessions.is_empty() {
+                    "📭 未找到匹配的会话摘要".to_string()
+                } else {
+                    let lines: Vec<String> = sessions.iter().map(|s| s.one_line()).collect();
+                    format!("🔎 找到 {} 条会话摘要:\n{}", sessions.len(), lines.join("\n"))
+                }
+            }
+            "初始化" => {
+                let options = KnowledgeInitOptions {
+                    repo_url: request.repo_url.clone(),
+                    remote_url: request.remote_url.clone(),
+                }.with_configured_repo();
+                manager.init_knowledge(&options)
+                    .map_err(|e| McpError::internal_error(format!("初始化知识库失败: {}", e), None))?
+                    .message()
+            }
+            "检查" => {
+                manager.knowledge_doctor()
+                    .map_err(|e| McpError::internal_error(format!("知识库体检失败: {}", e), None))?
+                    .render()
+            }
             "" | "选择" => {
                 // 返回选项菜单，让 AI 调用 zhi 展示给用户
                 r#"📋 **请选择 ji 操作**
@@ -171,7 +245,7 @@ impl MemoryTool {
             }
             _ => {
                 return Err(McpError::invalid_params(
-                    format!("未知的操作类型: {}。可选：回忆/记忆/沉淀/摘要，或留空显示选项", request.action),
+                    format!("未知的操作类型: {}。可选：回忆/记忆/沉淀/摘要/查询/修复/验证/复发/置顶/取消置顶/整理/初始化/检查，或留空显示选项", request.action),
                     None
                 ));
             }
@@ -6,8 +6,26 @@ pub mod manager;
 pub mod types;
 pub mod mcp;
 pub mod commands;
+pub mod interchange;
+pub mod lifecycle;
+pub mod sessions;
+pub mod editor;
+pub mod knowledge;
+pub mod knowledge_base;
+pub mod sync;
+pub mod bootstrap;
 
 // 重新导出主要类型和功能
-pub use manager::MemoryManager;
-pub use types::{MemoryEntry, MemoryCategory, MemoryMetadata};
+pub use manager::{MemoryManager, SettleResult};
+pub use types::{MemoryEntry, MemoryCategory, MemoryMetadata, MemoryOptions};
 pub use mcp::MemoryTool;
+pub use interchange::{RuleFormat, MemoryBundle, ImportReport};
+pub use lifecycle::CompactionReport;
+pub use sessions::{SessionSummary, SessionQuery};
+pub use editor::{MemoryFilter, MemoryUpdate, MemoryHistoryRecord};
+pub use sync::{KnowledgeSync, SyncOutcome, SyncStatus};
+pub use bootstrap::{KnowledgeInitOptions, KnowledgeInitReport, DoctorReport, DoctorCheck, DoctorLevel};
+pub use knowledge::{KnowledgeHistoryRecord, ProblemTransition, ProblemTransitionResult};
+pub use knowledge_base::{
+    KnowledgeBase, KnowledgeEntry, KnowledgeKind, KnowledgeStatus, KnowledgeDiagnostic, DiagnosticSeverity,
+};
@@ -0,0 +1,515 @@
+//! 会话摘要（L3 近期对话摘要层）
+//!
+//! 每条摘要以 `## YYYY-MM-DD HH:MM` 开头，随后按行记录结构化字段：
+//!
+//! ```text
+//! ## 2024-05-01 10:30
+//! 主题：xxx
+//! 关键词：a, b
+//! 意图：xxx
+//! 文件：src/a.rs, src/b.rs
+//! 结果：xxx
+//! ```
+//!
+//! 只有时间戳格式正确的 `## ` 行才会开始新的摘要，备注中的其他二级标题不会拆分摘要。
+//! 超出保留条数的摘要移入 sessions-archive.md，查询时一并检索
+
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use super::manager::MemoryManager;
+use super::types::{MemoryCategory, MemoryEntry};
+use crate::constants::mcp::SESSION_RETENTION_DEFAULT;
+use crate::utils::update_locked;
+
+/// 会话摘要文件
+const SESSIONS_FILE: &str = "sessions.md";
+/// 会话摘要归档文件
+const SESSIONS_ARCHIVE_FILE: &str = "sessions-archive.md";
+/// 时间戳格式
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// 结构化会话摘要
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionSummary {
+    #[serde(default)]
+    pub timestamp: String,
+    #[serde(default)]
+    pub topic: String,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[serde(default)]
+    pub intent: String,
+    #[serde(default, alias = "files_touched")]
+    pub files: Vec<String>,
+    #[serde(default)]
+    pub outcome: String,
+    /// 无法识别为字段的其他内容
+    #[serde(default)]
+    pub notes: String,
+}
+
+/// 会话查询条件
+#[derive(Debug, Clone, Default)]
+pub struct SessionQuery {
+    /// 关键词（空格分隔，全部命中才算匹配）
+    pub keyword: Option<String>,
+    pub date_from: Option<NaiveDate>,
+    pub date_to: Option<NaiveDate>,
+    pub limit: usize,
+}
+
+/// 字段标签（中文/英文）
+const FIELD_LABELS: &[(&str, &[&str])] = &[
+    ("timestamp", &["时间", "timestamp"]),
+    ("topic", &["主题", "topic"]),
+    ("keywords", &["关键词", "keywords", "keyword"]),
+    ("intent", &["意图", "intent"]),
+    ("files", &["文件", "files", "files_touched"]),
+    ("outcome", &["结果", "outcome"]),
+];
+
+impl SessionSummary {
+    /// 从 ji 摘要内容解析：支持 JSON 对象，或 `主题：xxx | 关键词：xxx` 形式（可用 `|` 或换行分隔）
+    pub fn parse_input(content: &str) -> Self {
+        let trimmed = content.trim();
+        if trimmed.starts_with('{') {
+            if let Ok(summary) = serde_json::from_str::<SessionSummary>(trimmed) {
+                return summary;
+            }
+        }
+
+        let mut summary = SessionSummary::default();
+        summary.apply_body(trimmed);
+        summary
+    }
+
+    /// 解析正文中的字段行
+    fn apply_body(&mut self, body: &str) {
+        let mut notes = Vec::new();
+
+        for line in body.lines() {
+            for segment in line.split(" | ") {
+                let segment = segment.trim();
+                if segment.is_empty() {
+                    continue;
+                }
+                if !self.apply_field(segment) {
+                    notes.push(segment.to_string());
+                }
+            }
+        }
+
+        self.notes = notes.join("\n");
+    }
+
+    /// 识别 `标签：值` 形式的字段，返回是否识别成功
+    fn apply_field(&mut self, segment: &str) -> bool {
+        let Some((label, value)) = segment.split_once('：').or_else(|| segment.split_once(':')) else {
+            return false;
+        };
+        let label = label.trim().trim_matches('*').to_lowercase();
+        let value = value.trim();
+
+        let Some((field, _)) = FIELD_LABELS.iter().find(|(_, names)| names.contains(&label.as_str())) else {
+            return false;
+        };
+
+        match *field {
+            "timestamp" => self.timestamp = value.to_string(),
+            "topic" => self.topic = value.to_string(),
+            "keywords" => self.keywords = split_list(value),
+            "intent" => self.intent = value.to_string(),
+            "files" => self.files = split_list(value),
+            "outcome" => self.outcome = value.to_string(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// 是否没有任何内容
+    pub fn is_empty(&self) -> bool {
+        self.topic.is_empty()
+            && self.keywords.is_empty()
+            && self.intent.is_empty()
+            && self.files.is_empty()
+            && self.outcome.is_empty()
+            && self.notes.is_empty()
+    }
+
+    /// 解析时间戳中的日期
+    pub fn date(&self) -> Option<NaiveDate> {
+        NaiveDateTime::parse_from_str(&self.timestamp, TIMESTAMP_FORMAT)
+            .ok()
+            .map(|t| t.date())
+    }
+
+    /// 渲染为 sessions.md 中的一个区块
+    fn render(&self) -> String {
+        let mut out = format!("## {}\n", self.timestamp);
+        if !self.topic.is_empty() {
+            out.push_str(&format!("主题：{}\n", self.topic));
+        }
+        if !self.keywords.is_empty() {
+            out.push_str(&format!("关键词：{}\n", self.keywords.join(", ")));
+        }
+        if !self.intent.is_empty() {
+            out.push_str(&format!("意图：{}\n", self.intent));
+        }
+        if !self.files.is_empty() {
+            out.push_str(&format!("文件：{}\n", self.files.join(", ")));
+        }
+        if !self.outcome.is_empty() {
+            out.push_str(&format!("结果：{}\n", self.outcome));
+        }
+        for line in self.notes.lines() {
+            // 形如摘要标题的备注行加缩进，避免重新解析时被当作新的摘要
+            if session_header(line).is_some() {
+                out.push(' ');
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.push('\n');
+        out
+    }
+
+    /// 渲染为单行字段文本（`时间：xxx | 主题：xxx | ...`），可由 `parse_input` 还原
+    pub fn to_input(&self) -> String {
+        let mut parts = Vec::new();
+        let fields = [
+            ("时间", self.timestamp.clone()),
+            ("主题", self.topic.clone()),
+            ("关键词", self.keywords.join(", ")),
+            ("意图", self.intent.clone()),
+            ("文件", self.files.join(", ")),
+            ("结果", self.outcome.clone()),
+        ];
+        for (label, value) in fields {
+            if !value.is_empty() {
+                parts.push(format!("{}：{}", label, value));
+            }
+        }
+        parts.extend(self.notes.lines().map(str::to_string));
+        parts.join(" | ")
+    }
+
+    /// 转换为记忆条目，供全部记忆列表和 JSON 导出使用
+    pub fn to_entry(&self) -> MemoryEntry {
+        let created_at = NaiveDateTime::parse_from_str(&self.timestamp, TIMESTAMP_FORMAT)
+            .map(|t| Utc.from_utc_datetime(&t))
+            .unwrap_or_else(|_| Utc::now());
+        let id: String = self.timestamp.chars().filter(char::is_ascii_digit).collect();
+        MemoryEntry {
+            id: format!("session-{}", id),
+            content: self.to_input(),
+            category: MemoryCategory::Session,
+            created_at,
+            updated_at: created_at,
+            pinned: false,
+            expires_at: None,
+        }
+    }
+
+    /// 单行概要（用于回忆/查询结果）
+    pub fn one_line(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.topic.is_empty() {
+            parts.push(self.topic.clone());
+        }
+        if !self.keywords.is_empty() {
+            parts.push(format!("关键词: {}", self.keywords.join(", ")));
+        }
+        if !self.intent.is_empty() {
+            parts.push(format!("意图: {}", self.intent));
+        }
+        if !self.files.is_empty() {
+            parts.push(format!("文件: {}", self.files.join(", ")));
+        }
+        if !self.outcome.is_empty() {
+            parts.push(format!("结果: {}", self.outcome));
+        }
+        if parts.is_empty() {
+            parts.push(self.notes.lines().next().unwrap_or("").to_string());
+        }
+        format!("- **{}**: {}", self.timestamp, parts.join(" | "))
+    }
+
+    /// 关键词匹配（所有词都需命中任一字段）
+    fn matches_keyword(&self, keyword: &str) -> bool {
+        let haystack = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            self.topic,
+            self.keywords.join(" "),
+            self.intent,
+            self.files.join(" "),
+            self.outcome,
+            self.notes
+        )
+        .to_lowercase();
+
+        keyword
+            .split_whitespace()
+            .all(|term| haystack.contains(&term.to_lowercase()))
+    }
+}
+
+/// 拆分逗号/顿号分隔的列表
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split([',', '，', '、'])
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// 识别 `## YYYY-MM-DD HH:MM` 形式的摘要标题，返回时间戳
+fn session_header(line: &str) -> Option<&str> {
+    let timestamp = line.strip_prefix("## ")?.trim();
+    NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT)
+        .is_ok()
+        .then_some(timestamp)
+}
+
+/// 解析会话文件：返回（文件头, 摘要列表）
+fn parse_sessions_file(content: &str) -> (String, Vec<SessionSummary>) {
+    let mut header = String::new();
+    let mut sessions = Vec::new();
+    let mut current: Option<(String, Vec<&str>)> = None;
+
+    for line in content.lines() {
+        if let Some(timestamp) = session_header(line) {
+            if let Some((ts, body)) = current.take() {
+                sessions.push(build_session(ts, &body));
+            }
+            current = Some((timestamp.to_string(), Vec::new()));
+        } else if let Some((_, body)) = current.as_mut() {
+            body.push(line);
+        } else {
+            header.push_str(line);
+            header.push('\n');
+        }
+    }
+    if let Some((ts, body)) = current.take() {
+        sessions.push(build_session(ts, &body));
+    }
+
+    (header, sessions)
+}
+
+fn build_session(timestamp: String, body: &[&str]) -> SessionSummary {
+    let mut summary = SessionSummary {
+        timestamp,
+        ..Default::default()
+    };
+    summary.apply_body(&body.join("\n"));
+    summary
+}
+
+/// 渲染会话文件
+fn render_sessions_file(header: &str, sessions: &[SessionSummary]) -> String {
+    let mut out = header.trim_end().to_string();
+    out.push_str("\n\n");
+    for session in sessions {
+        out.push_str(&session.render());
+    }
+    out
+}
+
+/// 读取某个会话文件中的全部摘要
+fn read_sessions(path: &Path) -> Result<Vec<SessionSummary>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(path)?;
+    Ok(parse_sessions_file(&content).1)
+}
+
+impl MemoryManager {
+    /// 添加会话摘要，使用默认保留条数
+    pub fn add_session_summary(&self, content: &str) -> Result<String> {
+        self.add_session_summary_with_retention(content, SESSION_RETENTION_DEFAULT)
+    }
+
+    /// 添加会话摘要（最新的在前），超出 `retention` 条的旧摘要移入归档文件
+    pub fn add_session_summary_with_retention(&self, content: &str, retention: usize) -> Result<String> {
+        let mut summary = SessionSummary::parse_input(content);
+        if summary.is_empty() {
+            return Err(anyhow::anyhow!("会话摘要内容为空"));
+        }
+        summary.timestamp = Utc::now().format(TIMESTAMP_FORMAT).to_string();
+
+        let archived = self.insert_session(&summary, retention)?;
+
+        let mut result = format!("✅ 会话摘要已添加\n📅 时间: {}\n📝 {}", summary.timestamp, summary.one_line());
+        if archived > 0 {
+            result.push_str(&format!("\n🗄️ 已归档 {} 条旧摘要", archived));
+        }
+        Ok(result)
+    }
+
+    /// 按时间顺序插入一条摘要（最新的在前），超出 `retention` 条的旧摘要移入归档文件，返回归档条数
+    ///
+    /// 时间戳缺失或格式不对时使用当前时间
+    pub(super) fn insert_session(&self, summary: &SessionSummary, retention: usize) -> Result<usize> {
+        let mut summary = summary.clone();
+        if summary.date().is_none() {
+            summary.timestamp = Utc::now().format(TIMESTAMP_FORMAT).to_string();
+        }
+        let retention = retention.max(1);
+        let file_path = self.memory_dir().join(SESSIONS_FILE);
+
+        let overflow = update_locked(&file_path, "# 会话摘要\n\n", |existing| {
+            let (header, mut sessions) = parse_sessions_file(existing);
+            // 时间戳格式固定，可直接按字符串比较
+            let position = sessions
+                .iter()
+                .position(|s| s.timestamp <= summary.timestamp)
+                .unwrap_or(sessions.len());
+            sessions.insert(position, summary.clone());
+            let overflow = if sessions.len() > retention {
+                sessions.split_off(retention)
+            } else {
+                Vec::new()
+            };
+            *existing = render_sessions_file(&header, &sessions);
+            Ok(overflow)
+        })?;
+
+        if !overflow.is_empty() {
+            let archive_path = self.memory_dir().join(SESSIONS_ARCHIVE_FILE);
+            update_locked(&archive_path, "# 会话摘要归档\n\n", |existing| {
+                for session in &overflow {
+                    existing.push_str(&session.render());
+                }
+                Ok(())
+            })?;
+        }
+        Ok(overflow.len())
+    }
+
+    /// 当前保留的会话摘要（不含归档），按时间倒序
+    pub fn get_sessions(&self) -> Result<Vec<SessionSummary>> {
+        read_sessions(&self.memory_dir().join(SESSIONS_FILE))
+    }
+
+    /// 获取最近的会话摘要（用于上下文注入）
+    pub fn get_recent_sessions(&self, limit: usize) -> Result<String> {
+        let sessions = self.get_sessions()?;
+        if sessions.is_empty() {
+            return Ok("📭 暂无会话摘要".to_string());
+        }
+
+        let lines: Vec<String> = sessions.iter().take(limit).map(|s| s.one_line()).collect();
+        Ok(format!("📋 最近会话:\n{}", lines.join("\n")))
+    }
+
+    /// 按关键词和日期范围查询会话摘要（包含归档），按时间倒序
+    pub fn query_sessions(&self, query: &SessionQuery) -> Result<Vec<SessionSummary>> {
+        let mut sessions = read_sessions(&self.memory_dir().join(SESSIONS_FILE))?;
+        sessions.extend(read_sessions(&self.memory_dir().join(SESSIONS_ARCHIVE_FILE))?);
+
+        let keyword = query.keyword.as_deref().map(str::trim).filter(|k| !k.is_empty());
+        let mut matched: Vec<SessionSummary> = sessions
+            .into_iter()
+            .filter(|s| {
+                let date = s.date();
+                let after_from = match (query.date_from, date) {
+                    (Some(from), Some(d)) => d >= from,
+                    (Some(_), None) => false,
+                    (None, _) => true,
+                };
+                let before_to = match (query.date_to, date) {
+                    (Some(to), Some(d)) => d <= to,
+                    (Some(_), None) => false,
+                    (None, _) => true,
+                };
+                after_from && before_to && keyword.map(|k| s.matches_keyword(k)).unwrap_or(true)
+            })
+            .collect();
+
+        // 时间戳格式固定，可直接按字符串倒序
+        matched.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        if query.limit > 0 {
+            matched.truncate(query.limit);
+        }
+        Ok(matched)
+    }
+}
+
+/// 解析 YYYY-MM-DD 日期
+pub fn parse_query_date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("日期格式错误: {}，应为 YYYY-MM-DD", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::tools::memory::interchange::RuleFormat;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_retention_query_and_export() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        let manager = MemoryManager::new(dir.path().to_str().unwrap()).unwrap();
+
+        // 备注里的二级标题不会拆分摘要，重新渲染后仍然只有一条
+        let content = "# 会话摘要\n\n## 2024-05-02 09:00\n主题：登录重构\n关键词：auth, token\n## 背景说明\n旧实现依赖 cookie\n   ## 2024-05-01 08:00\n\n## 2024-05-01 10:30\n主题：修复构建\n文件：build.rs\n";
+        let (header, sessions) = parse_sessions_file(content);
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].keywords, vec!["auth", "token"]);
+        assert_eq!(sessions[0].notes, "## 背景说明\n旧实现依赖 cookie\n## 2024-05-01 08:00");
+        let rendered = render_sessions_file(&header, &sessions);
+        assert_eq!(parse_sessions_file(&rendered).1.len(), 2);
+        fs::write(dir.path().join(".cunzhi-memory").join(SESSIONS_FILE), rendered).unwrap();
+
+        // 超出保留条数的旧摘要进入归档，查询时仍能找到
+        manager.add_session_summary_with_retention("主题：新功能 | 关键词：search", 2).unwrap();
+        let kept = manager.get_sessions().unwrap();
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0].topic, "新功能");
+        assert_eq!(kept[1].topic, "登录重构");
+        let archived = read_sessions(&dir.path().join(".cunzhi-memory").join(SESSIONS_ARCHIVE_FILE)).unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].topic, "修复构建");
+
+        let query = |keyword: Option<&str>, from: Option<&str>, to: Option<&str>| {
+            manager
+                .query_sessions(&SessionQuery {
+                    keyword: keyword.map(str::to_string),
+                    date_from: from.map(|d| parse_query_date(d).unwrap()),
+                    date_to: to.map(|d| parse_query_date(d).unwrap()),
+                    limit: 0,
+                })
+                .unwrap()
+                .into_iter()
+                .map(|s| s.topic)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(query(Some("build.rs"), None, None), vec!["修复构建"]);
+        assert_eq!(query(Some("AUTH cookie"), None, None), vec!["登录重构"]);
+        assert_eq!(query(None, Some("2024-05-01"), Some("2024-05-01")), vec!["修复构建"]);
+        assert_eq!(query(None, None, Some("2024-05-02")), vec!["登录重构", "修复构建"]);
+
+        // 会话摘要出现在全部记忆和 JSON 导出中，并能按原时间戳导入
+        let all = manager.get_all_memories().unwrap();
+        assert_eq!(all.iter().filter(|e| e.category == MemoryCategory::Session).count(), 2);
+        let bundle = manager.export_memories(RuleFormat::JsonBundle).unwrap();
+        assert!(bundle.contains("时间：2024-05-02 09:00 | 主题：登录重构"));
+
+        let other = tempdir().unwrap();
+        fs::create_dir_all(other.path().join(".git")).unwrap();
+        let imported = MemoryManager::new(other.path().to_str().unwrap()).unwrap();
+        assert_eq!(imported.import_memories(RuleFormat::JsonBundle, &bundle).unwrap().imported, 2);
+        let sessions = imported.get_sessions().unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[1].timestamp, "2024-05-02 09:00");
+        assert_eq!(sessions[1].notes, kept[1].notes);
+        assert_eq!(imported.import_memories(RuleFormat::JsonBundle, &bundle).unwrap().imported, 0);
+    }
+}
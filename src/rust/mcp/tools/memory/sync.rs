@@ -0,0 +1,497 @@
+//! 知识库 git 同步
+//!
+//! 提交后先 `git pull --rebase` 再推送；只追加的对话记录（conversations/*.md）
+//! 出现冲突时保留双方内容自动解决，其他文件冲突时中止 rebase 交给用户处理。离线时提交留在本地排队，下次同步一并推送。
+//! 同步状态记录在知识库的 git 目录中（cunzhi-sync.json），供设置窗口查询
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use crate::utils::{atomic_write, FileLock};
+
+/// 同步锁名称（按知识库目录区分，锁文件位于缓存目录，串行化所有 git 操作）
+pub const SYNC_LOCK_NAME: &str = "git-sync";
+
+/// git 目录中的同步状态文件
+const SYNC_STATE_FILE: &str = "cunzhi-sync.json";
+
+/// 判定为网络不可用的 git 错误输出（只匹配域名解析和连接错误，认证、权限错误不算离线）
+const OFFLINE_MARKERS: [&str; 8] = [
+    "Could not resolve host",
+    "Temporary failure in name resolution",
+    "Failed to connect to",
+    "Couldn't connect to server",
+    "Connection refused",
+    "Connection timed out",
+    "Network is unreachable",
+    "Operation timed out",
+];
+
+/// 冲突时可以保留双方内容自动解决的目录（只追加的对话记录）
+const APPEND_ONLY_DIR: &str = "conversations/";
+
+/// 一次同步的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncOutcome {
+    /// 已推送（含之前排队的提交）
+    Pushed { commits: usize },
+    /// 本地与远程一致，无需推送
+    UpToDate,
+    /// 网络不可用，提交已在本地排队
+    Queued { pending: usize, reason: String },
+    /// 未配置远程仓库，仅本地提交
+    LocalOnly,
+}
+
+impl SyncOutcome {
+    pub fn message(&self) -> String {
+        match self {
+            SyncOutcome::Pushed { commits } => format!("🚀 已同步到远程仓库（推送 {} 个提交）", commits),
+            SyncOutcome::UpToDate => "✅ 知识库已是最新".to_string(),
+            SyncOutcome::Queued { pending, reason } => {
+                format!("📦 网络不可用，{} 个提交已在本地排队，下次同步时推送（{}）", pending, reason)
+            }
+            SyncOutcome::LocalOnly => "💾 已提交到本地（未配置远程仓库）".to_string(),
+        }
+    }
+}
+
+/// 持久化的同步状态
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SyncState {
+    last_attempt_at: Option<DateTime<Utc>>,
+    last_success_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    offline: bool,
+}
+
+/// 同步状态（Tauri 命令返回）
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncStatus {
+    pub knowledge_dir: String,
+    pub has_remote: bool,
+    pub branch: Option<String>,
+    /// 尚未推送的本地提交数
+    pub pending_commits: usize,
+    /// 未提交的改动文件数
+    pub uncommitted_files: usize,
+    pub offline: bool,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+/// 知识库同步器
+pub struct KnowledgeSync {
+    dir: PathBuf,
+}
+
+impl KnowledgeSync {
+    pub fn new(knowledge_dir: &Path) -> Self {
+        Self { dir: knowledge_dir.to_path_buf() }
+    }
+
+    /// 提交指定路径并同步，返回给用户的状态说明（失败时也返回说明，不中断调用方）
+    pub fn commit_and_sync(&self, paths: &[&str], commit_msg: &str) -> String {
+        match self.try_commit_and_sync(paths, commit_msg) {
+            Ok(outcome) => outcome.message(),
+            Err(e) => format!("⚠️ Git 同步失败: {}\n请手动执行 git pull --rebase && git push", e),
+        }
+    }
+
+    pub fn try_commit_and_sync(&self, paths: &[&str], commit_msg: &str) -> Result<SyncOutcome> {
+        let _lock = FileLock::for_file(&self.dir.join(SYNC_LOCK_NAME))?;
+        self.commit(paths, commit_msg)?;
+        self.sync_locked()
+    }
+
+    /// 只同步（推送排队的提交、拉取远程更新）
+    pub fn sync(&self) -> Result<SyncOutcome> {
+        let _lock = FileLock::for_file(&self.dir.join(SYNC_LOCK_NAME))?;
+        self.sync_locked()
+    }
+
+    /// 当前同步状态
+    pub fn status(&self) -> Result<SyncStatus> {
+        let state = self.load_state();
+        let has_remote = self.has_remote()?;
+        let branch = self.git_stdout(&["rev-parse", "--abbrev-ref", "HEAD"]).ok();
+        let uncommitted_files = self
+            .git_stdout(&["status", "--porcelain"])
+            .map(|s| s.lines().count())
+            .unwrap_or(0);
+
+        Ok(SyncStatus {
+            knowledge_dir: self.dir.to_string_lossy().to_string(),
+            has_remote,
+            branch,
+            pending_commits: if has_remote { self.pending_commits() } else { 0 },
+            uncommitted_files,
+            offline: state.offline,
+            last_attempt_at: state.last_attempt_at,
+            last_success_at: state.last_success_at,
+            last_error: state.last_error,
+        })
+    }
+
+    fn git(&self, args: &[&str]) -> Result<Output> {
+        Ok(Command::new("git")
+            .args(args)
+            .current_dir(&self.dir)
+            // rebase --continue 等命令不打开编辑器
+            .env("GIT_EDITOR", "true")
+            .output()?)
+    }
+
+    fn git_stdout(&self, args: &[&str]) -> Result<String> {
+        let output = self.git(args)?;
+        if !output.status.success() {
+            return Err(anyhow!("git {} 失败: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn commit(&self, paths: &[&str], commit_msg: &str) -> Result<()> {
+        let mut add_args = vec!["add", "--"];
+        add_args.extend_from_slice(paths);
+        self.git_stdout(&add_args)?;
+
+        let output = self.git(&["commit", "-m", commit_msg])?;
+        if !output.status.success() {
+            let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+            // "nothing to commit" 不算错误，仍继续推送排队的提交
+            if !text.contains("nothing to commit") && !text.contains("nothing added to commit") {
+                return Err(anyhow!("git commit 失败: {}", text.trim()));
+            }
+        }
+        Ok(())
+    }
+
+    fn has_remote(&self) -> Result<bool> {
+        Ok(!self.git_stdout(&["remote"])?.is_empty())
+    }
+
+    fn has_upstream(&self) -> bool {
+        self.git_stdout(&["rev-parse", "--abbrev-ref", "--symbolic-full-name", "@{u}"]).is_ok()
+    }
+
+    /// 未推送的提交数（没有上游分支时视为全部未推送）
+    fn pending_commits(&self) -> usize {
+        let range = if self.has_upstream() { "@{u}..HEAD" } else { "HEAD" };
+        self.git_stdout(&["rev-list", "--count", range])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0)
+    }
+
+    fn sync_locked(&self) -> Result<SyncOutcome> {
+        let mut state = self.load_state();
+        state.last_attempt_at = Some(Utc::now());
+
+        let result = self.pull_and_push();
+        match &result {
+            Ok(SyncOutcome::Queued { reason, .. }) => {
+                state.offline = true;
+                state.last_error = Some(reason.clone());
+            }
+            Ok(_) => {
+                state.offline = false;
+                state.last_error = None;
+                state.last_success_at = state.last_attempt_at;
+            }
+            Err(e) => {
+                state.offline = false;
+                state.last_error = Some(e.to_string());
+            }
+        }
+        if let Err(e) = self.save_state(&state) {
+            crate::log_important!(warn, "保存知识库同步状态失败: {}", e);
+        }
+        result
+    }
+
+    fn pull_and_push(&self) -> Result<SyncOutcome> {
+        if !self.has_remote()? {
+            return Ok(SyncOutcome::LocalOnly);
+        }
+
+        if self.has_upstream() {
+            // 知识库目录嵌套在其他仓库中时，rebase 会作用于整个外层仓库，拒绝自动拉取
+            let root = self.repo_root()?;
+            if !same_dir(&root, &self.dir) {
+                return Err(anyhow!(
+                    "知识库目录 {} 不是 git 仓库根目录（仓库根目录为 {}），已跳过 pull --rebase",
+                    self.dir.display(),
+                    root.display()
+                ));
+            }
+
+            let output = self.git(&["pull", "--rebase", "--autostash"])?;
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+                if let Some(reason) = offline_reason(&stderr) {
+                    return Ok(SyncOutcome::Queued { pending: self.pending_commits(), reason });
+                }
+                if !self.rebase_in_progress() {
+                    return Err(anyhow!("git pull --rebase 失败: {}", stderr.trim()));
+                }
+                self.resolve_rebase_conflicts()?;
+            }
+        }
+
+        let pending = self.pending_commits();
+        if pending == 0 && self.has_upstream() {
+            return Ok(SyncOutcome::UpToDate);
+        }
+
+        let push_args: &[&str] = if self.has_upstream() { &["push"] } else { &["push", "-u", "origin", "HEAD"] };
+        let output = self.git(push_args)?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            if let Some(reason) = offline_reason(&stderr) {
+                return Ok(SyncOutcome::Queued { pending, reason });
+            }
+            return Err(anyhow!("git push 失败: {}", stderr.trim()));
+        }
+
+        Ok(SyncOutcome::Pushed { commits: pending })
+    }
+
+    fn rebase_in_progress(&self) -> bool {
+        ["rebase-merge", "rebase-apply"].iter().any(|name| {
+            self.git_stdout(&["rev-parse", "--git-path", name])
+                .map(|p| self.dir.join(p).exists())
+                .unwrap_or(false)
+        })
+    }
+
+    /// 仓库根目录（`git diff --name-only` 等输出的路径相对于它）
+    fn repo_root(&self) -> Result<PathBuf> {
+        Ok(PathBuf::from(self.git_stdout(&["rev-parse", "--show-toplevel"])?))
+    }
+
+    /// 逐个提交解决 rebase 冲突：对话记录保留双方追加的内容，其他冲突中止 rebase
+    fn resolve_rebase_conflicts(&self) -> Result<()> {
+        let root = self.repo_root()?;
+        while self.rebase_in_progress() {
+            let conflicted = self.git_stdout(&["diff", "--name-only", "--diff-filter=U"])?;
+            if conflicted.is_empty() {
+                return self.abort_rebase("rebase 中断但没有冲突文件".to_string());
+            }
+
+            for file in conflicted.lines() {
+                let resolved = if file.starts_with(APPEND_ONLY_DIR) && file.ends_with(".md") {
+                    std::fs::read_to_string(root.join(file))
+                        .ok()
+                        .and_then(|text| resolve_append_conflicts(&text))
+                } else {
+                    None
+                };
+                let Some(resolved) = resolved else {
+                    return self.abort_rebase(format!("无法自动解决冲突: {}", file));
+                };
+                atomic_write(&root.join(file), &resolved)?;
+                self.git_stdout(&["-C", &root.to_string_lossy(), "add", "--", file])?;
+            }
+
+            let output = self.git(&["rebase", "--continue"])?;
+            if !output.status.success() && !self.rebase_in_progress() {
+                return Err(anyhow!("git rebase --continue 失败: {}", String::from_utf8_lossy(&output.stderr).trim()));
+            }
+        }
+        Ok(())
+    }
+
+    fn abort_rebase(&self, reason: String) -> Result<()> {
+        let _ = self.git(&["rebase", "--abort"]);
+        Err(anyhow!("{}，已中止 rebase，请手动合并", reason))
+    }
+
+    fn state_path(&self) -> Option<PathBuf> {
+        self.git_stdout(&["rev-parse", "--git-path", SYNC_STATE_FILE])
+            .ok()
+            .map(|p| self.dir.join(p))
+    }
+
+    fn load_state(&self) -> SyncState {
+        self.state_path()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_state(&self, state: &SyncState) -> Result<()> {
+        let path = self.state_path().ok_or_else(|| anyhow!("不是 git 仓库: {}", self.dir.display()))?;
+        atomic_write(&path, &serde_json::to_string_pretty(state)?)
+    }
+}
+
+/// 比较两个目录是否相同（解析符号链接后比较）
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn offline_reason(stderr: &str) -> Option<String> {
+    OFFLINE_MARKERS
+        .iter()
+        .find(|marker| stderr.contains(*marker))
+        .map(|_| stderr.lines().find(|l| !l.trim().is_empty()).unwrap_or("网络不可用").trim().to_string())
+}
+
+/// 解决只追加文件的冲突：每个冲突块保留双方内容（先远程后本地），返回 None 表示没有冲突标记
+pub fn resolve_append_conflicts(text: &str) -> Option<String> {
+    if !text.contains("<<<<<<< ") {
+        return None;
+    }
+
+    let mut out = Vec::new();
+    let mut ours: Vec<&str> = Vec::new();
+    let mut theirs: Vec<&str> = Vec::new();
+    // 0: 冲突外，1: 第一段，2: 第二段（diff3 的 base 段直接丢弃）
+    let mut section = 0;
+
+    for line in text.lines() {
+        if line.starts_with("<<<<<<< ") {
+            section = 1;
+        } else if section > 0 && line.starts_with("||||||| ") {
+            section = 3;
+        } else if section > 0 && line == "=======" {
+            section = 2;
+        } else if section > 0 && line.starts_with(">>>>>>> ") {
+            let kept = ours.len();
+            out.append(&mut ours);
+            // 双方追加了相同的内容行时只保留一份（空行、分隔线照常保留）
+            for line in theirs.drain(..) {
+                let duplicate = !line.trim().is_empty() && line != "---" && out[out.len() - kept..].contains(&line);
+                if !duplicate {
+                    out.push(line);
+                }
+            }
+            section = 0;
+        } else {
+            match section {
+                0 => out.push(line),
+                1 => ours.push(line),
+                2 => theirs.push(line),
+                _ => {}
+            }
+        }
+    }
+
+    if section != 0 {
+        return None;
+    }
+    let mut resolved = out.join("\n");
+    resolved.push('\n');
+    Some(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn run(dir: &Path, args: &[&str]) {
+        let output = Command::new("git").args(args).current_dir(dir).output().unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    }
+
+    fn clone(remote: &Path, dir: &Path) {
+        run(remote.parent().unwrap(), &["clone", "-q", remote.to_str().unwrap(), dir.to_str().unwrap()]);
+        run(dir, &["config", "user.name", "test"]);
+        run(dir, &["config", "user.email", "test@example.com"]);
+    }
+
+    #[test]
+    fn test_resolve_append_conflicts_keeps_both_sides() {
+        let text = "# 记录\n<<<<<<< HEAD\n## A\n=======\n## B\n>>>>>>> local\n";
+        assert_eq!(resolve_append_conflicts(text).unwrap(), "# 记录\n## A\n## B\n");
+        assert!(resolve_append_conflicts("# 无冲突\n").is_none());
+    }
+
+    #[test]
+    fn test_sync_rebases_over_remote_appends_and_queues_offline() {
+        let root = tempdir().unwrap();
+        let remote = root.path().join("remote.git");
+        run(root.path(), &["init", "-q", "--bare", remote.to_str().unwrap()]);
+
+        // 机器 A 初始化并推送
+        let a = root.path().join("a");
+        clone(&remote, &a);
+        fs::create_dir_all(a.join("conversations")).unwrap();
+        fs::write(a.join("conversations/log.md"), "# 对话记录\n").unwrap();
+        fs::write(a.join("problems.md"), "# 问题记录\n").unwrap();
+        let sync_a = KnowledgeSync::new(&a);
+        let outcome = sync_a.try_commit_and_sync(&["conversations/log.md", "problems.md"], "init").unwrap();
+        assert!(matches!(outcome, SyncOutcome::Pushed { .. }));
+
+        // 机器 B 克隆后，A、B 各自追加同一份对话记录
+        let b = root.path().join("b");
+        clone(&remote, &b);
+        fs::write(a.join("conversations/log.md"), "# 对话记录\n## 来自 A\n").unwrap();
+        sync_a.try_commit_and_sync(&["conversations/log.md"], "a").unwrap();
+        fs::write(b.join("conversations/log.md"), "# 对话记录\n## 来自 B\n").unwrap();
+        let sync_b = KnowledgeSync::new(&b);
+        let outcome = sync_b.try_commit_and_sync(&["conversations/log.md"], "b").unwrap();
+        assert!(matches!(outcome, SyncOutcome::Pushed { commits: 1 }));
+
+        let merged = fs::read_to_string(b.join("conversations/log.md")).unwrap();
+        assert!(merged.contains("来自 A") && merged.contains("来自 B"));
+        assert!(!merged.contains("<<<<<<<"));
+
+        // 其他文件冲突时中止 rebase，不自动合并
+        fs::write(a.join("problems.md"), "# 问题记录\n## P-2024-001 来自 A\n").unwrap();
+        sync_a.try_commit_and_sync(&["problems.md"], "a").unwrap();
+        fs::write(b.join("problems.md"), "# 问题记录\n## P-2024-001 来自 B\n").unwrap();
+        assert!(sync_b.try_commit_and_sync(&["problems.md"], "b").is_err());
+        assert!(!sync_b.rebase_in_progress());
+        assert!(fs::read_to_string(b.join("problems.md")).unwrap().contains("来自 B"));
+        run(&b, &["reset", "-q", "--hard", "@{u}"]);
+
+        // 远程仓库不存在不算离线
+        run(&b, &["remote", "set-url", "origin", root.path().join("missing.git").to_str().unwrap()]);
+        assert!(sync_b.sync().is_err());
+        assert!(!sync_b.status().unwrap().offline);
+
+        // 远程不可达时提交排队
+        run(&b, &["remote", "set-url", "origin", "http://127.0.0.1:1/knowledge.git"]);
+        fs::write(b.join("patterns.md"), "# 最佳实践\n").unwrap();
+        let outcome = sync_b.try_commit_and_sync(&["patterns.md"], "offline").unwrap();
+        assert!(matches!(outcome, SyncOutcome::Queued { pending: 1, .. }));
+        let status = sync_b.status().unwrap();
+        assert!(status.offline);
+        assert_eq!(status.pending_commits, 1);
+
+        // 恢复后推送排队的提交
+        run(&b, &["remote", "set-url", "origin", remote.to_str().unwrap()]);
+        assert_eq!(sync_b.sync().unwrap(), SyncOutcome::Pushed { commits: 1 });
+        assert!(!sync_b.status().unwrap().offline);
+    }
+
+    #[test]
+    fn test_sync_refuses_rebase_in_nested_repo() {
+        let root = tempdir().unwrap();
+        let remote = root.path().join("remote.git");
+        run(root.path(), &["init", "-q", "--bare", remote.to_str().unwrap()]);
+        let project = root.path().join("project");
+        clone(&remote, &project);
+        fs::write(project.join("README.md"), "项目\n").unwrap();
+        run(&project, &["add", "README.md"]);
+        run(&project, &["commit", "-q", "-m", "init"]);
+        run(&project, &["push", "-q", "-u", "origin", "HEAD"]);
+
+        // 知识库目录不是独立仓库，而是项目仓库的子目录
+        let knowledge = project.join(".cunzhi-knowledge");
+        fs::create_dir_all(&knowledge).unwrap();
+        fs::write(knowledge.join("problems.md"), "# 问题记录\n").unwrap();
+        let error = KnowledgeSync::new(&knowledge).try_commit_and_sync(&["problems.md"], "nested").unwrap_err();
+        assert!(error.to_string().contains("不是 git 仓库根目录"));
+    }
+}
@@ -9,10 +9,31 @@ pub struct MemoryEntry {
     pub category: MemoryCategory,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// 置顶条目不会过期，回忆时总是优先返回
+    #[serde(default)]
+    pub pinned: bool,
+    /// 过期时间，None 表示永不过期
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl MemoryEntry {
+    /// 是否已过期（置顶条目永不过期）
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        !self.pinned && self.expires_at.map(|t| t <= now).unwrap_or(false)
+    }
+}
+
+/// 添加记忆时的可选参数
+#[derive(Debug, Clone, Default)]
+pub struct MemoryOptions {
+    /// 有效天数：None 使用分类默认值，Some(0) 表示永不过期
+    pub ttl_days: Option<i64>,
+    pub pinned: bool,
 }
 
 /// 记忆分类
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum MemoryCategory {
     Rule,        // 开发规范和规则
     Preference,  // 用户偏好设置
@@ -21,6 +42,48 @@ pub enum MemoryCategory {
     Session,     // 会话摘要（L3 近期对话摘要）
 }
 
+impl MemoryCategory {
+    /// 所有分类（按展示顺序）
+    pub const ALL: [MemoryCategory; 5] = [
+        MemoryCategory::Rule,
+        MemoryCategory::Preference,
+        MemoryCategory::Note,
+        MemoryCategory::Context,
+        MemoryCategory::Session,
+    ];
+
+    /// 分类的字符串标识（与 ji 工具的 category 参数一致）
+    pub fn key(&self) -> &'static str {
+        match self {
+            MemoryCategory::Rule => "rule",
+            MemoryCategory::Preference => "preference",
+            MemoryCategory::Note => "note",
+            MemoryCategory::Context => "context",
+            MemoryCategory::Session => "session",
+        }
+    }
+
+    /// 分类的默认有效天数（None 表示永不过期）
+    pub fn default_ttl_days(&self) -> Option<i64> {
+        match self {
+            MemoryCategory::Note => Some(crate::constants::mcp::MEMORY_NOTE_TTL_DAYS),
+            _ => None,
+        }
+    }
+
+    /// 从字符串标识解析分类
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key.trim().to_lowercase().as_str() {
+            "rule" | "rules" => Some(MemoryCategory::Rule),
+            "preference" | "preferences" => Some(MemoryCategory::Preference),
+            "note" | "notes" | "pattern" => Some(MemoryCategory::Note),
+            "context" => Some(MemoryCategory::Context),
+            "session" | "sessions" => Some(MemoryCategory::Session),
+            _ => None,
+        }
+    }
+}
+
 /// 记忆元数据
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MemoryMetadata {
@@ -7,6 +7,7 @@ pub mod acemcp;
 pub mod dispatch;
 pub mod xi;
 pub mod ci;
+pub mod hui;
 
 // 重新导出工具以便访问
 pub use memory::MemoryTool;
@@ -15,3 +16,4 @@ pub use acemcp::AcemcpTool;
 pub use dispatch::DispatchTool;
 pub use xi::XiTool;
 pub use ci::CiTool;
+pub use hui::HuiTool;
@@ -6,6 +6,10 @@ pub struct ExecuteXiArgs {
     pub query: String,
     #[serde(alias = "projectPath", alias = "project_path")]
     pub project_path: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub id: Option<String>,
 }
 
 fn call_tool_result_to_text(result: &rmcp::model::CallToolResult) -> Result<String, String> {
@@ -33,6 +37,8 @@ pub async fn execute_xi_tool(args: ExecuteXiArgs) -> Result<String, String> {
     let req = XiRequest {
         query: args.query,
         project_path: args.project_path,
+        limit: args.limit,
+        id: args.id,
     };
 
     let result = XiTool::search_experience(req)
@@ -1,9 +1,11 @@
 use anyhow::Result;
 use rmcp::{Error as McpError, model::*};
-use std::fs;
 use std::path::Path;
 
 use crate::mcp::types::XiRequest;
+use super::search;
+use crate::constants::mcp::XI_DEFAULT_RESULT_LIMIT;
+use crate::mcp::tools::memory::{KnowledgeBase, KnowledgeEntry, KnowledgeKind};
 
 /// 经验查找工具
 ///
@@ -34,86 +36,70 @@ impl XiTool {
             )]));
         }
 
-        let query = request.query.to_lowercase();
-        let mut results = Vec::new();
+        let base = KnowledgeBase::load(&knowledge_dir)
+            .map_err(|e| McpError::internal_error(format!("读取知识库失败: {}", e), None))?;
 
-        // 搜索 patterns.md
-        let patterns_path = knowledge_dir.join("patterns.md");
-        if patterns_path.exists() {
-            if let Ok(content) = fs::read_to_string(&patterns_path) {
-                let matches = Self::search_in_content(&content, &query, "patterns.md");
-                if !matches.is_empty() {
-                    results.push(format!("## 📘 最佳实践 (patterns.md)\n\n{}", matches.join("\n\n")));
-                }
-            }
-        }
+        // 知识库有格式错误时提示，避免条目因格式问题被漏掉
+        let diagnostics_hint = if base.error_count() > 0 {
+            format!("\n\n⚠️ 知识库有 {} 处格式错误，部分条目可能未被识别", base.error_count())
+        } else {
+            String::new()
+        };
 
-        // 搜索 problems.md
-        let problems_path = knowledge_dir.join("problems.md");
-        if problems_path.exists() {
-            if let Ok(content) = fs::read_to_string(&problems_path) {
-                let matches = Self::search_in_content(&content, &query, "problems.md");
-                if !matches.is_empty() {
-                    results.push(format!("## 🐛 问题记录 (problems.md)\n\n{}", matches.join("\n\n")));
-                }
+        // 按编号取完整条目：显式传入 id，或查询本身就是编号
+        let requested_id = request.id.as_deref()
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .or_else(|| Some(request.query.trim()).filter(|q| KnowledgeKind::from_id(q).is_some() && q.split('-').count() == 3));
+        if let Some(id) = requested_id {
+            if let Some(entry) = base.get(id) {
+                return Ok(CallToolResult::success(vec![Content::text(
+                    format!("# 📖 {} ({})\n\n{}", entry.id, entry.kind.filename(), entry.render().trim_end())
+                )]));
             }
-        }
-
-        // 搜索 regressions.md
-        let regressions_path = knowledge_dir.join("regressions.md");
-        if regressions_path.exists() {
-            if let Ok(content) = fs::read_to_string(&regressions_path) {
-                let matches = Self::search_in_content(&content, &query, "regressions.md");
-                if !matches.is_empty() {
-                    results.push(format!("## 🔄 回归经验 (regressions.md)\n\n{}", matches.join("\n\n")));
-                }
+            if request.id.is_some() {
+                return Ok(CallToolResult::success(vec![Content::text(
+                    format!("📭 知识库中没有编号为 {} 的条目{}", id, diagnostics_hint)
+                )]));
             }
         }
 
-        if results.is_empty() {
-            Ok(CallToolResult::success(vec![Content::text(
-                format!("📭 未找到与「{}」相关的历史经验", request.query)
-            )]))
-        } else {
-            Ok(CallToolResult::success(vec![Content::text(
-                format!("# 🔍 历史经验查找结果\n\n查询：「{}」\n\n{}", request.query, results.join("\n\n---\n\n"))
-            )]))
+        let limit = request.limit.filter(|l| *l > 0).unwrap_or(XI_DEFAULT_RESULT_LIMIT);
+        // 没有编号的手写小节也参与检索
+        let hits = search::search(base.entries.iter().chain(base.sections.iter()), &request.query, limit);
+
+        if hits.is_empty() {
+            return Ok(CallToolResult::success(vec![Content::text(
+                format!("📭 未找到与「{}」相关的历史经验{}", request.query, diagnostics_hint)
+            )]));
         }
+
+        let results: Vec<String> = hits.iter().enumerate().map(|(i, hit)| {
+            let icon = match hit.entry.kind {
+                KnowledgeKind::Pattern => "📘",
+                KnowledgeKind::Problem => "🐛",
+                KnowledgeKind::Regression => "🔄",
+            };
+            format!("### {}. {} {}\n\n{}", i + 1, icon, hit.entry.kind.filename(), Self::excerpt(hit.entry))
+        }).collect();
+
+        Ok(CallToolResult::success(vec![Content::text(
+            format!(
+                "# 🔍 历史经验查找结果\n\n查询：「{}」，按相关度排序（共 {} 条，传入 id 可查看完整条目）\n\n{}{}",
+                request.query,
+                hits.len(),
+                results.join("\n\n---\n\n"),
+                diagnostics_hint
+            )
+        )]))
     }
 
-    /// 在内容中搜索匹配的段落
-    fn search_in_content(content: &str, query: &str, _filename: &str) -> Vec<String> {
-        let mut matches = Vec::new();
-        
-        // 按 ## 分割为段落
-        let sections: Vec<&str> = content.split("\n## ").collect();
-        
-        for (i, section) in sections.iter().enumerate() {
-            let section_lower = section.to_lowercase();
-            
-            // 检查段落是否包含查询关键词
-            if section_lower.contains(query) {
-                // 提取段落标题和前几行内容
-                let lines: Vec<&str> = section.lines().collect();
-                if !lines.is_empty() {
-                    let title = if i == 0 {
-                        // 第一个段落可能没有 ##
-                        lines[0].trim_start_matches("# ").to_string()
-                    } else {
-                        format!("## {}", lines[0])
-                    };
-                    
-                    // 取前 10 行作为摘要
-                    let summary: Vec<&str> = lines.iter().take(10).copied().collect();
-                    let truncated = if lines.len() > 10 { "\n..." } else { "" };
-                    
-                    matches.push(format!("{}\n{}{}", title, summary.join("\n"), truncated));
-                }
-            }
-        }
-        
-        // 限制返回数量
-        matches.truncate(5);
-        matches
+    /// 条目摘要：取渲染结果的前 10 行
+    fn excerpt(entry: &KnowledgeEntry) -> String {
+        let rendered = entry.render();
+        let lines: Vec<&str> = rendered.lines().collect();
+        let summary: Vec<&str> = lines.iter().take(10).copied().collect();
+        let truncated = if lines.len() > 10 { "\n..." } else { "" };
+        format!("{}{}", summary.join("\n").trim_end(), truncated)
     }
 }
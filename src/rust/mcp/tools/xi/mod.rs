@@ -4,5 +4,6 @@
 
 pub mod mcp;
 pub mod commands;
+pub mod search;
 
 pub use mcp::XiTool;
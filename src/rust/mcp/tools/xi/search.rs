@@ -0,0 +1,129 @@
+//! 知识库排序检索
+//!
+//! 查询与条目都切分为词元（见 utils::tokenizer），每个查询词元取在编号、标题、标签、
+//! 正文中的最佳命中并按字段加权
+
+use crate::mcp::tools::memory::KnowledgeEntry;
+use crate::utils::{token_match, tokenize};
+
+/// 各字段权重
+const WEIGHT_ID: f64 = 5.0;
+const WEIGHT_TITLE: f64 = 3.0;
+const WEIGHT_TAG: f64 = 2.5;
+const WEIGHT_BODY: f64 = 1.0;
+
+/// 一条检索结果
+#[derive(Debug, Clone)]
+pub struct SearchHit<'a> {
+    pub entry: &'a KnowledgeEntry,
+    pub score: f64,
+}
+
+/// 为条目打分，0 表示不相关
+pub fn score_entry(entry: &KnowledgeEntry, query: &str) -> f64 {
+    let query_tokens = tokenize(query);
+    if query_tokens.is_empty() {
+        return 0.0;
+    }
+
+    let id_tokens = tokenize(&entry.id);
+    let title_tokens = tokenize(&entry.title);
+    let tag_tokens: Vec<String> = entry.tags.iter().flat_map(|t| tokenize(t)).collect();
+    let body_tokens = tokenize(&entry.body);
+
+    let mut score = 0.0;
+    let mut matched = 0usize;
+    for token in &query_tokens {
+        let best = [
+            token_match(token, &id_tokens) * WEIGHT_ID,
+            token_match(token, &title_tokens) * WEIGHT_TITLE,
+            token_match(token, &tag_tokens) * WEIGHT_TAG,
+            token_match(token, &body_tokens) * WEIGHT_BODY,
+        ]
+        .into_iter()
+        .fold(0.0, f64::max);
+
+        if best > 0.0 {
+            matched += 1;
+            score += best;
+        }
+    }
+
+    if matched == 0 {
+        return 0.0;
+    }
+
+    // 覆盖率：命中的查询词元越多越靠前
+    let coverage = matched as f64 / query_tokens.len() as f64;
+    score *= coverage;
+
+    // 整句出现在标题或正文中时额外加分
+    let phrase = query.trim().to_lowercase();
+    if !phrase.is_empty()
+        && (entry.title.to_lowercase().contains(&phrase) || entry.body.to_lowercase().contains(&phrase))
+    {
+        score += WEIGHT_TITLE;
+    }
+
+    score
+}
+
+/// 按得分排序检索，返回前 limit 条
+pub fn search<'a>(entries: impl Iterator<Item = &'a KnowledgeEntry>, query: &str, limit: usize) -> Vec<SearchHit<'a>> {
+    let mut hits: Vec<SearchHit<'a>> = entries
+        .map(|entry| SearchHit { entry, score: score_entry(entry, query) })
+        .filter(|hit| hit.score > 0.0)
+        .collect();
+
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.entry.id.cmp(&b.entry.id)));
+    hits.truncate(limit);
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::tools::memory::knowledge_base::parse_knowledge;
+    use crate::mcp::tools::memory::{KnowledgeEntry, KnowledgeKind};
+
+    fn entry(id: &str, title: &str, tags: &[&str], body: &str) -> KnowledgeEntry {
+        let content = format!("## {} {}\n\n- 标签：{}\n\n{}", id, title, tags.join(", "), body);
+        KnowledgeEntry::from_input(&content, KnowledgeKind::from_id(id).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_ranking_typos_and_cjk() {
+        let entries = [
+            entry("P-2024-001", "弹窗不显示", &["popup"], "macOS 上 Telegram 模式下弹窗被隐藏"),
+            entry("P-2024-002", "知识库推送失败", &["git"], "git push 被拒绝，需要先 rebase"),
+            entry("PAT-2024-001", "并发写入加锁", &["concurrency"], "多个代理同时写入记忆文件时使用文件锁"),
+        ];
+
+        // 中英混合、多词查询
+        let hits = search(entries.iter(), "telegram 弹窗", 5);
+        assert_eq!(hits[0].entry.id, "P-2024-001");
+
+        // 拼写错误仍能命中
+        let hits = search(entries.iter(), "concurency", 5);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].entry.id, "PAT-2024-001");
+
+        // 纯中文查询按二元组命中
+        let hits = search(entries.iter(), "推送", 5);
+        assert_eq!(hits[0].entry.id, "P-2024-002");
+
+        assert!(search(entries.iter(), "完全无关", 5).is_empty());
+        assert_eq!(search(entries.iter(), "2024", 2).len(), 2);
+
+        // 没有编号的手写小节也能检索到
+        let parsed = parse_knowledge(
+            "# 问题记录\n\n## 旧笔记：代理超时\n\n设置 HTTPS_PROXY 后重试\n\n## P-2024-003 其他\n状态：open\n",
+            KnowledgeKind::Problem,
+            "problems.md",
+        );
+        assert_eq!(parsed.sections.len(), 1);
+        let hits = search(parsed.entries.iter().chain(parsed.sections.iter()), "代理超时", 5);
+        assert_eq!(hits[0].entry.title, "旧笔记：代理超时");
+        assert_eq!(hits[0].entry.body, "设置 HTTPS_PROXY 后重试");
+    }
+}
@@ -1,5 +1,6 @@
 use chrono;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct ZhiRequest {
@@ -22,7 +23,7 @@ fn default_is_markdown() -> bool {
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct JiyiRequest {
-    #[schemars(description = "操作类型：记忆(添加记忆), 回忆(获取项目信息), 沉淀(预览待沉淀内容), 确认沉淀(用户确认后执行), 摘要(添加会话摘要)")]
+    #[schemars(description = "操作类型：记忆(添加记忆), 回忆(获取项目信息), 沉淀(写入knowledge，patterns 先预览；可省略编号由系统自动分配), 确认沉淀(用户确认后执行), 摘要(添加会话摘要), 置顶/取消置顶(content 为条目 ID 或内容), 整理(归档过期记忆), 查询(按关键词/日期范围搜索历史会话摘要), 修复/验证/复发(问题状态 open→fixed→verified，复发时自动创建回归条目；content 为说明), 初始化(创建 .cunzhi-knowledge/ 模板并设置 git 远程), 检查(知识库体检：结构、编号、重复条目、git 远程)")]
     pub action: String,
     #[schemars(description = "项目路径（必需）")]
     pub project_path: String,
@@ -34,6 +35,33 @@ pub struct JiyiRequest {
     )]
     #[serde(default = "default_category")]
     pub category: String,
+    #[schemars(description = "有效天数（可选，记忆操作时使用；0 表示永不过期，默认 note 分类 30 天）")]
+    #[serde(default)]
+    pub ttl_days: Option<i64>,
+    #[schemars(description = "是否置顶（可选，记忆操作时使用；置顶条目永不过期且总是被回忆）")]
+    #[serde(default)]
+    pub pinned: Option<bool>,
+    #[schemars(description = "起始日期 YYYY-MM-DD（可选，查询操作时使用）")]
+    #[serde(default)]
+    pub date_from: Option<String>,
+    #[schemars(description = "结束日期 YYYY-MM-DD（可选，查询操作时使用）")]
+    #[serde(default)]
+    pub date_to: Option<String>,
+    #[schemars(description = "返回条数上限（可选，查询操作时使用，默认 10）")]
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[schemars(description = "问题编号 P-YYYY-NNN（修复/验证/复发操作时使用，省略时从 content 中提取）")]
+    #[serde(default)]
+    pub entry_id: Option<String>,
+    #[schemars(description = "修复提交哈希（可选，修复操作时使用）")]
+    #[serde(default)]
+    pub commit: Option<String>,
+    #[schemars(description = "共享知识库仓库地址（可选，初始化操作时使用；知识库不存在时从此 clone，省略时使用配置中的 knowledge_repo_url）")]
+    #[serde(default)]
+    pub repo_url: Option<String>,
+    #[schemars(description = "git 远程地址（可选，初始化操作时设置为 origin）")]
+    #[serde(default)]
+    pub remote_url: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -54,29 +82,100 @@ pub struct XiRequest {
     pub query: String,
     #[schemars(description = "项目路径（必需）")]
     pub project_path: String,
+    #[schemars(description = "返回条数上限（可选，默认 5）")]
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[schemars(description = "条目编号（可选，如 P-2024-001，传入时返回该条目完整内容）")]
+    #[serde(default)]
+    pub id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct HuiRequest {
+    #[schemars(description = "操作类型：搜索(按条件检索对话记录), 查看(按 id 查看完整记录), 重开(按 id 重新弹出该次交互)，默认为搜索")]
+    #[serde(default)]
+    pub action: Option<String>,
+    #[schemars(description = "项目路径（必需，用于定位知识库）")]
+    pub project_path: String,
+    #[schemars(description = "只看某个项目的对话（可选，项目路径或目录名）")]
+    #[serde(default)]
+    pub project: Option<String>,
+    #[schemars(description = "起始日期（可选，YYYY-MM-DD）")]
+    #[serde(default)]
+    pub date_from: Option<String>,
+    #[schemars(description = "结束日期（可选，YYYY-MM-DD）")]
+    #[serde(default)]
+    pub date_to: Option<String>,
+    #[schemars(description = "关键词（可选，空格分隔，全部命中才算匹配）")]
+    #[serde(default)]
+    pub text: Option<String>,
+    #[schemars(description = "用户选择过的选项（可选）")]
+    #[serde(default)]
+    pub option: Option<String>,
+    #[schemars(description = "返回条数上限（可选，默认 10）")]
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[schemars(description = "对话记录 ID（查看/重开时必需，如 2024-05-01#3）")]
+    #[serde(default)]
+    pub id: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct CiRequest {
-    #[schemars(description = "提示词库目录名（如 ci、git、testing），默认为 ci")]
+    #[schemars(description = "提示词库目录名（如 ci、git、testing），* 表示整个提示词库")]
     pub directory: String,
     #[schemars(description = "项目路径（必需）")]
     pub project_path: String,
-    #[schemars(description = "搜索关键词（可选，用于过滤模板）")]
+    #[schemars(description = "搜索关键词（可选，按标签、标题、描述排序）")]
     #[serde(default)]
     pub query: Option<String>,
+    #[schemars(description = "模式：search(搜索模板，默认) 或 render(渲染模板)")]
+    #[serde(default)]
+    pub mode: Option<String>,
+    #[schemars(description = "要渲染的模板（render 模式必需，相对目录的路径或模板标题）")]
+    #[serde(default)]
+    pub template: Option<String>,
+    #[schemars(description = "模板变量（render 模式使用，如 {\"branch\": \"main\"}）")]
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    #[schemars(description = "返回条数上限（可选，默认 5）")]
+    #[serde(default)]
+    pub limit: Option<usize>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct PaiRequest {
-    #[schemars(description = "任务类型（如：补录回归检查、批量重命名、代码审查）")]
+    #[schemars(description = "操作类型：派发(默认，切分批次并生成子代理提示词), 完成(汇报批次完成，需 job_id 与 batch), 进度(查询剩余批次，省略 job_id 时列出未完成任务), 执行(用配置的命令行代理在后台逐批执行；可传 job_id 续做或 batch 指定批次), 验收(检查 target_file 中新增条目的数量、重复和格式，结果通过寸止窗口汇报)")]
+    #[serde(default)]
+    pub action: Option<String>,
+    #[schemars(description = "项目路径（可选，任务文件保存在项目 .cunzhi-memory/dispatch/ 下）")]
+    #[serde(default)]
+    pub project_path: Option<String>,
+    #[schemars(description = "任务类型（如：补录回归检查、批量重命名、代码审查；派发时必需）")]
+    #[serde(default)]
     pub task_type: String,
-    #[schemars(description = "任务范围列表（显式列表，不用模糊表述）")]
+    #[schemars(description = "任务范围列表（显式列表，不用模糊表述；派发时必需）")]
+    #[serde(default)]
     pub items: Vec<String>,
+    #[schemars(description = "每批条目数（可选，默认使用配置 pai_batch_size 或 10）")]
+    #[serde(default)]
+    pub batch_size: Option<usize>,
+    #[schemars(description = "任务 ID（完成/进度/执行/验收操作时使用）")]
+    #[serde(default)]
+    pub job_id: Option<String>,
+    #[schemars(description = "批次号，从 1 开始（完成操作时必需，执行/验收操作时可选）")]
+    #[serde(default)]
+    pub batch: Option<usize>,
+    #[schemars(description = "处理失败的条目（可选，完成操作时使用，其余条目视为完成）")]
+    #[serde(default)]
+    pub failed_items: Vec<String>,
+    #[schemars(description = "批次说明（可选，完成操作时使用）")]
+    #[serde(default)]
+    pub note: Option<String>,
     #[schemars(description = "源文件路径（可选）")]
     #[serde(default)]
     pub source_file: Option<String>,
-    #[schemars(description = "目标文件路径（可选）")]
+    #[schemars(description = "目标文件路径（可选，相对路径按项目路径解析；验收时可覆盖派发时的目标文件）")]
     #[serde(default)]
     pub target_file: Option<String>,
     #[schemars(description = "输出格式模板（可选，用于指定子代理输出格式）")]
@@ -85,6 +184,12 @@ pub struct PaiRequest {
     #[schemars(description = "额外步骤说明（可选）")]
     #[serde(default)]
     pub extra_steps: Option<String>,
+    #[schemars(description = "验收标准列表（可选，默认：条目数量正确、格式符合规范、无重复条目）")]
+    #[serde(default)]
+    pub acceptance_criteria: Vec<String>,
+    #[schemars(description = "每个新增条目必须匹配的正则（可选，验收操作时使用）")]
+    #[serde(default)]
+    pub entry_pattern: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -111,7 +216,7 @@ pub struct McpResponse {
     pub metadata: ResponseMetadata,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageAttachment {
     pub data: String,
     pub media_type: String,
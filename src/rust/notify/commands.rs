@@ -0,0 +1,86 @@
+use crate::config::{save_config, AppState, NotificationConfig};
+use crate::telegram::TelegramCore;
+use tauri::{AppHandle, State};
+
+use super::{Notification, NotificationChannel, Notifier};
+
+/// 获取通知渠道配置
+#[tauri::command]
+pub async fn get_notification_config(state: State<'_, AppState>) -> Result<NotificationConfig, String> {
+    let config = state
+        .config
+        .lock()
+        .map_err(|e| format!("获取配置失败: {}", e))?;
+    Ok(config.notification_config.clone())
+}
+
+/// 设置通知渠道配置
+#[tauri::command]
+pub async fn set_notification_config(
+    notification_config: NotificationConfig,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<(), String> {
+    {
+        let mut config = state
+            .config
+            .lock()
+            .map_err(|e| format!("获取配置失败: {}", e))?;
+        config.notification_config = notification_config;
+    }
+
+    // 保存配置到文件
+    save_config(&state, &app)
+        .await
+        .map_err(|e| format!("保存配置失败: {}", e))?;
+
+    Ok(())
+}
+
+/// 向所有已启用的通知渠道（以及已启用的 Telegram）发送测试通知，返回各渠道结果
+#[tauri::command]
+pub async fn test_notification_channels(
+    notification_config: NotificationConfig,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let telegram_config = {
+        let config = state
+            .config
+            .lock()
+            .map_err(|e| format!("获取配置失败: {}", e))?;
+        config.telegram_config.clone()
+    };
+
+    let notification = Notification {
+        title: "寸止：测试通知".to_string(),
+        message: "收到这条消息说明通知渠道配置正确".to_string(),
+        project_path: None,
+        options: Vec::new(),
+    };
+
+    let mut lines = Vec::new();
+    if telegram_config.enabled {
+        let result = match TelegramCore::from_config(&telegram_config, None).await {
+            Ok(core) => core.notify(&notification).await,
+            Err(e) => Err(e),
+        };
+        lines.push(format_result("Telegram", &result));
+    }
+
+    let notifiers = Notifier::from_config(&notification_config);
+    for (name, result) in super::notify_all(&notifiers, &notification).await {
+        lines.push(format_result(&name, &result));
+    }
+
+    if lines.is_empty() {
+        return Err("没有已启用且配置完整的通知渠道".to_string());
+    }
+    Ok(lines.join("\n"))
+}
+
+fn format_result(name: &str, result: &anyhow::Result<()>) -> String {
+    match result {
+        Ok(()) => format!("✅ {}", name),
+        Err(e) => format!("❌ {}: {}", name, e),
+    }
+}
@@ -0,0 +1,175 @@
+//! SMTP 邮件通知
+
+use anyhow::{anyhow, Result};
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::time::Duration;
+
+use super::{Notification, NotificationChannel};
+use crate::config::{NotificationChannelConfig, SmtpSecurity};
+use crate::constants::notify::REQUEST_TIMEOUT_SECS;
+
+/// 邮件通知
+#[derive(Debug, Clone)]
+pub struct EmailNotifier {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: String,
+    pub password: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+impl EmailNotifier {
+    /// 从配置创建；缺少 SMTP 服务器或收件人时返回 None，未填发件人时使用用户名
+    pub fn from_config(config: &NotificationChannelConfig) -> Option<Self> {
+        let host = config.smtp_host.trim();
+        let to: Vec<String> = config
+            .to
+            .iter()
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect();
+        let from = Some(config.from.trim())
+            .filter(|f| !f.is_empty())
+            .unwrap_or(config.username.trim());
+        if host.is_empty() || to.is_empty() || from.is_empty() {
+            return None;
+        }
+
+        let port = config.smtp_port.unwrap_or(match config.smtp_security {
+            SmtpSecurity::StartTls => 587,
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::None => 25,
+        });
+        Some(Self {
+            host: host.to_string(),
+            port,
+            security: config.smtp_security,
+            username: config.username.trim().to_string(),
+            password: config.password.clone(),
+            from: from.to_string(),
+            to,
+        })
+    }
+
+    fn build_message(&self, notification: &Notification) -> Result<Message> {
+        let parse = |address: &str| {
+            address
+                .parse::<Mailbox>()
+                .map_err(|e| anyhow!("邮箱地址无效 {}: {}", address, e))
+        };
+        let mut builder = Message::builder()
+            .from(parse(&self.from)?)
+            .subject(&notification.title)
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            builder = builder.to(parse(to)?);
+        }
+        Ok(builder.body(notification.body())?)
+    }
+
+    fn transport(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>> {
+        let builder = match self.security {
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&self.host)?,
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.host),
+        };
+        let mut builder = builder
+            .port(self.port)
+            .timeout(Some(Duration::from_secs(REQUEST_TIMEOUT_SECS)));
+        if !self.username.is_empty() {
+            builder = builder.credentials(Credentials::new(self.username.clone(), self.password.clone()));
+        }
+        Ok(builder.build())
+    }
+}
+
+impl NotificationChannel for EmailNotifier {
+    fn name(&self) -> String {
+        "邮件".to_string()
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        let message = self.build_message(notification)?;
+        self.transport()?
+            .send(message)
+            .await
+            .map_err(|e| anyhow!("发送邮件失败: {}", e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::NotificationChannelKind;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    #[tokio::test]
+    async fn test_sends_mail_to_stub_smtp_server() {
+        // 最简 SMTP 桩：应答每条命令并记录会话
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut session = Vec::new();
+            let mut in_data = false;
+            writer.write_all(b"220 stub ESMTP\r\n").await.unwrap();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                session.push(line.clone());
+                let reply: &[u8] = if in_data {
+                    if line != "." {
+                        continue;
+                    }
+                    in_data = false;
+                    b"250 queued\r\n"
+                } else if line.starts_with("EHLO") {
+                    b"250 stub\r\n"
+                } else if line == "DATA" {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                } else if line == "QUIT" {
+                    writer.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    b"250 ok\r\n"
+                };
+                writer.write_all(reply).await.unwrap();
+            }
+            session
+        });
+
+        let config = NotificationChannelConfig {
+            kind: NotificationChannelKind::Email,
+            smtp_host: "127.0.0.1".to_string(),
+            smtp_port: Some(port),
+            smtp_security: SmtpSecurity::None,
+            from: "cunzhi <bot@example.com>".to_string(),
+            to: vec!["dev@example.com".to_string(), " ".to_string()],
+            ..Default::default()
+        };
+        let notifier = EmailNotifier::from_config(&config).unwrap();
+        assert_eq!(notifier.to, vec!["dev@example.com"]);
+
+        let notification = Notification {
+            title: "build finished".to_string(),
+            message: "ready for review".to_string(),
+            project_path: None,
+            options: Vec::new(),
+        };
+        notifier.notify(&notification).await.unwrap();
+
+        let session = server.await.unwrap().join("\n");
+        assert!(session.contains("MAIL FROM:<bot@example.com>"));
+        assert!(session.contains("RCPT TO:<dev@example.com>"));
+        assert!(session.contains("Subject: build finished"));
+        assert!(session.contains("ready for review"));
+
+        assert!(EmailNotifier::from_config(&NotificationChannelConfig { to: Vec::new(), ..config }).is_none());
+    }
+}
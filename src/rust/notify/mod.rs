@@ -0,0 +1,302 @@
+//! 通知渠道
+//!
+//! 有新的 zhi 请求时提醒用户回到弹窗或 Telegram 作答。Telegram 既能通知也能收回复，
+//! 其余渠道只负责通知：通用 Webhook（Slack/Discord/飞书兼容）、ntfy/Gotify 推送和 SMTP 邮件。
+//! 通知内容发往第三方服务，发送前按对话日志的脱敏设置处理
+
+pub mod commands;
+pub mod email;
+pub mod push;
+pub mod webhook;
+
+use anyhow::Result;
+use serde::Serialize;
+use std::future::Future;
+use std::path::Path;
+
+pub use email::EmailNotifier;
+pub use push::{PushNotifier, PushService};
+pub use webhook::WebhookNotifier;
+
+use crate::config::{load_standalone_config, NotificationChannelConfig, NotificationChannelKind, NotificationConfig};
+use crate::constants::notify::MAX_MESSAGE_CHARS;
+use crate::log_important;
+use crate::mcp::tools::interaction::redact::Redactor;
+use crate::mcp::types::PopupRequest;
+use crate::mcp::utils::decode_and_normalize_path;
+
+/// 一条通知
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub title: String,
+    pub message: String,
+    pub project_path: Option<String>,
+    pub options: Vec<String>,
+}
+
+impl Notification {
+    /// 由 zhi 请求生成提醒：正文和选项先脱敏，正文过长时截断
+    pub fn from_request(request: &PopupRequest, redactor: &Redactor) -> Self {
+        let project_path = request
+            .project_path
+            .as_deref()
+            .map(|p| decode_and_normalize_path(p).unwrap_or_else(|_| p.to_string()));
+        let project_name = project_path
+            .as_deref()
+            .and_then(|p| Path::new(p).file_name())
+            .map(|n| n.to_string_lossy().to_string());
+        let title = match project_name {
+            Some(name) => format!("寸止：{} 等待回复", name),
+            None => "寸止：有新的请求等待回复".to_string(),
+        };
+
+        let redacted = redactor.redact(&request.message);
+        let mut message: String = redacted.chars().take(MAX_MESSAGE_CHARS).collect();
+        if redacted.chars().count() > MAX_MESSAGE_CHARS {
+            message.push('…');
+        }
+
+        Self {
+            title,
+            message,
+            project_path,
+            options: request.predefined_options.iter().flatten().map(|o| redactor.redact(o)).collect(),
+        }
+    }
+
+    /// 纯文本正文：消息、可选项和项目路径
+    pub fn body(&self) -> String {
+        let mut body = self.message.trim().to_string();
+        if !self.options.is_empty() {
+            body.push_str("\n\n可选项：");
+            for option in &self.options {
+                body.push_str("\n- ");
+                body.push_str(option);
+            }
+        }
+        if let Some(path) = &self.project_path {
+            body.push_str("\n\n项目：");
+            body.push_str(path);
+        }
+        body
+    }
+}
+
+/// 通知渠道
+pub trait NotificationChannel {
+    /// 渠道名称，用于日志和测试结果
+    fn name(&self) -> String;
+
+    /// 发送一条通知
+    fn notify(&self, notification: &Notification) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// 按配置创建的只通知渠道
+#[derive(Debug, Clone)]
+pub enum Notifier {
+    Webhook(WebhookNotifier),
+    Push(PushNotifier),
+    Email(EmailNotifier),
+}
+
+impl Notifier {
+    /// 从渠道配置创建；缺少必填项时返回 None
+    pub fn from_channel_config(config: &NotificationChannelConfig) -> Option<Self> {
+        match config.kind {
+            NotificationChannelKind::Webhook => WebhookNotifier::from_config(config).map(Self::Webhook),
+            NotificationChannelKind::Ntfy => PushNotifier::from_config(PushService::Ntfy, config).map(Self::Push),
+            NotificationChannelKind::Gotify => PushNotifier::from_config(PushService::Gotify, config).map(Self::Push),
+            NotificationChannelKind::Email => EmailNotifier::from_config(config).map(Self::Email),
+        }
+    }
+
+    /// 所有已启用且配置完整的渠道
+    pub fn from_config(config: &NotificationConfig) -> Vec<Self> {
+        if !config.enabled {
+            return Vec::new();
+        }
+        config
+            .channels
+            .iter()
+            .filter(|c| c.enabled)
+            .filter_map(|c| {
+                let notifier = Self::from_channel_config(c);
+                if notifier.is_none() {
+                    log_important!(warn, "通知渠道配置不完整，已跳过: {:?}", c.kind);
+                }
+                notifier
+            })
+            .collect()
+    }
+}
+
+impl NotificationChannel for Notifier {
+    fn name(&self) -> String {
+        match self {
+            Self::Webhook(notifier) => notifier.name(),
+            Self::Push(notifier) => notifier.name(),
+            Self::Email(notifier) => notifier.name(),
+        }
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        match self {
+            Self::Webhook(notifier) => notifier.notify(notification).await,
+            Self::Push(notifier) => notifier.notify(notification).await,
+            Self::Email(notifier) => notifier.notify(notification).await,
+        }
+    }
+}
+
+/// 并发发送到所有渠道，返回每个渠道的结果
+pub async fn notify_all(notifiers: &[Notifier], notification: &Notification) -> Vec<(String, Result<()>)> {
+    let sends = notifiers.iter().map(|notifier| async move {
+        (notifier.name(), notifier.notify(notification).await)
+    });
+    futures::future::join_all(sends).await
+}
+
+/// 在后台线程中提醒用户有新的 zhi 请求，不阻塞弹窗或 Telegram 渠道
+pub fn spawn_request_notification(request_file: &str) {
+    let (notifiers, redactor) = match load_standalone_config() {
+        Ok(config) => (
+            Notifier::from_config(&config.notification_config),
+            Redactor::from_config(&config.conversation_config),
+        ),
+        Err(e) => {
+            log_important!(warn, "加载通知配置失败: {}", e);
+            return;
+        }
+    };
+    if notifiers.is_empty() {
+        return;
+    }
+
+    let request = match std::fs::read_to_string(request_file)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Ok(serde_json::from_str::<PopupRequest>(&json)?))
+    {
+        Ok(request) => request,
+        Err(e) => {
+            log_important!(warn, "读取请求失败，跳过通知: {}", e);
+            return;
+        }
+    };
+
+    let notification = Notification::from_request(&request, &redactor);
+    std::thread::spawn(move || {
+        let runtime = match tokio::runtime::Runtime::new() {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                log_important!(warn, "创建通知运行时失败: {}", e);
+                return;
+            }
+        };
+        for (name, result) in runtime.block_on(notify_all(&notifiers, &notification)) {
+            if let Err(e) = result {
+                log_important!(warn, "{}通知发送失败: {}", name, e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WebhookFormat;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// 本地 HTTP 桩：依次接收请求并返回 200，结束后返回收到的原始请求
+    async fn stub_server(count: usize) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for _ in 0..count {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut received = Vec::new();
+                let mut buf = [0u8; 4096];
+                loop {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    received.extend_from_slice(&buf[..n]);
+                    let Some(head_end) = received.windows(4).position(|w| w == b"\r\n\r\n") else {
+                        if n == 0 {
+                            break;
+                        }
+                        continue;
+                    };
+                    let length = String::from_utf8_lossy(&received[..head_end])
+                        .lines()
+                        .find_map(|l| l.to_lowercase().strip_prefix("content-length:").and_then(|v| v.trim().parse::<usize>().ok()))
+                        .unwrap_or(0);
+                    if n == 0 || received.len() >= head_end + 4 + length {
+                        break;
+                    }
+                }
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                    .await
+                    .unwrap();
+                requests.push(String::from_utf8_lossy(&received).to_string());
+            }
+            requests
+        });
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn test_http_channels_against_stub_server() {
+        let request = PopupRequest {
+            id: "1".to_string(),
+            message: "要提交吗？token=abcd1234efgh".to_string(),
+            predefined_options: Some(vec!["提交".to_string()]),
+            is_markdown: true,
+            project_path: Some("/work/app".to_string()),
+            link_url: None,
+            link_title: None,
+            browser_ai_response: None,
+        };
+        let redactor = Redactor::from_config(&crate::config::default_conversation_config());
+        let notification = Notification::from_request(&request, &redactor);
+        assert_eq!(notification.title, "寸止：app 等待回复");
+        assert_eq!(notification.body(), "要提交吗？[已脱敏:Token]\n\n可选项：\n- 提交\n\n项目：/work/app");
+
+        let (url, server) = stub_server(3).await;
+        let channel = |kind, format| NotificationChannelConfig {
+            kind,
+            enabled: true,
+            url: url.clone(),
+            webhook_format: format,
+            topic: "cunzhi".to_string(),
+            token: "secret".to_string(),
+            priority: Some(4),
+            ..Default::default()
+        };
+        let config = NotificationConfig {
+            enabled: true,
+            channels: vec![
+                channel(NotificationChannelKind::Webhook, WebhookFormat::Lark),
+                channel(NotificationChannelKind::Ntfy, WebhookFormat::default()),
+                channel(NotificationChannelKind::Gotify, WebhookFormat::default()),
+                // 缺少 SMTP 服务器和收件人，跳过
+                channel(NotificationChannelKind::Email, WebhookFormat::default()),
+            ],
+        };
+
+        // 按顺序发送，便于桩服务器逐个检查
+        let notifiers = Notifier::from_config(&config);
+        assert_eq!(notifiers.len(), 3);
+        for notifier in &notifiers {
+            notifier.notify(&notification).await.unwrap();
+        }
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].contains(r#""msg_type":"text""#) && requests[0].contains("寸止：app 等待回复\\n要提交吗？[已脱敏:Token]"));
+        assert!(requests[1].to_lowercase().contains("authorization: bearer secret"));
+        assert!(requests[1].contains(r#""topic":"cunzhi""#) && requests[1].contains(r#""priority":4"#));
+        assert!(requests[2].starts_with("POST /message ") && requests[2].to_lowercase().contains("x-gotify-key: secret"));
+        assert!(requests[2].contains("text/markdown"));
+
+        assert!(Notifier::from_config(&NotificationConfig { enabled: false, ..config }).is_empty());
+    }
+}
@@ -0,0 +1,110 @@
+//! ntfy / Gotify 推送
+//!
+//! ntfy 使用 JSON 发布接口（`POST /`，主题写在请求体中），Gotify 使用 `POST /message` 和应用令牌
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::webhook::check_response;
+use super::{Notification, NotificationChannel};
+use crate::config::NotificationChannelConfig;
+use crate::constants::notify::REQUEST_TIMEOUT_SECS;
+
+/// 推送服务类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushService {
+    Ntfy,
+    Gotify,
+}
+
+/// ntfy / Gotify 推送通知
+#[derive(Debug, Clone)]
+pub struct PushNotifier {
+    pub service: PushService,
+    pub server_url: String,
+    pub topic: String,
+    pub token: String,
+    pub priority: Option<u8>,
+}
+
+impl PushNotifier {
+    /// 从配置创建；缺少服务器地址、ntfy 主题或 Gotify 令牌时返回 None
+    pub fn from_config(service: PushService, config: &NotificationChannelConfig) -> Option<Self> {
+        let notifier = Self {
+            service,
+            server_url: config.url.trim().trim_end_matches('/').to_string(),
+            topic: config.topic.trim().to_string(),
+            token: config.token.trim().to_string(),
+            priority: config.priority,
+        };
+        let required = match service {
+            PushService::Ntfy => &notifier.topic,
+            PushService::Gotify => &notifier.token,
+        };
+        (!notifier.server_url.is_empty() && !required.is_empty()).then_some(notifier)
+    }
+
+    /// 按服务生成请求体
+    pub fn payload(&self, notification: &Notification) -> Value {
+        match self.service {
+            PushService::Ntfy => {
+                let mut payload = json!({
+                    "topic": self.topic,
+                    "title": notification.title,
+                    "message": notification.body(),
+                    "markdown": true,
+                });
+                if let Some(priority) = self.priority {
+                    payload["priority"] = json!(priority.clamp(1, 5));
+                }
+                payload
+            }
+            PushService::Gotify => {
+                let mut payload = json!({
+                    "title": notification.title,
+                    "message": notification.body(),
+                    "extras": { "client::display": { "contentType": "text/markdown" } },
+                });
+                if let Some(priority) = self.priority {
+                    payload["priority"] = json!(priority.min(10));
+                }
+                payload
+            }
+        }
+    }
+}
+
+impl NotificationChannel for PushNotifier {
+    fn name(&self) -> String {
+        match self.service {
+            PushService::Ntfy => "ntfy".to_string(),
+            PushService::Gotify => "Gotify".to_string(),
+        }
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+            .build()?;
+        let request = match self.service {
+            PushService::Ntfy => {
+                let request = client.post(&self.server_url);
+                if self.token.is_empty() {
+                    request
+                } else {
+                    request.bearer_auth(&self.token)
+                }
+            }
+            PushService::Gotify => client
+                .post(format!("{}/message", self.server_url))
+                .header("X-Gotify-Key", &self.token),
+        };
+        let response = request
+            .json(&self.payload(notification))
+            .send()
+            .await
+            .map_err(|e| anyhow!("请求{}失败: {}", self.name(), e))?;
+        check_response(response).await
+    }
+}
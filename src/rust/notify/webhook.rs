@@ -0,0 +1,78 @@
+//! 通用 Webhook 通知
+//!
+//! 兼容 Slack、Discord、飞书（Lark）的 incoming webhook，或直接 POST 通知的 JSON
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::{Notification, NotificationChannel};
+use crate::config::{NotificationChannelConfig, WebhookFormat};
+use crate::constants::notify::{DISCORD_MAX_CONTENT_CHARS, REQUEST_TIMEOUT_SECS};
+
+/// Webhook 通知
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+    pub url: String,
+    pub format: WebhookFormat,
+}
+
+impl WebhookNotifier {
+    /// 从配置创建；未填写地址时返回 None
+    pub fn from_config(config: &NotificationChannelConfig) -> Option<Self> {
+        let url = config.url.trim();
+        (!url.is_empty()).then(|| Self { url: url.to_string(), format: config.webhook_format })
+    }
+
+    /// 按格式生成请求体
+    pub fn payload(&self, notification: &Notification) -> Value {
+        let body = notification.body();
+        match self.format {
+            WebhookFormat::Json => json!(notification),
+            WebhookFormat::Slack => json!({ "text": format!("*{}*\n{}", notification.title, body) }),
+            WebhookFormat::Discord => {
+                let content = format!("**{}**\n{}", notification.title, body);
+                json!({ "content": content.chars().take(DISCORD_MAX_CONTENT_CHARS).collect::<String>() })
+            }
+            WebhookFormat::Lark => json!({
+                "msg_type": "text",
+                "content": { "text": format!("{}\n{}", notification.title, body) },
+            }),
+        }
+    }
+}
+
+impl NotificationChannel for WebhookNotifier {
+    fn name(&self) -> String {
+        let format = match self.format {
+            WebhookFormat::Json => "JSON",
+            WebhookFormat::Slack => "Slack",
+            WebhookFormat::Discord => "Discord",
+            WebhookFormat::Lark => "飞书",
+        };
+        format!("Webhook（{}）", format)
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+            .build()?;
+        let response = client
+            .post(&self.url)
+            .json(&self.payload(notification))
+            .send()
+            .await
+            .map_err(|e| anyhow!("请求Webhook失败: {}", e))?;
+        check_response(response).await
+    }
+}
+
+/// 非 2xx 响应时带上响应内容开头作为错误信息
+pub(super) async fn check_response(response: reqwest::Response) -> Result<()> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(anyhow!("返回 {}: {}", status, body.chars().take(200).collect::<String>()))
+}
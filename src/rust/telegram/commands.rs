@@ -1,7 +1,12 @@
+use crate::app::{ResponseChannel, ResponseCoordinator};
 use crate::config::{save_config, AppState, TelegramConfig};
 use crate::constants::telegram as telegram_constants;
+use crate::mcp::utils::decode_and_normalize_path;
+use crate::telegram::media::{download_message_file, ReceivedFile};
+use crate::telegram::speech::{SpeechBackend, SpeechToText};
 use crate::telegram::{
-    handle_callback_query, handle_text_message, TelegramCore,
+    handle_callback_query, handle_text_message, Registration, Subscription, TelegramCore,
+    TelegramEvent, UpdateDispatcher,
 };
 use crate::log_important;
 use tauri::{AppHandle, Emitter, Manager, State};
@@ -106,6 +111,8 @@ pub async fn auto_get_chat_id(
                         if let teloxide::types::UpdateKind::Message(message) = update.kind {
                             let chat_id = message.chat.id.0.to_string();
                             let chat_title = message.chat.title().unwrap_or("私聊").to_string();
+                            let message_thread_id = message.thread_id.map(|id| id.0 .0);
+                            let user_id = message.from.as_ref().map(|u| u.id.0);
                             let username = message.from.as_ref()
                                 .and_then(|u| u.username.as_ref())
                                 .map(|s| s.as_str())
@@ -115,6 +122,8 @@ pub async fn auto_get_chat_id(
                             let chat_info = serde_json::json!({
                                 "chat_id": chat_id,
                                 "chat_title": chat_title,
+                                "message_thread_id": message_thread_id,
+                                "user_id": user_id,
                                 "username": username,
                                 "message_text": message.text().unwrap_or(""),
                             });
@@ -178,76 +187,82 @@ pub async fn start_telegram_sync(
     message: String,
     predefined_options: Vec<String>,
     is_markdown: bool,
+    project_path: Option<String>,
     state: State<'_, AppState>,
     app_handle: AppHandle,
 ) -> Result<(), String> {
     // 获取Telegram配置
-    let (enabled, bot_token, chat_id, continue_reply_enabled) = {
+    let (telegram_config, continue_reply_enabled) = {
         let config = state
             .config
             .lock()
             .map_err(|e| format!("获取配置失败: {}", e))?;
         (
-            config.telegram_config.enabled,
-            config.telegram_config.bot_token.clone(),
-            config.telegram_config.chat_id.clone(),
+            config.telegram_config.clone(),
             config.reply_config.enable_continue_reply,
         )
     };
 
-    if !enabled {
+    if !telegram_config.enabled {
         return Ok(());
     }
 
-    if bot_token.trim().is_empty() || chat_id.trim().is_empty() {
+    // MCP请求的Telegram渠道已由回复协调器处理，弹窗不再重复发送
+    if ResponseCoordinator::global().is_some_and(|c| c.has_channel(ResponseChannel::Telegram)) {
+        return Ok(());
+    }
+
+    if telegram_config.bot_token.trim().is_empty() || telegram_config.chat_id.trim().is_empty() {
         return Err("Telegram配置不完整".to_string());
     }
 
-    // 获取API URL配置
-    let api_url = {
-        let config = state
-            .config
-            .lock()
-            .map_err(|e| format!("获取配置失败: {}", e))?;
-        config.telegram_config.api_base_url.clone()
-    };
+    // 按项目路径选择目标聊天并创建Telegram核心实例
+    let core = TelegramCore::from_config(&telegram_config, project_path.as_deref())
+        .await
+        .map_err(|e| format!("创建Telegram核心失败: {}", e))?;
 
-    // 使用默认API URL时传递None，否则传递自定义URL
-    let api_url_option = if api_url == telegram_constants::API_BASE_URL {
-        None
-    } else {
-        Some(api_url)
-    };
+    // 先注册到共享的更新分发器，再发送消息
+    let dispatcher = UpdateDispatcher::shared(&core.bot, &telegram_config)
+        .map_err(|e| format!("启动Telegram更新分发失败: {}", e))?;
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let subscription = dispatcher.subscribe(Registration::for_core(&core, &request_id));
 
-    // 创建Telegram核心实例
-    let core = TelegramCore::new_with_api_url(bot_token.clone(), chat_id.clone(), api_url_option)
-        .map_err(|e| format!("创建Telegram核心失败: {}", e))?;
+    // 先转发消息中引用的图片，选项按钮留在最后
+    if is_markdown {
+        let base_dir = project_path.as_deref().and_then(|p| decode_and_normalize_path(p).ok());
+        core.send_markdown_images(&message, base_dir.as_deref().map(std::path::Path::new)).await;
+    }
 
     // 发送选项消息
-    core.send_options_message(&message, &predefined_options, is_markdown)
+    let options_message_id = core
+        .send_options_message(&message, &predefined_options, is_markdown)
         .await
         .map_err(|e| format!("发送选项消息失败: {}", e))?;
+    subscription.add_message_id(options_message_id);
 
     // 短暂延迟确保消息顺序
     tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
 
     // 发送操作消息
-    core.send_operation_message(continue_reply_enabled)
+    let operation_message_id = core
+        .send_operation_message(continue_reply_enabled)
         .await
         .map_err(|e| format!("发送操作消息失败: {}", e))?;
+    subscription.add_message_id(operation_message_id);
 
     // 启动消息监听（根据是否有预定义选项选择监听模式）
-    let bot_token_clone = bot_token.clone();
-    let chat_id_clone = chat_id.clone();
     let app_handle_clone = app_handle.clone();
+    let speech = SpeechBackend::from_config(&telegram_config.speech_to_text);
 
     tokio::spawn(async move {
         // 使用统一的监听器，传递选项参数
         match start_telegram_listener(
-            bot_token_clone,
-            chat_id_clone,
+            core,
+            subscription,
             app_handle_clone,
             predefined_options,
+            options_message_id,
+            speech,
         )
         .await
         {
@@ -261,187 +276,229 @@ pub async fn start_telegram_sync(
 
 /// 启动Telegram消息监听（统一版本，支持有选项和无选项模式）
 async fn start_telegram_listener(
-    bot_token: String,
-    chat_id: String,
+    core: TelegramCore,
+    mut subscription: Subscription,
     app_handle: AppHandle,
     predefined_options_list: Vec<String>,
+    options_message_id: i32,
+    speech: Option<SpeechBackend>,
 ) -> Result<(), String> {
-    // 从AppHandle获取应用状态来读取API URL配置
-    let api_url = match app_handle.try_state::<AppState>() {
-        Some(state) => {
-            let config = state
-                .config
-                .lock()
-                .map_err(|e| format!("获取配置失败: {}", e))?;
-            let api_url = config.telegram_config.api_base_url.clone();
-                         if api_url == telegram_constants::API_BASE_URL {
-                None
-            } else {
-                Some(api_url)
-            }
-        }
-        None => None, // 如果无法获取状态，使用默认API
-    };
-
-    let core = TelegramCore::new_with_api_url(bot_token, chat_id, api_url)
-        .map_err(|e| format!("创建Telegram核心失败: {}", e))?;
-
-    let mut offset = 0i32;
-
     // 用于跟踪选项状态和消息ID
     let mut selected_options: std::collections::HashSet<String> = std::collections::HashSet::new();
-    let mut options_message_id: Option<i32> = None;
-    let mut user_input: String = String::new(); // 存储用户输入的文本
+    let mut options_message_id: Option<i32> = Some(options_message_id).filter(|id| *id > 0);
+    let mut user_input: String = String::new(); // 存储用户输入的文本（文字消息与附加内容合并后）
+    let mut typed_input = String::new(); // 最近一条文字消息
+    let mut attached_input: Vec<String> = Vec::new(); // 说明文字、语音转写和文件路径
     let predefined_options = predefined_options_list;
     let has_options = !predefined_options.is_empty(); // 是否有预定义选项
 
-    // 获取当前最新的消息ID作为基准
-    if let Ok(updates) = core.bot.get_updates().limit(10).await {
-        if let Some(update) = updates.last() {
-            offset = update.id.0 as i32 + 1;
-        }
-    }
-
-    // 监听循环
-    loop {
-        match core.bot.get_updates().offset(offset).timeout(10).await {
-            Ok(updates) => {
-                for update in updates {
-                    offset = update.id.0 as i32 + 1;
-
-                    match update.kind {
-                        teloxide::types::UpdateKind::CallbackQuery(callback_query) => {
-                            // 只有当有预定义选项时才处理 callback queries
-                            if has_options {
-                                // 从callback_query中提取消息ID
-                                if let Some(message) = &callback_query.message {
-                                    if options_message_id.is_none() {
-                                        options_message_id = Some(message.id().0);
-                                    }
-                                }
-
-                                if let Ok(Some(option)) =
-                                    handle_callback_query(&core.bot, &callback_query, core.chat_id)
-                                        .await
-                                {
-                                    // 切换选项状态
-                                    let selected = if selected_options.contains(&option) {
-                                        selected_options.remove(&option);
-                                        false
-                                    } else {
-                                        selected_options.insert(option.clone());
-                                        true
-                                    };
-
-                                    // 发送事件到前端
-                                    use crate::telegram::TelegramEvent;
-                                    let event = TelegramEvent::OptionToggled {
-                                        option: option.clone(),
-                                        selected,
-                                    };
-
-                                    let _ = app_handle.emit("telegram-event", &event);
-
-                                    // 更新按钮状态
-                                    if let Some(msg_id) = options_message_id {
-                                        let selected_vec: Vec<String> =
-                                            selected_options.iter().cloned().collect();
-                                        if let Ok(_) = core
-                                            .update_inline_keyboard(
-                                                msg_id,
-                                                &predefined_options,
-                                                &selected_vec,
-                                            )
-                                            .await {}
-                                    }
-                                }
-                            }
+    // 监听分发器路由给本请求的更新
+    while let Some(update) = subscription.recv().await {
+        match update.kind {
+            teloxide::types::UpdateKind::CallbackQuery(callback_query) => {
+                // 只有当有预定义选项时才处理 callback queries
+                if has_options {
+                    // 从callback_query中提取消息ID
+                    if let Some(message) = &callback_query.message {
+                        if options_message_id.is_none() {
+                            options_message_id = Some(message.id().0);
                         }
-                        teloxide::types::UpdateKind::Message(message) => {
-                            // 只有当有预定义选项时才检查 inline keyboard
-                            if has_options {
-                                // 检查是否是包含 inline keyboard 的选项消息
-                                if let Some(inline_keyboard) = message.reply_markup() {
-                                    // 检查是否包含我们的选项按钮
-                                    let mut contains_our_options = false;
-                                    for row in &inline_keyboard.inline_keyboard {
-                                        for button in row {
-                                            if let teloxide::types::InlineKeyboardButtonKind::CallbackData(callback_data) = &button.kind {
-                                                if callback_data.starts_with("toggle:") {
-                                                    contains_our_options = true;
-                                                    break;
-                                                }
-                                            }
-                                        }
-                                        if contains_our_options {
-                                            break;
-                                        }
-                                    }
+                    }
 
-                                    if contains_our_options {
-                                        options_message_id = Some(message.id.0);
+                    if let Ok(Some(option)) =
+                        handle_callback_query(&core, &callback_query).await
+                    {
+                        // 切换选项状态
+                        let selected = if selected_options.contains(&option) {
+                            selected_options.remove(&option);
+                            false
+                        } else {
+                            selected_options.insert(option.clone());
+                            true
+                        };
+
+                        // 发送事件到前端
+                        let event = TelegramEvent::OptionToggled {
+                            option: option.clone(),
+                            selected,
+                        };
+
+                        let _ = app_handle.emit("telegram-event", &event);
+
+                        // 更新按钮状态
+                        if let Some(msg_id) = options_message_id {
+                            let selected_vec: Vec<String> =
+                                selected_options.iter().cloned().collect();
+                            if let Ok(_) = core
+                                .update_inline_keyboard(
+                                    msg_id,
+                                    &predefined_options,
+                                    &selected_vec,
+                                )
+                                .await {}
+                        }
+                    }
+                }
+            }
+            teloxide::types::UpdateKind::Message(message) => {
+                // 只有当有预定义选项时才检查 inline keyboard
+                if has_options {
+                    // 检查是否是包含 inline keyboard 的选项消息
+                    if let Some(inline_keyboard) = message.reply_markup() {
+                        // 检查是否包含我们的选项按钮
+                        let mut contains_our_options = false;
+                        for row in &inline_keyboard.inline_keyboard {
+                            for button in row {
+                                if let teloxide::types::InlineKeyboardButtonKind::CallbackData(callback_data) = &button.kind {
+                                    if callback_data.starts_with("toggle:") {
+                                        contains_our_options = true;
+                                        break;
                                     }
                                 }
                             }
+                            if contains_our_options {
+                                break;
+                            }
+                        }
 
-                            if let Ok(Some(event)) = handle_text_message(
-                                &message,
-                                core.chat_id,
-                                None, // 简化版本不过滤消息ID
-                            )
-                            .await
-                            {
-                                // 处理发送和继续按钮，发送反馈消息
-                                match &event {
-                                    crate::telegram::TelegramEvent::SendPressed => {
-                                        let selected_list: Vec<String> =
-                                            selected_options.iter().cloned().collect();
-
-                                        // 使用统一的反馈消息生成函数
-                                        let feedback_message =
-                                            crate::telegram::core::build_feedback_message(
-                                                &selected_list,
-                                                &user_input,
-                                                false, // 不是继续操作
-                                            );
-
-                                        let _ = core.send_message(&feedback_message).await;
-                                    }
-                                    crate::telegram::TelegramEvent::ContinuePressed => {
-                                        // 使用统一的反馈消息生成函数
-                                        let feedback_message =
-                                            crate::telegram::core::build_feedback_message(
-                                                &[],  // 继续操作没有选项
-                                                "",   // 继续操作没有用户输入
-                                                true, // 是继续操作
-                                            );
-
-                                        let _ = core.send_message(&feedback_message).await;
-                                    }
-                                    crate::telegram::TelegramEvent::TextUpdated { text } => {
-                                        // 保存用户输入的文本
-                                        user_input = text.clone();
-                                    }
-                                    _ => {
-                                        // 其他事件不需要发送反馈消息
-                                    }
-                                }
+                        if contains_our_options {
+                            options_message_id = Some(message.id.0);
+                        }
+                    }
+                }
 
+                // 照片、语音和文件：处理后同步到前端
+                if core.accepts_message(&message) {
+                    match download_message_file(&core.bot, &message).await {
+                        Ok(Some(file)) => {
+                            // 说明文字和语音转写单独保存，之后的文字消息不会覆盖它们
+                            if let Some(caption) = message.caption().map(str::trim).filter(|c| !c.is_empty()) {
+                                attached_input.push(caption.to_string());
+                            }
+                            if let Some(event) = receive_file(&core, file, &mut attached_input, speech.as_ref()).await {
+                                let _ = app_handle.emit("telegram-event", &event);
+                            }
+                            let merged = crate::telegram::core::merge_reply_input(&typed_input, &attached_input);
+                            if merged != user_input {
+                                user_input = merged;
+                                let event = TelegramEvent::TextUpdated { text: user_input.clone() };
                                 let _ = app_handle.emit("telegram-event", &event);
                             }
+                            continue;
+                        }
+                        Ok(None) => {}
+                        Err(e) => {
+                            let _ = core.send_message(&format!("❌ 文件接收失败：{}", e)).await;
+                            continue;
+                        }
+                    }
+                }
+
+                if let Ok(Some(event)) = handle_text_message(
+                    &message,
+                    &core,
+                    None, // 简化版本不过滤消息ID
+                )
+                .await
+                {
+                    // 文字消息替换之前的文字，与说明文字、语音转写合并后同步到前端
+                    let event = match event {
+                        TelegramEvent::TextUpdated { text } => {
+                            typed_input = text;
+                            user_input = crate::telegram::core::merge_reply_input(&typed_input, &attached_input);
+                            TelegramEvent::TextUpdated { text: user_input.clone() }
+                        }
+                        other => other,
+                    };
+
+                    // 处理发送和继续按钮，发送反馈消息
+                    match &event {
+                        crate::telegram::TelegramEvent::SendPressed => {
+                            let selected_list: Vec<String> =
+                                selected_options.iter().cloned().collect();
+
+                            // 使用统一的反馈消息生成函数
+                            let feedback_message =
+                                crate::telegram::core::build_feedback_message(
+                                    &selected_list,
+                                    &user_input,
+                                    false, // 不是继续操作
+                                );
+
+                            let _ = core.send_message(&feedback_message).await;
+                        }
+                        crate::telegram::TelegramEvent::ContinuePressed => {
+                            // 使用统一的反馈消息生成函数
+                            let feedback_message =
+                                crate::telegram::core::build_feedback_message(
+                                    &[],  // 继续操作没有选项
+                                    "",   // 继续操作没有用户输入
+                                    true, // 是继续操作
+                                );
+
+                            let _ = core.send_message(&feedback_message).await;
                         }
                         _ => {
-                            // 忽略其他类型的更新
+                            // 其他事件不需要发送反馈消息
                         }
                     }
+
+                    let _ = app_handle.emit("telegram-event", &event);
                 }
             }
-            Err(_) => {
-                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+            _ => {
+                // 忽略其他类型的更新
             }
         }
+    }
+
+    Err("Telegram更新分发已停止".to_string())
+}
 
-        // 短暂延迟避免过于频繁的请求
-        tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
+/// 收下用户发来的文件：图片作为附图返回同步给前端的事件，语音转写后追加到附加内容，
+/// 其他文件（含原始音频）保存到临时目录并把路径追加到附加内容
+async fn receive_file(
+    core: &TelegramCore,
+    file: ReceivedFile,
+    attached_input: &mut Vec<String>,
+    speech: Option<&SpeechBackend>,
+) -> Option<TelegramEvent> {
+    let filename = file.filename.clone();
+    if file.is_image() {
+        let image = file.into_attachment();
+        let _ = core.send_message(&format!("🖼️ 已收到图片 {}", filename)).await;
+        return Some(TelegramEvent::ImageReceived {
+            data_url: format!("data:{};base64,{}", image.media_type, image.data),
+        });
     }
+
+    let path = match file.save_to_temp() {
+        Ok(path) => path,
+        Err(e) => {
+            let _ = core.send_message(&format!("❌ 保存文件 {} 失败：{}", filename, e)).await;
+            return None;
+        }
+    };
+
+    let mut lines = Vec::new();
+    let notice = if !file.is_audio() {
+        format!("📎 已收到文件 {}", filename)
+    } else if let Some(backend) = speech {
+        let _ = core.send_message("🎙️ 正在识别语音…").await;
+        match backend.transcribe(&file).await {
+            Ok(text) => {
+                let notice = format!("🎙️ 识别结果：\n{}", text);
+                lines.push(text);
+                notice
+            }
+            Err(e) => format!("❌ 语音识别失败：{}（音频已作为文件附上）", e),
+        }
+    } else {
+        "🎙️ 已收到语音（未配置语音识别，音频已作为文件附上）".to_string()
+    };
+    lines.push(format!("📎 {}", path.display()));
+
+    attached_input.extend(lines);
+    let _ = core.send_message(&notice).await;
+    None
 }
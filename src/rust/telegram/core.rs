@@ -1,16 +1,25 @@
 use anyhow::Result;
 use serde::Serialize;
+use std::path::Path;
 // use tauri::{AppHandle, Emitter}; // 暂时不需要，由调用方处理事件
 use teloxide::{
     prelude::*,
     types::{
-        ChatId, InlineKeyboardButton, InlineKeyboardMarkup, KeyboardButton, KeyboardMarkup,
-        MessageId, ParseMode,
+        ChatId, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, KeyboardButton,
+        KeyboardMarkup, KeyboardRemove, MessageId, ParseMode, Recipient, ThreadId,
     },
     Bot,
 };
 
 use super::markdown::process_telegram_markdown;
+use super::media::{markdown_image_refs, resolve_image_source, split_message, utf16_len, ImageSource};
+use super::routing::{is_user_allowed, resolve_target};
+use crate::config::TelegramConfig;
+use crate::constants::telegram::{
+    MAX_FORWARDED_IMAGES, MAX_MESSAGE_LENGTH, MAX_SPLIT_PARTS, SPLIT_CHUNK_UNITS,
+};
+use crate::log_important;
+use crate::notify::{Notification, NotificationChannel};
 
 /// Telegram事件类型
 #[derive(Debug, Clone, Serialize)]
@@ -20,6 +29,8 @@ pub enum TelegramEvent {
     OptionToggled { option: String, selected: bool },
     /// 文本输入更新
     TextUpdated { text: String },
+    /// 收到图片（data URL）
+    ImageReceived { data_url: String },
     /// 继续按钮点击
     ContinuePressed,
     /// 发送按钮点击
@@ -27,9 +38,14 @@ pub enum TelegramEvent {
 }
 
 /// Telegram Bot 核心功能
+#[derive(Clone)]
 pub struct TelegramCore {
     pub bot: Bot,
     pub chat_id: ChatId,
+    /// 论坛群组中的话题，消息发到该话题且只接受该话题内的回复
+    pub thread_id: Option<ThreadId>,
+    /// 允许作答的用户ID，为空时不限制
+    pub allowed_user_ids: Vec<i64>,
 }
 
 impl TelegramCore {
@@ -40,26 +56,64 @@ impl TelegramCore {
 
     /// 创建新的Telegram核心实例，支持自定义API URL
     pub fn new_with_api_url(bot_token: String, chat_id: String, api_url: Option<String>) -> Result<Self> {
-        let mut bot = Bot::new(bot_token);
+        let bot = build_bot(&bot_token, api_url.as_deref())?;
 
-        // 如果提供了自定义API URL，则设置它
-        if let Some(url_str) = api_url {
-            let url = reqwest::Url::parse(&url_str)
-                .map_err(|e| anyhow::anyhow!("无效的API URL格式: {}", e))?;
-            bot = bot.set_api_url(url);
-        }
-
-        // 解析chat_id
+        // 解析chat_id（@username 需要请求 getChat，见 from_config）
         let chat_id = if chat_id.starts_with('@') {
-            return Err(anyhow::anyhow!("暂不支持@username格式，请使用数字Chat ID"));
+            return Err(anyhow::anyhow!("@username格式需要在线解析，请使用 TelegramCore::from_config"));
         } else {
-            let id = chat_id
-                .parse::<i64>()
-                .map_err(|_| anyhow::anyhow!("无效的Chat ID格式，请使用数字ID"))?;
-            ChatId(id)
+            parse_numeric_chat_id(&chat_id)?
         };
 
-        Ok(Self { bot, chat_id })
+        Ok(Self { bot, chat_id, thread_id: None, allowed_user_ids: Vec::new() })
+    }
+
+    /// 按配置创建：根据项目路径选择路由，解析 @channel，并带上话题与允许列表
+    pub async fn from_config(config: &TelegramConfig, project_path: Option<&str>) -> Result<Self> {
+        let target = resolve_target(config, project_path);
+        let api_url = (config.api_base_url != crate::constants::telegram::API_BASE_URL)
+            .then_some(config.api_base_url.as_str());
+        let bot = build_bot(&config.bot_token, api_url)?;
+        let chat_id = resolve_chat_id(&bot, &target.chat_id).await?;
+
+        Ok(Self {
+            bot,
+            chat_id,
+            thread_id: target.message_thread_id.map(|id| ThreadId(MessageId(id))),
+            allowed_user_ids: config.allowed_user_ids.clone(),
+        })
+    }
+
+    /// 发往目标聊天（及话题）的消息请求
+    fn send_to_target(&self, text: impl Into<String>) -> <Bot as Requester>::SendMessage {
+        let request = self.bot.send_message(self.chat_id, text);
+        match self.thread_id {
+            Some(thread_id) => request.message_thread_id(thread_id),
+            None => request,
+        }
+    }
+
+    /// 是否来自目标聊天和话题
+    fn is_target_chat(&self, chat_id: ChatId, thread_id: Option<ThreadId>) -> bool {
+        chat_id == self.chat_id && (self.thread_id.is_none() || thread_id == self.thread_id)
+    }
+
+    /// 消息是否应被处理：目标聊天/话题内，且发送者在允许列表中
+    pub fn accepts_message(&self, message: &Message) -> bool {
+        self.is_target_chat(message.chat.id, message.thread_id)
+            && is_user_allowed(&self.allowed_user_ids, message.from.as_ref().map(|u| u.id.0))
+    }
+
+    /// 按钮回调是否应被处理
+    pub fn accepts_callback(&self, callback_query: &CallbackQuery) -> bool {
+        let in_target = match &callback_query.message {
+            Some(message) => match message.regular_message() {
+                Some(msg) => self.is_target_chat(msg.chat.id, msg.thread_id),
+                None => message.chat().id == self.chat_id,
+            },
+            None => true,
+        };
+        in_target && is_user_allowed(&self.allowed_user_ids, Some(callback_query.from.id.0))
     }
 
     /// 发送普通消息
@@ -73,7 +127,7 @@ impl TelegramCore {
         message: &str,
         use_markdown: bool,
     ) -> Result<()> {
-        let mut send_request = self.bot.send_message(self.chat_id, message);
+        let mut send_request = self.send_to_target(message);
 
         // 如果启用Markdown，设置解析模式
         if use_markdown {
@@ -87,22 +141,65 @@ impl TelegramCore {
         Ok(())
     }
 
-    /// 发送选项消息（消息一）
+    /// 发送选项消息（消息一），返回带选项按钮的消息ID
+    ///
+    /// 超出 Telegram 长度上限时按段拆分（按钮放在最后一段），
+    /// 段数过多或无法拆分时改为发送完整内容的文件，再单独发送选项按钮
     pub async fn send_options_message(
         &self,
         message: &str,
         predefined_options: &[String],
         is_markdown: bool,
-    ) -> Result<()> {
+    ) -> Result<i32> {
         // 处理消息内容
-        let processed_message = if is_markdown {
-            process_telegram_markdown(message)
-        } else {
-            message.to_string()
+        let render = |text: &str| {
+            if is_markdown {
+                process_telegram_markdown(text)
+            } else {
+                text.to_string()
+            }
         };
+        let processed_message = render(message);
+        if utf16_len(&processed_message) <= MAX_MESSAGE_LENGTH {
+            return self.send_options_part(processed_message, predefined_options, is_markdown).await;
+        }
+
+        let parts = split_message(message, SPLIT_CHUNK_UNITS, MAX_SPLIT_PARTS)
+            .map(|parts| parts.iter().map(|part| render(part)).collect::<Vec<_>>())
+            .filter(|parts| parts.iter().all(|part| utf16_len(part) <= MAX_MESSAGE_LENGTH));
+
+        match parts {
+            Some(mut parts) => {
+                let last = parts.pop().unwrap_or_default();
+                for part in parts {
+                    let mut send_request = self.send_to_target(part);
+                    if is_markdown {
+                        send_request = send_request.parse_mode(ParseMode::MarkdownV2);
+                    }
+                    send_request
+                        .await
+                        .map_err(|e| anyhow::anyhow!("发送选项消息失败: {}", e))?;
+                }
+                self.send_options_part(last, predefined_options, is_markdown).await
+            }
+            None => {
+                let filename = if is_markdown { "zhi-message.md" } else { "zhi-message.txt" };
+                self.send_document(message.as_bytes().to_vec(), filename).await?;
+                let notice = format!("📄 消息较长（{} 字），完整内容见上方文件", message.chars().count());
+                self.send_options_part(notice, predefined_options, false).await
+            }
+        }
+    }
 
+    /// 发送一段选项消息，有预定义选项时附带按钮
+    async fn send_options_part(
+        &self,
+        text: String,
+        predefined_options: &[String],
+        is_markdown: bool,
+    ) -> Result<i32> {
         // 创建消息发送请求
-        let mut send_request = self.bot.send_message(self.chat_id, processed_message);
+        let mut send_request = self.send_to_target(text);
 
         // 只有当有预定义选项时才添加inline keyboard
         if !predefined_options.is_empty() {
@@ -116,7 +213,7 @@ impl TelegramCore {
         }
 
         match send_request.await {
-            Ok(_) => Ok(()),
+            Ok(msg) => Ok(msg.id.0),
             Err(e) => {
                 let error_str = e.to_string();
 
@@ -125,8 +222,8 @@ impl TelegramCore {
                 let has_ok_true = error_str.contains("\\\"ok\\\":true");
 
                 if has_parsing_json && has_ok_true {
-                    // 消息实际发送成功
-                    Ok(())
+                    // 消息实际发送成功，但无法得知消息ID
+                    Ok(0)
                 } else {
                     Err(anyhow::anyhow!("发送选项消息失败: {}", e))
                 }
@@ -134,6 +231,49 @@ impl TelegramCore {
         }
     }
 
+    /// 发送文件
+    pub async fn send_document(&self, bytes: Vec<u8>, filename: &str) -> Result<i32> {
+        let mut send_request = self
+            .bot
+            .send_document(self.chat_id, InputFile::memory(bytes).file_name(filename.to_string()));
+        if let Some(thread_id) = self.thread_id {
+            send_request = send_request.message_thread_id(thread_id);
+        }
+        send_request
+            .await
+            .map(|msg| msg.id.0)
+            .map_err(|e| anyhow::anyhow!("发送文件失败: {}", e))
+    }
+
+    /// 转发 Markdown 中引用的图片（链接、data URL 或项目内的本地图片），单张失败不影响其他图片
+    pub async fn send_markdown_images(&self, markdown: &str, base_dir: Option<&Path>) -> usize {
+        let mut sent = 0;
+        for target in markdown_image_refs(markdown).into_iter().take(MAX_FORWARDED_IMAGES) {
+            let Some(source) = resolve_image_source(&target, base_dir) else {
+                log_important!(warn, "无法解析图片地址，跳过转发: {}", target);
+                continue;
+            };
+            let input = match source {
+                ImageSource::Url(url) => InputFile::url(url),
+                ImageSource::File(path) => InputFile::file(path),
+                ImageSource::Data { media_type, bytes } => {
+                    let ext = media_type.rsplit('/').next().unwrap_or("png").to_string();
+                    InputFile::memory(bytes).file_name(format!("image.{}", ext))
+                }
+            };
+
+            let mut send_request = self.bot.send_photo(self.chat_id, input);
+            if let Some(thread_id) = self.thread_id {
+                send_request = send_request.message_thread_id(thread_id);
+            }
+            match send_request.await {
+                Ok(_) => sent += 1,
+                Err(e) => log_important!(warn, "转发图片失败 {}: {}", target, e),
+            }
+        }
+        sent
+    }
+
     /// 发送操作消息（消息二）
     pub async fn send_operation_message(&self, continue_reply_enabled: bool) -> Result<i32> {
         // 创建reply keyboard
@@ -143,8 +283,7 @@ impl TelegramCore {
         let operation_message = "键盘上选择操作完成对话";
 
         match self
-            .bot
-            .send_message(self.chat_id, operation_message)
+            .send_to_target(operation_message)
             .reply_markup(reply_keyboard)
             .await
         {
@@ -162,6 +301,28 @@ impl TelegramCore {
         }
     }
 
+    /// 撤回请求（已在其他渠道回复）：移除选项按钮，删除操作消息，并发送提示收起回复键盘
+    pub async fn retract_request(
+        &self,
+        options_message_id: Option<i32>,
+        operation_message_id: Option<i32>,
+        notice: &str,
+    ) {
+        if let Some(id) = options_message_id {
+            if let Err(e) = self.bot.edit_message_reply_markup(self.chat_id, MessageId(id)).await {
+                log_important!(warn, "移除选项按钮失败: {}", e);
+            }
+        }
+        if let Some(id) = operation_message_id {
+            if let Err(e) = self.bot.delete_message(self.chat_id, MessageId(id)).await {
+                log_important!(warn, "删除操作消息失败: {}", e);
+            }
+        }
+        if let Err(e) = self.send_to_target(notice).reply_markup(KeyboardRemove::new()).await {
+            log_important!(warn, "发送撤回提示失败: {}", e);
+        }
+    }
+
     /// 创建inline keyboard
     pub fn create_inline_keyboard(
         predefined_options: &[String],
@@ -227,17 +388,26 @@ impl TelegramCore {
     }
 }
 
+/// Telegram 也可作为只通知渠道（如测试通知时）；交互式请求仍走选项消息和操作消息
+impl NotificationChannel for TelegramCore {
+    fn name(&self) -> String {
+        "Telegram".to_string()
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        self.send_message(&format!("{}\n\n{}", notification.title, notification.body()))
+            .await
+    }
+}
+
 /// 处理callback query的通用函数（不发送事件，由调用方处理）
 pub async fn handle_callback_query(
-    bot: &Bot,
+    core: &TelegramCore,
     callback_query: &CallbackQuery,
-    target_chat_id: ChatId,
 ) -> ResponseResult<Option<String>> {
-    // 检查是否是目标聊天
-    if let Some(message) = &callback_query.message {
-        if message.chat().id != target_chat_id {
-            return Ok(None);
-        }
+    // 检查是否是目标聊天，以及用户是否允许作答
+    if !core.accepts_callback(callback_query) {
+        return Ok(None);
     }
 
     let mut toggled_option = None;
@@ -250,7 +420,7 @@ pub async fn handle_callback_query(
     }
 
     // 回答callback query
-    bot.answer_callback_query(&callback_query.id).await?;
+    core.bot.answer_callback_query(&callback_query.id).await?;
 
     Ok(toggled_option)
 }
@@ -258,11 +428,11 @@ pub async fn handle_callback_query(
 /// 处理文本消息的通用函数（不发送事件，由调用方处理）
 pub async fn handle_text_message(
     message: &Message,
-    target_chat_id: ChatId,
+    core: &TelegramCore,
     operation_message_id: Option<i32>,
 ) -> ResponseResult<Option<TelegramEvent>> {
-    // 检查是否是目标聊天
-    if message.chat.id != target_chat_id {
+    // 检查是否是目标聊天，以及发送者是否允许作答
+    if !core.accepts_message(message) {
         return Ok(None);
     }
 
@@ -288,6 +458,17 @@ pub async fn handle_text_message(
     Ok(None)
 }
 
+/// 合并用户回复：文字消息在前，之后按收到的顺序附上说明文字、语音转写等内容
+///
+/// 文字消息只替换 `text`，不会覆盖之前收到的说明文字和语音转写
+pub fn merge_reply_input(text: &str, attached: &[String]) -> String {
+    std::iter::once(text)
+        .chain(attached.iter().map(String::as_str))
+        .filter(|s| !s.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 /// 生成统一的反馈消息
 pub fn build_feedback_message(
     selected_options: &[String],
@@ -343,26 +524,51 @@ pub async fn test_telegram_connection_with_api_url(
     }
 
     // 创建Bot实例
-    let mut bot = Bot::new(bot_token);
-
-    // 如果提供了自定义API URL，则设置它
-    if let Some(url_str) = api_url {
-        let url = reqwest::Url::parse(url_str)
-            .map_err(|e| anyhow::anyhow!("无效的API URL格式: {}", e))?;
-        bot = bot.set_api_url(url);
-    }
+    let bot = build_bot(bot_token, api_url)?;
 
-    // 验证Chat ID格式
-    let chat_id_parsed: i64 = chat_id
-        .parse()
-        .map_err(|_| anyhow::anyhow!("Chat ID格式无效，请输入有效的数字ID"))?;
+    // 解析Chat ID（支持 @channel）
+    let chat_id = resolve_chat_id(&bot, chat_id.trim()).await?;
 
     // 发送测试消息
     let test_message =
         "🤖 iterate 应用测试消息\n\n这是一条来自 iterate 应用的测试消息，表示Telegram Bot配置成功！";
 
-    match bot.send_message(ChatId(chat_id_parsed), test_message).await {
+    match bot.send_message(chat_id, test_message).await {
         Ok(_) => Ok("测试消息发送成功！Telegram Bot配置正确。".to_string()),
         Err(e) => Err(anyhow::anyhow!("发送测试消息失败: {}", e)),
     }
 }
+
+/// 创建Bot实例，支持自定义API URL
+fn build_bot(bot_token: &str, api_url: Option<&str>) -> Result<Bot> {
+    let bot = Bot::new(bot_token);
+    match api_url {
+        Some(url_str) => {
+            let url = reqwest::Url::parse(url_str)
+                .map_err(|e| anyhow::anyhow!("无效的API URL格式: {}", e))?;
+            Ok(bot.set_api_url(url))
+        }
+        None => Ok(bot),
+    }
+}
+
+fn parse_numeric_chat_id(chat_id: &str) -> Result<ChatId> {
+    chat_id
+        .trim()
+        .parse::<i64>()
+        .map(ChatId)
+        .map_err(|_| anyhow::anyhow!("无效的Chat ID格式，请使用数字ID或@channel"))
+}
+
+/// 解析Chat ID：数字ID直接使用，@channel 通过 getChat 查询（Bot 需已加入该频道或群组）
+pub async fn resolve_chat_id(bot: &Bot, chat_id: &str) -> Result<ChatId> {
+    if chat_id.starts_with('@') {
+        let chat = bot
+            .get_chat(Recipient::ChannelUsername(chat_id.to_string()))
+            .await
+            .map_err(|e| anyhow::anyhow!("解析 {} 失败: {}", chat_id, e))?;
+        Ok(chat.id)
+    } else {
+        parse_numeric_chat_id(chat_id)
+    }
+}
@@ -0,0 +1,841 @@
+//! Telegram 更新分发
+//!
+//! 一个 Bot 同时只能有一个更新消费者，而每个 zhi 请求都是独立进程，各自轮询 getUpdates
+//! 会互相抢走回复。这里由第一个占用本地分发端口的进程作为主进程，负责接收更新（长轮询或
+//! Webhook），再按消息 ID 路由到对应的待处理请求；其他进程通过 WebSocket 连到主进程注册请求、
+//! 接收属于自己的更新，主进程退出后由剩下的进程接替。连接主进程时需在握手中携带由 Bot Token
+//! 派生的密钥，本机其他程序无法冒充请求接收更新。
+//!
+//! 路由规则：
+//! - 按钮回调：按按钮所在消息的 ID 精确匹配
+//! - 消息：回复了某个请求的消息时交给该请求，否则交给同一聊天（话题）中最新注册的请求
+
+use anyhow::{anyhow, Result};
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::{Update, UpdateKind};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::{header::AUTHORIZATION, HeaderValue, StatusCode};
+use tokio_tungstenite::{accept_hdr_async, connect_async, tungstenite::Message as WsMessage};
+
+use super::core::TelegramCore;
+use crate::config::{TelegramConfig, TelegramUpdateMode};
+use crate::constants::telegram::{POLL_TIMEOUT_SECS, WEBHOOK_MAX_BODY_BYTES};
+use crate::{log_debug, log_important};
+
+/// 容忍的时钟偏差：早于注册时间这么多秒以上的消息视为旧消息
+const MESSAGE_CLOCK_SKEW_SECS: i64 = 2;
+
+/// 轮询出错或主进程切换时的重试间隔
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// 一个待处理请求的注册信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Registration {
+    pub request_id: String,
+    pub chat_id: i64,
+    #[serde(default)]
+    pub thread_id: Option<i32>,
+    /// 该请求发出的消息（选项消息、操作消息）
+    #[serde(default)]
+    pub message_ids: Vec<i32>,
+    /// 注册时间（Unix 秒），更早的消息不会交给该请求
+    pub registered_at: i64,
+}
+
+impl Registration {
+    /// 以核心实例的目标聊天和话题注册
+    pub fn for_core(core: &TelegramCore, request_id: &str) -> Self {
+        Self {
+            request_id: request_id.to_string(),
+            chat_id: core.chat_id.0,
+            thread_id: core.thread_id.map(|id| id.0 .0),
+            message_ids: Vec::new(),
+            registered_at: chrono::Utc::now().timestamp(),
+        }
+    }
+}
+
+/// 路由所需的更新信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateKey {
+    pub chat_id: i64,
+    pub thread_id: Option<i32>,
+    /// 按钮回调所在的消息
+    pub callback_message_id: Option<i32>,
+    /// 消息回复的目标消息
+    pub reply_to: Option<i32>,
+    /// 消息发送时间（Unix 秒）
+    pub date: Option<i64>,
+}
+
+/// 提取路由信息；只路由按钮回调和消息
+pub fn update_key(update: &Update) -> Option<UpdateKey> {
+    match &update.kind {
+        UpdateKind::CallbackQuery(query) => {
+            let message = query.message.as_ref()?;
+            Some(UpdateKey {
+                chat_id: message.chat().id.0,
+                thread_id: message.regular_message().and_then(|m| m.thread_id).map(|id| id.0 .0),
+                callback_message_id: Some(message.id().0),
+                reply_to: None,
+                date: None,
+            })
+        }
+        UpdateKind::Message(message) => Some(UpdateKey {
+            chat_id: message.chat.id.0,
+            thread_id: message.thread_id.map(|id| id.0 .0),
+            callback_message_id: None,
+            reply_to: message.reply_to_message().map(|m| m.id.0),
+            date: Some(message.date.timestamp()),
+        }),
+        _ => None,
+    }
+}
+
+/// 按消息 ID 把更新路由到待处理请求
+#[derive(Debug, Default)]
+pub struct UpdateRouter {
+    /// 按注册先后排列
+    registrations: Vec<Registration>,
+}
+
+impl UpdateRouter {
+    pub fn register(&mut self, registration: Registration) {
+        self.unregister(&registration.request_id);
+        self.registrations.push(registration);
+    }
+
+    pub fn add_message_ids(&mut self, request_id: &str, message_ids: &[i32]) {
+        if let Some(registration) = self.registrations.iter_mut().find(|r| r.request_id == request_id) {
+            registration.message_ids.extend(message_ids.iter().filter(|id| **id > 0));
+        }
+    }
+
+    pub fn unregister(&mut self, request_id: &str) {
+        self.registrations.retain(|r| r.request_id != request_id);
+    }
+
+    /// 返回应接收该更新的请求 ID
+    pub fn route(&self, key: &UpdateKey) -> Option<&str> {
+        let owner = |message_id: i32| {
+            self.registrations
+                .iter()
+                .find(|r| r.chat_id == key.chat_id && r.message_ids.contains(&message_id))
+        };
+        let found = match (key.callback_message_id, key.reply_to) {
+            (Some(message_id), _) => owner(message_id),
+            (None, Some(reply_to)) => owner(reply_to).or_else(|| self.latest(key)),
+            (None, None) => self.latest(key),
+        };
+        found.map(|r| r.request_id.as_str())
+    }
+
+    /// 同一聊天（话题）中最新注册、且不晚于消息发送时间的请求
+    fn latest(&self, key: &UpdateKey) -> Option<&Registration> {
+        self.registrations.iter().rev().find(|r| {
+            r.chat_id == key.chat_id
+                && (r.thread_id.is_none() || r.thread_id == key.thread_id)
+                && key.date.is_none_or(|date| date + MESSAGE_CLOCK_SKEW_SECS >= r.registered_at)
+        })
+    }
+}
+
+/// 更新来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateSource {
+    /// getUpdates 长轮询
+    Polling,
+    /// Telegram 推送到公网地址，再由反向代理或隧道转发到本地监听地址
+    Webhook { public_url: String, listen_addr: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DispatcherOptions {
+    pub source: UpdateSource,
+    /// 主进程与其他进程通信的本地地址
+    pub hub_addr: SocketAddr,
+    /// 连接主进程时握手携带的密钥，见 [`hub_secret`]
+    pub hub_secret: String,
+}
+
+impl DispatcherOptions {
+    pub fn from_config(config: &TelegramConfig) -> Result<Self> {
+        let source = match config.update_mode {
+            TelegramUpdateMode::Polling => UpdateSource::Polling,
+            TelegramUpdateMode::Webhook => {
+                let public_url = config.webhook_url.trim();
+                if public_url.is_empty() {
+                    return Err(anyhow!("Webhook模式需要配置公网地址 webhook_url"));
+                }
+                UpdateSource::Webhook {
+                    public_url: public_url.to_string(),
+                    listen_addr: config.webhook_listen_addr.trim().to_string(),
+                }
+            }
+        };
+        Ok(Self {
+            source,
+            hub_addr: SocketAddr::from(([127, 0, 0, 1], config.dispatcher_port)),
+            hub_secret: hub_secret(&config.bot_token),
+        })
+    }
+}
+
+/// 由 Bot Token 派生分发密钥：同一个 Bot 的进程得到相同密钥，Token 本身不会出现在握手中
+pub fn hub_secret(bot_token: &str) -> String {
+    let input = format!("cunzhi-telegram-hub:{}", bot_token.trim());
+    hex::encode(ring::digest::digest(&ring::digest::SHA256, input.as_bytes()).as_ref())
+}
+
+/// 逐字节比较全部内容，耗时与密钥在哪一位不同无关
+fn secret_matches(provided: &str, expected: &str) -> bool {
+    provided.len() == expected.len()
+        && provided.bytes().zip(expected.bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// 其他进程发给主进程的请求
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum HubRequest {
+    Register { registration: Registration },
+    AddMessageIds { request_id: String, message_ids: Vec<i32> },
+    Unregister { request_id: String },
+}
+
+/// 主进程转发给其他进程的更新
+#[derive(Debug, Serialize, Deserialize)]
+struct HubUpdate {
+    request_id: String,
+    update: Update,
+}
+
+/// 本进程内的订阅变更
+enum Control {
+    Subscribe(Registration, mpsc::UnboundedSender<Update>),
+    AddMessageIds(String, Vec<i32>),
+    Unsubscribe(String),
+}
+
+impl Control {
+    fn to_hub_request(&self) -> HubRequest {
+        match self {
+            Control::Subscribe(registration, _) => HubRequest::Register { registration: registration.clone() },
+            Control::AddMessageIds(request_id, message_ids) => HubRequest::AddMessageIds {
+                request_id: request_id.clone(),
+                message_ids: message_ids.clone(),
+            },
+            Control::Unsubscribe(request_id) => HubRequest::Unregister { request_id: request_id.clone() },
+        }
+    }
+}
+
+/// 本进程的订阅，切换主进程时据此重新注册
+#[derive(Default)]
+struct LocalSubscriptions {
+    entries: HashMap<String, (Registration, mpsc::UnboundedSender<Update>)>,
+}
+
+impl LocalSubscriptions {
+    fn apply(&mut self, control: &Control) {
+        match control {
+            Control::Subscribe(registration, sender) => {
+                self.entries.insert(registration.request_id.clone(), (registration.clone(), sender.clone()));
+            }
+            Control::AddMessageIds(request_id, message_ids) => {
+                if let Some((registration, _)) = self.entries.get_mut(request_id) {
+                    registration.message_ids.extend(message_ids);
+                }
+            }
+            Control::Unsubscribe(request_id) => {
+                self.entries.remove(request_id);
+            }
+        }
+    }
+
+    fn deliver(&self, request_id: &str, update: Update) {
+        if let Some((_, sender)) = self.entries.get(request_id) {
+            let _ = sender.send(update);
+        }
+    }
+}
+
+/// 主进程中更新的去向
+enum Sink {
+    Local(mpsc::UnboundedSender<Update>),
+    Remote(mpsc::UnboundedSender<HubUpdate>),
+}
+
+#[derive(Default)]
+struct Hub {
+    router: UpdateRouter,
+    sinks: HashMap<String, Sink>,
+}
+
+impl Hub {
+    fn register(&mut self, registration: Registration, sink: Sink) {
+        self.sinks.insert(registration.request_id.clone(), sink);
+        self.router.register(registration);
+    }
+
+    fn unregister(&mut self, request_id: &str) {
+        self.sinks.remove(request_id);
+        self.router.unregister(request_id);
+    }
+
+    /// 路由并投递，返回是否送达
+    fn dispatch(&mut self, update: Update) -> bool {
+        let Some(request_id) = update_key(&update)
+            .and_then(|key| self.router.route(&key).map(str::to_string))
+        else {
+            return false;
+        };
+        let delivered = match self.sinks.get(&request_id) {
+            Some(Sink::Local(sender)) => sender.send(update).is_ok(),
+            Some(Sink::Remote(sender)) => sender.send(HubUpdate { request_id: request_id.clone(), update }).is_ok(),
+            None => false,
+        };
+        if !delivered {
+            self.unregister(&request_id);
+        }
+        delivered
+    }
+}
+
+/// 待处理请求的更新订阅，离开作用域时自动注销
+pub struct Subscription {
+    request_id: String,
+    receiver: mpsc::UnboundedReceiver<Update>,
+    control: mpsc::UnboundedSender<Control>,
+}
+
+impl Subscription {
+    pub async fn recv(&mut self) -> Option<Update> {
+        self.receiver.recv().await
+    }
+
+    /// 记录该请求发出的消息，按钮回调和对它的回复据此路由
+    pub fn add_message_id(&self, message_id: i32) {
+        if message_id > 0 {
+            let _ = self.control.send(Control::AddMessageIds(self.request_id.clone(), vec![message_id]));
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let _ = self.control.send(Control::Unsubscribe(self.request_id.clone()));
+    }
+}
+
+/// 进程内共享的分发器及启动它的 Bot Token 和配置
+static SHARED: Mutex<Option<(String, DispatcherOptions, Arc<UpdateDispatcher>)>> = Mutex::new(None);
+
+/// 更新分发器
+pub struct UpdateDispatcher {
+    control: mpsc::UnboundedSender<Control>,
+}
+
+impl UpdateDispatcher {
+    /// 启动分发器（需在 tokio 运行时中调用）
+    pub fn start(bot: Bot, options: DispatcherOptions) -> Self {
+        let (control, control_rx) = mpsc::unbounded_channel();
+        tokio::spawn(run(bot, options, control_rx));
+        Self { control }
+    }
+
+    /// 进程内共享的分发器，首次调用时按配置启动
+    ///
+    /// Bot Token 或分发配置变化时启动新的分发器；旧分发器在其订阅全部结束后自动退出，
+    /// 在此之前新分发器作为客户端连接它或等待接替
+    pub fn shared(bot: &Bot, config: &TelegramConfig) -> Result<Arc<Self>> {
+        let options = DispatcherOptions::from_config(config)?;
+        let mut shared = SHARED.lock().map_err(|e| anyhow!("分发器状态锁定失败: {}", e))?;
+        if let Some((token, current, dispatcher)) = shared.as_ref() {
+            if token == bot.token() && *current == options {
+                return Ok(dispatcher.clone());
+            }
+            log_important!(info, "Telegram配置已变化，重新启动更新分发");
+        }
+        let dispatcher = Arc::new(Self::start(bot.clone(), options.clone()));
+        *shared = Some((bot.token().to_string(), options, dispatcher.clone()));
+        Ok(dispatcher)
+    }
+
+    pub fn subscribe(&self, registration: Registration) -> Subscription {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let request_id = registration.request_id.clone();
+        let _ = self.control.send(Control::Subscribe(registration, sender));
+        Subscription { request_id, receiver, control: self.control.clone() }
+    }
+}
+
+/// 一轮主进程/客户端运行结束的原因
+enum Exit {
+    /// 分发器已被释放
+    Stopped,
+    /// 与主进程断开，需要重新竞争
+    Disconnected,
+}
+
+async fn run(bot: Bot, options: DispatcherOptions, mut control_rx: mpsc::UnboundedReceiver<Control>) {
+    let mut local = LocalSubscriptions::default();
+    loop {
+        let result = match TcpListener::bind(options.hub_addr).await {
+            Ok(listener) => {
+                log_debug!("成为Telegram更新分发主进程: {}", options.hub_addr);
+                run_leader(&bot, &options, listener, &mut local, &mut control_rx).await
+            }
+            Err(_) => run_client(&options, &mut local, &mut control_rx).await,
+        };
+        match result {
+            Ok(Exit::Stopped) => return,
+            Ok(Exit::Disconnected) => {}
+            Err(e) => {
+                log_debug!("Telegram更新分发中断，稍后重试: {}", e);
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+/// 主进程：接收更新并路由给本进程和其他进程的请求
+async fn run_leader(
+    bot: &Bot,
+    options: &DispatcherOptions,
+    hub_listener: TcpListener,
+    local: &mut LocalSubscriptions,
+    control_rx: &mut mpsc::UnboundedReceiver<Control>,
+) -> Result<Exit> {
+    let hub = Arc::new(Mutex::new(Hub::default()));
+    {
+        let mut hub = hub.lock().map_err(|e| anyhow!("分发状态锁定失败: {}", e))?;
+        for (registration, sender) in local.entries.values() {
+            hub.register(registration.clone(), Sink::Local(sender.clone()));
+        }
+    }
+
+    // 更新来源和各客户端连接随本函数返回一起结束
+    let mut tasks = JoinSet::new();
+    let (updates_tx, mut updates_rx) = mpsc::unbounded_channel();
+    match options.source.clone() {
+        UpdateSource::Polling => {
+            tasks.spawn(poll_updates(bot.clone(), updates_tx));
+        }
+        UpdateSource::Webhook { public_url, listen_addr } => {
+            let bot = bot.clone();
+            tasks.spawn(async move {
+                if let Err(e) = serve_webhook(bot, &public_url, &listen_addr, updates_tx).await {
+                    log_important!(error, "Telegram Webhook监听失败: {}", e);
+                }
+            });
+        }
+    }
+
+    loop {
+        tokio::select! {
+            biased;
+            control = control_rx.recv() => {
+                let Some(control) = control else { return Ok(Exit::Stopped) };
+                local.apply(&control);
+                let mut hub = hub.lock().map_err(|e| anyhow!("分发状态锁定失败: {}", e))?;
+                match control {
+                    Control::Subscribe(registration, sender) => hub.register(registration, Sink::Local(sender)),
+                    Control::AddMessageIds(request_id, message_ids) => hub.router.add_message_ids(&request_id, &message_ids),
+                    Control::Unsubscribe(request_id) => hub.unregister(&request_id),
+                }
+            }
+            update = updates_rx.recv() => {
+                let Some(update) = update else { return Err(anyhow!("更新来源已停止")) };
+                let callback_id = match &update.kind {
+                    UpdateKind::CallbackQuery(query) => Some(query.id.clone()),
+                    _ => None,
+                };
+                let delivered = hub.lock().map_err(|e| anyhow!("分发状态锁定失败: {}", e))?.dispatch(update);
+                // 已结束请求上的按钮，直接应答以免客户端一直转圈
+                if let (false, Some(callback_id)) = (delivered, callback_id) {
+                    let _ = bot.answer_callback_query(&callback_id).text("该请求已结束").await;
+                }
+            }
+            accepted = hub_listener.accept() => {
+                match accepted {
+                    Ok((stream, _)) => {
+                        tasks.spawn(serve_hub_client(stream, hub.clone(), options.hub_secret.clone()));
+                    }
+                    Err(e) => log_debug!("接受分发连接失败: {}", e),
+                }
+                while tasks.try_join_next().is_some() {}
+            }
+        }
+    }
+}
+
+/// 为连到主进程的其他进程转发更新；握手未携带正确密钥的连接直接拒绝
+async fn serve_hub_client(stream: TcpStream, hub: Arc<Mutex<Hub>>, secret: String) {
+    let authorize = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+        let provided = request.headers().get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        if secret_matches(provided, &secret) {
+            Ok(response)
+        } else {
+            let mut rejected = ErrorResponse::new(Some("invalid hub secret".to_string()));
+            *rejected.status_mut() = StatusCode::FORBIDDEN;
+            Err(rejected)
+        }
+    };
+    let ws_stream = match accept_hdr_async(stream, authorize).await {
+        Ok(ws_stream) => ws_stream,
+        Err(e) => {
+            log_debug!("拒绝分发连接: {}", e);
+            return;
+        }
+    };
+    let (mut write, mut read) = ws_stream.split();
+    let (sender, mut receiver) = mpsc::unbounded_channel::<HubUpdate>();
+    let mut owned: Vec<String> = Vec::new();
+
+    loop {
+        tokio::select! {
+            outgoing = receiver.recv() => {
+                let Some(outgoing) = outgoing else { break };
+                let Ok(text) = serde_json::to_string(&outgoing) else { continue };
+                if write.send(WsMessage::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            incoming = read.next() => {
+                let text = match incoming {
+                    Some(Ok(WsMessage::Text(text))) => text,
+                    Some(Ok(_)) => continue,
+                    _ => break,
+                };
+                let request = match serde_json::from_str::<HubRequest>(&text) {
+                    Ok(request) => request,
+                    Err(e) => {
+                        log_debug!("无效的分发请求: {}", e);
+                        continue;
+                    }
+                };
+                let Ok(mut hub) = hub.lock() else { break };
+                match request {
+                    HubRequest::Register { registration } => {
+                        owned.push(registration.request_id.clone());
+                        hub.register(registration, Sink::Remote(sender.clone()));
+                    }
+                    HubRequest::AddMessageIds { request_id, message_ids } => hub.router.add_message_ids(&request_id, &message_ids),
+                    HubRequest::Unregister { request_id } => {
+                        owned.retain(|id| id != &request_id);
+                        hub.unregister(&request_id);
+                    }
+                }
+            }
+        }
+    }
+
+    if let Ok(mut hub) = hub.lock() {
+        for request_id in owned {
+            hub.unregister(&request_id);
+        }
+    }
+}
+
+/// 客户端：向主进程注册本进程的请求并接收更新
+async fn run_client(
+    options: &DispatcherOptions,
+    local: &mut LocalSubscriptions,
+    control_rx: &mut mpsc::UnboundedReceiver<Control>,
+) -> Result<Exit> {
+    let mut request = format!("ws://{}", options.hub_addr).into_client_request()?;
+    request.headers_mut().insert(AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", options.hub_secret))?);
+    let (ws_stream, _) = connect_async(request)
+        .await
+        .map_err(|e| anyhow!("连接更新分发主进程失败: {}", e))?;
+    let (mut write, mut read) = ws_stream.split();
+
+    for (registration, _) in local.entries.values() {
+        let request = HubRequest::Register { registration: registration.clone() };
+        write.send(WsMessage::Text(serde_json::to_string(&request)?)).await?;
+    }
+
+    loop {
+        tokio::select! {
+            biased;
+            control = control_rx.recv() => {
+                let Some(control) = control else { return Ok(Exit::Stopped) };
+                local.apply(&control);
+                let text = serde_json::to_string(&control.to_hub_request())?;
+                if write.send(WsMessage::Text(text)).await.is_err() {
+                    return Ok(Exit::Disconnected);
+                }
+            }
+            incoming = read.next() => {
+                match incoming {
+                    Some(Ok(WsMessage::Text(text))) => match serde_json::from_str::<HubUpdate>(&text) {
+                        Ok(HubUpdate { request_id, update }) => local.deliver(&request_id, update),
+                        Err(e) => log_debug!("无效的分发更新: {}", e),
+                    },
+                    Some(Ok(_)) => {}
+                    // 主进程已退出
+                    _ => return Ok(Exit::Disconnected),
+                }
+            }
+        }
+    }
+}
+
+/// getUpdates 长轮询
+async fn poll_updates(bot: Bot, updates_tx: mpsc::UnboundedSender<Update>) {
+    // 之前的主进程可能设置过 Webhook，此时 getUpdates 会被拒绝
+    if let Err(e) = bot.delete_webhook().await {
+        log_debug!("删除Telegram Webhook失败: {}", e);
+    }
+
+    let mut offset = 0i32;
+    loop {
+        match bot.get_updates().offset(offset).timeout(POLL_TIMEOUT_SECS).await {
+            Ok(updates) => {
+                for update in updates {
+                    offset = update.id.0 as i32 + 1;
+                    if updates_tx.send(update).is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(e) => {
+                log_debug!("获取Telegram更新失败: {}", e);
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+/// 注册 Webhook 并在本地监听 Telegram 推送
+async fn serve_webhook(
+    bot: Bot,
+    public_url: &str,
+    listen_addr: &str,
+    updates_tx: mpsc::UnboundedSender<Update>,
+) -> Result<()> {
+    let listener = TcpListener::bind(listen_addr)
+        .await
+        .map_err(|e| anyhow!("监听 {} 失败: {}", listen_addr, e))?;
+    let url = reqwest::Url::parse(public_url).map_err(|e| anyhow!("无效的Webhook地址: {}", e))?;
+    // 每个主进程使用新的密钥，拒绝伪造的推送
+    let secret = uuid::Uuid::new_v4().simple().to_string();
+    bot.set_webhook(url)
+        .secret_token(secret.clone())
+        .await
+        .map_err(|e| anyhow!("设置Webhook失败: {}", e))?;
+    log_important!(info, "Telegram Webhook已启动: {} -> {}", public_url, listen_addr);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let secret = secret.clone();
+        let updates_tx = updates_tx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_webhook_connection(stream, &secret, &updates_tx).await {
+                log_debug!("处理Webhook请求失败: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle_webhook_connection(
+    stream: TcpStream,
+    secret: &str,
+    updates_tx: &mpsc::UnboundedSender<Update>,
+) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+    while let Some(request) = read_http_request(&mut reader).await? {
+        let status = if request.method != "POST" {
+            "405 Method Not Allowed"
+        } else if request.headers.get("x-telegram-bot-api-secret-token").map(String::as_str) != Some(secret) {
+            "403 Forbidden"
+        } else {
+            match serde_json::from_slice::<Update>(&request.body) {
+                Ok(update) => {
+                    let _ = updates_tx.send(update);
+                    "200 OK"
+                }
+                Err(e) => {
+                    log_debug!("无法解析Webhook更新（{}）: {}", request.path, e);
+                    "400 Bad Request"
+                }
+            }
+        };
+        write_http_response(reader.get_mut(), status, "").await?;
+    }
+    Ok(())
+}
+
+/// 最简单的 HTTP/1.1 请求
+struct HttpRequest {
+    method: String,
+    path: String,
+    /// 头部名称统一为小写
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+/// 读取一个请求，连接关闭时返回 None
+async fn read_http_request<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<HttpRequest>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 {
+            return Err(anyhow!("请求头不完整"));
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length: usize = headers.get("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+    if length > WEBHOOK_MAX_BODY_BYTES {
+        return Err(anyhow!("请求体过大: {} 字节", length));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(HttpRequest { method, path, headers, body }))
+}
+
+async fn write_http_response<W: AsyncWrite + Unpin>(writer: &mut W, status: &str, body: &str) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    writer.write_all(response.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn registration(request_id: &str, message_ids: Vec<i32>, registered_at: i64) -> Registration {
+        Registration { request_id: request_id.to_string(), chat_id: 100, thread_id: None, message_ids, registered_at }
+    }
+
+    /// 本地假 Bot API：第一次 getUpdates 返回给定更新，之后返回空列表，其他方法一律成功
+    async fn fake_bot_api(listener: TcpListener, updates: serde_json::Value) {
+        let served = Arc::new(AtomicBool::new(false));
+        while let Ok((stream, _)) = listener.accept().await {
+            let served = served.clone();
+            let updates = updates.clone();
+            tokio::spawn(async move {
+                let mut reader = BufReader::new(stream);
+                while let Ok(Some(request)) = read_http_request(&mut reader).await {
+                    let method = request.path.rsplit('/').next().unwrap_or_default().to_ascii_lowercase();
+                    let result = if method != "getupdates" {
+                        json!(true)
+                    } else if !served.swap(true, Ordering::SeqCst) {
+                        updates.clone()
+                    } else {
+                        tokio::time::sleep(Duration::from_millis(200)).await;
+                        json!([])
+                    };
+                    let body = json!({ "ok": true, "result": result }).to_string();
+                    if write_http_response(reader.get_mut(), "200 OK", &body).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    }
+
+    async fn next(subscription: &mut Subscription) -> Update {
+        tokio::time::timeout(Duration::from_secs(5), subscription.recv()).await.unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_router_prefers_message_ids_then_latest_request() {
+        let mut router = UpdateRouter::default();
+        router.register(registration("first", vec![10, 11], 1000));
+        router.register(registration("second", vec![20], 1005));
+        let key = |callback: Option<i32>, reply_to: Option<i32>, date: Option<i64>| UpdateKey {
+            chat_id: 100,
+            thread_id: None,
+            callback_message_id: callback,
+            reply_to,
+            date,
+        };
+
+        assert_eq!(router.route(&key(Some(11), None, None)), Some("first"));
+        assert_eq!(router.route(&key(Some(99), None, None)), None);
+        assert_eq!(router.route(&key(None, Some(10), Some(1010))), Some("first"));
+        assert_eq!(router.route(&key(None, None, Some(1010))), Some("second"));
+        // 早于第二个请求注册的旧消息不会交给它
+        assert_eq!(router.route(&key(None, None, Some(1001))), Some("first"));
+        assert_eq!(router.route(&UpdateKey { chat_id: 200, ..key(None, None, Some(1010)) }), None);
+
+        router.unregister("second");
+        assert_eq!(router.route(&key(None, None, Some(1010))), Some("first"));
+    }
+
+    #[tokio::test]
+    async fn test_routes_updates_from_fake_bot_api() {
+        let api = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let api_url = reqwest::Url::parse(&format!("http://{}/", api.local_addr().unwrap())).unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let chat = json!({ "id": 100, "type": "private", "first_name": "U" });
+        let user = json!({ "id": 100, "is_bot": false, "first_name": "U" });
+        let updates = json!([
+            { "update_id": 1, "callback_query": { "id": "cb1", "from": user, "chat_instance": "1", "data": "toggle:A",
+                "message": { "message_id": 20, "date": now, "chat": chat, "text": "选项" } } },
+            { "update_id": 2, "message": { "message_id": 30, "date": now, "chat": chat, "from": user, "text": "回复第一个",
+                "reply_to_message": { "message_id": 10, "date": now, "chat": chat, "text": "操作" } } },
+            { "update_id": 3, "message": { "message_id": 31, "date": now, "chat": chat, "from": user, "text": "最新" } },
+        ]);
+        tokio::spawn(fake_bot_api(api, updates));
+
+        let hub_addr = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        let bot = Bot::new("123:test").set_api_url(api_url);
+        let options = DispatcherOptions { source: UpdateSource::Polling, hub_addr, hub_secret: hub_secret("123:test") };
+        let dispatcher = UpdateDispatcher::start(bot, options);
+
+        let mut first = dispatcher.subscribe(registration("first", vec![10], now));
+        let mut second = dispatcher.subscribe(registration("second", Vec::new(), now));
+        second.add_message_id(20);
+
+        assert!(matches!(next(&mut second).await.kind, UpdateKind::CallbackQuery(_)));
+        let UpdateKind::Message(reply) = next(&mut first).await.kind else { panic!("应为消息") };
+        assert_eq!(reply.text(), Some("回复第一个"));
+        let UpdateKind::Message(latest) = next(&mut second).await.kind else { panic!("应为消息") };
+        assert_eq!(latest.text(), Some("最新"));
+
+        // 没有密钥或密钥错误的连接在握手时被拒绝
+        assert!(connect_async(format!("ws://{}", hub_addr)).await.is_err());
+        let mut forged = format!("ws://{}", hub_addr).into_client_request().unwrap();
+        forged.headers_mut().insert(AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", hub_secret("456:other"))).unwrap());
+        assert!(connect_async(forged).await.is_err());
+    }
+}
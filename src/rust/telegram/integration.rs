@@ -5,6 +5,7 @@ use teloxide::prelude::*;
 use tokio::sync::Mutex;
 
 use super::core::{handle_text_message, TelegramCore, TelegramEvent};
+use super::dispatcher::{Registration, Subscription, UpdateDispatcher};
 use crate::log_important;
 
 /// Telegram集成管理器
@@ -30,15 +31,19 @@ impl TelegramIntegration {
     /// 创建新的Telegram集成实例，支持自定义API URL
     pub fn new_with_api_url(bot_token: String, chat_id: String, app_handle: AppHandle, api_url: Option<String>) -> Result<Self> {
         let core = TelegramCore::new_with_api_url(bot_token, chat_id, api_url)?;
+        Ok(Self::with_core(core, app_handle))
+    }
 
-        Ok(Self {
+    /// 使用已创建的核心实例（如按项目路由的 TelegramCore::from_config）
+    pub fn with_core(core: TelegramCore, app_handle: AppHandle) -> Self {
+        Self {
             core,
             app_handle,
             selected_options: Arc::new(Mutex::new(Vec::new())),
             user_input: Arc::new(Mutex::new(String::new())),
             operation_message_id: Arc::new(Mutex::new(None)),
             stop_sender: None,
-        })
+        }
     }
 
     /// 发送MCP请求消息到Telegram
@@ -55,16 +60,25 @@ impl TelegramIntegration {
             selected.clear();
         }
 
+        // 先注册到共享的更新分发器，再发送消息
+        let telegram_config = crate::config::load_standalone_config()?.telegram_config;
+        let dispatcher = UpdateDispatcher::shared(&self.core.bot, &telegram_config)?;
+        let request_id = uuid::Uuid::new_v4().to_string();
+        let subscription = dispatcher.subscribe(Registration::for_core(&self.core, &request_id));
+
         // 发送选项消息
-        self.core
+        let options_msg_id = self
+            .core
             .send_options_message(message, &predefined_options, is_markdown)
             .await?;
+        subscription.add_message_id(options_msg_id);
 
         // 发送操作消息
         let op_msg_id = self
             .core
             .send_operation_message(continue_reply_enabled)
             .await?;
+        subscription.add_message_id(op_msg_id);
 
         // 保存操作消息ID
         {
@@ -73,17 +87,15 @@ impl TelegramIntegration {
         }
 
         // 启动消息监听
-        self.start_message_listener().await?;
+        self.start_message_listener(subscription).await?;
 
         Ok(())
     }
 
-
-
     /// 启动消息监听
-    async fn start_message_listener(&mut self) -> Result<()> {
-        let bot = self.core.bot.clone();
-        let chat_id = self.core.chat_id;
+    async fn start_message_listener(&mut self, mut subscription: Subscription) -> Result<()> {
+        let core = self.core.clone();
+        let bot = core.bot.clone();
         let app_handle = self.app_handle.clone();
         let selected_options = self.selected_options.clone();
         let user_input = self.user_input.clone();
@@ -92,104 +104,90 @@ impl TelegramIntegration {
         let (stop_tx, mut stop_rx) = tokio::sync::oneshot::channel();
         self.stop_sender = Some(stop_tx);
 
-        // 启动监听任务
+        // 启动监听任务，只处理分发器路由给本请求的更新
         tokio::spawn(async move {
-            let mut offset = 0i32;
-
             loop {
                 tokio::select! {
                     _ = &mut stop_rx => {
                         break;
                     }
-                    _ = tokio::time::sleep(tokio::time::Duration::from_millis(1000)) => {
-                        // 轮询获取更新
-                        match bot.get_updates().offset(offset).await {
-                            Ok(updates) => {
-                                for update in updates {
-                                    offset = update.id.0 as i32 + 1;
-
-                                    // 处理不同类型的更新
-                                    match update.kind {
-                                        teloxide::types::UpdateKind::CallbackQuery(callback_query) => {
-                                            // 处理callback query
-                                            if let Some(message) = &callback_query.message {
-                                                if message.chat().id != chat_id {
-                                                    continue;
-                                                }
-                                            }
+                    update = subscription.recv() => {
+                        let Some(update) = update else { break };
+
+                        // 处理不同类型的更新
+                        match update.kind {
+                            teloxide::types::UpdateKind::CallbackQuery(callback_query) => {
+                                // 处理callback query（仅目标聊天和允许的用户）
+                                if !core.accepts_callback(&callback_query) {
+                                    continue;
+                                }
 
-                                            if let Some(data) = &callback_query.data {
-                                                if data.starts_with("toggle:") {
-                                                    let option = data.strip_prefix("toggle:").unwrap().to_string();
-
-                                                    // 切换选项状态
-                                                    let selected = {
-                                                        let mut selected_opts = selected_options.lock().await;
-                                                        if selected_opts.contains(&option) {
-                                                            selected_opts.retain(|x| x != &option);
-                                                            false
-                                                        } else {
-                                                            selected_opts.push(option.clone());
-                                                            true
-                                                        }
-                                                    };
-
-                                                    // 发送更新后的事件到前端
-                                                    let event = TelegramEvent::OptionToggled {
-                                                        option: option.clone(),
-                                                        selected,
-                                                    };
-
-                                                    if let Err(e) = app_handle.emit("telegram-event", &event) {
-                                                        log_important!(warn, "Telegram事件发送失败: {}", e);
-                                                    }
-                                                }
+                                if let Some(data) = &callback_query.data {
+                                    if data.starts_with("toggle:") {
+                                        let option = data.strip_prefix("toggle:").unwrap().to_string();
+
+                                        // 切换选项状态
+                                        let selected = {
+                                            let mut selected_opts = selected_options.lock().await;
+                                            if selected_opts.contains(&option) {
+                                                selected_opts.retain(|x| x != &option);
+                                                false
+                                            } else {
+                                                selected_opts.push(option.clone());
+                                                true
                                             }
+                                        };
 
-                                            // 回答callback query
-                                            let _ = bot.answer_callback_query(callback_query.id).await;
+                                        // 发送更新后的事件到前端
+                                        let event = TelegramEvent::OptionToggled {
+                                            option: option.clone(),
+                                            selected,
+                                        };
+
+                                        if let Err(e) = app_handle.emit("telegram-event", &event) {
+                                            log_important!(warn, "Telegram事件发送失败: {}", e);
                                         }
-                                        teloxide::types::UpdateKind::Message(message) => {
-                                            // 获取操作消息ID
-                                            let op_msg_id = {
-                                                let op_id = operation_message_id.lock().await;
-                                                *op_id
-                                            };
-
-                                            // 使用核心模块的处理函数
-                                            match handle_text_message(
-                                                &message,
-                                                chat_id,
-                                                op_msg_id,
-                                            ).await {
-                                                Ok(Some(event)) => {
-                                                    // 如果是文本更新，保存到用户输入
-                                                    if let TelegramEvent::TextUpdated { text } = &event {
-                                                        let mut input = user_input.lock().await;
-                                                        *input = text.clone();
-                                                    }
-
-                                                    // 发送事件到前端
-                                                    if let Err(e) = app_handle.emit("telegram-event", &event) {
-                                                        log_important!(warn, "Telegram文本事件发送失败: {}", e);
-                                                    }
-                                                }
-                                                Ok(None) => {
-                                                    // 文本消息被过滤或忽略
-                                                }
-                                                Err(e) => {
-                                                    log_important!(warn, "文本消息处理失败: {}", e);
-                                                }
-                                            }
+                                    }
+                                }
+
+                                // 回答callback query
+                                let _ = bot.answer_callback_query(callback_query.id).await;
+                            }
+                            teloxide::types::UpdateKind::Message(message) => {
+                                // 获取操作消息ID
+                                let op_msg_id = {
+                                    let op_id = operation_message_id.lock().await;
+                                    *op_id
+                                };
+
+                                // 使用核心模块的处理函数
+                                match handle_text_message(
+                                    &message,
+                                    &core,
+                                    op_msg_id,
+                                ).await {
+                                    Ok(Some(event)) => {
+                                        // 如果是文本更新，保存到用户输入
+                                        if let TelegramEvent::TextUpdated { text } = &event {
+                                            let mut input = user_input.lock().await;
+                                            *input = text.clone();
                                         }
-                                        _ => {
-                                            // 忽略其他类型的更新
+
+                                        // 发送事件到前端
+                                        if let Err(e) = app_handle.emit("telegram-event", &event) {
+                                            log_important!(warn, "Telegram文本事件发送失败: {}", e);
                                         }
                                     }
+                                    Ok(None) => {
+                                        // 文本消息被过滤或忽略
+                                    }
+                                    Err(e) => {
+                                        log_important!(warn, "文本消息处理失败: {}", e);
+                                    }
                                 }
                             }
-                            Err(_e) => {
-                                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                            _ => {
+                                // 忽略其他类型的更新
                             }
                         }
                     }
@@ -1,14 +1,68 @@
 use anyhow::Result;
 use std::collections::HashSet;
-use teloxide::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+use crate::app::coordinator::{ResponseChannel, ResponseCoordinator};
 use crate::config::load_standalone_config;
-use crate::mcp::types::{build_continue_response, build_send_response, PopupRequest};
-use crate::telegram::{handle_callback_query, handle_text_message, TelegramCore, TelegramEvent};
+use crate::mcp::types::{build_continue_response, build_send_response, ImageAttachment, PopupRequest};
+use crate::mcp::utils::decode_and_normalize_path;
+use crate::telegram::core::merge_reply_input;
+use crate::telegram::media::{download_message_file, ReceivedFile};
+use crate::telegram::speech::{SpeechBackend, SpeechToText};
+use crate::telegram::{
+    handle_callback_query, handle_text_message, Registration, Subscription, TelegramCore,
+    TelegramEvent, UpdateDispatcher,
+};
 use crate::log_important;
 
-/// 处理纯Telegram模式的MCP请求（不启动GUI）
-pub async fn handle_telegram_only_mcp_request(request_file: &str) -> Result<()> {
+/// 监听期间累积的用户回复
+#[derive(Debug, Default)]
+struct ReplyState {
+    selected_options: HashSet<String>,
+    /// 最近一条文字消息（新的文字消息替换它）
+    user_input: String,
+    /// 说明文字和语音转写，按收到的顺序保留，不会被文字消息覆盖
+    attached_input: Vec<String>,
+    images: Vec<ImageAttachment>,
+    /// 保存到临时目录的非图片文件
+    files: Vec<PathBuf>,
+    options_message_id: Option<i32>,
+    operation_message_id: Option<i32>,
+}
+
+impl ReplyState {
+    /// 文字消息替换之前输入的文字，说明文字和语音转写单独保留
+    fn set_text(&mut self, text: String) {
+        self.user_input = text;
+    }
+
+    /// 追加说明文字或语音转写
+    fn attach_text(&mut self, text: &str) {
+        let text = text.trim();
+        if !text.is_empty() {
+            self.attached_input.push(text.to_string());
+        }
+    }
+
+    /// 用户输入：文字消息、说明文字和语音转写，再附上收到的文件路径
+    fn full_input(&self) -> String {
+        let mut input = merge_reply_input(&self.user_input, &self.attached_input);
+        if !self.files.is_empty() {
+            if !input.is_empty() {
+                input.push_str("\n\n");
+            }
+            input.push_str("📎 用户发送的文件：");
+            for path in &self.files {
+                input.push_str(&format!("\n- {}", path.display()));
+            }
+        }
+        input
+    }
+}
+
+/// 通过Telegram处理MCP请求（纯Telegram模式下为唯一渠道，否则与弹窗同时等待回复）
+pub async fn handle_telegram_mcp_request(request_file: &str, coordinator: Arc<ResponseCoordinator>) -> Result<()> {
     // 读取MCP请求文件
     let request_json = std::fs::read_to_string(request_file)?;
     let request: PopupRequest = serde_json::from_str(&request_json)?;
@@ -27,102 +81,106 @@ pub async fn handle_telegram_only_mcp_request(request_file: &str) -> Result<()>
         return Ok(());
     }
 
-    // 创建Telegram核心实例，使用配置中的API URL
-    let api_url = if telegram_config.api_base_url == crate::constants::telegram::API_BASE_URL {
-        None
-    } else {
-        Some(telegram_config.api_base_url.clone())
-    };
+    // 按项目路径选择目标聊天并创建Telegram核心实例
+    let core = TelegramCore::from_config(telegram_config, request.project_path.as_deref()).await?;
 
-    let core = TelegramCore::new_with_api_url(
-        telegram_config.bot_token.clone(),
-        telegram_config.chat_id.clone(),
-        api_url,
-    )?;
+    // 先注册到共享的更新分发器，再发送消息，避免漏掉发送后立即到达的回复
+    let dispatcher = UpdateDispatcher::shared(&core.bot, telegram_config)?;
+    let subscription = dispatcher.subscribe(Registration::for_core(&core, &request.id));
 
     // 发送消息到Telegram
     let predefined_options = request.predefined_options.clone().unwrap_or_default();
 
+    // 先转发消息中引用的图片，选项按钮留在最后
+    if request.is_markdown {
+        let base_dir = request.project_path.as_deref().and_then(|p| decode_and_normalize_path(p).ok());
+        core.send_markdown_images(&request.message, base_dir.as_deref().map(Path::new)).await;
+    }
+
     // 发送选项消息
-    core.send_options_message(&request.message, &predefined_options, request.is_markdown)
+    let options_message_id = core
+        .send_options_message(&request.message, &predefined_options, request.is_markdown)
         .await?;
+    subscription.add_message_id(options_message_id);
 
     // 短暂延迟确保消息顺序
     tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
 
     // 发送操作消息（假设启用继续回复）
-    core.send_operation_message(true).await?;
+    let operation_message_id = core.send_operation_message(true).await?;
+    subscription.add_message_id(operation_message_id);
+
+    let state = ReplyState {
+        options_message_id: Some(options_message_id).filter(|id| *id > 0),
+        operation_message_id: Some(operation_message_id).filter(|id| *id > 0),
+        ..Default::default()
+    };
+
+    // 语音识别后端（未配置时语音只作为文件附上）
+    let speech = SpeechBackend::from_config(&telegram_config.speech_to_text);
 
     // 启动消息监听循环
-    start_telegram_mcp_listener(core, request, predefined_options).await
+    start_telegram_mcp_listener(core, subscription, request, predefined_options, state, speech, coordinator).await
 }
 
 /// 启动Telegram MCP消息监听循环
 async fn start_telegram_mcp_listener(
     core: TelegramCore,
+    mut subscription: Subscription,
     request: PopupRequest,
     predefined_options: Vec<String>,
+    mut state: ReplyState,
+    speech: Option<SpeechBackend>,
+    coordinator: Arc<ResponseCoordinator>,
 ) -> Result<()> {
-    let mut offset = 0i32;
-    let mut selected_options: HashSet<String> = HashSet::new();
-    let mut user_input = String::new();
-    let mut options_message_id: Option<i32> = None;
-
-    // 获取当前最新的消息ID作为基准
-    if let Ok(updates) = core.bot.get_updates().limit(10).await {
-        if let Some(update) = updates.last() {
-            offset = update.id.0 as i32 + 1;
-        }
-    }
-
-    // 监听循环（简化版本，只等待发送或继续操作）
+    // 只处理分发器路由给本请求的更新（只等待发送或继续操作），其他渠道先回复时撤回请求
     loop {
-        match core.bot.get_updates().offset(offset).timeout(10).await {
-            Ok(updates) => {
-                for update in updates {
-                    offset = update.id.0 as i32 + 1;
-
-                    match update.kind {
-                        teloxide::types::UpdateKind::CallbackQuery(callback_query) => {
-                            if let Err(e) = handle_callback_query_update(
-                                &core,
-                                &callback_query,
-                                &predefined_options,
-                                &mut selected_options,
-                                &mut options_message_id,
-                            ).await {
-                                log_important!(warn, "处理callback query失败: {}", e);
-                            }
-                        }
-                        teloxide::types::UpdateKind::Message(message) => {
-                            // 处理选项消息ID识别
-                            if let Err(e) = handle_message_update(
-                                &core,
-                                &message,
-                                &predefined_options,
-                                &mut options_message_id,
-                                &mut user_input,
-                                &selected_options,
-                                &request,
-                            ).await {
-                                if let Some(_result) = e.downcast_ref::<ProcessingComplete>() {
-                                    return Ok(());
-                                }
-                                log_important!(warn, "处理消息失败: {}", e);
-                            }
-                        }
-                        _ => {}
-                    }
+        let update = tokio::select! {
+            update = subscription.recv() => match update {
+                Some(update) => update,
+                None => break,
+            },
+            answer = coordinator.answered() => {
+                if answer.channel != ResponseChannel::Telegram {
+                    core.retract_request(state.options_message_id, state.operation_message_id, &answer.notice()).await;
+                }
+                return Ok(());
+            }
+        };
+
+        match update.kind {
+            teloxide::types::UpdateKind::CallbackQuery(callback_query) => {
+                if let Err(e) = handle_callback_query_update(
+                    &core,
+                    &callback_query,
+                    &predefined_options,
+                    &mut state,
+                ).await {
+                    log_important!(warn, "处理callback query失败: {}", e);
                 }
             }
-            Err(_) => {
-                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+            teloxide::types::UpdateKind::Message(message) => {
+                // 处理选项消息ID识别
+                if let Err(e) = handle_message_update(
+                    &core,
+                    &message,
+                    &predefined_options,
+                    &mut state,
+                    &request,
+                    speech.as_ref(),
+                    &coordinator,
+                ).await {
+                    if let Some(_result) = e.downcast_ref::<ProcessingComplete>() {
+                        return Ok(());
+                    }
+                    log_important!(warn, "处理消息失败: {}", e);
+                }
             }
+            _ => {}
         }
-
-        // 短暂延迟避免过于频繁的请求
-        tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
     }
+
+    Err(anyhow::anyhow!("Telegram更新分发已停止"))
 }
 
 /// 处理callback query更新
@@ -130,8 +188,7 @@ async fn handle_callback_query_update(
     core: &TelegramCore,
     callback_query: &teloxide::types::CallbackQuery,
     predefined_options: &[String],
-    selected_options: &mut HashSet<String>,
-    options_message_id: &mut Option<i32>,
+    state: &mut ReplyState,
 ) -> Result<()> {
     // 只有当有预定义选项时才处理 callback queries
     if predefined_options.is_empty() {
@@ -140,22 +197,22 @@ async fn handle_callback_query_update(
 
     // 从callback_query中提取消息ID
     if let Some(message) = &callback_query.message {
-        if options_message_id.is_none() {
-            *options_message_id = Some(message.id().0);
+        if state.options_message_id.is_none() {
+            state.options_message_id = Some(message.id().0);
         }
     }
 
-    if let Ok(Some(option)) = handle_callback_query(&core.bot, callback_query, core.chat_id).await {
+    if let Ok(Some(option)) = handle_callback_query(core, callback_query).await {
         // 切换选项状态
-        if selected_options.contains(&option) {
-            selected_options.remove(&option);
+        if state.selected_options.contains(&option) {
+            state.selected_options.remove(&option);
         } else {
-            selected_options.insert(option.clone());
+            state.selected_options.insert(option.clone());
         }
 
         // 更新按钮状态
-        if let Some(msg_id) = *options_message_id {
-            let selected_vec: Vec<String> = selected_options.iter().cloned().collect();
+        if let Some(msg_id) = state.options_message_id {
+            let selected_vec: Vec<String> = state.selected_options.iter().cloned().collect();
             let _ = core
                 .update_inline_keyboard(msg_id, predefined_options, &selected_vec)
                 .await;
@@ -170,27 +227,46 @@ async fn handle_message_update(
     core: &TelegramCore,
     message: &teloxide::types::Message,
     predefined_options: &[String],
-    options_message_id: &mut Option<i32>,
-    user_input: &mut String,
-    selected_options: &HashSet<String>,
+    state: &mut ReplyState,
     request: &PopupRequest,
+    speech: Option<&SpeechBackend>,
+    coordinator: &ResponseCoordinator,
 ) -> Result<()> {
     // 识别选项消息ID
-    identify_options_message_id(message, predefined_options, options_message_id);
+    identify_options_message_id(message, predefined_options, &mut state.options_message_id);
+
+    // 照片、语音和文件：下载后加入回复，说明文字作为补充输入
+    if core.accepts_message(message) {
+        match download_message_file(&core.bot, message).await {
+            Ok(Some(file)) => {
+                // 说明文字与语音转写一样单独保留，之后的文字消息不会覆盖
+                if let Some(caption) = message.caption() {
+                    state.attach_text(caption);
+                }
+                receive_file(core, file, state, speech).await;
+                return Ok(());
+            }
+            Ok(None) => {}
+            Err(e) => {
+                let _ = core.send_message(&format!("❌ 文件接收失败：{}", e)).await;
+                return Ok(());
+            }
+        }
+    }
 
     // 处理文本消息事件
-    if let Ok(Some(event)) = handle_text_message(message, core.chat_id, None).await {
+    if let Ok(Some(event)) = handle_text_message(message, core, None).await {
         match event {
             TelegramEvent::SendPressed => {
-                handle_send_pressed(core, selected_options, user_input, request).await?;
+                handle_send_pressed(core, state, request, coordinator).await?;
                 return Err(ProcessingComplete.into());
             }
             TelegramEvent::ContinuePressed => {
-                handle_continue_pressed(core, request).await?;
+                handle_continue_pressed(core, request, coordinator).await?;
                 return Err(ProcessingComplete.into());
             }
             TelegramEvent::TextUpdated { text } => {
-                *user_input = text;
+                state.set_text(text);
             }
             _ => {}
         }
@@ -199,6 +275,49 @@ async fn handle_message_update(
     Ok(())
 }
 
+/// 收下用户发来的文件：图片作为附图，语音转写后追加到输入，其他文件（含原始音频）保存到临时目录并在回复中附上路径
+async fn receive_file(
+    core: &TelegramCore,
+    file: ReceivedFile,
+    state: &mut ReplyState,
+    speech: Option<&SpeechBackend>,
+) {
+    let filename = file.filename.clone();
+    if file.is_image() {
+        state.images.push(file.into_attachment());
+        let notice = format!("🖼️ 已收到图片 {}（共 {} 张）", filename, state.images.len());
+        let _ = core.send_message(&notice).await;
+        return;
+    }
+
+    match file.save_to_temp() {
+        Ok(path) => state.files.push(path),
+        Err(e) => {
+            let _ = core.send_message(&format!("❌ 保存文件 {} 失败：{}", filename, e)).await;
+            return;
+        }
+    }
+    if !file.is_audio() {
+        let _ = core.send_message(&format!("📎 已收到文件 {}", filename)).await;
+        return;
+    }
+
+    let notice = match speech {
+        Some(backend) => {
+            let _ = core.send_message("🎙️ 正在识别语音…").await;
+            match backend.transcribe(&file).await {
+                Ok(text) => {
+                    state.attach_text(&text);
+                    format!("🎙️ 识别结果：\n{}", text)
+                }
+                Err(e) => format!("❌ 语音识别失败：{}（音频已作为文件附上）", e),
+            }
+        }
+        None => "🎙️ 已收到语音（未配置语音识别，音频已作为文件附上）".to_string(),
+    };
+    let _ = core.send_message(&notice).await;
+}
+
 /// 识别选项消息ID
 fn identify_options_message_id(
     message: &teloxide::types::Message,
@@ -237,36 +356,42 @@ fn identify_options_message_id(
 /// 处理发送按钮按下
 async fn handle_send_pressed(
     core: &TelegramCore,
-    selected_options: &HashSet<String>,
-    user_input: &str,
+    state: &ReplyState,
     request: &PopupRequest,
+    coordinator: &ResponseCoordinator,
 ) -> Result<()> {
     // 使用统一的响应构建函数
-    let selected_list: Vec<String> = selected_options.iter().cloned().collect();
+    let selected_list: Vec<String> = state.selected_options.iter().cloned().collect();
+    let user_input = state.full_input();
 
     let user_input_option = if user_input.is_empty() {
         None
     } else {
-        Some(user_input.to_string())
+        Some(user_input.clone())
     };
 
     let response = build_send_response(
         user_input_option,
         selected_list.clone(),
-        vec![], // 无GUI模式下没有图片
+        state.images.clone(),
         Some(request.id.clone()),
         "telegram",
     );
 
-    // 输出JSON响应到stdout（MCP协议要求）
-    println!("{}", response);
+    // 由协调器输出JSON响应到stdout（MCP协议要求），其他渠道已先回复时不再生效
+    if !coordinator.submit(ResponseChannel::Telegram, &response) {
+        return Ok(());
+    }
 
     // 发送确认消息（使用统一的反馈消息生成函数）
-    let feedback_message = crate::telegram::core::build_feedback_message(
+    let mut feedback_message = crate::telegram::core::build_feedback_message(
         &selected_list,
-        user_input,
+        &user_input,
         false, // 不是继续操作
     );
+    if !state.images.is_empty() {
+        feedback_message.push_str(&format!("\n\n🖼️ 图片：{} 张", state.images.len()));
+    }
     let _ = core.send_message(&feedback_message).await;
 
     Ok(())
@@ -276,6 +401,7 @@ async fn handle_send_pressed(
 async fn handle_continue_pressed(
     core: &TelegramCore,
     request: &PopupRequest,
+    coordinator: &ResponseCoordinator,
 ) -> Result<()> {
     // 使用统一的继续响应构建函数
     let response = build_continue_response(
@@ -283,8 +409,10 @@ async fn handle_continue_pressed(
         "telegram_continue",
     );
 
-    // 输出JSON响应到stdout（MCP协议要求）
-    println!("{}", response);
+    // 由协调器输出JSON响应到stdout（MCP协议要求），其他渠道已先回复时不再生效
+    if !coordinator.submit(ResponseChannel::Telegram, &response) {
+        return Ok(());
+    }
 
     // 发送确认消息（使用统一的反馈消息生成函数）
     let feedback_message = crate::telegram::core::build_feedback_message(
@@ -308,3 +436,23 @@ impl std::fmt::Display for ProcessingComplete {
 }
 
 impl std::error::Error for ProcessingComplete {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_message_keeps_transcripts_and_captions() {
+        let mut state = ReplyState::default();
+        state.attach_text("语音转写的内容");
+        state.set_text("第一条文字".to_string());
+        state.set_text("修改后的文字".to_string());
+        state.attach_text("图片说明");
+        state.files.push(PathBuf::from("/tmp/voice.ogg"));
+
+        assert_eq!(
+            state.full_input(),
+            "修改后的文字\n语音转写的内容\n图片说明\n\n📎 用户发送的文件：\n- /tmp/voice.ogg"
+        );
+    }
+}
@@ -0,0 +1,280 @@
+//! Telegram 图片与文件
+//!
+//! 下载用户发来的照片、语音和文件（图片转为 `ImageAttachment`，其他文件保存到临时目录），
+//! 拆分超出长度上限的 zhi 消息，并提取消息中的 Markdown 图片以便转发
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use std::path::{Path, PathBuf};
+use teloxide::{net::Download, prelude::*, types::FileMeta};
+
+use crate::constants::telegram::MAX_DOWNLOAD_BYTES;
+use crate::mcp::types::ImageAttachment;
+
+/// 临时目录下保存非图片文件的子目录
+pub const RECEIVED_FILES_DIR: &str = "cunzhi-telegram";
+
+/// 从 Telegram 下载的文件
+#[derive(Debug, Clone)]
+pub struct ReceivedFile {
+    pub filename: String,
+    pub media_type: String,
+    pub bytes: Vec<u8>,
+}
+
+impl ReceivedFile {
+    pub fn is_image(&self) -> bool {
+        self.media_type.starts_with("image/")
+    }
+
+    /// 语音消息或音频文件
+    pub fn is_audio(&self) -> bool {
+        self.media_type.starts_with("audio/")
+    }
+
+    pub fn into_attachment(self) -> ImageAttachment {
+        ImageAttachment {
+            data: base64::engine::general_purpose::STANDARD.encode(&self.bytes),
+            media_type: self.media_type,
+            filename: Some(self.filename),
+        }
+    }
+
+    /// 保存到临时目录，返回文件路径（同名文件加上时间戳前缀以免覆盖）
+    pub fn save_to_temp(&self) -> Result<PathBuf> {
+        let dir = std::env::temp_dir().join(RECEIVED_FILES_DIR);
+        std::fs::create_dir_all(&dir)?;
+        let name = Path::new(&self.filename)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .filter(|n| !n.is_empty())
+            .unwrap_or_else(|| "file".to_string());
+        let path = dir.join(format!("{}-{}", chrono::Local::now().format("%Y%m%d%H%M%S"), name));
+        std::fs::write(&path, &self.bytes)?;
+        Ok(path)
+    }
+}
+
+/// 下载消息中的照片、语音、音频或文件；消息不含这些内容时返回 None
+pub async fn download_message_file(bot: &Bot, message: &Message) -> Result<Option<ReceivedFile>> {
+    let (meta, filename, media_type) = if let Some(photo) = message.photo().and_then(|sizes| sizes.last()) {
+        // 同一张照片有多个尺寸，最后一个最大
+        (&photo.file, format!("photo_{}.jpg", message.id.0), "image/jpeg".to_string())
+    } else if let Some(voice) = message.voice() {
+        let media_type = voice
+            .mime_type
+            .as_ref()
+            .map(|m| m.essence_str().to_string())
+            .unwrap_or_else(|| "audio/ogg".to_string());
+        (&voice.file, format!("voice_{}.ogg", message.id.0), media_type)
+    } else if let Some(audio) = message.audio() {
+        let filename = audio.file_name.clone().unwrap_or_else(|| format!("audio_{}.mp3", message.id.0));
+        let media_type = audio
+            .mime_type
+            .as_ref()
+            .map(|m| m.essence_str().to_string())
+            .unwrap_or_else(|| guess_media_type(&filename).to_string());
+        (&audio.file, filename, media_type)
+    } else if let Some(document) = message.document() {
+        let filename = document.file_name.clone().unwrap_or_else(|| format!("file_{}", message.id.0));
+        let media_type = document
+            .mime_type
+            .as_ref()
+            .map(|m| m.essence_str().to_string())
+            .unwrap_or_else(|| guess_media_type(&filename).to_string());
+        (&document.file, filename, media_type)
+    } else {
+        return Ok(None);
+    };
+
+    let bytes = download(bot, meta).await?;
+    Ok(Some(ReceivedFile { filename, media_type, bytes }))
+}
+
+/// 通过 getFile 下载文件内容
+pub async fn download(bot: &Bot, meta: &FileMeta) -> Result<Vec<u8>> {
+    if meta.size > MAX_DOWNLOAD_BYTES {
+        return Err(anyhow!(
+            "文件过大（{} MB），Bot API 仅支持下载 {} MB 以内的文件",
+            meta.size / 1024 / 1024,
+            MAX_DOWNLOAD_BYTES / 1024 / 1024
+        ));
+    }
+
+    let file = bot
+        .get_file(meta.id.clone())
+        .await
+        .map_err(|e| anyhow!("获取文件信息失败: {}", e))?;
+    let mut bytes = Vec::with_capacity(meta.size as usize);
+    bot.download_file(&file.path, &mut bytes)
+        .await
+        .map_err(|e| anyhow!("下载文件失败: {}", e))?;
+    Ok(bytes)
+}
+
+/// 按扩展名推断媒体类型
+pub fn guess_media_type(filename: &str) -> &'static str {
+    let ext = Path::new(filename)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "wav" => "audio/wav",
+        "md" | "txt" | "log" => "text/plain",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Telegram 计算消息长度的方式：UTF-16 码元数（emoji 等辅助平面字符占 2 个）
+pub fn utf16_len(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+/// 按行拆分长消息，代码块内不拆分，每段长度按 UTF-16 码元计；
+/// 超过段数上限或某一段仍超长（如过长的代码块）时返回 None，由调用方改为发送文件
+pub fn split_message(text: &str, chunk_units: usize, max_parts: usize) -> Option<Vec<String>> {
+    let mut parts: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut current_units = 0;
+    let mut in_code_block = false;
+
+    for line in text.split_inclusive('\n') {
+        let line_units = utf16_len(line);
+        if !in_code_block && current_units > 0 && current_units + line_units > chunk_units {
+            parts.push(std::mem::take(&mut current));
+            current_units = 0;
+        }
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        current.push_str(line);
+        current_units += line_units;
+    }
+    if !current.trim().is_empty() {
+        parts.push(current);
+    }
+
+    let parts: Vec<String> = parts.into_iter().map(|p| p.trim_end().to_string()).collect();
+    (parts.len() <= max_parts && parts.iter().all(|p| utf16_len(p) <= chunk_units)).then_some(parts)
+}
+
+/// Markdown 图片的来源
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageSource {
+    Url(reqwest::Url),
+    File(PathBuf),
+    Data { media_type: String, bytes: Vec<u8> },
+}
+
+/// 提取 Markdown 中的图片地址（`![说明](地址 "标题")`），保持出现顺序并去重
+pub fn markdown_image_refs(markdown: &str) -> Vec<String> {
+    let re = regex::Regex::new(r#"!\[[^\]]*\]\(\s*<?([^)\s>]+)>?(?:\s+"[^"]*")?\s*\)"#).unwrap();
+    let mut refs: Vec<String> = Vec::new();
+    for cap in re.captures_iter(markdown) {
+        let target = cap[1].to_string();
+        if !refs.contains(&target) {
+            refs.push(target);
+        }
+    }
+    refs
+}
+
+/// 解析图片地址：http(s) 链接、data URL 或本地路径（相对路径按项目目录解析）
+///
+/// 本地文件只接受项目目录内（按规范路径判断，`../` 和指向外部的符号链接都会被拒绝）的图片文件，
+/// 未提供项目目录时不转发任何本地文件，避免把密钥等文件发到聊天中
+pub fn resolve_image_source(target: &str, base_dir: Option<&Path>) -> Option<ImageSource> {
+    if target.starts_with("http://") || target.starts_with("https://") {
+        return reqwest::Url::parse(target).ok().map(ImageSource::Url);
+    }
+    if let Some(rest) = target.strip_prefix("data:") {
+        let (header, payload) = rest.split_once(',')?;
+        let media_type = header.split(';').next().filter(|t| !t.is_empty()).unwrap_or("image/png");
+        let bytes = base64::engine::general_purpose::STANDARD.decode(payload).ok()?;
+        return Some(ImageSource::Data { media_type: media_type.to_string(), bytes });
+    }
+
+    let base_dir = base_dir?.canonicalize().ok()?;
+    let path = base_dir.join(target.strip_prefix("file://").unwrap_or(target)).canonicalize().ok()?;
+    let is_image = guess_media_type(&path.to_string_lossy()).starts_with("image/");
+    (is_image && path.starts_with(&base_dir) && path.is_file()).then_some(ImageSource::File(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_keeps_code_blocks_and_extracts_images() {
+        let intro = "第一段第一段第一段第一段第一段\n第二行第二行第二行第二行第二行\n";
+        let text = format!("{}```rust\nfn a() {{}}\nfn b() {{}}\n```\n最后一段\n", intro);
+        let parts = split_message(&text, 35, 3).unwrap();
+        assert_eq!(parts, vec![intro.trim_end(), "```rust\nfn a() {}\nfn b() {}\n```", "最后一段"]);
+
+        // 段数超限或代码块本身超长时改为发送文件
+        assert!(split_message(&text, 35, 2).is_none());
+        assert!(split_message(&text, 20, 5).is_none());
+
+        let markdown = "见截图 ![界面](docs/ui.png) 和 ![图](https://example.com/a.png \"标题\")\n再次 ![界面](docs/ui.png)";
+        assert_eq!(markdown_image_refs(markdown), vec!["docs/ui.png", "https://example.com/a.png"]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::write(root.join("docs/ui.png"), b"png").unwrap();
+        assert_eq!(
+            resolve_image_source("docs/ui.png", Some(dir.path())),
+            Some(ImageSource::File(root.join("docs/ui.png")))
+        );
+        assert_eq!(resolve_image_source("docs/missing.png", Some(dir.path())), None);
+        assert_eq!(
+            resolve_image_source("data:image/gif;base64,R0lG", None),
+            Some(ImageSource::Data { media_type: "image/gif".to_string(), bytes: b"GIF".to_vec() })
+        );
+
+        // 按 UTF-16 计长：emoji 占 2 个码元
+        assert_eq!(utf16_len("a😀"), 3);
+        assert!(split_message("😀😀\n😀😀\n", 5, 2).is_some_and(|parts| parts.len() == 2));
+    }
+
+    #[test]
+    fn test_local_images_are_confined_to_project() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.png"), b"png").unwrap();
+        let project = outside.path().join("project");
+        std::fs::create_dir_all(project.join("docs")).unwrap();
+        std::fs::write(project.join("docs/ui.png"), b"png").unwrap();
+        std::fs::write(project.join("docs/id_rsa"), b"key").unwrap();
+        std::fs::write(project.join("docs/notes.txt"), b"text").unwrap();
+
+        let outside_png = outside.path().join("secret.png").to_string_lossy().to_string();
+        for target in [
+            "../secret.png".to_string(),
+            "docs/../../secret.png".to_string(),
+            outside_png.clone(),
+            format!("file://{}", outside_png),
+            "docs/id_rsa".to_string(),
+            "docs/notes.txt".to_string(),
+        ] {
+            assert_eq!(resolve_image_source(&target, Some(&project)), None, "{}", target);
+        }
+        // 未提供项目目录时不转发本地文件
+        assert_eq!(resolve_image_source("docs/ui.png", None), None);
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(outside.path().join("secret.png"), project.join("docs/link.png")).unwrap();
+            assert_eq!(resolve_image_source("docs/link.png", Some(&project)), None);
+        }
+        let inside = project.join("docs/ui.png").canonicalize().unwrap();
+        assert_eq!(resolve_image_source(&inside.to_string_lossy(), Some(&project)), Some(ImageSource::File(inside)));
+    }
+}
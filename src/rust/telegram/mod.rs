@@ -1,14 +1,20 @@
 pub mod commands;
 pub mod core;
+pub mod dispatcher;
 pub mod integration;
 pub mod markdown;
 pub mod mcp_handler;
+pub mod media;
+pub mod routing;
+pub mod speech;
 
 pub use commands::*;
 pub use core::{
     handle_callback_query, handle_text_message, test_telegram_connection, TelegramCore,
     TelegramEvent,
 };
+pub use dispatcher::{Registration, Subscription, UpdateDispatcher};
 pub use integration::TelegramIntegration;
 pub use markdown::process_telegram_markdown;
-pub use mcp_handler::handle_telegram_only_mcp_request;
+pub use mcp_handler::handle_telegram_mcp_request;
+pub use routing::{resolve_target, TelegramTarget};
@@ -0,0 +1,90 @@
+//! Telegram 聊天路由
+//!
+//! 按项目路径把请求发到不同的聊天或论坛话题（最长前缀优先，未匹配时使用默认聊天），
+//! 并按允许列表限制谁可以作答
+
+use crate::config::TelegramConfig;
+
+/// 请求的目标聊天
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TelegramTarget {
+    /// 数字 Chat ID 或 `@channel`
+    pub chat_id: String,
+    pub message_thread_id: Option<i32>,
+}
+
+/// 统一分隔符并去掉末尾斜杠，便于按路径前缀比较
+fn normalize_path(path: &str) -> String {
+    let normalized = path.trim().replace('\\', "/");
+    let trimmed = normalized.trim_end_matches('/');
+    if trimmed.is_empty() { normalized } else { trimmed.to_string() }
+}
+
+/// 按目录边界判断前缀，避免 `/work/app` 匹配到 `/work/app2`
+fn path_has_prefix(path: &str, prefix: &str) -> bool {
+    path == prefix
+        || path.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/') || prefix.ends_with('/'))
+}
+
+/// 选择项目对应的聊天：路由规则中最长的匹配前缀优先，规则未指定 Chat ID 时沿用默认聊天
+pub fn resolve_target(config: &TelegramConfig, project_path: Option<&str>) -> TelegramTarget {
+    let default = TelegramTarget {
+        chat_id: config.chat_id.trim().to_string(),
+        message_thread_id: config.message_thread_id,
+    };
+    let Some(project) = project_path.map(normalize_path).filter(|p| !p.is_empty()) else {
+        return default;
+    };
+
+    config
+        .routes
+        .iter()
+        .filter_map(|route| {
+            let prefix = normalize_path(&route.project_path);
+            (!prefix.is_empty() && path_has_prefix(&project, &prefix)).then_some((prefix.len(), route))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, route)| TelegramTarget {
+            chat_id: Some(route.chat_id.trim()).filter(|c| !c.is_empty()).unwrap_or(&default.chat_id).to_string(),
+            message_thread_id: route.message_thread_id,
+        })
+        .unwrap_or(default)
+}
+
+/// 允许列表为空时不限制；否则只有列表中的用户可以作答
+pub fn is_user_allowed(allowed_user_ids: &[i64], user_id: Option<u64>) -> bool {
+    allowed_user_ids.is_empty()
+        || user_id.and_then(|id| i64::try_from(id).ok()).is_some_and(|id| allowed_user_ids.contains(&id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{default_telegram_config, TelegramRoute};
+
+    #[test]
+    fn test_longest_prefix_route_wins() {
+        let mut config = default_telegram_config();
+        config.chat_id = "100".to_string();
+        config.routes = vec![
+            TelegramRoute { project_path: "/work".to_string(), chat_id: "-200".to_string(), message_thread_id: None },
+            TelegramRoute { project_path: "C:\\work\\app\\".to_string(), chat_id: String::new(), message_thread_id: Some(7) },
+            TelegramRoute { project_path: "/work/app".to_string(), chat_id: "@team".to_string(), message_thread_id: Some(3) },
+        ];
+
+        let target = |path: Option<&str>| {
+            let t = resolve_target(&config, path);
+            (t.chat_id, t.message_thread_id)
+        };
+        assert_eq!(target(Some("/work/app/src")), ("@team".to_string(), Some(3)));
+        assert_eq!(target(Some("/work/app2")), ("-200".to_string(), None));
+        assert_eq!(target(Some("C:/work/app")), ("100".to_string(), Some(7)));
+        assert_eq!(target(Some("/home/me")), ("100".to_string(), None));
+        assert_eq!(target(None), ("100".to_string(), None));
+
+        assert!(is_user_allowed(&[], None));
+        assert!(is_user_allowed(&[42], Some(42)));
+        assert!(!is_user_allowed(&[42], Some(43)));
+        assert!(!is_user_allowed(&[42], None));
+    }
+}
@@ -0,0 +1,301 @@
+//! 语音转文字
+//!
+//! 语音消息交给可替换的识别后端：本地 whisper.cpp 兼容程序（`-f 音频 -nt` 输出到 stdout），
+//! 或接受 multipart 上传的 HTTP 接口（OpenAI `/v1/audio/transcriptions`、whisper.cpp server `/inference` 等）
+
+use anyhow::{anyhow, Result};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+
+use super::media::{ReceivedFile, RECEIVED_FILES_DIR};
+use crate::config::{SpeechToTextBackend, SpeechToTextConfig};
+use crate::constants::telegram::SPEECH_TO_TEXT_TIMEOUT_SECS;
+
+/// 语音识别后端
+pub trait SpeechToText {
+    /// 识别音频，返回转写文本
+    fn transcribe(&self, audio: &ReceivedFile) -> impl Future<Output = Result<String>> + Send;
+}
+
+/// 按配置选择的识别后端
+#[derive(Debug, Clone)]
+pub enum SpeechBackend {
+    WhisperCpp(WhisperCppTranscriber),
+    Http(HttpTranscriber),
+}
+
+impl SpeechBackend {
+    /// 从配置创建；未启用或缺少必填项时返回 None
+    pub fn from_config(config: &SpeechToTextConfig) -> Option<Self> {
+        let timeout = Duration::from_secs(SPEECH_TO_TEXT_TIMEOUT_SECS);
+        match config.backend {
+            SpeechToTextBackend::Disabled => None,
+            SpeechToTextBackend::WhisperCpp => {
+                let binary_path = config.binary_path.trim();
+                (!binary_path.is_empty()).then(|| {
+                    Self::WhisperCpp(WhisperCppTranscriber {
+                        binary_path: PathBuf::from(binary_path),
+                        model_path: config.model_path.trim().to_string(),
+                        ffmpeg_path: config.ffmpeg_path.trim().to_string(),
+                        language: config.language.trim().to_string(),
+                        extra_args: config.extra_args.clone(),
+                        timeout,
+                    })
+                })
+            }
+            SpeechToTextBackend::Http => {
+                let endpoint_url = config.endpoint_url.trim();
+                (!endpoint_url.is_empty()).then(|| {
+                    Self::Http(HttpTranscriber {
+                        endpoint_url: endpoint_url.to_string(),
+                        api_key: config.api_key.trim().to_string(),
+                        model: config.model.trim().to_string(),
+                        language: config.language.trim().to_string(),
+                        timeout,
+                    })
+                })
+            }
+        }
+    }
+}
+
+impl SpeechToText for SpeechBackend {
+    async fn transcribe(&self, audio: &ReceivedFile) -> Result<String> {
+        match self {
+            Self::WhisperCpp(backend) => backend.transcribe(audio).await,
+            Self::Http(backend) => backend.transcribe(audio).await,
+        }
+    }
+}
+
+/// 本地 whisper.cpp 兼容程序
+#[derive(Debug, Clone)]
+pub struct WhisperCppTranscriber {
+    pub binary_path: PathBuf,
+    pub model_path: String,
+    pub ffmpeg_path: String,
+    pub language: String,
+    pub extra_args: Vec<String>,
+    pub timeout: Duration,
+}
+
+impl SpeechToText for WhisperCppTranscriber {
+    async fn transcribe(&self, audio: &ReceivedFile) -> Result<String> {
+        let work_dir = std::env::temp_dir()
+            .join(RECEIVED_FILES_DIR)
+            .join(format!("stt-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&work_dir)?;
+        let result = self.transcribe_in(&work_dir, audio).await;
+        let _ = std::fs::remove_dir_all(&work_dir);
+        result
+    }
+}
+
+impl WhisperCppTranscriber {
+    async fn transcribe_in(&self, work_dir: &Path, audio: &ReceivedFile) -> Result<String> {
+        let ext = Path::new(&audio.filename)
+            .extension()
+            .map(|e| e.to_string_lossy().to_string())
+            .unwrap_or_else(|| "ogg".to_string());
+        let mut input = work_dir.join(format!("input.{}", ext));
+        std::fs::write(&input, &audio.bytes)?;
+
+        // whisper.cpp 只接受 16kHz WAV，Telegram 语音是 OGG/Opus
+        if !self.ffmpeg_path.is_empty() {
+            let wav = work_dir.join("input.wav");
+            let mut ffmpeg = Command::new(&self.ffmpeg_path);
+            ffmpeg.args(["-y", "-loglevel", "error", "-i"]).arg(&input).args(["-ar", "16000", "-ac", "1"]).arg(&wav);
+            run(ffmpeg, self.timeout, "ffmpeg").await?;
+            input = wav;
+        }
+
+        let mut whisper = Command::new(&self.binary_path);
+        if !self.model_path.is_empty() {
+            whisper.arg("-m").arg(&self.model_path);
+        }
+        if !self.language.is_empty() {
+            whisper.arg("-l").arg(&self.language);
+        }
+        whisper.arg("-nt").args(&self.extra_args).arg("-f").arg(&input);
+        let stdout = run(whisper, self.timeout, "语音识别程序").await?;
+
+        let text = normalize_transcript(&stdout);
+        if text.is_empty() {
+            return Err(anyhow!("未识别到语音内容"));
+        }
+        Ok(text)
+    }
+}
+
+/// 运行命令并返回 stdout，非零退出时带上 stderr 最后一行
+async fn run(mut command: Command, timeout: Duration, label: &str) -> Result<String> {
+    command.kill_on_drop(true);
+    let output = tokio::time::timeout(timeout, command.output())
+        .await
+        .map_err(|_| anyhow!("{}超时（{} 秒）", label, timeout.as_secs()))?
+        .map_err(|e| anyhow!("启动{}失败: {}", label, e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!(
+            "{}执行失败（{}）: {}",
+            label,
+            output.status,
+            stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("").trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// 整理转写输出：去掉空行和 `[BLANK_AUDIO]` 之类的整行标记
+pub fn normalize_transcript(output: &str) -> String {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .filter(|line| !is_marker(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 整行的非语音标记，如 `[BLANK_AUDIO]`、`(music)`
+fn is_marker(line: &str) -> bool {
+    [('[', ']'), ('(', ')')].iter().any(|(open, close)| line.starts_with(*open) && line.ends_with(*close))
+}
+
+/// multipart 上传的 HTTP 识别接口
+#[derive(Debug, Clone)]
+pub struct HttpTranscriber {
+    pub endpoint_url: String,
+    pub api_key: String,
+    pub model: String,
+    pub language: String,
+    pub timeout: Duration,
+}
+
+impl SpeechToText for HttpTranscriber {
+    async fn transcribe(&self, audio: &ReceivedFile) -> Result<String> {
+        let part = reqwest::multipart::Part::bytes(audio.bytes.clone())
+            .file_name(audio.filename.clone())
+            .mime_str(&audio.media_type)?;
+        let mut form = reqwest::multipart::Form::new()
+            .part("file", part)
+            .text("response_format", "json");
+        if !self.model.is_empty() {
+            form = form.text("model", self.model.clone());
+        }
+        if !self.language.is_empty() {
+            form = form.text("language", self.language.clone());
+        }
+
+        let client = reqwest::Client::builder().timeout(self.timeout).build()?;
+        let mut request = client.post(&self.endpoint_url).multipart(form);
+        if !self.api_key.is_empty() {
+            request = request.bearer_auth(&self.api_key);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| anyhow!("请求语音识别接口失败: {}", e))?;
+
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            return Err(anyhow!("语音识别接口返回 {}: {}", status, body.chars().take(200).collect::<String>()));
+        }
+
+        let text = parse_transcription(&body);
+        if text.is_empty() {
+            return Err(anyhow!("未识别到语音内容"));
+        }
+        Ok(text)
+    }
+}
+
+/// 解析接口响应：JSON 取 `text` 字段，否则按纯文本处理
+pub fn parse_transcription(body: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(value) => value
+            .get("text")
+            .and_then(|t| t.as_str())
+            .map(normalize_transcript)
+            .unwrap_or_default(),
+        Err(_) => normalize_transcript(body),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn voice() -> ReceivedFile {
+        ReceivedFile { filename: "voice_1.ogg".to_string(), media_type: "audio/ogg".to_string(), bytes: b"OggS-voice".to_vec() }
+    }
+
+    #[tokio::test]
+    async fn test_stub_backends_transcribe_voice() {
+        // 假的 whisper 程序：确认收到的参数和音频内容后输出转写结果
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("whisper.sh");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\nfor a; do last=$a; done\n[ \"$1 $2 $3 $4 $5\" = \"-m model.bin -l zh -nt\" ] || { echo \"bad args: $*\" >&2; exit 2; }\ngrep -q OggS-voice \"$last\" || exit 3\nprintf '\\n [BLANK_AUDIO]\\n 把按钮改成蓝色\\n 然后提交\\n'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = SpeechToTextConfig {
+            backend: SpeechToTextBackend::WhisperCpp,
+            binary_path: script.to_string_lossy().to_string(),
+            model_path: "model.bin".to_string(),
+            language: "zh".to_string(),
+            ..Default::default()
+        };
+        let whisper = SpeechBackend::from_config(&config).unwrap();
+        assert_eq!(whisper.transcribe(&voice()).await.unwrap(), "把按钮改成蓝色\n然后提交");
+
+        config.model_path = "other.bin".to_string();
+        let err = SpeechBackend::from_config(&config).unwrap().transcribe(&voice()).await.unwrap_err();
+        assert!(err.to_string().contains("bad args"));
+
+        // 本地 HTTP 桩：检查 multipart 内容和鉴权头后返回 JSON
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            let mut buf = [0u8; 4096];
+            while !String::from_utf8_lossy(&received).contains("whisper-1") || !received.ends_with(b"--\r\n") {
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                received.extend_from_slice(&buf[..n]);
+            }
+            let body = r#"{"text":" 运行测试 "}"#;
+            let response = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&received).to_string()
+        });
+
+        let config = SpeechToTextConfig {
+            backend: SpeechToTextBackend::Http,
+            endpoint_url: format!("http://{}/v1/audio/transcriptions", addr),
+            api_key: "secret".to_string(),
+            model: "whisper-1".to_string(),
+            ..Default::default()
+        };
+        let http = SpeechBackend::from_config(&config).unwrap();
+        assert_eq!(http.transcribe(&voice()).await.unwrap(), "运行测试");
+        let request = server.await.unwrap();
+        assert!(request.to_lowercase().contains("authorization: bearer secret"));
+        assert!(request.contains("filename=\"voice_1.ogg\"") && request.contains("OggS-voice"));
+
+        assert!(SpeechBackend::from_config(&SpeechToTextConfig::default()).is_none());
+        assert_eq!(parse_transcription("纯文本结果\n"), "纯文本结果");
+    }
+}
@@ -1,7 +1,10 @@
-use crate::config::{save_config, load_config, AppState, ReplyConfig, WindowConfig, CustomPrompt, CustomPromptConfig, ShortcutConfig, ShortcutBinding};
+use crate::config::{save_config, load_config, AppState, ReplyConfig, WindowConfig, CustomPrompt, CustomPromptConfig, ShortcutConfig, ShortcutBinding, ConversationConfig};
+use crate::app::{ResponseChannel, ResponseCoordinator};
 use crate::constants::{window, ui, validation};
+use crate::constants::app::CHANNEL_SETTLE_TIMEOUT_SECS;
 use crate::mcp::types::{build_continue_response, build_send_response, ImageAttachment, PopupRequest};
 use crate::mcp::handlers::create_tauri_popup;
+use std::time::Duration;
 use tauri::{AppHandle, Manager, State};
 
 #[tauri::command]
@@ -385,7 +388,17 @@ pub async fn send_mcp_response(
     let args: Vec<String> = std::env::args().collect();
     let is_mcp_mode = args.len() >= 3 && args[1] == "--mcp-request";
 
-    if is_mcp_mode {
+    if let Some(coordinator) = ResponseCoordinator::global().filter(|_| is_mcp_mode) {
+        // MCP模式：由协调器输出到stdout（MCP协议要求），其他渠道已先回复时拒绝
+        if !coordinator.submit(ResponseChannel::Popup, &response_str) {
+            return Err("该请求已在其他渠道回复".to_string());
+        }
+        coordinator.settle(ResponseChannel::Popup);
+        // 等待其他渠道撤回请求后再退出
+        coordinator
+            .wait_settled(Duration::from_secs(CHANNEL_SETTLE_TIMEOUT_SECS))
+            .await;
+    } else if is_mcp_mode {
         // MCP模式：直接输出到stdout（MCP协议要求）
         println!("{}", response_str);
         std::io::Write::flush(&mut std::io::stdout())
@@ -972,6 +985,43 @@ pub async fn reset_shortcuts_to_default(
     Ok(())
 }
 
+// 对话日志相关命令
+
+/// 获取对话日志配置
+#[tauri::command]
+pub async fn get_conversation_config(state: State<'_, AppState>) -> Result<ConversationConfig, String> {
+    let config = state
+        .config
+        .lock()
+        .map_err(|e| format!("获取配置失败: {}", e))?;
+    Ok(config.conversation_config.clone())
+}
+
+/// 设置对话日志配置（脱敏规则、排除项目、保留天数、远程同步）
+#[tauri::command]
+pub async fn set_conversation_config(
+    conversation_config: ConversationConfig,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<(), String> {
+    crate::mcp::tools::interaction::redact::validate_patterns(&conversation_config.custom_patterns)?;
+
+    {
+        let mut config = state
+            .config
+            .lock()
+            .map_err(|e| format!("获取配置失败: {}", e))?;
+        config.conversation_config = conversation_config;
+    }
+
+    // 保存配置到文件
+    save_config(&state, &app)
+        .await
+        .map_err(|e| format!("保存配置失败: {}", e))?;
+
+    Ok(())
+}
+
 // 窗口注册相关命令
 
 /// 注册当前窗口实例
@@ -101,7 +101,7 @@ impl WindowRegistry {
     }
 }
 
-fn is_process_running(pid: u32) -> bool {
+pub fn is_process_running(pid: u32) -> bool {
     #[cfg(target_os = "macos")]
     {
         use std::process::Command;
@@ -0,0 +1,212 @@
+//! 文件锁与原子写入工具
+//!
+//! 为 .cunzhi-memory 和 .cunzhi-knowledge 中的"读取-修改-写回"操作提供跨进程互斥，
+//! 并通过"写临时文件 + rename"保证读者永远看不到写了一半的文件。
+//! 锁文件放在用户缓存目录中，不会出现在项目或知识库仓库的 `git status` 里
+
+use anyhow::Result;
+use fs2::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 独占文件锁，离开作用域时自动释放
+pub struct FileLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl FileLock {
+    /// 对指定的锁文件加独占锁（阻塞直到获得）
+    pub fn acquire(lock_path: &Path) -> Result<Self> {
+        if let Some(parent) = lock_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .open(lock_path)
+            .map_err(|e| anyhow::anyhow!("无法打开锁文件 {}: {}", lock_path.display(), e))?;
+
+        file.lock_exclusive()
+            .map_err(|e| anyhow::anyhow!("获取文件锁失败 {}: {}", lock_path.display(), e))?;
+
+        Ok(Self {
+            file,
+            path: lock_path.to_path_buf(),
+        })
+    }
+
+    /// 为目标文件加锁，锁文件见 [`lock_path_for`]
+    ///
+    /// 不直接锁目标文件本身，因为原子替换后旧的文件句柄上的锁会失效
+    pub fn for_file(target: &Path) -> Result<Self> {
+        // 先创建目标目录，保证各进程按同一个规范路径计算锁文件
+        if let Some(parent) = target.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        Self::acquire(&lock_path_for(target))
+    }
+
+    /// 锁文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// 计算目标文件对应的锁文件路径：`<缓存目录>/cunzhi/locks/<路径哈希>-<文件名>.lock`
+///
+/// 按目标文件的规范绝对路径取哈希，同一文件在所有进程中得到同一个锁文件
+pub fn lock_path_for(target: &Path) -> PathBuf {
+    let file_name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "unnamed".to_string());
+    let dir = target
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let dir = fs::canonicalize(dir)
+        .or_else(|_| std::path::absolute(dir))
+        .unwrap_or_else(|_| dir.to_path_buf());
+
+    let key = dir.join(&file_name);
+    let digest = ring::digest::digest(&ring::digest::SHA256, key.to_string_lossy().as_bytes());
+    lock_dir().join(format!("{}-{}.lock", &hex::encode(digest.as_ref())[..16], file_name))
+}
+
+/// 锁文件目录
+fn lock_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("cunzhi")
+        .join("locks")
+}
+
+/// 原子写入：先写同目录临时文件并 fsync，再 rename 覆盖目标文件；
+/// 目标文件已存在时沿用其权限，避免替换后变成 umask 默认权限
+pub fn atomic_write(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(dir)?;
+
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "unnamed".to_string());
+    let tmp_path = dir.join(format!(".{}.tmp-{}", file_name, uuid::Uuid::new_v4()));
+
+    let write_result = (|| -> Result<()> {
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(contents.as_ref())?;
+        if let Ok(metadata) = fs::metadata(path) {
+            tmp.set_permissions(metadata.permissions())?;
+        }
+        tmp.sync_all()?;
+        Ok(())
+    })();
+
+    if let Err(e) = write_result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(anyhow::anyhow!("写入临时文件失败 {}: {}", tmp_path.display(), e));
+    }
+
+    if let Err(e) = fs::rename(&tmp_path, path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(anyhow::anyhow!("替换文件失败 {}: {}", path.display(), e));
+    }
+
+    Ok(())
+}
+
+/// 在文件锁保护下执行"读取-修改-写回"
+///
+/// 文件不存在时以 `initial` 作为初始内容；闭包返回 Err 时不写回
+pub fn update_locked<T, F>(path: &Path, initial: &str, f: F) -> Result<T>
+where
+    F: FnOnce(&mut String) -> Result<T>,
+{
+    let _lock = FileLock::for_file(path)?;
+
+    let mut content = if path.exists() {
+        fs::read_to_string(path)?
+    } else {
+        initial.to_string()
+    };
+
+    let result = f(&mut content)?;
+    atomic_write(path, content.as_bytes())?;
+
+    Ok(result)
+}
+
+/// 在文件锁保护下整体写入文件
+pub fn write_locked(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let _lock = FileLock::for_file(path)?;
+    atomic_write(path, contents)
+}
+
+/// 仅在文件不存在时创建（已存在则不做任何事），避免并发初始化覆盖已写入的内容；返回是否新建了文件
+pub fn create_if_missing(path: &Path, contents: &str) -> Result<bool> {
+    match OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(mut file) => {
+            file.write_all(contents.as_bytes())?;
+            Ok(true)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_files_stay_out_of_target_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join(".cunzhi-memory").join("rules.md");
+
+        update_locked(&target, "# 规则\n", |content| {
+            content.push_str("- 使用中文\n");
+            Ok(())
+        })
+        .unwrap();
+
+        // 目标目录中只有目标文件本身
+        let names: Vec<_> = fs::read_dir(target.parent().unwrap())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec!["rules.md"]);
+
+        // 同一文件的不同写法得到同一个锁文件
+        let lock = lock_path_for(&target);
+        assert!(!lock.starts_with(dir.path()));
+        assert_eq!(lock, lock_path_for(&dir.path().join(".cunzhi-memory/../.cunzhi-memory/rules.md")));
+        assert_ne!(lock, lock_path_for(&dir.path().join("rules.md")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_atomic_write_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("knowledge.md");
+        fs::write(&target, "旧内容").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+
+        atomic_write(&target, "新内容").unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "新内容");
+        assert_eq!(fs::metadata(&target).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+}
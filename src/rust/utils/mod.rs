@@ -1,3 +1,7 @@
 pub mod logger;
+pub mod file_lock;
+pub mod tokenizer;
 
 pub use logger::{LogConfig, init_logger, auto_init_logger};
+pub use file_lock::{FileLock, atomic_write, update_locked, write_locked, create_if_missing};
+pub use tokenizer::{token_match, tokenize};
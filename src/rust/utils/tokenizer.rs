@@ -0,0 +1,118 @@
+//! 检索用的分词与模糊匹配（知识库 xi 与提示词库 ci 共用）
+//!
+//! 英文/数字按单词切分并转小写，中文等 CJK 文本切为二元组（单字保留原样）；
+//! 较长的英文词元允许 1～2 个字符的拼写误差
+
+/// 模糊命中相对精确命中的折扣
+const FUZZY_FACTOR: f64 = 0.6;
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF     // 日文假名
+        | 0x3400..=0x4DBF   // CJK 扩展 A
+        | 0x4E00..=0x9FFF   // CJK 统一汉字
+        | 0xAC00..=0xD7AF   // 韩文
+        | 0xF900..=0xFAFF)  // CJK 兼容汉字
+}
+
+/// 切分词元（小写）
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut cjk_run: Vec<char> = Vec::new();
+
+    let flush_word = |word: &mut String, tokens: &mut Vec<String>| {
+        if !word.is_empty() {
+            tokens.push(std::mem::take(word));
+        }
+    };
+    let flush_cjk = |run: &mut Vec<char>, tokens: &mut Vec<String>| {
+        match run.len() {
+            0 => {}
+            1 => tokens.push(run[0].to_string()),
+            _ => tokens.extend(run.windows(2).map(|w| w.iter().collect::<String>())),
+        }
+        run.clear();
+    };
+
+    for c in text.chars() {
+        if is_cjk(c) {
+            flush_word(&mut word, &mut tokens);
+            cjk_run.push(c);
+        } else if c.is_alphanumeric() || c == '_' {
+            flush_cjk(&mut cjk_run, &mut tokens);
+            word.extend(c.to_lowercase());
+        } else {
+            flush_word(&mut word, &mut tokens);
+            flush_cjk(&mut cjk_run, &mut tokens);
+        }
+    }
+    flush_word(&mut word, &mut tokens);
+    flush_cjk(&mut cjk_run, &mut tokens);
+    tokens
+}
+
+/// 编辑距离（超过 max 时提前返回 max + 1）
+fn bounded_edit_distance(a: &str, b: &str, max: usize) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max {
+        return max + 1;
+    }
+
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (prev[j] + 1).min(current[j - 1] + 1).min(prev[j - 1] + cost);
+        }
+        if current.iter().min().copied().unwrap_or(0) > max {
+            return max + 1;
+        }
+        prev = current;
+    }
+    prev[b.len()]
+}
+
+/// 允许的拼写误差：短词必须精确，CJK 二元组也必须精确
+fn typo_budget(token: &str) -> usize {
+    if token.chars().any(is_cjk) {
+        return 0;
+    }
+    match token.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// 查询词元与一组文档词元的匹配程度：1.0 精确 / 前缀，FUZZY_FACTOR 模糊，0 未命中
+pub fn token_match(query: &str, doc_tokens: &[String]) -> f64 {
+    let budget = typo_budget(query);
+    let mut best: f64 = 0.0;
+    for token in doc_tokens {
+        if token == query {
+            return 1.0;
+        }
+        // 前缀命中（如 "popup" 命中 "popups"）
+        if query.chars().count() >= 3 && token.starts_with(query) {
+            best = best.max(0.9);
+        } else if budget > 0 && bounded_edit_distance(query, token, budget) <= budget {
+            best = best.max(FUZZY_FACTOR);
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_mixed_cjk_and_ascii() {
+        assert_eq!(tokenize("Telegram弹窗不显示"), vec!["telegram", "弹窗", "窗不", "不显", "显示"]);
+        assert_eq!(tokenize("git push 失败"), vec!["git", "push", "失败"]);
+        assert_eq!(tokenize("锁"), vec!["锁"]);
+    }
+}